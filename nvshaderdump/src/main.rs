@@ -1,12 +1,14 @@
 use std::{
     fs::File,
-    io::{Cursor, Read, Write},
+    io::{Read, Write},
     path::PathBuf,
 };
 
 use argh::FromArgs;
+use base64::{prelude::BASE64_STANDARD, Engine};
 use lzma_rs::decompress::Options;
-use reqwest::multipart::Part;
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Serialize};
 
 const NVDA_MAGIC: u32 = 0x4144564e;
 const NVVM_MAGIC: u32 = 0x4d56564e;
@@ -52,7 +54,7 @@ struct RemoteSubCommand {
     #[argh(option)]
     extensions: Option<String>,
 
-    /// the shader flags to pass.
+    /// the comma separated shader flags to pass, such as `link_stage`.
     #[argh(option)]
     shader_flags: Option<String>,
 
@@ -82,8 +84,60 @@ struct LocalSubCommand {
     output_directory: Option<PathBuf>,
 }
 
+/// Body of `POST /api/v1/compile` on shader-dump.
+#[derive(Serialize)]
+struct CompileRequest {
+    device: PhysicalDeviceSelector,
+    extensions: Vec<String>,
+    flags: Vec<String>,
+    capture_mode: &'static str,
+    stages: Vec<ShaderStage>,
+}
+
+#[derive(Serialize)]
+struct PhysicalDeviceSelector {
+    vendor_id: usize,
+    device_id: usize,
+}
+
+#[derive(Serialize)]
+struct ShaderStage {
+    spirv: String,
+    entry_point: String,
+}
+
+#[derive(Deserialize)]
+struct CompileResponse {
+    outputs: Vec<CompileOutput>,
+}
+
+#[derive(Deserialize)]
+struct CompileOutput {
+    data: String,
+}
+
+#[derive(Deserialize)]
+struct ApiErrorBody {
+    error: ApiError,
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    kind: String,
+    message: String,
+}
+
+fn split_list(list: &Option<String>) -> Vec<String> {
+    list.as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_string())
+        .collect()
+}
+
 async fn get_shader_binary(args: &RemoteSubCommand) -> Vec<u8> {
-    let url = format!("http://{}:{}/get_shader_binary", args.hostname, args.port);
+    let url = format!("http://{}:{}/api/v1/compile", args.hostname, args.port);
 
     let mut spirv_data = Vec::new();
 
@@ -91,37 +145,55 @@ async fn get_shader_binary(args: &RemoteSubCommand) -> Vec<u8> {
 
     file.read_to_end(&mut spirv_data).unwrap();
 
-    let form = reqwest::multipart::Form::new()
-        .text(
-            "entry_point",
-            args.entrypoint.clone().unwrap_or("main".into()),
-        )
-        .text("vendor_id", args.vendor_id.to_string())
-        .text("device_id", args.device_id.to_string())
-        .text("extensions", args.extensions.clone().unwrap_or_default())
-        .text("shader_flags", args.shader_flags.clone().unwrap())
-        .text("has_task_shader", true.to_string())
-        .part("file", Part::bytes(spirv_data));
+    let request = CompileRequest {
+        device: PhysicalDeviceSelector {
+            vendor_id: args.vendor_id,
+            device_id: args.device_id,
+        },
+        extensions: split_list(&args.extensions),
+        flags: split_list(&args.shader_flags),
+        capture_mode: "shader_object",
+        stages: vec![ShaderStage {
+            spirv: BASE64_STANDARD.encode(spirv_data),
+            entry_point: args.entrypoint.clone().unwrap_or("main".into()),
+        }],
+    };
 
     let response = reqwest::Client::new()
         .post(url)
-        .multipart(form)
+        .header(CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(&request).unwrap())
         .send()
         .await
         .expect("send");
 
-    let mut result = Vec::new();
-
-    if response.status() != 200 {
-        let error_txt = response.text_with_charset("utf-8").await.unwrap();
+    let status = response.status();
+    let body = response.bytes().await.unwrap();
 
-        panic!("Server replied with error: {error_txt}")
+    if status != 200 {
+        match serde_json::from_slice::<ApiErrorBody>(&body) {
+            Ok(ApiErrorBody { error }) => {
+                panic!(
+                    "Server replied with {} error: {}",
+                    error.kind, error.message
+                )
+            }
+            Err(_) => panic!(
+                "Server replied with error: {}",
+                String::from_utf8_lossy(&body)
+            ),
+        }
     }
 
-    let mut content = Cursor::new(response.bytes().await.unwrap());
-    std::io::copy(&mut content, &mut result).unwrap();
+    let mut response: CompileResponse = serde_json::from_slice(&body).unwrap();
+
+    // A single stage compiled as a shader object has exactly one output.
+    let output = response
+        .outputs
+        .pop()
+        .expect("Server replied without output");
 
-    result
+    BASE64_STANDARD.decode(output.data).unwrap()
 }
 
 pub fn find_u32_magic(bin: &[u8], offset: usize, magic: u32) -> Option<usize> {
@@ -0,0 +1,173 @@
+//! Schema of the versioned JSON API served under `/api/v1`.
+
+use ash::vk::ShaderCreateFlagsEXT;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use usami::UsamiSpecializationValue;
+
+use crate::CaptureMode;
+
+/// Physical device to compile with, the first device is used when no field is given.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub struct PhysicalDeviceSelector {
+    pub vendor_id: Option<u32>,
+    pub device_id: Option<u32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShaderFlag {
+    LinkStage,
+    AllowVaryingSubgroupSize,
+    RequireFullSubgroups,
+    NoTaskShader,
+    DispatchBase,
+    FragmentShadingRateAttachment,
+    FragmentDensityMapAttachment,
+}
+
+impl ShaderFlag {
+    pub fn to_vk(self) -> ShaderCreateFlagsEXT {
+        match self {
+            Self::LinkStage => ShaderCreateFlagsEXT::LINK_STAGE,
+            Self::AllowVaryingSubgroupSize => ShaderCreateFlagsEXT::ALLOW_VARYING_SUBGROUP_SIZE,
+            Self::RequireFullSubgroups => ShaderCreateFlagsEXT::REQUIRE_FULL_SUBGROUPS,
+            Self::NoTaskShader => ShaderCreateFlagsEXT::NO_TASK_SHADER,
+            Self::DispatchBase => ShaderCreateFlagsEXT::DISPATCH_BASE,
+            Self::FragmentShadingRateAttachment => {
+                ShaderCreateFlagsEXT::FRAGMENT_SHADING_RATE_ATTACHMENT
+            }
+            Self::FragmentDensityMapAttachment => {
+                ShaderCreateFlagsEXT::FRAGMENT_DENSITY_MAP_ATTACHMENT
+            }
+        }
+    }
+}
+
+/// Deserialize a [UsamiSpecializationValue] from a JSON boolean or number.
+#[derive(Deserialize)]
+#[serde(remote = "UsamiSpecializationValue", untagged)]
+enum SpecializationValueDef {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct SpecializationConstant {
+    pub id: u32,
+    #[serde(with = "SpecializationValueDef")]
+    pub value: UsamiSpecializationValue,
+}
+
+/// Push constant range visible to every stage of the request.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct PushConstantRange {
+    pub offset: u32,
+    pub size: u32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ShaderStage {
+    /// Base64 encoded SPIR-V module.
+    pub spirv: String,
+    #[serde(default = "default_entry_point")]
+    pub entry_point: String,
+}
+
+fn default_entry_point() -> String {
+    "main".into()
+}
+
+/// Body of `POST /api/v1/compile` and `POST /api/v1/statistics`, every stage is compiled and linked together.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CompileRequest {
+    #[serde(default)]
+    pub device: PhysicalDeviceSelector,
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub flags: Vec<ShaderFlag>,
+    #[serde(default)]
+    pub capture_mode: CaptureMode,
+    #[serde(default)]
+    pub specialization_constants: Vec<SpecializationConstant>,
+    /// Replace the push constant ranges reflected from the modules when given.
+    pub push_constant_ranges: Option<Vec<PushConstantRange>>,
+    pub stages: Vec<ShaderStage>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DeviceInformation {
+    pub device_name: String,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub driver_version: u32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CompileOutput {
+    pub name: String,
+    pub stage: String,
+    /// Name of the output when dumped to a file, without the name of the module.
+    pub file_suffix: String,
+    /// Base64 encoded output.
+    pub data: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CompileResponse {
+    pub device: DeviceInformation,
+    pub outputs: Vec<CompileOutput>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiErrorKind {
+    InvalidRequest,
+    DeviceCreation,
+    Compilation,
+}
+
+/// Error returned by every `/api/v1` endpoint as `{"error": {"kind": ..., "message": ...}}`.
+#[derive(Clone, Debug, Serialize)]
+pub struct ApiError {
+    pub kind: ApiErrorKind,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(kind: ApiErrorKind, message: String) -> Self {
+        Self { kind, message }
+    }
+}
+
+#[derive(Serialize)]
+struct ApiErrorBody {
+    error: ApiError,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match self.kind {
+            ApiErrorKind::InvalidRequest => StatusCode::BAD_REQUEST,
+            ApiErrorKind::DeviceCreation => StatusCode::SERVICE_UNAVAILABLE,
+            ApiErrorKind::Compilation => StatusCode::UNPROCESSABLE_ENTITY,
+        };
+
+        (status, Json(ApiErrorBody { error: self })).into_response()
+    }
+}
+
+/// Bit of every flag in `flags`.
+pub fn shader_flags_to_vk(flags: &[ShaderFlag]) -> ShaderCreateFlagsEXT {
+    flags
+        .iter()
+        .fold(ShaderCreateFlagsEXT::empty(), |result, flag| {
+            result | flag.to_vk()
+        })
+}
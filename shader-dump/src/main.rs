@@ -1,26 +1,42 @@
+mod api;
+mod state;
+
 use ash::{
     ext::shader_object::Device as ShaderObject,
-    prelude::VkResult,
-    vk::{self, DescriptorSetLayout, ShaderCodeTypeEXT, ShaderCreateFlagsEXT, ShaderStageFlags},
+    vk::{
+        self, AttachmentDescription, AttachmentLoadOp, AttachmentReference, AttachmentStoreOp,
+        ComputePipelineCreateInfo, DescriptorSetLayout, Format, ImageLayout, PipelineBindPoint,
+        PipelineCache, PipelineCreateFlags, PipelineShaderStageCreateInfo, PrimitiveTopology,
+        PushConstantRange, RenderPassCreateInfo, SampleCountFlags, ShaderCodeTypeEXT,
+        ShaderCreateFlagsEXT, ShaderStageFlags, SubpassDescription,
+    },
 };
 use axum_typed_multipart::{FieldData, TryFromMultipart, TypedMultipart};
+use base64::{prelude::BASE64_STANDARD, Engine};
 use hyper::{body::Bytes, header};
 use serde_json::json;
-use spirv_reflect::{types::ReflectDescriptorType, ShaderModule};
+use state::ServerState;
 use std::{ffi::CString, net::SocketAddr, sync::Arc};
 use tower_http::limit::RequestBodyLimitLayer;
-use usami::{descriptor::UsamiDescriptorSetLayout, UsamiDevice, UsamiInstance};
+use usami::{
+    descriptor::UsamiDescriptorSetLayout,
+    reflection::{merge_descriptor_bindings, merge_push_constant_ranges},
+    UsamiDescriptorBinding, UsamiDevice, UsamiEntryPoint, UsamiGraphicsPipelineBuilder,
+    UsamiInstance, UsamiPhysicalDeviceSelector, UsamiPipeline,
+    UsamiPipelineExecutableStatisticValue, UsamiResult, UsamiShader, UsamiShaderReflection,
+    UsamiSpecializationBuilder,
+};
 
 use axum::{
-    extract::DefaultBodyLimit,
+    extract::{DefaultBodyLimit, State},
     http::StatusCode,
     response::{Html, IntoResponse, Response},
-    routing::get,
+    routing::{get, post},
     Json, Router,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-fn create_instance() -> VkResult<UsamiInstance> {
+fn create_instance() -> UsamiResult<UsamiInstance> {
     UsamiInstance::new(
         "shader_dumper",
         "usami",
@@ -34,41 +50,22 @@ fn create_device(
     vendor_id: Option<usize>,
     device_id: Option<usize>,
     extensions: &[String],
-) -> VkResult<Arc<UsamiDevice>> {
-    UsamiDevice::new_by_filter(
-        create_instance()?,
-        extensions,
-        Box::new(move |physical_device| {
-            if let Some(vendor_id) = vendor_id {
-                if physical_device.properties.vendor_id != vendor_id as u32 {
-                    return None;
-                }
-            }
+) -> UsamiResult<Arc<UsamiDevice>> {
+    let mut selector = UsamiPhysicalDeviceSelector::new();
 
-            if let Some(device_id) = device_id {
-                if physical_device.properties.device_id != device_id as u32 {
-                    return None;
-                }
-            }
+    if let Some(vendor_id) = vendor_id {
+        selector = selector.vendor_id(vendor_id as u32);
+    }
 
-            physical_device
-                .queue_familiy_properties
-                .iter()
-                .enumerate()
-                .find_map(|(i, x)| {
-                    if x.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
-                        Some(i as u32)
-                    } else {
-                        None
-                    }
-                })
-                .map(|x| (physical_device, x))
-        }),
-    )
+    if let Some(device_id) = device_id {
+        selector = selector.device_id(device_id as u32);
+    }
+
+    UsamiDevice::new_by_selector(create_instance()?, extensions, selector)
 }
 
-fn next_stages(stage: vk::ShaderStageFlags) -> vk::ShaderStageFlags {
-    if stage == vk::ShaderStageFlags::VERTEX {
+fn next_stages(stage: vk::ShaderStageFlags) -> Result<vk::ShaderStageFlags, String> {
+    let next_stages = if stage == vk::ShaderStageFlags::VERTEX {
         vk::ShaderStageFlags::TESSELLATION_CONTROL
             | vk::ShaderStageFlags::GEOMETRY
             | vk::ShaderStageFlags::FRAGMENT
@@ -85,42 +82,36 @@ fn next_stages(stage: vk::ShaderStageFlags) -> vk::ShaderStageFlags {
     } else if stage == vk::ShaderStageFlags::MESH_EXT {
         vk::ShaderStageFlags::FRAGMENT
     } else {
-        panic!("Unsupported shader stage");
-    }
+        return Err(format!("Unsupported shader stage {stage:?}"));
+    };
+
+    Ok(next_stages)
 }
 
 fn create_descriptor_set_layouts(
     device: &Arc<UsamiDevice>,
-    reflection_module: &ShaderModule,
-    entry_point: &str,
-    stage: vk::ShaderStageFlags,
+    descriptor_bindings: &[UsamiDescriptorBinding],
     update_after_bind: bool,
 ) -> Result<Vec<UsamiDescriptorSetLayout>, String> {
-    let reflection_sets = reflection_module.enumerate_descriptor_sets(Some(entry_point))?;
-
-    let max_set = reflection_sets.iter().map(|s| s.set).max().unwrap_or(0);
-    let num_sets = usize::try_from(max_set).unwrap() + 1;
-
-    let mut reflection_set_bindings = Vec::new();
-    reflection_set_bindings.resize_with(num_sets, || Vec::new());
-    for reflection_set in reflection_sets {
-        let set_idx = usize::try_from(reflection_set.set).unwrap();
-        reflection_set_bindings[set_idx] = reflection_set.bindings;
-    }
+    let num_sets = descriptor_bindings
+        .iter()
+        .map(|x| x.set + 1)
+        .max()
+        .unwrap_or(1);
 
     let mut sets = Vec::new();
-    for reflection_set_binding in reflection_set_bindings {
+    for set in 0..num_sets {
         let mut has_update_after_bind = false;
         let mut binding_flags = Vec::new();
         let mut bindings = Vec::new();
-        for reflection_binding in reflection_set_binding {
+        for reflection_binding in descriptor_bindings.iter().filter(|x| x.set == set) {
             if update_after_bind {
                 let mut flags = vk::DescriptorBindingFlags::empty();
                 match reflection_binding.descriptor_type {
-                    ReflectDescriptorType::Sampler
-                    | ReflectDescriptorType::CombinedImageSampler
-                    | ReflectDescriptorType::SampledImage
-                    | ReflectDescriptorType::StorageImage => {
+                    vk::DescriptorType::SAMPLER
+                    | vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+                    | vk::DescriptorType::SAMPLED_IMAGE
+                    | vk::DescriptorType::STORAGE_IMAGE => {
                         has_update_after_bind = true;
                         flags |= vk::DescriptorBindingFlags::UPDATE_AFTER_BIND;
                     }
@@ -129,38 +120,11 @@ fn create_descriptor_set_layouts(
                 binding_flags.push(flags);
             }
 
-            let desc_type = match reflection_binding.descriptor_type {
-                ReflectDescriptorType::Undefined => panic!("Unknown descriptor type"),
-                ReflectDescriptorType::Sampler => vk::DescriptorType::SAMPLER,
-                ReflectDescriptorType::CombinedImageSampler => {
-                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER
-                }
-                ReflectDescriptorType::SampledImage => vk::DescriptorType::SAMPLED_IMAGE,
-                ReflectDescriptorType::StorageImage => vk::DescriptorType::STORAGE_IMAGE,
-                ReflectDescriptorType::UniformTexelBuffer => {
-                    vk::DescriptorType::UNIFORM_TEXEL_BUFFER
-                }
-                ReflectDescriptorType::StorageTexelBuffer => {
-                    vk::DescriptorType::STORAGE_TEXEL_BUFFER
-                }
-                ReflectDescriptorType::UniformBuffer => vk::DescriptorType::UNIFORM_BUFFER,
-                ReflectDescriptorType::StorageBuffer => vk::DescriptorType::STORAGE_BUFFER,
-                ReflectDescriptorType::UniformBufferDynamic => {
-                    vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC
-                }
-                ReflectDescriptorType::StorageBufferDynamic => {
-                    vk::DescriptorType::STORAGE_BUFFER_DYNAMIC
-                }
-                ReflectDescriptorType::InputAttachment => vk::DescriptorType::INPUT_ATTACHMENT,
-                ReflectDescriptorType::AccelerationStructureKHR => {
-                    vk::DescriptorType::ACCELERATION_STRUCTURE_KHR
-                }
-            };
             bindings.push(vk::DescriptorSetLayoutBinding {
                 binding: reflection_binding.binding,
-                descriptor_type: desc_type,
-                descriptor_count: reflection_binding.count,
-                stage_flags: stage,
+                descriptor_type: reflection_binding.descriptor_type,
+                descriptor_count: reflection_binding.descriptor_count,
+                stage_flags: reflection_binding.stage_flags,
                 ..Default::default()
             });
         }
@@ -179,19 +143,118 @@ fn create_descriptor_set_layouts(
 
         let set_layout =
             UsamiDevice::create_descriptor_set_layout(device, "set_layout".into(), layout_info)
-                .map_err(|x| format!("Vulkan error: {x}"))?;
+                .map_err(|x| x.to_string())?;
         sets.push(set_layout);
     }
 
     Ok(sets)
 }
 
+#[derive(Clone)]
 pub struct Shader {
     pub name: String,
     pub stage: String,
+    /// Name of the output in archives, without the name of the uploaded file.
+    pub file_suffix: String,
     pub data: Vec<u8>,
 }
 
+/// How shader binaries are captured from the driver.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureMode {
+    /// Binary of shader objects, from `vkGetShaderBinaryDataEXT`.
+    #[default]
+    ShaderObject,
+    /// Internal representations of pipeline executables, from `VK_KHR_pipeline_executable_properties`.
+    PipelineExecutable,
+    /// Pipeline cache blob after creating the pipeline.
+    PipelineCache,
+}
+
+impl CaptureMode {
+    fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "" | "shader_object" => Ok(Self::ShaderObject),
+            "pipeline_executable" => Ok(Self::PipelineExecutable),
+            "pipeline_cache" => Ok(Self::PipelineCache),
+            _ => Err(format!("Unknown capture mode \"{name}\"")),
+        }
+    }
+
+    fn required_extension(self) -> Option<&'static str> {
+        match self {
+            Self::ShaderObject => Some("VK_EXT_shader_object"),
+            Self::PipelineExecutable => Some("VK_KHR_pipeline_executable_properties"),
+            Self::PipelineCache => None,
+        }
+    }
+}
+
+/// Options shared by every capture mode.
+#[derive(Default)]
+struct CompileOptions {
+    shader_flags: ShaderCreateFlagsEXT,
+    specialization_constants: Vec<api::SpecializationConstant>,
+    /// Replace the push constant ranges reflected from the modules when given.
+    push_constant_ranges: Option<Vec<api::PushConstantRange>>,
+}
+
+impl CompileOptions {
+    fn from_request(request: &api::CompileRequest) -> Self {
+        Self {
+            shader_flags: api::shader_flags_to_vk(&request.flags),
+            specialization_constants: request.specialization_constants.clone(),
+            push_constant_ranges: request.push_constant_ranges.clone(),
+        }
+    }
+
+    fn push_constant_ranges(&self, entry_points: &[&UsamiEntryPoint]) -> Vec<PushConstantRange> {
+        let Some(ranges) = &self.push_constant_ranges else {
+            return merge_push_constant_ranges(entry_points);
+        };
+
+        let stage_flags = entry_points
+            .iter()
+            .fold(ShaderStageFlags::empty(), |result, x| result | x.stage);
+
+        ranges
+            .iter()
+            .map(|x| PushConstantRange {
+                stage_flags,
+                offset: x.offset,
+                size: x.size,
+            })
+            .collect()
+    }
+
+    /// Encode the specialization constants with the type declared in the module, constants unknown to the module are 32-bit.
+    fn specialization_constants(&self, reflection: &UsamiShaderReflection) -> Vec<(u32, Vec<u8>)> {
+        self.specialization_constants
+            .iter()
+            .map(|constant| {
+                let (kind, size) = reflection
+                    .specialization_constants
+                    .iter()
+                    .find(|x| x.constant_id == constant.id)
+                    .map(|x| (x.kind, x.size()))
+                    .unwrap_or((constant.value.kind(), 4));
+
+                (constant.id, constant.value.encode(kind, size))
+            })
+            .collect()
+    }
+
+    fn specialization(&self, reflection: &UsamiShaderReflection) -> UsamiSpecializationBuilder {
+        self.specialization_constants(reflection).iter().fold(
+            UsamiSpecializationBuilder::new(),
+            |specialization, (constant_id, value)| {
+                specialization.constant_bytes(*constant_id, value)
+            },
+        )
+    }
+}
+
 const SHADER_FLAGS_MAPPING: [(&'static str, ShaderCreateFlagsEXT); 7] = [
     ("link_stage", ShaderCreateFlagsEXT::LINK_STAGE),
     (
@@ -226,103 +289,515 @@ fn human_flags_to_shader_flags(raw_flags: Vec<String>) -> ShaderCreateFlagsEXT {
     result
 }
 
+/// Name of every stage in `stages` joined with `_`, such as `vert_frag` for an executable covering two stages.
+fn stage_name(stages: ShaderStageFlags) -> String {
+    let names = (0..u32::BITS)
+        .map(|bit| ShaderStageFlags::from_raw(1 << bit))
+        .filter(|stage| stages.contains(*stage))
+        .map(|stage| match stage {
+            ShaderStageFlags::VERTEX => "vert".into(),
+            ShaderStageFlags::TESSELLATION_CONTROL => "tesc".into(),
+            ShaderStageFlags::TESSELLATION_EVALUATION => "tese".into(),
+            ShaderStageFlags::GEOMETRY => "geom".into(),
+            ShaderStageFlags::FRAGMENT => "frag".into(),
+            ShaderStageFlags::COMPUTE => "comp".into(),
+            ShaderStageFlags::TASK_EXT => "task".into(),
+            ShaderStageFlags::MESH_EXT => "mesh".into(),
+            _ => format!("{stage:?}").to_lowercase(),
+        })
+        .collect::<Vec<String>>();
+
+    if names.is_empty() {
+        "unknown".into()
+    } else {
+        names.join("_")
+    }
+}
+
+/// A SPIR-V module and the entry point to compile from it.
+pub struct ShaderSource<'a> {
+    pub spirv: &'a [u8],
+    pub entry_point: &'a str,
+}
+
+fn reflect_sources(sources: &[ShaderSource]) -> Result<Vec<UsamiShaderReflection>, String> {
+    sources
+        .iter()
+        .map(|source| {
+            UsamiShaderReflection::new(&usami::utils::as_u32_vec(source.spirv))
+                .map_err(|x| x.to_string())
+        })
+        .collect()
+}
+
+fn find_entry_points<'a>(
+    sources: &[ShaderSource],
+    reflections: &'a [UsamiShaderReflection],
+) -> Result<Vec<&'a UsamiEntryPoint>, String> {
+    let entry_points = sources
+        .iter()
+        .zip(reflections)
+        .map(|(source, reflection)| {
+            reflection
+                .entry_point(source.entry_point)
+                .map_err(|x| x.to_string())
+        })
+        .collect::<Result<Vec<&UsamiEntryPoint>, String>>()?;
+
+    for (index, entry_point) in entry_points.iter().enumerate() {
+        if entry_points[..index]
+            .iter()
+            .any(|x| x.stage == entry_point.stage)
+        {
+            return Err(format!(
+                "Stage {} given more than once",
+                stage_name(entry_point.stage)
+            ));
+        }
+    }
+
+    Ok(entry_points)
+}
+
+/// Compile all the sources in a single `vkCreateShadersEXT` call, linking them when there is more than one stage.
 fn compile_shaders(
     device: &Arc<UsamiDevice>,
-    spirv: &[u8],
-    entry_point: &str,
-    shader_flags: Vec<String>,
+    sources: &[ShaderSource],
+    options: &CompileOptions,
 ) -> Result<Vec<Shader>, String> {
-    let reflection_module = ShaderModule::load_u8_data(spirv)?;
-    let entrypoints = reflection_module.enumerate_entry_points()?;
     let eso = ShaderObject::new(&device.instance.vk_instance, &device.handle);
-    let flags = human_flags_to_shader_flags(shader_flags);
+    let flags = options.shader_flags;
 
-    let mut shaders = Vec::new();
-    for e in entrypoints {
-        if e.name != entry_point {
-            continue;
-        }
+    let reflections = reflect_sources(sources)?;
+    let entry_points = find_entry_points(sources, &reflections)?;
 
-        let stage = vk::ShaderStageFlags::from_raw(e.shader_stage.bits());
+    // Linked stages must use the same layout, so it is created from the resources of every stage.
+    let descriptor_bindings =
+        merge_descriptor_bindings(&entry_points).map_err(|x| x.to_string())?;
+    let push_constant_ranges = options.push_constant_ranges(&entry_points);
+    let set_layouts = create_descriptor_set_layouts(device, &descriptor_bindings, true)?;
+    let set_layouts_handle = set_layouts
+        .iter()
+        .map(|x| x.handle)
+        .collect::<Vec<DescriptorSetLayout>>();
 
-        let c_name = CString::new(e.name.as_str()).unwrap();
+    let is_linked = entry_points.len() > 1;
 
-        {
-            let set_layouts =
-                create_descriptor_set_layouts(&device, &reflection_module, &e.name, stage, true)
-                    .map_err(|x| format!("Vulkan error: {x}"))?;
-            let set_layouts_handle = set_layouts
-                .as_slice()
-                .iter()
-                .map(|x| x.handle)
-                .collect::<Vec<DescriptorSetLayout>>();
+    if is_linked
+        && entry_points
+            .iter()
+            .any(|x| x.stage == ShaderStageFlags::COMPUTE)
+    {
+        return Err("Compute shaders cannot be linked with other stages".into());
+    }
+
+    let has_task_shader = entry_points
+        .iter()
+        .any(|x| x.stage == ShaderStageFlags::TASK_EXT);
+    let c_names = entry_points
+        .iter()
+        .map(|x| CString::new(x.name.as_str()).unwrap())
+        .collect::<Vec<CString>>();
+
+    let specializations = reflections
+        .iter()
+        .map(|reflection| options.specialization(reflection))
+        .collect::<Vec<UsamiSpecializationBuilder>>();
+    let specialization_infos = specializations
+        .iter()
+        .map(UsamiSpecializationBuilder::info)
+        .collect::<Vec<vk::SpecializationInfo>>();
+
+    let shader_infos = sources
+        .iter()
+        .zip(&entry_points)
+        .zip(&c_names)
+        .zip(&specialization_infos)
+        .map(|(((source, entry_point), c_name), specialization_info)| {
+            let mut stage_flags = flags;
+
+            if is_linked {
+                stage_flags |= ShaderCreateFlagsEXT::LINK_STAGE;
+            }
+
+            if entry_point.stage != ShaderStageFlags::MESH_EXT || has_task_shader {
+                stage_flags &= !ShaderCreateFlagsEXT::NO_TASK_SHADER;
+            }
 
             let shader_info = vk::ShaderCreateInfoEXT::default()
-                .stage(stage)
-                .next_stage(next_stages(stage))
+                .stage(entry_point.stage)
+                .next_stage(next_stages(entry_point.stage)?)
                 .code_type(ShaderCodeTypeEXT::SPIRV)
-                .code(spirv)
+                .code(source.spirv)
                 .name(c_name.as_c_str())
-                .flags(flags)
-                .set_layouts(&set_layouts_handle);
+                .flags(stage_flags)
+                .set_layouts(&set_layouts_handle)
+                .push_constant_ranges(&push_constant_ranges);
+
+            if specialization_info.map_entry_count == 0 {
+                Ok(shader_info)
+            } else {
+                Ok(shader_info.specialization_info(specialization_info))
+            }
+        })
+        .collect::<Result<Vec<vk::ShaderCreateInfoEXT>, String>>()?;
+
+    let binaries = unsafe {
+        let shader_objects =
+            eso.create_shaders(&shader_infos, None)
+                .map_err(|(shader_objects, x)| {
+                    for shader_object in shader_objects {
+                        if shader_object != vk::ShaderEXT::null() {
+                            eso.destroy_shader(shader_object, None);
+                        }
+                    }
 
-            let bin = unsafe {
-                let shader_object = eso
-                    .create_shaders(&[shader_info], None)
-                    .map_err(|(_, x)| format!("Vulkan error: {x}"))?[0];
+                    format!("Vulkan error: {x}")
+                })?;
 
-                let bin: Vec<u8> = eso
-                    .get_shader_binary_data(shader_object)
-                    .map_err(|x| format!("Vulkan error: {x}"))?;
+        let binaries = shader_objects
+            .iter()
+            .map(|shader_object| {
+                eso.get_shader_binary_data(*shader_object)
+                    .map_err(|x| format!("Vulkan error: {x}"))
+            })
+            .collect::<Result<Vec<Vec<u8>>, String>>();
 
-                eso.destroy_shader(shader_object, None);
+        for shader_object in shader_objects {
+            eso.destroy_shader(shader_object, None);
+        }
 
-                bin
-            };
+        binaries?
+    };
 
-            std::mem::drop(set_layouts);
+    std::mem::drop(set_layouts);
 
-            let mut stages = Vec::new();
+    Ok(entry_points
+        .iter()
+        .zip(binaries)
+        .map(|(entry_point, data)| Shader {
+            name: entry_point.name.clone(),
+            stage: stage_name(entry_point.stage),
+            file_suffix: format!("{}.bin", stage_name(entry_point.stage)),
+            data,
+        })
+        .collect())
+}
 
-            if stage & ShaderStageFlags::VERTEX == ShaderStageFlags::VERTEX {
-                stages.push("vert");
-            }
+/// Create a compute pipeline, or a graphics pipeline with a single color attachment render pass.
+fn create_pipeline(
+    device: &Arc<UsamiDevice>,
+    sources: &[ShaderSource],
+    options: &CompileOptions,
+    flags: PipelineCreateFlags,
+    pipeline_cache: PipelineCache,
+) -> Result<UsamiPipeline, String> {
+    let reflections = reflect_sources(sources)?;
+    let entry_points = find_entry_points(sources, &reflections)?;
+    let descriptor_bindings =
+        merge_descriptor_bindings(&entry_points).map_err(|x| x.to_string())?;
+    let set_layouts = create_descriptor_set_layouts(device, &descriptor_bindings, false)?;
+    let set_layouts_handle = set_layouts
+        .iter()
+        .map(|x| x.handle)
+        .collect::<Vec<DescriptorSetLayout>>();
+    let layout = UsamiDevice::create_pipeline_layout(
+        device,
+        "pipeline_layout".into(),
+        &set_layouts_handle,
+        &options.push_constant_ranges(&entry_points),
+    )
+    .map_err(|x| x.to_string())?;
+    let modules = sources
+        .iter()
+        .map(|source| {
+            UsamiDevice::create_shader(
+                device,
+                "shader".into(),
+                &usami::utils::as_u32_vec(source.spirv),
+            )
+        })
+        .collect::<UsamiResult<Vec<UsamiShader>>>()
+        .map_err(|x| x.to_string())?;
 
-            if stage & ShaderStageFlags::TESSELLATION_CONTROL
-                == ShaderStageFlags::TESSELLATION_CONTROL
-            {
-                stages.push("tesc");
-            }
+    if entry_points
+        .iter()
+        .any(|x| x.stage == ShaderStageFlags::COMPUTE)
+    {
+        if entry_points.len() != 1 {
+            return Err("Compute shaders cannot be linked with other stages".into());
+        }
 
-            if stage & ShaderStageFlags::TESSELLATION_EVALUATION
-                == ShaderStageFlags::TESSELLATION_EVALUATION
-            {
-                stages.push("tese");
-            }
+        let c_name = CString::new(entry_points[0].name.as_str()).unwrap();
+        let specialization = options.specialization(&reflections[0]);
+        let specialization_info = specialization.info();
+        let mut stage_info = PipelineShaderStageCreateInfo::default()
+            .stage(ShaderStageFlags::COMPUTE)
+            .module(modules[0].handle)
+            .name(c_name.as_c_str());
 
-            if stage & ShaderStageFlags::GEOMETRY == ShaderStageFlags::GEOMETRY {
-                stages.push("geom");
-            }
+        if !specialization.is_empty() {
+            stage_info = stage_info.specialization_info(&specialization_info);
+        }
 
-            if stage & ShaderStageFlags::FRAGMENT == ShaderStageFlags::FRAGMENT {
-                stages.push("frag");
-            }
+        let create_info = ComputePipelineCreateInfo::default()
+            .flags(flags)
+            .stage(stage_info)
+            .layout(layout.handle);
 
-            if stage & ShaderStageFlags::COMPUTE == ShaderStageFlags::COMPUTE {
-                stages.push("comp");
-            }
+        return UsamiDevice::create_compute_pipelines(
+            device,
+            "pipeline".into(),
+            pipeline_cache,
+            &[create_info],
+        )
+        .map(|mut x| x.remove(0))
+        .map_err(|x| x.to_string());
+    }
+
+    let attachments = [AttachmentDescription::default()
+        .format(Format::R8G8B8A8_UNORM)
+        .samples(SampleCountFlags::TYPE_1)
+        .load_op(AttachmentLoadOp::CLEAR)
+        .store_op(AttachmentStoreOp::STORE)
+        .final_layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)];
+    let color_attachment_refs = [AttachmentReference::default()
+        .attachment(0)
+        .layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)];
+    let subpasses = [SubpassDescription::default()
+        .color_attachments(&color_attachment_refs)
+        .pipeline_bind_point(PipelineBindPoint::GRAPHICS)];
+    let render_pass = UsamiDevice::create_render_pass(
+        device,
+        "render_pass".into(),
+        RenderPassCreateInfo::default()
+            .attachments(&attachments)
+            .subpasses(&subpasses),
+    )
+    .map_err(|x| x.to_string())?;
+
+    let mut builder = UsamiGraphicsPipelineBuilder::new()
+        .flags(flags)
+        .render_pass(&render_pass, 0);
+
+    for ((module, entry_point), reflection) in modules.iter().zip(&entry_points).zip(&reflections) {
+        builder = builder
+            .stage_with_entry_point(entry_point.stage, module, &entry_point.name)
+            .map_err(|x| x.to_string())?;
+
+        for (constant_id, value) in options.specialization_constants(reflection) {
+            builder = builder.specialization_constant_bytes(entry_point.stage, constant_id, &value);
+        }
+    }
 
-            let stage = stages.join(".");
+    if entry_points
+        .iter()
+        .any(|x| x.stage == ShaderStageFlags::TESSELLATION_CONTROL)
+    {
+        builder = builder
+            .topology(PrimitiveTopology::PATCH_LIST)
+            .patch_control_points(3);
+    }
 
-            shaders.push(Shader {
-                name: e.name.clone(),
-                stage,
-                data: bin,
+    builder
+        .build_with_cache(device, "pipeline".into(), &layout, pipeline_cache)
+        .map_err(|x| x.to_string())
+}
+
+fn compile(
+    device: &Arc<UsamiDevice>,
+    sources: &[ShaderSource],
+    options: &CompileOptions,
+    capture_mode: CaptureMode,
+) -> Result<Vec<Shader>, String> {
+    if capture_mode == CaptureMode::ShaderObject {
+        compile_shaders(device, sources, options)
+    } else {
+        compile_pipeline(device, sources, options, capture_mode)
+    }
+}
+
+/// Collect the statistics and textual internal representations of every executable of the pipeline.
+fn collect_pipeline_statistics(
+    device: &Arc<UsamiDevice>,
+    sources: &[ShaderSource],
+    options: &CompileOptions,
+) -> Result<Vec<PipelineExecutableInformation>, String> {
+    let pipeline = create_pipeline(
+        device,
+        sources,
+        options,
+        PipelineCreateFlags::CAPTURE_STATISTICS_KHR
+            | PipelineCreateFlags::CAPTURE_INTERNAL_REPRESENTATIONS_KHR,
+        PipelineCache::null(),
+    )?;
+
+    pipeline
+        .executables()
+        .map_err(|x| x.to_string())?
+        .into_iter()
+        .map(|executable| {
+            let statistics = pipeline
+                .statistics(executable.index)
+                .map_err(|x| x.to_string())?
+                .into_iter()
+                .map(|x| PipelineStatistic {
+                    name: x.name,
+                    description: x.description,
+                    value: match x.value {
+                        UsamiPipelineExecutableStatisticValue::Bool(value) => json!(value),
+                        UsamiPipelineExecutableStatisticValue::Int64(value) => json!(value),
+                        UsamiPipelineExecutableStatisticValue::Uint64(value) => json!(value),
+                        UsamiPipelineExecutableStatisticValue::Float64(value) => json!(value),
+                    },
+                })
+                .collect();
+            let internal_representations = pipeline
+                .internal_representations(executable.index)
+                .map_err(|x| x.to_string())?
+                .into_iter()
+                .filter(|x| x.is_text)
+                .map(|x| PipelineInternalRepresentation {
+                    name: x.name,
+                    description: x.description,
+                    text: String::from_utf8_lossy(&x.data).into_owned(),
+                })
+                .collect();
+
+            Ok(PipelineExecutableInformation {
+                name: executable.name,
+                description: executable.description,
+                stage: stage_name(executable.stages),
+                subgroup_size: executable.subgroup_size,
+                statistics,
+                internal_representations,
+            })
+        })
+        .collect()
+}
+
+/// Capture the driver output of regular pipelines, for drivers without `VK_EXT_shader_object`.
+fn compile_pipeline(
+    device: &Arc<UsamiDevice>,
+    sources: &[ShaderSource],
+    options: &CompileOptions,
+    capture_mode: CaptureMode,
+) -> Result<Vec<Shader>, String> {
+    if capture_mode == CaptureMode::PipelineCache {
+        let pipeline_cache =
+            UsamiDevice::create_pipeline_cache(device, "pipeline_cache".into(), &[])
+                .map_err(|x| x.to_string())?;
+        let pipeline = create_pipeline(
+            device,
+            sources,
+            options,
+            PipelineCreateFlags::empty(),
+            pipeline_cache.handle,
+        )?;
+
+        std::mem::drop(pipeline);
+
+        return Ok(vec![Shader {
+            name: "pipeline_cache".into(),
+            stage: "pipeline".into(),
+            file_suffix: "pipeline_cache.bin".into(),
+            data: pipeline_cache.data().map_err(|x| x.to_string())?,
+        }]);
+    }
+
+    let pipeline = create_pipeline(
+        device,
+        sources,
+        options,
+        PipelineCreateFlags::CAPTURE_INTERNAL_REPRESENTATIONS_KHR,
+        PipelineCache::null(),
+    )?;
+    let mut result = Vec::new();
+
+    for executable in pipeline.executables().map_err(|x| x.to_string())? {
+        let stage = stage_name(executable.stages);
+
+        for representation in pipeline
+            .internal_representations(executable.index)
+            .map_err(|x| x.to_string())?
+        {
+            let representation_name = representation
+                .name
+                .chars()
+                .map(|x| if x.is_ascii_alphanumeric() { x } else { '_' })
+                .collect::<String>();
+            let extension = if representation.is_text { "txt" } else { "bin" };
+
+            result.push(Shader {
+                name: representation.name,
+                stage: stage.clone(),
+                file_suffix: format!(
+                    "{stage}.{}.{representation_name}.{extension}",
+                    executable.index
+                ),
+                data: representation.data,
             });
         }
     }
 
-    Ok(shaders)
+    if result.is_empty() {
+        return Err("The driver did not report any internal representation".into());
+    }
+
+    Ok(result)
+}
+
+/// Pack files in an uncompressed ustar archive.
+///
+/// Names longer than 100 bytes are split at a `/` between the prefix and name fields.
+fn write_tar_archive(files: &[(String, &[u8])]) -> Result<Vec<u8>, String> {
+    const BLOCK_SIZE: usize = 512;
+    const NAME_SIZE: usize = 100;
+    const PREFIX_SIZE: usize = 155;
+
+    let mut archive = Vec::new();
+
+    for (name, data) in files {
+        let mut header = [0u8; BLOCK_SIZE];
+        let bytes = name.as_bytes();
+        let (prefix, name_bytes) = if bytes.len() <= NAME_SIZE {
+            (&[][..], bytes)
+        } else {
+            let split = (0..bytes.len())
+                .find(|index| {
+                    bytes[*index] == b'/'
+                        && *index <= PREFIX_SIZE
+                        && (1..=NAME_SIZE).contains(&(bytes.len() - index - 1))
+                })
+                .ok_or_else(|| format!("\"{name}\" is too long to be stored in a tar archive"))?;
+
+            (&bytes[..split], &bytes[split + 1..])
+        };
+
+        header[..name_bytes.len()].copy_from_slice(name_bytes);
+        header[100..108].copy_from_slice(b"0000644\0");
+        header[108..116].copy_from_slice(b"0000000\0");
+        header[116..124].copy_from_slice(b"0000000\0");
+        header[124..136].copy_from_slice(format!("{:011o}\0", data.len()).as_bytes());
+        header[136..148].copy_from_slice(b"00000000000\0");
+        header[148..156].copy_from_slice(b"        ");
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[345..345 + prefix.len()].copy_from_slice(prefix);
+
+        let checksum: u32 = header.iter().map(|x| u32::from(*x)).sum();
+        header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+
+        archive.extend_from_slice(&header);
+        archive.extend_from_slice(data);
+        archive.resize(archive.len().next_multiple_of(BLOCK_SIZE), 0);
+    }
+
+    // The archive ends with two empty blocks.
+    archive.resize(archive.len() + 2 * BLOCK_SIZE, 0);
+
+    Ok(archive)
 }
 
 #[tokio::main]
@@ -330,6 +805,8 @@ async fn main() {
     // initialize tracing
     tracing_subscriber::fmt::init();
 
+    let state = Arc::new(ServerState::default());
+
     // build our application with a route
     let app = Router::new()
         .route("/devices", get(list_devices))
@@ -337,6 +814,11 @@ async fn main() {
             "/get_shader_binary",
             get(show_get_shader_binary_form).post(get_shader_binary_form),
         )
+        .route("/get_shader_statistics", post(get_shader_statistics_form))
+        .route("/api/v1/devices", get(list_devices_api))
+        .route("/api/v1/compile", post(compile_api))
+        .route("/api/v1/statistics", post(statistics_api))
+        .with_state(state)
         .layer(DefaultBodyLimit::disable())
         .layer(RequestBodyLimitLayer::new(
             250 * 1024 * 1024, /* 250MiB */
@@ -362,6 +844,38 @@ struct PhysicalDeviceInformation {
     pub device_id: u32,
 }
 
+#[derive(Serialize)]
+struct PipelineStatistic {
+    pub name: String,
+    pub description: String,
+    pub value: serde_json::Value,
+}
+
+#[derive(Serialize)]
+struct PipelineInternalRepresentation {
+    pub name: String,
+    pub description: String,
+    pub text: String,
+}
+
+#[derive(Serialize)]
+struct PipelineExecutableInformation {
+    pub name: String,
+    pub description: String,
+    pub stage: String,
+    pub subgroup_size: u32,
+    pub statistics: Vec<PipelineStatistic>,
+    /// Only the textual representations, binary ones are returned by `/get_shader_binary`.
+    pub internal_representations: Vec<PipelineInternalRepresentation>,
+}
+
+#[derive(Serialize)]
+struct PipelineStatisticsInformation {
+    pub device_name: String,
+    pub driver_version: u32,
+    pub executables: Vec<PipelineExecutableInformation>,
+}
+
 enum ServerError {
     ErrorMessage(String),
 }
@@ -380,56 +894,69 @@ impl IntoResponse for ServerError {
     }
 }
 
-async fn list_devices() -> Result<Json<Vec<PhysicalDeviceInformation>>, ServerError> {
-    let instance = match create_instance() {
-        Ok(instance) => instance,
-        Err(error) => {
-            return Err(ServerError::ErrorMessage(format!(
-                "Cannot create instance: {error}"
-            )))
-        }
-    };
+fn physical_devices(state: &ServerState) -> Result<Vec<PhysicalDeviceInformation>, String> {
+    state
+        .with_instance(|instance| {
+            let physical_devices = unsafe { instance.vk_instance.enumerate_physical_devices() }
+                .map_err(|error| format!("enumerate_physical_devices failed: {error}"))?;
+
+            Ok::<_, String>(
+                physical_devices
+                    .iter()
+                    .map(|x| physical_device_information(instance, *x))
+                    .collect::<Vec<PhysicalDeviceInformation>>(),
+            )
+        })
+        .map_err(|error| format!("Cannot create instance: {error}"))?
+}
 
-    let physical_devices = match unsafe { instance.vk_instance.enumerate_physical_devices() } {
-        Ok(physical_devices) => physical_devices,
-        Err(error) => {
-            return Err(ServerError::ErrorMessage(format!(
-                "enumerate_physical_devices failed: {error}"
-            )))
-        }
-    };
+async fn list_devices(
+    State(state): State<Arc<ServerState>>,
+) -> Result<Json<Vec<PhysicalDeviceInformation>>, ServerError> {
+    physical_devices(&state)
+        .map(Json)
+        .map_err(ServerError::ErrorMessage)
+}
 
-    let result = physical_devices
-        .iter()
-        .map(|x| {
-            let prop: vk::PhysicalDeviceProperties =
-                unsafe { instance.vk_instance.get_physical_device_properties(*x) };
-            let device_name = prop.device_name;
-            let device_name_size = device_name
-                .iter()
-                .enumerate()
-                .find(|(_, x)| **x == 0)
-                .map(|(i, _)| i)
-                .unwrap_or(device_name.len());
-
-            PhysicalDeviceInformation {
-                device_name: unsafe {
-                    CString::new(std::slice::from_raw_parts(
-                        prop.device_name.as_ptr() as *const _,
-                        device_name_size,
-                    ))
-                }
-                .unwrap()
-                .to_string_lossy()
-                .into(),
-                driver_version: prop.driver_version,
-                device_id: prop.device_id,
-                vendor_id: prop.vendor_id,
-            }
-        })
-        .collect::<Vec<PhysicalDeviceInformation>>();
+async fn list_devices_api(
+    State(state): State<Arc<ServerState>>,
+) -> Result<Json<Vec<PhysicalDeviceInformation>>, api::ApiError> {
+    physical_devices(&state)
+        .map(Json)
+        .map_err(|error| api::ApiError::new(api::ApiErrorKind::DeviceCreation, error))
+}
 
-    Ok(Json(result))
+fn physical_device_information(
+    instance: &UsamiInstance,
+    physical_device: vk::PhysicalDevice,
+) -> PhysicalDeviceInformation {
+    let prop: vk::PhysicalDeviceProperties = unsafe {
+        instance
+            .vk_instance
+            .get_physical_device_properties(physical_device)
+    };
+    let device_name = prop.device_name;
+    let device_name_size = device_name
+        .iter()
+        .enumerate()
+        .find(|(_, x)| **x == 0)
+        .map(|(i, _)| i)
+        .unwrap_or(device_name.len());
+
+    PhysicalDeviceInformation {
+        device_name: unsafe {
+            CString::new(std::slice::from_raw_parts(
+                prop.device_name.as_ptr() as *const _,
+                device_name_size,
+            ))
+        }
+        .unwrap()
+        .to_string_lossy()
+        .into(),
+        driver_version: prop.driver_version,
+        device_id: prop.device_id,
+        vendor_id: prop.vendor_id,
+    }
 }
 
 #[derive(TryFromMultipart)]
@@ -439,7 +966,9 @@ struct ShaderBinaryRequestData {
     pub entry_point: String,
     pub extensions: String,
     pub shader_flags: String,
-    pub file: FieldData<Bytes>,
+    /// One of `shader_object` (default), `pipeline_executable` or `pipeline_cache`.
+    pub capture_mode: Option<String>,
+    pub file: Vec<FieldData<Bytes>>,
 }
 
 async fn show_get_shader_binary_form() -> Html<&'static str> {
@@ -461,7 +990,7 @@ async fn show_get_shader_binary_form() -> Html<&'static str> {
                     </label>
 
                     <label>
-                        Entrypoint:
+                        Entrypoints (one per file or per stage of a single file):
                         <input type="text" name="entry_point" value="main" required />
                     </label>
 
@@ -476,8 +1005,17 @@ async fn show_get_shader_binary_form() -> Html<&'static str> {
                     </label>
 
                     <label>
-                        Upload SPIR-V file:
-                        <input type="file" name="file" required />
+                        Capture mode:
+                        <select name="capture_mode">
+                            <option value="shader_object">Shader object binary</option>
+                            <option value="pipeline_executable">Pipeline executable internal representations</option>
+                            <option value="pipeline_cache">Pipeline cache</option>
+                        </select>
+                    </label>
+
+                    <label>
+                        Upload SPIR-V files (several files are linked together):
+                        <input type="file" name="file" multiple required />
                     </label>
 
                     <input type="submit" value="Upload file">
@@ -488,25 +1026,250 @@ async fn show_get_shader_binary_form() -> Html<&'static str> {
     )
 }
 
+/// Read the uploaded SPIR-V files along with their names.
+fn read_uploaded_files(files: Vec<FieldData<Bytes>>) -> Vec<(String, Vec<u8>)> {
+    files
+        .into_iter()
+        .map(|file| {
+            (
+                file.metadata.file_name.unwrap_or(String::from("data.spv")),
+                file.contents.to_vec(),
+            )
+        })
+        .collect()
+}
+
+/// Pair the uploaded files with the comma separated entry points.
+///
+/// A single module can provide several entry points, otherwise each module gets its own entry point (or they all share the same one).
+fn pair_sources<'a>(
+    files: &'a [(String, Vec<u8>)],
+    entry_point: &'a str,
+) -> Result<Vec<ShaderSource<'a>>, String> {
+    let entry_points = entry_point.split(',').map(str::trim).collect::<Vec<&str>>();
+
+    let sources = if files.len() == 1 {
+        entry_points
+            .iter()
+            .map(|entry_point| ShaderSource {
+                spirv: &files[0].1,
+                entry_point: *entry_point,
+            })
+            .collect::<Vec<ShaderSource>>()
+    } else if entry_points.len() == 1 || entry_points.len() == files.len() {
+        files
+            .iter()
+            .enumerate()
+            .map(|(index, (_, spirv))| ShaderSource {
+                spirv,
+                entry_point: entry_points[index.min(entry_points.len() - 1)],
+            })
+            .collect::<Vec<ShaderSource>>()
+    } else {
+        return Err(format!(
+            "Got {} entry points for {} files",
+            entry_points.len(),
+            files.len()
+        ));
+    };
+
+    if sources.is_empty() {
+        return Err("No SPIR-V file given".into());
+    }
+
+    Ok(sources)
+}
+
+/// Split the comma separated extensions and add the one required by the capture mode.
+fn parse_extensions(extensions: &str, capture_mode: CaptureMode) -> Vec<String> {
+    let mut extensions = extensions
+        .split(",")
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_string())
+        .collect::<Vec<String>>();
+
+    if let Some(extension) = capture_mode.required_extension() {
+        if !extensions.iter().any(|x| x == extension) {
+            extensions.push(extension.into());
+        }
+    }
+
+    extensions
+}
+
+fn parse_api_request(body: &[u8]) -> Result<api::CompileRequest, api::ApiError> {
+    serde_json::from_slice(body)
+        .map_err(|error| api::ApiError::new(api::ApiErrorKind::InvalidRequest, error.to_string()))
+}
+
+/// Decode the base64 SPIR-V module of every stage of a request.
+fn decode_api_stages(stages: &[api::ShaderStage]) -> Result<Vec<Vec<u8>>, api::ApiError> {
+    if stages.is_empty() {
+        return Err(api::ApiError::new(
+            api::ApiErrorKind::InvalidRequest,
+            "No stage given".into(),
+        ));
+    }
+
+    stages
+        .iter()
+        .map(|stage| BASE64_STANDARD.decode(&stage.spirv))
+        .collect::<Result<Vec<Vec<u8>>, base64::DecodeError>>()
+        .map_err(|error| {
+            api::ApiError::new(
+                api::ApiErrorKind::InvalidRequest,
+                format!("Invalid base64 SPIR-V: {error}"),
+            )
+        })
+}
+
+fn api_sources<'a>(
+    stages: &'a [api::ShaderStage],
+    modules: &'a [Vec<u8>],
+) -> Vec<ShaderSource<'a>> {
+    stages
+        .iter()
+        .zip(modules)
+        .map(|(stage, spirv)| ShaderSource {
+            spirv,
+            entry_point: &stage.entry_point,
+        })
+        .collect()
+}
+
+/// Get the device selected by a request, with the extension required by `capture_mode` enabled.
+async fn api_device(
+    state: &ServerState,
+    request: &api::CompileRequest,
+    capture_mode: CaptureMode,
+) -> Result<Arc<UsamiDevice>, api::ApiError> {
+    let mut extensions = request.extensions.clone();
+
+    if let Some(extension) = capture_mode.required_extension() {
+        if !extensions.iter().any(|x| x == extension) {
+            extensions.push(extension.into());
+        }
+    }
+
+    state
+        .device(
+            request.device.vendor_id.map(|x| x as usize),
+            request.device.device_id.map(|x| x as usize),
+            &extensions,
+        )
+        .await
+        .map_err(|error| api::ApiError::new(api::ApiErrorKind::DeviceCreation, error.to_string()))
+}
+
+async fn compile_api(
+    State(state): State<Arc<ServerState>>,
+    body: Bytes,
+) -> Result<Json<api::CompileResponse>, api::ApiError> {
+    let request = parse_api_request(&body)?;
+    let modules = decode_api_stages(&request.stages)?;
+    let sources = api_sources(&request.stages, &modules);
+    let device = api_device(&state, &request, request.capture_mode).await?;
+    let options = CompileOptions::from_request(&request);
+    let shaders = state
+        .compile(&device, &sources, &options, request.capture_mode)
+        .map_err(|error| api::ApiError::new(api::ApiErrorKind::Compilation, error))?;
+
+    Ok(Json(api::CompileResponse {
+        device: api::DeviceInformation {
+            device_name: device.physical_device.name(),
+            vendor_id: device.physical_device.properties.vendor_id,
+            device_id: device.physical_device.properties.device_id,
+            driver_version: device.physical_device.properties.driver_version,
+        },
+        outputs: shaders
+            .into_iter()
+            .map(|shader| api::CompileOutput {
+                name: shader.name,
+                stage: shader.stage,
+                file_suffix: shader.file_suffix,
+                data: BASE64_STANDARD.encode(shader.data),
+            })
+            .collect(),
+    }))
+}
+
+/// Same body as `/api/v1/compile`, the capture mode is ignored.
+async fn statistics_api(
+    State(state): State<Arc<ServerState>>,
+    body: Bytes,
+) -> Result<Json<PipelineStatisticsInformation>, api::ApiError> {
+    let request = parse_api_request(&body)?;
+    let modules = decode_api_stages(&request.stages)?;
+    let sources = api_sources(&request.stages, &modules);
+    let device = api_device(&state, &request, CaptureMode::PipelineExecutable).await?;
+    let options = CompileOptions::from_request(&request);
+    let executables = collect_pipeline_statistics(&device, &sources, &options)
+        .map_err(|error| api::ApiError::new(api::ApiErrorKind::Compilation, error))?;
+
+    Ok(Json(PipelineStatisticsInformation {
+        device_name: device.physical_device.name(),
+        driver_version: device.physical_device.properties.driver_version,
+        executables,
+    }))
+}
+
+#[derive(TryFromMultipart)]
+struct ShaderStatisticsRequestData {
+    pub vendor_id: usize,
+    pub device_id: usize,
+    pub entry_point: String,
+    pub extensions: String,
+    pub file: Vec<FieldData<Bytes>>,
+}
+
+async fn get_shader_statistics_form(
+    State(state): State<Arc<ServerState>>,
+    TypedMultipart(ShaderStatisticsRequestData {
+        vendor_id,
+        device_id,
+        entry_point,
+        extensions,
+        file,
+    }): TypedMultipart<ShaderStatisticsRequestData>,
+) -> Result<Json<PipelineStatisticsInformation>, Response> {
+    let extensions = parse_extensions(&extensions, CaptureMode::PipelineExecutable);
+    let files = read_uploaded_files(file);
+    let sources = pair_sources(&files, &entry_point)
+        .map_err(|error| ServerError::ErrorMessage(error).into_response())?;
+
+    let device = state
+        .device(Some(vendor_id), Some(device_id), &extensions)
+        .await
+        .map_err(|error| {
+            ServerError::ErrorMessage(format!("create_device failed: {error}")).into_response()
+        })?;
+    let executables = collect_pipeline_statistics(&device, &sources, &CompileOptions::default())
+        .map_err(|error| {
+            ServerError::ErrorMessage(format!("collect_pipeline_statistics failed: {error}"))
+                .into_response()
+        })?;
+
+    Ok(Json(PipelineStatisticsInformation {
+        device_name: device.physical_device.name(),
+        driver_version: device.physical_device.properties.driver_version,
+        executables,
+    }))
+}
+
 async fn get_shader_binary_form(
+    State(state): State<Arc<ServerState>>,
     TypedMultipart(ShaderBinaryRequestData {
         vendor_id,
         device_id,
         entry_point,
         shader_flags,
-        mut extensions,
+        extensions,
+        capture_mode,
         file,
     }): TypedMultipart<ShaderBinaryRequestData>,
 ) -> Result<Response, Response> {
-    if !extensions.contains("VK_EXT_shader_object") {
-        let is_empty = extensions.is_empty();
-        if !is_empty {
-            extensions += ",";
-        }
-
-        extensions += "VK_EXT_shader_object".into();
-    }
-
+    let capture_mode = CaptureMode::from_name(capture_mode.as_deref().unwrap_or_default())
+        .map_err(|error| ServerError::ErrorMessage(error).into_response())?;
     let shader_flags = if !shader_flags.is_empty() {
         shader_flags
             .split(",")
@@ -515,30 +1278,135 @@ async fn get_shader_binary_form(
     } else {
         Vec::new()
     };
-    let extensions = extensions
-        .split(",")
-        .map(|x| x.to_string())
-        .collect::<Vec<String>>();
-    let file_name = file.metadata.file_name.unwrap_or(String::from("data.spv"));
-    let file_data = file.contents.to_vec();
-    let output_file_name = file_name.replace(".spv", ".bin");
-    let device = create_device(Some(vendor_id), Some(device_id), &extensions).map_err(|error| {
-        ServerError::ErrorMessage(format!("create_device failed: {error}")).into_response()
-    })?;
-    let mut shaders = compile_shaders(&device, &file_data, entry_point.as_str(), shader_flags)
+    let extensions = parse_extensions(&extensions, capture_mode);
+    let files = read_uploaded_files(file);
+    let sources = pair_sources(&files, &entry_point)
+        .map_err(|error| ServerError::ErrorMessage(error).into_response())?;
+
+    let output_base_name = files
+        .first()
+        .map(|(file_name, _)| file_name.trim_end_matches(".spv").to_string())
+        .unwrap_or_default();
+    let device = state
+        .device(Some(vendor_id), Some(device_id), &extensions)
+        .await
         .map_err(|error| {
-            ServerError::ErrorMessage(format!("compile_shaders failed: {error}")).into_response()
+            ServerError::ErrorMessage(format!("create_device failed: {error}")).into_response()
         })?;
-    assert!(shaders.len() == 1);
-    let shader_data = shaders.remove(0).data;
+    let options = CompileOptions {
+        shader_flags: human_flags_to_shader_flags(shader_flags),
+        ..Default::default()
+    };
+    let mut shaders = state
+        .compile(&device, &sources, &options, capture_mode)
+        .map_err(|error| {
+            ServerError::ErrorMessage(format!("compile failed: {error}")).into_response()
+        })?;
+
+    // A single output is returned as is, otherwise outputs are returned as a tar archive.
+    if shaders.len() == 1 {
+        let shader_data = shaders.remove(0).data;
+        let headers = [
+            (header::CONTENT_TYPE, "application/octet-stream".into()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{output_base_name}.bin\""),
+            ),
+        ];
+
+        return Ok((headers, shader_data).into_response());
+    }
 
+    let archive = write_tar_archive(
+        &shaders
+            .iter()
+            .map(|shader| {
+                (
+                    format!("{output_base_name}.{}", shader.file_suffix),
+                    shader.data.as_slice(),
+                )
+            })
+            .collect::<Vec<(String, &[u8])>>(),
+    )
+    .map_err(|error| ServerError::ErrorMessage(error).into_response())?;
     let headers = [
-        (header::CONTENT_TYPE, "application/octet-stream".into()),
+        (header::CONTENT_TYPE, "application/x-tar".into()),
         (
             header::CONTENT_DISPOSITION,
-            format!("attachment; filename=\"{output_file_name}\""),
+            format!("attachment; filename=\"{output_base_name}.tar\""),
         ),
     ];
 
-    Ok((headers, shader_data).into_response())
+    Ok((headers, archive).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(header: &[u8], range: std::ops::Range<usize>) -> &str {
+        let field = &header[range];
+        let end = field.iter().position(|x| *x == 0).unwrap_or(field.len());
+
+        std::str::from_utf8(&field[..end]).unwrap()
+    }
+
+    fn checksum(header: &[u8]) -> u32 {
+        header
+            .iter()
+            .enumerate()
+            .map(|(index, x)| {
+                if (148..156).contains(&index) {
+                    u32::from(b' ')
+                } else {
+                    u32::from(*x)
+                }
+            })
+            .sum()
+    }
+
+    #[test]
+    fn tar_archive_layout() {
+        let first = vec![0xAB; 600];
+        let archive = write_tar_archive(&[
+            ("shader.vert.bin".into(), first.as_slice()),
+            ("shader.frag.bin".into(), b"data".as_slice()),
+        ])
+        .unwrap();
+
+        // Header and two data blocks, header and one data block, then two empty blocks.
+        assert_eq!(archive.len(), 7 * 512);
+
+        let header = &archive[..512];
+        assert_eq!(field(header, 0..100), "shader.vert.bin");
+        assert_eq!(field(header, 124..136), "00000001130");
+        assert_eq!(field(header, 257..263), "ustar");
+        assert_eq!(
+            u32::from_str_radix(field(header, 148..154), 8).unwrap(),
+            checksum(header)
+        );
+        assert_eq!(&archive[512..1112], first.as_slice());
+        assert!(archive[1112..1536].iter().all(|x| *x == 0));
+
+        let header = &archive[1536..2048];
+        assert_eq!(field(header, 0..100), "shader.frag.bin");
+        assert_eq!(field(header, 124..136), "00000000004");
+        assert_eq!(&archive[2048..2052], b"data");
+        assert!(archive[2052..].iter().all(|x| *x == 0));
+    }
+
+    #[test]
+    fn tar_archive_long_names() {
+        let directory = "d".repeat(120);
+        let file_name = "f".repeat(100);
+        let archive =
+            write_tar_archive(&[(format!("{directory}/{file_name}"), b"".as_slice())]).unwrap();
+
+        assert_eq!(field(&archive, 0..100), file_name);
+        assert_eq!(field(&archive, 345..500), directory);
+
+        for name in ["n".repeat(101), format!("{}/{file_name}", "d".repeat(156))] {
+            assert!(write_tar_archive(&[(name, b"".as_slice())]).is_err());
+        }
+    }
 }
@@ -0,0 +1,198 @@
+//! State shared by every request, devices and compiled outputs are kept between requests.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use ash::vk;
+use tokio::sync::OnceCell;
+use usami::{UsamiDevice, UsamiInstance, UsamiResult, UsamiSpecializationValue};
+
+use crate::{create_device, create_instance, CaptureMode, CompileOptions, Shader, ShaderSource};
+
+/// Maximum number of compiled outputs kept in memory, the oldest ones are evicted first.
+const COMPILE_CACHE_CAPACITY: usize = 4096;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct DeviceKey {
+    vendor_id: Option<usize>,
+    device_id: Option<usize>,
+    /// Sorted and without duplicates.
+    extensions: Vec<String>,
+}
+
+/// Everything that can change the output of a compilation, including the driver version.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct CompileKey {
+    vendor_id: u32,
+    device_id: u32,
+    driver_version: u32,
+    enabled_extensions: Vec<String>,
+    capture_mode: CaptureMode,
+    shader_flags: u32,
+    /// Constant id, value kind and value bits.
+    specialization_constants: Vec<(u32, u8, u64)>,
+    push_constant_ranges: Option<Vec<(u32, u32)>>,
+    /// SPIR-V module and entry point of each stage.
+    sources: Vec<(Vec<u8>, String)>,
+}
+
+#[derive(Default)]
+struct CompileCache {
+    entries: HashMap<Arc<CompileKey>, Vec<Shader>>,
+    insertion_order: VecDeque<Arc<CompileKey>>,
+}
+
+impl CompileCache {
+    fn insert(&mut self, key: CompileKey, shaders: Vec<Shader>) {
+        let key = Arc::new(key);
+
+        if self.entries.insert(key.clone(), shaders).is_some() {
+            return;
+        }
+
+        self.insertion_order.push_back(key);
+
+        if self.insertion_order.len() > COMPILE_CACHE_CAPACITY {
+            if let Some(oldest_key) = self.insertion_order.pop_front() {
+                self.entries.remove(&oldest_key);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ServerState {
+    /// Instance used to list physical devices, created on first use.
+    instance: Mutex<Option<UsamiInstance>>,
+    /// Each device is created once, requests for a device being created wait on its cell.
+    devices: Mutex<HashMap<DeviceKey, Arc<OnceCell<Arc<UsamiDevice>>>>>,
+    compile_cache: Mutex<CompileCache>,
+}
+
+impl ServerState {
+    /// Call `callback` with the shared instance, creating it if needed.
+    pub fn with_instance<T>(&self, callback: impl FnOnce(&UsamiInstance) -> T) -> UsamiResult<T> {
+        let mut instance = self.instance.lock().unwrap();
+
+        if instance.is_none() {
+            *instance = Some(create_instance()?);
+        }
+
+        Ok(callback(instance.as_ref().unwrap()))
+    }
+
+    /// Return a device matching the selector and extensions, reusing the one created by a previous request if any.
+    pub async fn device(
+        &self,
+        vendor_id: Option<usize>,
+        device_id: Option<usize>,
+        extensions: &[String],
+    ) -> UsamiResult<Arc<UsamiDevice>> {
+        let mut extensions = extensions.to_vec();
+
+        extensions.sort();
+        extensions.dedup();
+
+        let key = DeviceKey {
+            vendor_id,
+            device_id,
+            extensions,
+        };
+
+        // The map is only locked to find the cell, devices are created outside of the async workers.
+        let cell = self
+            .devices
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+
+        cell.get_or_try_init(|| async move {
+            tokio::task::spawn_blocking(move || {
+                create_device(key.vendor_id, key.device_id, &key.extensions)
+            })
+            .await
+            .unwrap_or_else(|error| std::panic::resume_unwind(error.into_panic()))
+        })
+        .await
+        .cloned()
+    }
+
+    /// Compile the sources, reusing the outputs of an identical previous request if any.
+    pub fn compile(
+        &self,
+        device: &Arc<UsamiDevice>,
+        sources: &[ShaderSource],
+        options: &CompileOptions,
+        capture_mode: CaptureMode,
+    ) -> Result<Vec<Shader>, String> {
+        let key = compile_cache_key(device, sources, options, capture_mode);
+
+        if let Some(shaders) = self.compile_cache.lock().unwrap().entries.get(&key) {
+            return Ok(shaders.clone());
+        }
+
+        let shaders = crate::compile(device, sources, options, capture_mode).map_err(|error| {
+            self.remove_lost_device(device);
+            error
+        })?;
+
+        self.compile_cache
+            .lock()
+            .unwrap()
+            .insert(key, shaders.clone());
+
+        Ok(shaders)
+    }
+
+    /// Stop reusing `device` if it was lost, the next request creates a new one.
+    fn remove_lost_device(&self, device: &Arc<UsamiDevice>) {
+        // Nothing is ever submitted to the queues of pooled devices, so waiting is only a cheap status check.
+        let result = unsafe { device.handle.device_wait_idle() };
+
+        if result == Err(vk::Result::ERROR_DEVICE_LOST) {
+            self.devices
+                .lock()
+                .unwrap()
+                .retain(|_, cell| !matches!(cell.get(), Some(x) if Arc::ptr_eq(x, device)));
+        }
+    }
+}
+
+fn compile_cache_key(
+    device: &UsamiDevice,
+    sources: &[ShaderSource],
+    options: &CompileOptions,
+    capture_mode: CaptureMode,
+) -> CompileKey {
+    let properties = &device.physical_device.properties;
+
+    CompileKey {
+        vendor_id: properties.vendor_id,
+        device_id: properties.device_id,
+        driver_version: properties.driver_version,
+        enabled_extensions: device.enabled_extensions.clone(),
+        capture_mode,
+        shader_flags: options.shader_flags.as_raw(),
+        specialization_constants: options
+            .specialization_constants
+            .iter()
+            .map(|constant| match constant.value {
+                UsamiSpecializationValue::Bool(value) => (constant.id, 0, u64::from(value)),
+                UsamiSpecializationValue::Integer(value) => (constant.id, 1, value as u64),
+                UsamiSpecializationValue::Float(value) => (constant.id, 2, value.to_bits()),
+            })
+            .collect(),
+        push_constant_ranges: options
+            .push_constant_ranges
+            .as_ref()
+            .map(|ranges| ranges.iter().map(|x| (x.offset, x.size)).collect()),
+        sources: sources
+            .iter()
+            .map(|x| (x.spirv.to_vec(), x.entry_point.to_string()))
+            .collect(),
+    }
+}
@@ -1,20 +1,17 @@
-use std::ffi::CString;
-
-use ash::{
-    prelude::VkResult,
-    vk::{
-        self, AccessFlags, BufferCreateFlags, BufferUsageFlags, CommandBufferLevel,
-        CommandBufferUsageFlags, CommandPoolCreateFlags, CommandPoolCreateInfo, ComponentMapping,
-        ComponentSwizzle, ComputePipelineCreateInfo, DescriptorImageInfo, DescriptorPoolCreateInfo,
-        DescriptorSetLayoutCreateInfo, DescriptorType, Extent3D, FenceCreateFlags, Format,
-        ImageAspectFlags, ImageCreateInfo, ImageLayout, ImageSubresourceRange, ImageTiling,
-        ImageType, ImageUsageFlags, ImageViewCreateFlags, ImageViewType, MemoryPropertyFlags,
-        PipelineBindPoint, PipelineCache, PipelineShaderStageCreateInfo, PipelineStageFlags,
-        QueueFlags, SampleCountFlags, ShaderStageFlags, SharingMode, SubmitInfo,
-        WriteDescriptorSet,
-    },
+use std::{ffi::CString, sync::Arc};
+
+use ash::vk::{
+    self, BufferCreateFlags, BufferUsageFlags, CommandPoolCreateFlags, CommandPoolCreateInfo,
+    ComponentMapping, ComponentSwizzle, ComputePipelineCreateInfo, DescriptorPoolCreateInfo,
+    DescriptorSetLayoutCreateInfo, DescriptorType, Extent3D, Format, ImageAspectFlags,
+    ImageCreateInfo, ImageLayout, ImageSubresourceRange, ImageTiling, ImageType, ImageUsageFlags,
+    ImageViewCreateFlags, ImageViewType, MemoryPropertyFlags, PipelineBindPoint, PipelineCache,
+    PipelineShaderStageCreateInfo, QueueFlags, SampleCountFlags, ShaderStageFlags, SharingMode,
+};
+use usami::{
+    UsamiAccess, UsamiDevice, UsamiGraphAccess, UsamiInstance, UsamiPhysicalDeviceSelector,
+    UsamiRenderGraph, UsamiResult,
 };
-use usami::{UsamiDevice, UsamiInstance};
 
 #[derive(Clone, Debug, Copy, Default)]
 #[repr(packed(1))]
@@ -28,7 +25,7 @@ struct UniformBlock {
     color_bias: [f32; 4],
 }
 
-fn main() -> VkResult<()> {
+fn main() -> UsamiResult<()> {
     let extensions = ["VK_EXT_debug_utils".into()];
 
     let width = 128;
@@ -42,23 +39,10 @@ fn main() -> VkResult<()> {
         &extensions,
         false,
     )?;
-    let device = UsamiDevice::new_by_filter(
+    let device = UsamiDevice::new_by_selector(
         instance,
         &[],
-        Box::new(|physical_device| {
-            physical_device
-                .queue_familiy_properties
-                .iter()
-                .enumerate()
-                .find_map(|(i, x)| {
-                    if x.queue_flags.contains(QueueFlags::COMPUTE) {
-                        Some(i as u32)
-                    } else {
-                        None
-                    }
-                })
-                .map(|x| (physical_device, x))
-        }),
+        UsamiPhysicalDeviceSelector::new().queue_flags(QueueFlags::COMPUTE),
     )?;
 
     let output_image_info = ImageCreateInfo::default()
@@ -81,22 +65,24 @@ fn main() -> VkResult<()> {
         output_image_info,
         MemoryPropertyFlags::empty(),
     )?;
-    let output_image_view = output_image.create_simple_image_view(
-        "output_image_view".into(),
-        ImageViewType::TYPE_1D,
-        ImageSubresourceRange::default()
-            .aspect_mask(ImageAspectFlags::COLOR)
-            .base_mip_level(0)
-            .level_count(1)
-            .base_array_layer(0)
-            .layer_count(output_image.array_layers),
-        ComponentMapping::default()
-            .r(ComponentSwizzle::IDENTITY)
-            .g(ComponentSwizzle::IDENTITY)
-            .b(ComponentSwizzle::IDENTITY)
-            .a(ComponentSwizzle::IDENTITY),
-        ImageViewCreateFlags::empty(),
-    )?;
+    let output_image_view = Arc::new(
+        output_image.create_simple_image_view(
+            "output_image_view".into(),
+            ImageViewType::TYPE_1D,
+            ImageSubresourceRange::default()
+                .aspect_mask(ImageAspectFlags::COLOR)
+                .base_mip_level(0)
+                .level_count(1)
+                .base_array_layer(0)
+                .layer_count(output_image.array_layers),
+            ComponentMapping::default()
+                .r(ComponentSwizzle::IDENTITY)
+                .g(ComponentSwizzle::IDENTITY)
+                .b(ComponentSwizzle::IDENTITY)
+                .a(ComponentSwizzle::IDENTITY),
+            ImageViewCreateFlags::empty(),
+        )?,
+    );
 
     let output_readback_buffer = UsamiDevice::create_buffer_with_size(
         &device,
@@ -136,18 +122,15 @@ fn main() -> VkResult<()> {
     let descriptor_sets = descriptor_pool
         .allocate_descriptor_sets("descriptor_set".into(), &[descriptor_set_layout.handle])?;
 
-    unsafe {
-        device.handle.update_descriptor_sets(
-            &[WriteDescriptorSet::default()
-                .dst_set(descriptor_sets[0].handle)
-                .dst_binding(0)
-                .descriptor_type(DescriptorType::STORAGE_IMAGE)
-                .image_info(&[DescriptorImageInfo::default()
-                    .image_layout(ImageLayout::GENERAL)
-                    .image_view(output_image_view.handle)])],
-            &[],
-        );
-    }
+    descriptor_sets[0]
+        .write()
+        .image(
+            0,
+            DescriptorType::STORAGE_IMAGE,
+            &output_image_view,
+            ImageLayout::GENERAL,
+        )
+        .update();
 
     let pipeline_layout = UsamiDevice::create_pipeline_layout(
         &device,
@@ -188,25 +171,23 @@ fn main() -> VkResult<()> {
             .flags(CommandPoolCreateFlags::RESET_COMMAND_BUFFER),
     )?;
 
-    let command_buffers = command_pool.allocate_command_buffers(
-        "command_buffer".into(),
-        CommandBufferLevel::PRIMARY,
-        1,
-    )?;
-
     let pipeline = &pipelines[0];
 
-    command_buffers[0].record(
-        CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+    let vk_device = &device.handle;
+    let mut graph = UsamiRenderGraph::new(&device, "graph".into());
+    let output = graph.import_image(&output_image);
+    let output_readback = graph.import_buffer(&output_readback_buffer);
+
+    graph.add_pass(
+        "dispatch".into(),
+        &[UsamiGraphAccess::image(output, UsamiAccess::ComputeWrite)],
         |_, command_buffer| {
-            let vk_device = &device.handle;
             unsafe {
                 vk_device.cmd_bind_pipeline(
                     command_buffer.handle,
                     PipelineBindPoint::COMPUTE,
                     pipeline.handle,
                 );
-
                 vk_device.cmd_bind_descriptor_sets(
                     command_buffer.handle,
                     PipelineBindPoint::COMPUTE,
@@ -215,20 +196,6 @@ fn main() -> VkResult<()> {
                     &[descriptor_sets[0].handle],
                     &[],
                 );
-            }
-
-            command_buffer.add_image_barrier(
-                &output_image,
-                None,
-                PipelineStageFlags::HOST,
-                PipelineStageFlags::COMPUTE_SHADER,
-                AccessFlags::empty(),
-                AccessFlags::SHADER_WRITE,
-                ImageLayout::UNDEFINED,
-                ImageLayout::GENERAL,
-            )?;
-
-            unsafe {
                 vk_device.cmd_dispatch(
                     command_buffer.handle,
                     group_count_x,
@@ -237,40 +204,45 @@ fn main() -> VkResult<()> {
                 );
             }
 
-            command_buffer.copy_image_to_buffer(
-                &output_image,
-                &output_readback_buffer,
-                &[output_image.buffer_copy(ImageAspectFlags::COLOR, 0, 0, 1)],
-                AccessFlags::empty(),
-                ImageLayout::GENERAL,
-                output_image.array_layers,
-                output_image.mip_levels,
-                ImageAspectFlags::COLOR,
-                PipelineStageFlags::COMPUTE_SHADER,
-            )?;
-
             Ok(())
         },
-    )?;
+    );
+    graph.add_pass(
+        "copy".into(),
+        &[
+            UsamiGraphAccess::image(output, UsamiAccess::TransferRead),
+            UsamiGraphAccess::buffer(output_readback, UsamiAccess::TransferWrite),
+        ],
+        move |resources, command_buffer| {
+            unsafe {
+                vk_device.cmd_copy_image_to_buffer(
+                    command_buffer.handle,
+                    resources.image(output).handle,
+                    ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    resources.buffer(output_readback).handle,
+                    &[resources
+                        .image(output)
+                        .buffer_copy(ImageAspectFlags::COLOR, 0, 0, 1)],
+                );
+            }
 
-    let fence = UsamiDevice::create_fence(&device, "fence".into(), FenceCreateFlags::empty())?;
-    let queue = UsamiDevice::get_device_queue(&device, "queue".into(), device.vk_queue_index, 0)?;
+            Ok(())
+        },
+    );
+    graph.add_pass(
+        "host_read".into(),
+        &[UsamiGraphAccess::buffer(
+            output_readback,
+            UsamiAccess::HostRead,
+        )],
+        |_, _| Ok(()),
+    );
 
-    queue.submit(
-        &[SubmitInfo::default().command_buffers(&[command_buffers[0].handle])],
-        &fence,
-    )?;
-    fence.wait(u64::MAX)?;
-    fence.reset()?;
+    graph.execute(&command_pool)?;
 
-    let output_readback_raw_buffer = output_readback_buffer.device_memory.read_to_vec()?;
+    let output_readback = output_readback_buffer.mapped_slice::<f32>()?;
 
-    for i in 0..4 {
-        let val = f32::from_le_bytes(
-            output_readback_raw_buffer[(i) * 4..(i + 1) * 4]
-                .try_into()
-                .unwrap(),
-        );
+    for (i, val) in output_readback.iter().take(4).enumerate() {
         println!("val{i}: {val}");
     }
     //assert_eq!(val0, 42.0);
@@ -1,21 +1,20 @@
-use std::{ffi::CString, path::PathBuf};
+use std::{ffi::CString, path::PathBuf, sync::Arc};
 
 use argh::FromArgs;
-use ash::{
-    prelude::VkResult,
-    vk::{
-        self, AccessFlags, BufferCreateFlags, BufferUsageFlags, CommandBufferLevel,
-        CommandBufferUsageFlags, CommandPoolCreateFlags, CommandPoolCreateInfo, ComponentMapping,
-        ComponentSwizzle, ComputePipelineCreateInfo, DescriptorBufferInfo, DescriptorImageInfo,
-        DescriptorPoolCreateInfo, DescriptorSetLayoutCreateInfo, Extent3D, FenceCreateFlags,
-        Format, ImageAspectFlags, ImageCreateInfo, ImageLayout, ImageSubresourceRange, ImageTiling,
-        ImageType, ImageUsageFlags, ImageViewCreateFlags, ImageViewType, MemoryPropertyFlags,
-        PipelineBindPoint, PipelineCache, PipelineShaderStageCreateInfo, PipelineStageFlags,
-        QueueFlags, SampleCountFlags, ShaderStageFlags, SharingMode, SubmitInfo,
-        WriteDescriptorSet,
-    },
+use ash::vk::{
+    self, AccessFlags, BufferCreateFlags, BufferUsageFlags, CommandBufferLevel,
+    CommandBufferUsageFlags, CommandPoolCreateFlags, CommandPoolCreateInfo, ComponentMapping,
+    ComponentSwizzle, ComputePipelineCreateInfo, DescriptorPoolCreateFlags, Extent3D,
+    FenceCreateFlags, Format, ImageAspectFlags, ImageCreateInfo, ImageLayout,
+    ImageSubresourceRange, ImageTiling, ImageType, ImageUsageFlags, ImageViewCreateFlags,
+    ImageViewType, MemoryPropertyFlags, PipelineBindPoint, PipelineCache,
+    PipelineShaderStageCreateInfo, PipelineStageFlags, QueueFlags, SampleCountFlags,
+    ShaderStageFlags, SharingMode, SubmitInfo,
+};
+use usami::{
+    format::get_format_info, ResultExt, UsamiDevice, UsamiInstance, UsamiPhysicalDeviceSelector,
+    UsamiResult, UsamiSpecializationBuilder, UsamiSpecializationValue,
 };
-use usami::{UsamiDevice, UsamiInstance};
 
 #[derive(FromArgs)]
 /// Reach new heights.
@@ -48,16 +47,52 @@ struct Args {
     #[argh(option)]
     output_buffer_file: Option<PathBuf>,
 
-    /// make the input a buffer instead of a uniform.
-    #[argh(option, default = "false")]
-    input_as_buffer: bool,
-
     /// vulkan API raw version to use.
     #[argh(option, default = "0x400000")]
     vk_version: u32,
+
+    /// a specialization constant as `id=value`, the value is converted to the type of the constant in the shader.
+    #[argh(option, from_str_fn(parse_specialization_constant))]
+    spec: Vec<(u32, UsamiSpecializationValue)>,
+
+    /// the path of the file to load the push constant data.
+    #[argh(option)]
+    push_constants: Option<PathBuf>,
+}
+
+/// Parse `id=value` where value is a boolean, an integer (decimal or `0x` hexadecimal) or a float.
+fn parse_specialization_constant(spec: &str) -> Result<(u32, UsamiSpecializationValue), String> {
+    let (constant_id, value) = spec
+        .split_once('=')
+        .ok_or_else(|| format!("Expected id=value, got \"{spec}\""))?;
+    let constant_id = constant_id.trim().parse().map_err(|error| {
+        format!("Invalid specialization constant id \"{constant_id}\": {error}")
+    })?;
+    let value = value.trim();
+
+    let value = match value {
+        "true" => UsamiSpecializationValue::Bool(true),
+        "false" => UsamiSpecializationValue::Bool(false),
+        _ => {
+            if let Some(hex) = value.strip_prefix("0x") {
+                u64::from_str_radix(hex, 16)
+                    .map(|x| UsamiSpecializationValue::Integer(x as i64))
+                    .map_err(|error| format!("Invalid value \"{value}\": {error}"))?
+            } else if let Ok(integer) = value.parse::<i64>() {
+                UsamiSpecializationValue::Integer(integer)
+            } else {
+                value
+                    .parse::<f64>()
+                    .map(UsamiSpecializationValue::Float)
+                    .map_err(|error| format!("Invalid value \"{value}\": {error}"))?
+            }
+        }
+    };
+
+    Ok((constant_id, value))
 }
 
-fn main() -> VkResult<()> {
+fn main() -> UsamiResult<()> {
     let args: Args = argh::from_env();
 
     let group_count_x = args.group_count_x.unwrap_or(1);
@@ -74,145 +109,41 @@ fn main() -> VkResult<()> {
         &extensions,
         true,
     )?;
-    let device = UsamiDevice::new_by_filter(
+    let device = UsamiDevice::new_by_selector(
         instance,
         &args.device_extension,
-        Box::new(|physical_device| {
-            physical_device
-                .queue_familiy_properties
-                .iter()
-                .enumerate()
-                .find_map(|(i, x)| {
-                    if x.queue_flags.contains(QueueFlags::COMPUTE) {
-                        Some(i as u32)
-                    } else {
-                        None
-                    }
-                })
-                .map(|x| (physical_device, x))
-        }),
+        UsamiPhysicalDeviceSelector::new().queue_flags(QueueFlags::COMPUTE),
     )?;
 
-    let mut active_shaders = Vec::new();
-    let mut shader_stage_create_infos = Vec::new();
-
     let shader_entrypoint_name = CString::new("main").unwrap();
 
-    let shader_code = usami::utils::read_spv_file(args.compute_path);
+    let shader_code = usami::utils::read_spv_file(args.compute_path)?;
     let shader = UsamiDevice::create_shader(&device, "compute_shader".into(), &shader_code)?;
+    let reflection = shader.reflect()?;
+    let entry_point = reflection.entry_point("main")?;
 
-    shader_stage_create_infos.push(
-        PipelineShaderStageCreateInfo::default()
-            .module(shader.handle)
-            .name(shader_entrypoint_name.as_c_str())
-            .stage(ShaderStageFlags::COMPUTE),
-    );
-    active_shaders.push(shader);
-
-    let input_desc_type = if args.input_as_buffer {
-        vk::DescriptorType::STORAGE_BUFFER
-    } else {
-        vk::DescriptorType::UNIFORM_BUFFER
-    };
-
-    let descriptor_pool_sizes = [
-        vk::DescriptorPoolSize {
-            ty: vk::DescriptorType::STORAGE_BUFFER,
-            descriptor_count: 1,
-        },
-        vk::DescriptorPoolSize {
-            ty: input_desc_type,
-            descriptor_count: 1,
-        },
-        vk::DescriptorPoolSize {
-            ty: vk::DescriptorType::STORAGE_IMAGE,
-            descriptor_count: 1,
-        },
-        vk::DescriptorPoolSize {
-            ty: vk::DescriptorType::STORAGE_BUFFER,
-            descriptor_count: 1,
-        },
-    ];
-
-    let descriptor_pool_create_info = DescriptorPoolCreateInfo::default()
-        .pool_sizes(&descriptor_pool_sizes)
-        .max_sets(1);
-
-    let descriptor_pool = UsamiDevice::create_descriptor_pool(
+    let pipeline_layout = UsamiDevice::create_reflected_pipeline_layout(
         &device,
-        "descriptor_pool".into(),
-        descriptor_pool_create_info,
+        "base_pipeline_layout".into(),
+        &[entry_point],
     )?;
 
-    let desc_layout_bindings = [
-        vk::DescriptorSetLayoutBinding::default()
-            .binding(0)
-            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
-            .descriptor_count(1)
-            .stage_flags(ShaderStageFlags::COMPUTE),
-        vk::DescriptorSetLayoutBinding::default()
-            .binding(1)
-            .descriptor_type(input_desc_type)
-            .descriptor_count(1)
-            .stage_flags(ShaderStageFlags::COMPUTE),
-        vk::DescriptorSetLayoutBinding::default()
-            .binding(2)
-            .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
-            .descriptor_count(1)
-            .stage_flags(ShaderStageFlags::COMPUTE),
-        vk::DescriptorSetLayoutBinding::default()
-            .binding(3)
-            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
-            .descriptor_count(1)
-            .stage_flags(ShaderStageFlags::COMPUTE),
-    ];
-    let descriptor_set_layout = UsamiDevice::create_descriptor_set_layout(
+    let mut descriptor_allocator = UsamiDevice::create_descriptor_allocator(
         &device,
-        "descriptor_set_layout".into(),
-        DescriptorSetLayoutCreateInfo::default().bindings(&desc_layout_bindings),
-    )?;
-
-    let descriptor_sets = descriptor_pool
-        .allocate_descriptor_sets("descriptor_set".into(), &[descriptor_set_layout.handle]);
-
-    println!("{:?}", descriptor_sets.as_ref().err());
-
-    let descriptor_sets = descriptor_sets?;
+        "descriptor_allocator".into(),
+        DescriptorPoolCreateFlags::empty(),
+        1,
+    );
+    let descriptor_sets = descriptor_allocator
+        .allocate_for_pipeline_layout("descriptor_set".into(), &pipeline_layout)?;
 
     let uniform_block_data = if let Some(input_buffer_file) = &args.input_buffer_file {
-        std::fs::read(input_buffer_file).expect("Cannot read input buffer")
+        std::fs::read(input_buffer_file).context("Cannot read input buffer")?
     } else {
         0x42u32.to_le_bytes().to_vec()
     };
 
-    let uniform_block = UsamiDevice::create_buffer(
-        &device,
-        "uniform_block".into(),
-        BufferCreateFlags::empty(),
-        SharingMode::EXCLUSIVE,
-        if args.input_as_buffer {
-            BufferUsageFlags::STORAGE_BUFFER
-        } else {
-            BufferUsageFlags::UNIFORM_BUFFER
-        },
-        &uniform_block_data,
-    )?;
-
-
-    let uniform_block2 = UsamiDevice::create_buffer(
-        &device,
-        "uniform_block2".into(),
-        BufferCreateFlags::empty(),
-        SharingMode::EXCLUSIVE,
-        if args.input_as_buffer {
-            BufferUsageFlags::STORAGE_BUFFER
-        } else {
-            BufferUsageFlags::UNIFORM_BUFFER
-        },
-        &uniform_block_data,
-    )?;
-
-    let data_buffer = UsamiDevice::create_buffer_with_size(
+    let data_buffer = Arc::new(UsamiDevice::create_buffer_with_size(
         &device,
         "data_buffer".into(),
         BufferCreateFlags::empty(),
@@ -220,7 +151,7 @@ fn main() -> VkResult<()> {
         BufferUsageFlags::STORAGE_BUFFER,
         0x1000,
         MemoryPropertyFlags::HOST_VISIBLE,
-    )?;
+    )?);
 
     let output_image_info = ImageCreateInfo::default()
         .image_type(ImageType::TYPE_1D)
@@ -242,22 +173,24 @@ fn main() -> VkResult<()> {
         output_image_info,
         MemoryPropertyFlags::empty(),
     )?;
-    let output_image_view = output_image.create_simple_image_view(
-        "output_image_view".into(),
-        ImageViewType::TYPE_1D,
-        ImageSubresourceRange::default()
-            .aspect_mask(ImageAspectFlags::COLOR)
-            .base_mip_level(0)
-            .level_count(1)
-            .base_array_layer(0)
-            .layer_count(output_image.array_layers),
-        ComponentMapping::default()
-            .r(ComponentSwizzle::IDENTITY)
-            .g(ComponentSwizzle::IDENTITY)
-            .b(ComponentSwizzle::IDENTITY)
-            .a(ComponentSwizzle::IDENTITY),
-        ImageViewCreateFlags::empty(),
-    )?;
+    let output_image_view = Arc::new(
+        output_image.create_simple_image_view(
+            "output_image_view".into(),
+            ImageViewType::TYPE_1D,
+            ImageSubresourceRange::default()
+                .aspect_mask(ImageAspectFlags::COLOR)
+                .base_mip_level(0)
+                .level_count(1)
+                .base_array_layer(0)
+                .layer_count(output_image.array_layers),
+            ComponentMapping::default()
+                .r(ComponentSwizzle::IDENTITY)
+                .g(ComponentSwizzle::IDENTITY)
+                .b(ComponentSwizzle::IDENTITY)
+                .a(ComponentSwizzle::IDENTITY),
+            ImageViewCreateFlags::empty(),
+        )?,
+    );
 
     let output_readback_buffer = UsamiDevice::create_buffer_with_size(
         &device,
@@ -265,59 +198,110 @@ fn main() -> VkResult<()> {
         BufferCreateFlags::empty(),
         SharingMode::EXCLUSIVE,
         BufferUsageFlags::TRANSFER_DST,
-        u64::from(width * 4 * output_image_info.array_layers),
+        get_format_info(output_image_info.format)?
+            .size_for_extent(output_image_info.extent, ImageAspectFlags::COLOR)
+            * u64::from(output_image_info.array_layers),
         MemoryPropertyFlags::HOST_VISIBLE,
     )?;
 
-    unsafe {
-        device.handle.update_descriptor_sets(
-            &[
-                WriteDescriptorSet::default()
-                    .dst_set(descriptor_sets[0].handle)
-                    .dst_binding(0)
-                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
-                    .buffer_info(&[DescriptorBufferInfo::default()
-                        .buffer(data_buffer.handle)
-                        .offset(0)
-                        .range(vk::WHOLE_SIZE)]),
-                WriteDescriptorSet::default()
-                    .dst_set(descriptor_sets[0].handle)
-                    .dst_binding(1)
-                    .descriptor_type(input_desc_type)
-                    .buffer_info(&[DescriptorBufferInfo::default()
-                        .buffer(uniform_block.handle)
-                        .offset(0)
-                        .range(vk::WHOLE_SIZE)]),
-                WriteDescriptorSet::default()
-                    .dst_set(descriptor_sets[0].handle)
-                    .dst_binding(2)
-                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
-                    .image_info(&[DescriptorImageInfo::default()
-                        .image_layout(ImageLayout::GENERAL)
-                        .image_view(output_image_view.handle)]),
-                WriteDescriptorSet::default()
-                    .dst_set(descriptor_sets[0].handle)
-                    .dst_binding(3)
-                    .descriptor_type(input_desc_type)
-                    .buffer_info(&[DescriptorBufferInfo::default()
-                        .buffer(uniform_block2.handle)
-                        .offset(0)
-                        .range(vk::WHOLE_SIZE)]),
-            ],
-            &[],
-        );
+    // Set 0 binding 0 is the output buffer, every other buffer gets a copy of the input and storage images use the output image.
+    for descriptor_binding in &entry_point.descriptor_bindings {
+        let writer = descriptor_sets[descriptor_binding.set as usize].write();
+
+        match descriptor_binding.descriptor_type {
+            vk::DescriptorType::STORAGE_BUFFER | vk::DescriptorType::UNIFORM_BUFFER => {
+                let buffer = if descriptor_binding.set == 0 && descriptor_binding.binding == 0 {
+                    data_buffer.clone()
+                } else {
+                    Arc::new(UsamiDevice::create_buffer(
+                        &device,
+                        format!(
+                            "input_buffer_{}_{}",
+                            descriptor_binding.set, descriptor_binding.binding
+                        ),
+                        BufferCreateFlags::empty(),
+                        SharingMode::EXCLUSIVE,
+                        BufferUsageFlags::STORAGE_BUFFER | BufferUsageFlags::UNIFORM_BUFFER,
+                        &uniform_block_data,
+                    )?)
+                };
+
+                writer
+                    .buffer(
+                        descriptor_binding.binding,
+                        descriptor_binding.descriptor_type,
+                        &buffer,
+                        0,
+                        vk::WHOLE_SIZE,
+                    )
+                    .update();
+            }
+            vk::DescriptorType::STORAGE_IMAGE => {
+                writer
+                    .image(
+                        descriptor_binding.binding,
+                        descriptor_binding.descriptor_type,
+                        &output_image_view,
+                        ImageLayout::GENERAL,
+                    )
+                    .update();
+            }
+            descriptor_type => {
+                println!(
+                    "Unsupported descriptor type {descriptor_type:?} for {}, leaving it unbound",
+                    descriptor_binding.name
+                );
+            }
+        }
     }
 
-    let pipeline_layout = UsamiDevice::create_pipeline_layout(
-        &device,
-        "base_pipeline_layout".into(),
-        &[descriptor_set_layout.handle],
-        &[],
-    )?;
+    let mut specialization = UsamiSpecializationBuilder::new();
+
+    for (constant_id, value) in &args.spec {
+        let (kind, size) = match reflection
+            .specialization_constants
+            .iter()
+            .find(|x| x.constant_id == *constant_id)
+        {
+            Some(constant) => (constant.kind, constant.size()),
+            None => {
+                println!("Specialization constant {constant_id} is not used by the shader");
+                (value.kind(), 4)
+            }
+        };
+        specialization = specialization.constant_value(*constant_id, *value, kind, size);
+    }
+
+    let specialization_info = specialization.info();
+    let mut stage_create_info = PipelineShaderStageCreateInfo::default()
+        .module(shader.handle)
+        .name(shader_entrypoint_name.as_c_str())
+        .stage(ShaderStageFlags::COMPUTE);
+
+    if !specialization.is_empty() {
+        stage_create_info = stage_create_info.specialization_info(&specialization_info);
+    }
 
     let compute_pipeline_create_info = ComputePipelineCreateInfo::default()
         .layout(pipeline_layout.handle)
-        .stage(shader_stage_create_infos[0]);
+        .stage(stage_create_info);
+
+    let mut push_constant_data = if let Some(push_constants) = &args.push_constants {
+        std::fs::read(push_constants).context("Cannot read push constants")?
+    } else {
+        Vec::new()
+    };
+
+    // Push constant updates are made of whole words, like the ranges they update.
+    if !push_constant_data.len().is_multiple_of(4) {
+        println!("Padding the push constant data with zeros to a multiple of 4 bytes");
+        push_constant_data.resize(push_constant_data.len().next_multiple_of(4), 0);
+    }
+    let push_constant_range = entry_point.push_constant_ranges.first().copied();
+
+    if !push_constant_data.is_empty() && push_constant_range.is_none() {
+        println!("The shader has no push constants, ignoring the push constant data");
+    }
 
     let pipelines = UsamiDevice::create_compute_pipelines(
         &device,
@@ -347,14 +331,19 @@ fn main() -> VkResult<()> {
         |_, command_buffer| {
             let vk_device = &device.handle;
             unsafe {
-                vk_device.cmd_bind_descriptor_sets(
-                    command_buffer.handle,
-                    PipelineBindPoint::COMPUTE,
-                    pipeline_layout.handle,
-                    0,
-                    &[descriptor_sets[0].handle],
-                    &[],
-                );
+                if !descriptor_sets.is_empty() {
+                    vk_device.cmd_bind_descriptor_sets(
+                        command_buffer.handle,
+                        PipelineBindPoint::COMPUTE,
+                        pipeline_layout.handle,
+                        0,
+                        &descriptor_sets
+                            .iter()
+                            .map(|x| x.handle)
+                            .collect::<Vec<vk::DescriptorSet>>(),
+                        &[],
+                    );
+                }
 
                 vk_device.cmd_bind_pipeline(
                     command_buffer.handle,
@@ -363,6 +352,21 @@ fn main() -> VkResult<()> {
                 );
             }
 
+            if let Some(range) = push_constant_range {
+                let end = push_constant_data
+                    .len()
+                    .min((range.offset + range.size) as usize);
+
+                if end > range.offset as usize {
+                    command_buffer.push_constants_slice(
+                        &pipeline_layout,
+                        range.stage_flags,
+                        range.offset,
+                        &push_constant_data[range.offset as usize..end],
+                    );
+                }
+            }
+
             unsafe {
                 vk_device.cmd_dispatch(
                     command_buffer.handle,
@@ -398,10 +402,11 @@ fn main() -> VkResult<()> {
     fence.wait(u64::MAX)?;
     fence.reset()?;
 
-    let data_buffer_readback = data_buffer.device_memory.read_to_vec().unwrap();
+    let data_buffer_readback = data_buffer.allocation.read_to_vec()?;
 
     if let Some(output_buffer_file) = args.output_buffer_file {
-        std::fs::write(output_buffer_file, &data_buffer_readback).unwrap();
+        std::fs::write(output_buffer_file, &data_buffer_readback)
+            .context("Cannot write output buffer")?;
     }
 
     Ok(())
@@ -1,30 +1,18 @@
-use std::ffi::CString;
-
-use ash::{
-    prelude::VkResult,
-    vk::{
-        self, AccessFlags, AttachmentDescription, AttachmentLoadOp, AttachmentReference,
-        AttachmentStoreOp, BlendFactor, BlendOp, BorderColor, BufferCreateFlags, BufferUsageFlags,
-        ClearValue, ColorComponentFlags, CommandBufferLevel, CommandPoolCreateFlags,
-        CommandPoolCreateInfo, CompareOp, ComponentMapping, ComponentSwizzle, DescriptorBufferInfo,
-        DescriptorImageInfo, DescriptorPoolCreateInfo, DescriptorPoolSize,
-        DescriptorSetLayoutCreateInfo, DescriptorType, DynamicState, FenceCreateFlags, Filter,
-        Format, FrontFace, GraphicsPipelineCreateInfo, ImageAspectFlags, ImageLayout,
-        ImageSubresourceRange, ImageUsageFlags, ImageViewCreateFlags, ImageViewType, IndexType,
-        LogicOp, PipelineBindPoint, PipelineCache, PipelineColorBlendAttachmentState,
-        PipelineColorBlendStateCreateInfo, PipelineDepthStencilStateCreateInfo,
-        PipelineDynamicStateCreateInfo, PipelineInputAssemblyStateCreateInfo,
-        PipelineMultisampleStateCreateInfo, PipelineRasterizationStateCreateInfo,
-        PipelineShaderStageCreateInfo, PipelineStageFlags, PipelineVertexInputStateCreateInfo,
-        PipelineViewportStateCreateInfo, PolygonMode, PrimitiveTopology, QueueFlags,
-        RenderPassBeginInfo, RenderPassCreateInfo, SampleCountFlags, SamplerAddressMode,
-        SamplerCreateInfo, SamplerMipmapMode, ShaderStageFlags, SharingMode, StencilOp,
-        StencilOpState, SubmitInfo, SubpassContents, SubpassDependency, SubpassDescription,
-        VertexInputAttributeDescription, VertexInputBindingDescription, VertexInputRate,
-        WriteDescriptorSet,
-    },
+use ash::vk::{
+    self, AccessFlags, AttachmentDescription, AttachmentLoadOp, AttachmentReference,
+    AttachmentStoreOp, BorderColor, BufferCreateFlags, BufferUsageFlags, ClearValue,
+    CommandPoolCreateFlags, CommandPoolCreateInfo, CompareOp, ComponentMapping, ComponentSwizzle,
+    DescriptorBufferInfo, DescriptorImageInfo, DescriptorPoolCreateInfo, DescriptorPoolSize,
+    DescriptorSetLayoutCreateInfo, DescriptorType, Filter, Format, ImageAspectFlags, ImageLayout,
+    ImageSubresourceRange, ImageUsageFlags, ImageViewCreateFlags, ImageViewType, IndexType,
+    PipelineBindPoint, PipelineStageFlags, RenderPassBeginInfo, RenderPassCreateInfo,
+    SamplerAddressMode, SamplerCreateInfo, SamplerMipmapMode, ShaderStageFlags, SharingMode,
+    SubpassContents, SubpassDependency, SubpassDescription, VertexInputRate, WriteDescriptorSet,
+};
+use usami::{
+    offset_of, UsamiAccess, UsamiDevice, UsamiGraphAccess, UsamiGraphicsPipelineBuilder,
+    UsamiInstance, UsamiPhysicalDeviceSelector, UsamiPresentation, UsamiRenderGraph, UsamiResult,
 };
-use usami::{offset_of, UsamiDevice, UsamiInstance, UsamiPresentation};
 
 #[derive(Clone, Debug, Copy)]
 #[repr(C)]
@@ -45,7 +33,7 @@ struct UniformBlock {
     color_bias: [f32; 4],
 }
 
-fn main() -> VkResult<()> {
+fn main() -> UsamiResult<()> {
     let extensions = ["VK_EXT_debug_utils".into()];
 
     let width = 1920;
@@ -53,24 +41,7 @@ fn main() -> VkResult<()> {
     let mipmap_count = usami::utils::compute_mip_pyramid_levels(width, height);
 
     let instance = UsamiInstance::new("debug", "usami", vk::API_VERSION_1_1, &extensions, true)?;
-    let device = UsamiDevice::new_by_filter(
-        instance,
-        &[],
-        Box::new(|physical_device| {
-            physical_device
-                .queue_familiy_properties
-                .iter()
-                .enumerate()
-                .find_map(|(i, x)| {
-                    if x.queue_flags.contains(QueueFlags::GRAPHICS) {
-                        Some(i as u32)
-                    } else {
-                        None
-                    }
-                })
-                .map(|x| (physical_device, x))
-        }),
-    )?;
+    let device = UsamiDevice::new_by_selector(instance, &[], UsamiPhysicalDeviceSelector::new())?;
     let presentation = UsamiPresentation::new(&device, width, height)?;
 
     let command_pool = UsamiDevice::create_command_pool(
@@ -89,7 +60,7 @@ fn main() -> VkResult<()> {
         "input_image".into(),
         &gradient_raw_image,
         ImageUsageFlags::SAMPLED,
-        ImageLayout::GENERAL,
+        ImageLayout::SHADER_READ_ONLY_OPTIMAL,
     )?;
 
     let index_buffer_data = [0u32, 1, 2, 2, 3, 0];
@@ -145,13 +116,12 @@ fn main() -> VkResult<()> {
         &vertices,
     )?;
 
-    let vertex_shader_code = usami::utils::read_spv_file("./resources/debug/main.vert.spv");
-    let frag_shader_code = usami::utils::read_spv_file("./resources/debug/main.frag.spv");
+    let vertex_shader_code = usami::utils::read_spv_file("./resources/debug/main.vert.spv")?;
+    let frag_shader_code = usami::utils::read_spv_file("./resources/debug/main.frag.spv")?;
 
     let vertex_shader =
         UsamiDevice::create_shader(&device, "vertex_shader".into(), &vertex_shader_code)?;
     let frag_shader = UsamiDevice::create_shader(&device, "frag_shader".into(), &frag_shader_code)?;
-    let shader_entrypoint_name = CString::new("main").unwrap();
 
     let uniform_descriptor_pool = UsamiDevice::create_descriptor_pool(
         &device,
@@ -260,7 +230,7 @@ fn main() -> VkResult<()> {
                     .dst_binding(0)
                     .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
                     .image_info(&[DescriptorImageInfo::default()
-                        .image_layout(ImageLayout::GENERAL)
+                        .image_layout(ImageLayout::SHADER_READ_ONLY_OPTIMAL)
                         .image_view(input_image_view.handle)
                         .sampler(sampler)]),
             ],
@@ -278,88 +248,9 @@ fn main() -> VkResult<()> {
         &[],
     )?;
 
-    let shader_stage_create_infos = [
-        PipelineShaderStageCreateInfo::default()
-            .module(vertex_shader.handle)
-            .name(shader_entrypoint_name.as_c_str())
-            .stage(ShaderStageFlags::VERTEX),
-        PipelineShaderStageCreateInfo::default()
-            .module(frag_shader.handle)
-            .name(shader_entrypoint_name.as_c_str())
-            .stage(ShaderStageFlags::FRAGMENT),
-    ];
-
-    let vertex_input_binding_descriptions = [VertexInputBindingDescription::default()
-        .binding(0)
-        .stride(std::mem::size_of::<Vertex>() as u32)
-        .input_rate(VertexInputRate::VERTEX)];
-
-    let vertex_input_attribute_descriptions = [
-        VertexInputAttributeDescription::default()
-            .location(0)
-            .binding(0)
-            .offset(offset_of!(Vertex, pos) as u32)
-            .format(Format::R32G32B32A32_SFLOAT),
-        VertexInputAttributeDescription::default()
-            .location(1)
-            .binding(0)
-            .offset(offset_of!(Vertex, uv) as u32)
-            .format(Format::R32G32_SFLOAT),
-    ];
-
-    let vertex_input_state_create_info = PipelineVertexInputStateCreateInfo::default()
-        .vertex_attribute_descriptions(&vertex_input_attribute_descriptions)
-        .vertex_binding_descriptions(&vertex_input_binding_descriptions);
-
-    let vertex_input_assembly_state_create_info =
-        PipelineInputAssemblyStateCreateInfo::default().topology(PrimitiveTopology::TRIANGLE_LIST);
-
     let scissors = [presentation.rect2d()];
     let viewports = [presentation.viewport()];
 
-    let viewport_state_create_info = PipelineViewportStateCreateInfo::default()
-        .scissors(&scissors)
-        .viewports(&viewports);
-
-    let rasterization_create_info = PipelineRasterizationStateCreateInfo::default()
-        .front_face(FrontFace::COUNTER_CLOCKWISE)
-        .polygon_mode(PolygonMode::FILL)
-        .line_width(1.0);
-
-    let multisample_state_create_info = PipelineMultisampleStateCreateInfo::default()
-        .rasterization_samples(SampleCountFlags::TYPE_1);
-
-    let noop_stencil = StencilOpState::default()
-        .fail_op(StencilOp::KEEP)
-        .pass_op(StencilOp::KEEP)
-        .depth_fail_op(StencilOp::KEEP)
-        .compare_op(CompareOp::ALWAYS);
-
-    let depth_state_create_info = PipelineDepthStencilStateCreateInfo::default()
-        .depth_test_enable(true)
-        .depth_write_enable(true)
-        .depth_compare_op(CompareOp::LESS_OR_EQUAL)
-        .front(noop_stencil)
-        .back(noop_stencil)
-        .max_depth_bounds(1.0);
-
-    let attachements = [PipelineColorBlendAttachmentState::default()
-        .blend_enable(false)
-        .src_color_blend_factor(BlendFactor::SRC_COLOR)
-        .dst_color_blend_factor(BlendFactor::ONE_MINUS_DST_COLOR)
-        .color_blend_op(BlendOp::ADD)
-        .src_alpha_blend_factor(BlendFactor::ZERO)
-        .dst_alpha_blend_factor(BlendFactor::ZERO)
-        .alpha_blend_op(BlendOp::ADD)
-        .color_write_mask(ColorComponentFlags::RGBA)];
-
-    let color_blend_create_state = PipelineColorBlendStateCreateInfo::default()
-        .logic_op(LogicOp::CLEAR)
-        .attachments(&attachements);
-
-    let dynamic_state_create_info = PipelineDynamicStateCreateInfo::default()
-        .dynamic_states(&[DynamicState::VIEWPORT, DynamicState::SCISSOR]);
-
     let renderpass_attachments = [AttachmentDescription::default()
         .format(presentation.image.format)
         .samples(presentation.image.samples)
@@ -388,43 +279,40 @@ fn main() -> VkResult<()> {
     let render_pass =
         UsamiDevice::create_render_pass(&device, "render_pass".into(), render_pass_create_info)?;
 
-    let graphics_pipeline_create_info = GraphicsPipelineCreateInfo::default()
-        .stages(&shader_stage_create_infos)
-        .vertex_input_state(&vertex_input_state_create_info)
-        .input_assembly_state(&vertex_input_assembly_state_create_info)
-        .viewport_state(&viewport_state_create_info)
-        .rasterization_state(&rasterization_create_info)
-        .multisample_state(&multisample_state_create_info)
-        .depth_stencil_state(&depth_state_create_info)
-        .color_blend_state(&color_blend_create_state)
-        .dynamic_state(&dynamic_state_create_info)
-        .layout(pipeline_layout.handle)
-        .render_pass(render_pass.handle);
-
-    let pipelines = UsamiDevice::create_graphics_pipelines(
-        &device,
-        "pipeline".into(),
-        PipelineCache::null(),
-        &[graphics_pipeline_create_info],
-    )?;
-
-    let graphic_pipeline = &pipelines[0];
+    let graphic_pipeline = UsamiGraphicsPipelineBuilder::new()
+        .vertex_shader(&vertex_shader)
+        .fragment_shader(&frag_shader)
+        .vertex_binding(
+            0,
+            std::mem::size_of::<Vertex>() as u32,
+            VertexInputRate::VERTEX,
+        )
+        .vertex_attribute(
+            0,
+            0,
+            Format::R32G32B32A32_SFLOAT,
+            offset_of!(Vertex, pos) as u32,
+        )
+        .vertex_attribute(1, 0, Format::R32G32_SFLOAT, offset_of!(Vertex, uv) as u32)
+        .depth(true, true, CompareOp::LESS_OR_EQUAL)
+        .render_pass(&render_pass, 0)
+        .build(&device, "pipeline".into(), &pipeline_layout)?;
 
     let framebuffer =
         presentation.create_framebuffer(&device, "framebuffer".into(), &render_pass)?;
 
-    let command_buffers = command_pool.allocate_command_buffers(
-        "command_buffer".into(),
-        CommandBufferLevel::PRIMARY,
-        1,
-    )?;
+    let vk_device = &device.handle;
+    let mut graph = UsamiRenderGraph::new(&device, "graph".into());
+    let output = graph.import_image(&presentation.image);
+    let input = graph.import_image(&input_image);
 
-    usami::utils::record_command_buffer_with_image_dep(
-        &command_buffers[0],
-        &presentation.image,
-        &presentation.buffer_readback,
-        |device, command_buffer, _image| {
-            let vk_device = &device.handle;
+    graph.add_pass(
+        "draw".into(),
+        &[
+            UsamiGraphAccess::image(output, UsamiAccess::ColorAttachmentWrite),
+            UsamiGraphAccess::image(input, UsamiAccess::FragmentSampledRead),
+        ],
+        |_, command_buffer| {
             let clear_values = [ClearValue {
                 color: vk::ClearColorValue {
                     float32: [0.0, 0.0, 0.0, 0.0],
@@ -443,7 +331,7 @@ fn main() -> VkResult<()> {
                     &render_pass_begin_info,
                     SubpassContents::INLINE,
                 );
-                device.handle.cmd_bind_descriptor_sets(
+                vk_device.cmd_bind_descriptor_sets(
                     command_buffer.handle,
                     PipelineBindPoint::GRAPHICS,
                     pipeline_layout.handle,
@@ -451,7 +339,7 @@ fn main() -> VkResult<()> {
                     &[uniform_descriptor_set.handle],
                     &[],
                 );
-                device.handle.cmd_bind_descriptor_sets(
+                vk_device.cmd_bind_descriptor_sets(
                     command_buffer.handle,
                     PipelineBindPoint::GRAPHICS,
                     pipeline_layout.handle,
@@ -487,27 +375,19 @@ fn main() -> VkResult<()> {
                     1,
                 );
                 vk_device.cmd_end_render_pass(command_buffer.handle);
-
-                ImageLayout::COLOR_ATTACHMENT_OPTIMAL
             }
-        },
-    )?;
 
-    let fence = UsamiDevice::create_fence(&device, "fence".into(), FenceCreateFlags::empty())?;
-    let queue = UsamiDevice::get_device_queue(&device, "queue".into(), device.vk_queue_index, 0)?;
+            Ok(())
+        },
+    );
 
-    queue.submit(
-        &[SubmitInfo::default().command_buffers(&[command_buffers[0].handle])],
-        &fence,
-    )?;
-    fence.wait(u64::MAX)?;
-    fence.reset()?;
+    graph.execute(&command_pool)?;
 
     unsafe {
         device.handle.destroy_sampler(sampler, None);
     }
 
-    let res = presentation.buffer_readback.device_memory.read_to_vec()?;
+    let res = presentation.read_back(&command_pool)?;
 
     image::save_buffer_with_format(
         "output.bmp",
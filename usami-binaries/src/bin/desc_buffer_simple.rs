@@ -0,0 +1,264 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use argh::FromArgs;
+use ash::{
+    self,
+    vk::{
+        self, BorderColor, BufferCreateFlags, BufferUsageFlags, CompareOp, ComponentMapping,
+        ComponentSwizzle, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateFlags,
+        DescriptorSetLayoutCreateInfo, DescriptorType, Extent3D, Filter, Format, ImageAspectFlags,
+        ImageCreateInfo, ImageLayout, ImageSubresourceRange, ImageTiling, ImageType,
+        ImageUsageFlags, ImageViewCreateFlags, ImageViewType, MemoryPropertyFlags,
+        SampleCountFlags, SamplerAddressMode, SamplerCreateInfo, SamplerMipmapMode,
+        ShaderStageFlags, SharingMode, API_VERSION_1_3,
+    },
+};
+use usami::{Error, UsamiDevice, UsamiInstance, UsamiPhysicalDeviceSelector, UsamiResult};
+
+#[derive(FromArgs)]
+/// Write one descriptor of each type to a descriptor buffer and dump its raw bytes.
+struct Args {
+    /// the output directory to store the raw dump and its JSON sidecar, shared with nvshaderdump dumps.
+    #[argh(option, default = "PathBuf::from(\".\")")]
+    output_directory: PathBuf,
+}
+
+fn test_all_types(device: &Arc<UsamiDevice>, output_directory: &Path) -> UsamiResult<()> {
+    let desc_types = [
+        DescriptorType::SAMPLER,
+        DescriptorType::COMBINED_IMAGE_SAMPLER,
+        DescriptorType::SAMPLED_IMAGE,
+        DescriptorType::STORAGE_IMAGE,
+        DescriptorType::UNIFORM_TEXEL_BUFFER,
+        DescriptorType::STORAGE_TEXEL_BUFFER,
+        DescriptorType::UNIFORM_BUFFER,
+        DescriptorType::STORAGE_BUFFER,
+        DescriptorType::INPUT_ATTACHMENT,
+    ];
+
+    let desc_layout_bindings: Vec<DescriptorSetLayoutBinding> = desc_types
+        .iter()
+        .enumerate()
+        .map(|(binding, desc_type)| {
+            let stage_flags = if *desc_type == DescriptorType::INPUT_ATTACHMENT {
+                ShaderStageFlags::FRAGMENT
+            } else {
+                ShaderStageFlags::ALL
+            };
+
+            DescriptorSetLayoutBinding::default()
+                .binding(binding as u32)
+                .descriptor_type(*desc_type)
+                .descriptor_count(1)
+                .stage_flags(stage_flags)
+        })
+        .collect();
+
+    let descriptor_set_layout = UsamiDevice::create_descriptor_set_layout(
+        device,
+        "descriptor_set_layout".into(),
+        DescriptorSetLayoutCreateInfo::default()
+            .flags(DescriptorSetLayoutCreateFlags::DESCRIPTOR_BUFFER_EXT)
+            .bindings(&desc_layout_bindings),
+    )?;
+
+    let mut descriptor_buffer = UsamiDevice::create_descriptor_buffer(
+        device,
+        "descriptor_buffer".into(),
+        BufferUsageFlags::RESOURCE_DESCRIPTOR_BUFFER_EXT
+            | BufferUsageFlags::SAMPLER_DESCRIPTOR_BUFFER_EXT,
+        0x1000,
+    )?;
+
+    for desc_type in desc_types {
+        println!(
+            "{desc_type:?}: {} bytes",
+            descriptor_buffer.descriptor_size(desc_type).unwrap()
+        );
+    }
+
+    let sampler = Arc::new(UsamiDevice::create_sampler(
+        device,
+        "sampler".into(),
+        SamplerCreateInfo::default()
+            .mag_filter(Filter::NEAREST)
+            .min_filter(Filter::NEAREST)
+            .mipmap_mode(SamplerMipmapMode::NEAREST)
+            .address_mode_u(SamplerAddressMode::REPEAT)
+            .address_mode_v(SamplerAddressMode::REPEAT)
+            .address_mode_w(SamplerAddressMode::REPEAT)
+            .max_anisotropy(1.0)
+            .border_color(BorderColor::FLOAT_TRANSPARENT_BLACK)
+            .compare_op(CompareOp::NEVER),
+    )?);
+
+    let format = Format::R8G8B8A8_UNORM;
+
+    let image = UsamiDevice::create_image(
+        device,
+        "image".into(),
+        ImageCreateInfo::default()
+            .image_type(ImageType::TYPE_2D)
+            .format(format)
+            .extent(Extent3D {
+                width: 128,
+                height: 128,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(SampleCountFlags::TYPE_1)
+            .tiling(ImageTiling::OPTIMAL)
+            .usage(
+                ImageUsageFlags::TRANSFER_SRC
+                    | ImageUsageFlags::TRANSFER_DST
+                    | ImageUsageFlags::SAMPLED
+                    | ImageUsageFlags::STORAGE
+                    | ImageUsageFlags::INPUT_ATTACHMENT,
+            ),
+        MemoryPropertyFlags::empty(),
+    )?;
+
+    let image_view = Arc::new(
+        image.create_simple_image_view(
+            "image_view".into(),
+            ImageViewType::TYPE_2D,
+            ImageSubresourceRange::default()
+                .aspect_mask(ImageAspectFlags::COLOR)
+                .base_mip_level(0)
+                .level_count(image.mip_levels)
+                .base_array_layer(0)
+                .layer_count(image.array_layers),
+            ComponentMapping::default()
+                .r(ComponentSwizzle::IDENTITY)
+                .g(ComponentSwizzle::IDENTITY)
+                .b(ComponentSwizzle::IDENTITY)
+                .a(ComponentSwizzle::IDENTITY),
+            ImageViewCreateFlags::empty(),
+        )?,
+    );
+
+    let uniform_buffer = Arc::new(UsamiDevice::create_buffer_with_size(
+        device,
+        "uniform_buffer".into(),
+        BufferCreateFlags::empty(),
+        SharingMode::EXCLUSIVE,
+        BufferUsageFlags::UNIFORM_BUFFER
+            | BufferUsageFlags::UNIFORM_TEXEL_BUFFER
+            | BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        0x1000,
+        MemoryPropertyFlags::HOST_VISIBLE,
+    )?);
+
+    let storage_buffer = Arc::new(UsamiDevice::create_buffer_with_size(
+        device,
+        "storage_buffer".into(),
+        BufferCreateFlags::empty(),
+        SharingMode::EXCLUSIVE,
+        BufferUsageFlags::STORAGE_BUFFER
+            | BufferUsageFlags::STORAGE_TEXEL_BUFFER
+            | BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        0x1000,
+        MemoryPropertyFlags::HOST_VISIBLE,
+    )?);
+
+    let get_dst_binding = |t: DescriptorType| desc_types.binary_search(&t).unwrap() as u32;
+    let set_offset = descriptor_buffer.allocate_set(&descriptor_set_layout)?;
+
+    descriptor_buffer
+        .write(set_offset, &descriptor_set_layout)
+        .sampler(get_dst_binding(DescriptorType::SAMPLER), &sampler)
+        .combined_image_sampler(
+            get_dst_binding(DescriptorType::COMBINED_IMAGE_SAMPLER),
+            &image_view,
+            ImageLayout::GENERAL,
+            &sampler,
+        )
+        .image(
+            get_dst_binding(DescriptorType::SAMPLED_IMAGE),
+            DescriptorType::SAMPLED_IMAGE,
+            &image_view,
+            ImageLayout::GENERAL,
+        )
+        .image(
+            get_dst_binding(DescriptorType::STORAGE_IMAGE),
+            DescriptorType::STORAGE_IMAGE,
+            &image_view,
+            ImageLayout::GENERAL,
+        )
+        .texel_buffer(
+            get_dst_binding(DescriptorType::UNIFORM_TEXEL_BUFFER),
+            DescriptorType::UNIFORM_TEXEL_BUFFER,
+            &uniform_buffer,
+            0,
+            vk::WHOLE_SIZE,
+            format,
+        )
+        .texel_buffer(
+            get_dst_binding(DescriptorType::STORAGE_TEXEL_BUFFER),
+            DescriptorType::STORAGE_TEXEL_BUFFER,
+            &storage_buffer,
+            0,
+            vk::WHOLE_SIZE,
+            format,
+        )
+        .buffer(
+            get_dst_binding(DescriptorType::UNIFORM_BUFFER),
+            DescriptorType::UNIFORM_BUFFER,
+            &uniform_buffer,
+            0,
+            vk::WHOLE_SIZE,
+        )
+        .buffer(
+            get_dst_binding(DescriptorType::STORAGE_BUFFER),
+            DescriptorType::STORAGE_BUFFER,
+            &storage_buffer,
+            0,
+            vk::WHOLE_SIZE,
+        )
+        .image(
+            get_dst_binding(DescriptorType::INPUT_ATTACHMENT),
+            DescriptorType::INPUT_ATTACHMENT,
+            &image_view,
+            ImageLayout::GENERAL,
+        )
+        .update()?;
+
+    std::fs::create_dir_all(output_directory).map_err(|source| Error::Io {
+        path: Some(output_directory.to_path_buf()),
+        source,
+    })?;
+    descriptor_buffer.save_raw(output_directory.join("descriptor_buffer.bin"))?;
+
+    Ok(())
+}
+
+fn main() -> UsamiResult<()> {
+    let args: Args = argh::from_env();
+    let extensions = ["VK_EXT_debug_utils".into()];
+
+    let instance = UsamiInstance::new(
+        "desc_buffer_simple",
+        "usami",
+        API_VERSION_1_3,
+        &extensions,
+        true,
+    )?;
+    let device = UsamiDevice::builder()
+        .required_extension("VK_EXT_descriptor_buffer")
+        .required_features(|features| {
+            features.vulkan12.buffer_device_address = vk::TRUE;
+            features.descriptor_buffer.descriptor_buffer = vk::TRUE;
+        })
+        .physical_device_selector(
+            UsamiPhysicalDeviceSelector::new().queue_flags(vk::QueueFlags::empty()),
+        )
+        .build(instance)?;
+
+    test_all_types(&device, &args.output_directory)?;
+
+    Ok(())
+}
@@ -2,20 +2,19 @@ use std::sync::Arc;
 
 use ash::{
     self,
-    prelude::VkResult,
     vk::{
         self, BorderColor, BufferCreateFlags, BufferUsageFlags, BufferViewCreateFlags, CompareOp,
-        ComponentMapping, ComponentSwizzle, DescriptorBufferInfo, DescriptorImageInfo,
-        DescriptorPoolCreateInfo, DescriptorPoolSize, DescriptorSetLayoutBinding,
-        DescriptorSetLayoutCreateInfo, DescriptorType, Extent3D, Filter, Format, ImageAspectFlags,
-        ImageCreateInfo, ImageLayout, ImageSubresourceRange, ImageTiling, ImageType,
-        ImageUsageFlags, ImageViewCreateFlags, ImageViewType, MemoryPropertyFlags,
-        SampleCountFlags, Sampler, SamplerAddressMode, SamplerCreateInfo, SamplerMipmapMode,
-        ShaderStageFlags, SharingMode, WriteDescriptorSet, API_VERSION_1_1,
+        ComponentMapping, ComponentSwizzle, DescriptorPoolCreateInfo, DescriptorPoolSize,
+        DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo, DescriptorType, Extent3D,
+        Filter, Format, ImageAspectFlags, ImageCreateInfo, ImageLayout, ImageSubresourceRange,
+        ImageTiling, ImageType, ImageUsageFlags, ImageViewCreateFlags, ImageViewType,
+        MemoryPropertyFlags, SampleCountFlags, SamplerAddressMode, SamplerCreateInfo,
+        SamplerMipmapMode, ShaderStageFlags, SharingMode, API_VERSION_1_1,
     },
 };
 use usami::{
     descriptor::UsamiDescriptorSetLayout, UsamiDescriptorPool, UsamiDevice, UsamiInstance,
+    UsamiPhysicalDeviceSelector, UsamiResult,
 };
 
 fn create_simple_descriptor_pool(
@@ -24,7 +23,7 @@ fn create_simple_descriptor_pool(
     desc_types: &[DescriptorType],
     descriptor_count_per_type: u32,
     max_sets: u32,
-) -> VkResult<UsamiDescriptorPool> {
+) -> UsamiResult<UsamiDescriptorPool> {
     let mut descriptor_pool_sizes = Vec::new();
 
     for desc_type in desc_types {
@@ -48,7 +47,7 @@ fn create_simple_descriptor_set_layout(
     name: &str,
     desc_types: &[DescriptorType],
     stage_flags: ShaderStageFlags,
-) -> VkResult<UsamiDescriptorSetLayout> {
+) -> UsamiResult<UsamiDescriptorSetLayout> {
     let mut desc_layout_bindings = Vec::new();
 
     for (binding, desc_type) in desc_types.iter().enumerate() {
@@ -78,7 +77,7 @@ fn get_dst_binding(desc_types: &[DescriptorType], t: DescriptorType) -> u32 {
     desc_types.binary_search(&t).unwrap() as u32
 }
 
-fn test_all_types(device: &Arc<UsamiDevice>) -> VkResult<()> {
+fn test_all_types(device: &Arc<UsamiDevice>) -> UsamiResult<()> {
     let pool_desc_types = [
         DescriptorType::SAMPLER,
         DescriptorType::COMBINED_IMAGE_SAMPLER,
@@ -106,7 +105,7 @@ fn test_all_types(device: &Arc<UsamiDevice>) -> VkResult<()> {
     let descriptor_sets =
         pool.allocate_descriptor_sets("descriptor_set".into(), &[descriptor_set_layout_1.handle])?;
 
-    let sampler = UsamiDevice::create_sampler(
+    let sampler = Arc::new(UsamiDevice::create_sampler(
         &device,
         "sampler".into(),
         SamplerCreateInfo::default()
@@ -119,7 +118,7 @@ fn test_all_types(device: &Arc<UsamiDevice>) -> VkResult<()> {
             .max_anisotropy(1.0)
             .border_color(BorderColor::FLOAT_TRANSPARENT_BLACK)
             .compare_op(CompareOp::NEVER),
-    )?;
+    )?);
 
     let format = Format::R8G8B8A8_UNORM;
 
@@ -148,24 +147,26 @@ fn test_all_types(device: &Arc<UsamiDevice>) -> VkResult<()> {
         MemoryPropertyFlags::empty(),
     )?;
 
-    let image_view = image.create_simple_image_view(
-        "image_view".into(),
-        ImageViewType::TYPE_2D,
-        ImageSubresourceRange::default()
-            .aspect_mask(ImageAspectFlags::COLOR)
-            .base_mip_level(0)
-            .level_count(image.mip_levels)
-            .base_array_layer(0)
-            .layer_count(image.array_layers),
-        ComponentMapping::default()
-            .r(ComponentSwizzle::IDENTITY)
-            .g(ComponentSwizzle::IDENTITY)
-            .b(ComponentSwizzle::IDENTITY)
-            .a(ComponentSwizzle::IDENTITY),
-        ImageViewCreateFlags::empty(),
-    )?;
+    let image_view = Arc::new(
+        image.create_simple_image_view(
+            "image_view".into(),
+            ImageViewType::TYPE_2D,
+            ImageSubresourceRange::default()
+                .aspect_mask(ImageAspectFlags::COLOR)
+                .base_mip_level(0)
+                .level_count(image.mip_levels)
+                .base_array_layer(0)
+                .layer_count(image.array_layers),
+            ComponentMapping::default()
+                .r(ComponentSwizzle::IDENTITY)
+                .g(ComponentSwizzle::IDENTITY)
+                .b(ComponentSwizzle::IDENTITY)
+                .a(ComponentSwizzle::IDENTITY),
+            ImageViewCreateFlags::empty(),
+        )?,
+    );
 
-    let uniform_buffer = UsamiDevice::create_buffer_with_size(
+    let uniform_buffer = Arc::new(UsamiDevice::create_buffer_with_size(
         &device,
         "uniform_buffer".into(),
         BufferCreateFlags::empty(),
@@ -173,17 +174,17 @@ fn test_all_types(device: &Arc<UsamiDevice>) -> VkResult<()> {
         BufferUsageFlags::UNIFORM_BUFFER | BufferUsageFlags::UNIFORM_TEXEL_BUFFER,
         0x1000,
         MemoryPropertyFlags::HOST_VISIBLE,
-    )?;
+    )?);
 
-    let uniform_buffer_view = uniform_buffer.create_view(
+    let uniform_buffer_view = Arc::new(uniform_buffer.create_view(
         "uniform_buffer_view".into(),
         BufferViewCreateFlags::empty(),
         format,
         0,
         vk::WHOLE_SIZE,
-    )?;
+    )?);
 
-    let storage_buffer = UsamiDevice::create_buffer_with_size(
+    let storage_buffer = Arc::new(UsamiDevice::create_buffer_with_size(
         &device,
         "storage_buffer".into(),
         BufferCreateFlags::empty(),
@@ -191,132 +192,85 @@ fn test_all_types(device: &Arc<UsamiDevice>) -> VkResult<()> {
         BufferUsageFlags::STORAGE_BUFFER | BufferUsageFlags::STORAGE_TEXEL_BUFFER,
         0x1000,
         MemoryPropertyFlags::HOST_VISIBLE,
-    )?;
+    )?);
 
-    let storage_buffer_view = storage_buffer.create_view(
+    let storage_buffer_view = Arc::new(storage_buffer.create_view(
         "uniform_buffer_view".into(),
         BufferViewCreateFlags::empty(),
         Format::R8G8B8A8_UNORM,
         0,
         vk::WHOLE_SIZE,
-    )?;
+    )?);
 
-    unsafe {
-        device.handle.update_descriptor_sets(
-            &[
-                WriteDescriptorSet::default()
-                    .dst_set(descriptor_sets[0].handle)
-                    .dst_binding(get_dst_binding(&pool_desc_types, DescriptorType::SAMPLER))
-                    .descriptor_type(DescriptorType::SAMPLER)
-                    .image_info(&[DescriptorImageInfo::default().sampler(sampler.handle)]),
-                WriteDescriptorSet::default()
-                    .dst_set(descriptor_sets[0].handle)
-                    .dst_binding(get_dst_binding(
-                        &pool_desc_types,
-                        DescriptorType::COMBINED_IMAGE_SAMPLER,
-                    ))
-                    .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
-                    .image_info(&[DescriptorImageInfo::default()
-                        .image_layout(ImageLayout::GENERAL)
-                        .image_view(image_view.handle)
-                        .sampler(sampler.handle)]),
-                WriteDescriptorSet::default()
-                    .dst_set(descriptor_sets[0].handle)
-                    .dst_binding(get_dst_binding(
-                        &pool_desc_types,
-                        DescriptorType::SAMPLED_IMAGE,
-                    ))
-                    .descriptor_type(DescriptorType::SAMPLED_IMAGE)
-                    .image_info(&[DescriptorImageInfo::default()
-                        .image_layout(ImageLayout::GENERAL)
-                        .image_view(image_view.handle)
-                        .sampler(Sampler::null())]),
-                WriteDescriptorSet::default()
-                    .dst_set(descriptor_sets[0].handle)
-                    .dst_binding(get_dst_binding(
-                        &pool_desc_types,
-                        DescriptorType::STORAGE_IMAGE,
-                    ))
-                    .descriptor_type(DescriptorType::STORAGE_IMAGE)
-                    .image_info(&[DescriptorImageInfo::default()
-                        .image_layout(ImageLayout::GENERAL)
-                        .image_view(image_view.handle)
-                        .sampler(Sampler::null())]),
-                WriteDescriptorSet::default()
-                    .dst_set(descriptor_sets[0].handle)
-                    .dst_binding(get_dst_binding(
-                        &pool_desc_types,
-                        DescriptorType::UNIFORM_TEXEL_BUFFER,
-                    ))
-                    .descriptor_type(DescriptorType::UNIFORM_TEXEL_BUFFER)
-                    .texel_buffer_view(&[uniform_buffer_view.handle]),
-                WriteDescriptorSet::default()
-                    .dst_set(descriptor_sets[0].handle)
-                    .dst_binding(get_dst_binding(
-                        &pool_desc_types,
-                        DescriptorType::STORAGE_TEXEL_BUFFER,
-                    ))
-                    .descriptor_type(DescriptorType::STORAGE_TEXEL_BUFFER)
-                    .texel_buffer_view(&[storage_buffer_view.handle]),
-                WriteDescriptorSet::default()
-                    .dst_set(descriptor_sets[0].handle)
-                    .dst_binding(get_dst_binding(
-                        &pool_desc_types,
-                        DescriptorType::UNIFORM_BUFFER,
-                    ))
-                    .descriptor_type(DescriptorType::UNIFORM_BUFFER)
-                    .buffer_info(&[DescriptorBufferInfo::default()
-                        .buffer(uniform_buffer.handle)
-                        .offset(0)
-                        .range(vk::WHOLE_SIZE)]),
-                WriteDescriptorSet::default()
-                    .dst_set(descriptor_sets[0].handle)
-                    .dst_binding(get_dst_binding(
-                        &pool_desc_types,
-                        DescriptorType::STORAGE_BUFFER,
-                    ))
-                    .descriptor_type(DescriptorType::STORAGE_BUFFER)
-                    .buffer_info(&[DescriptorBufferInfo::default()
-                        .buffer(storage_buffer.handle)
-                        .offset(0)
-                        .range(vk::WHOLE_SIZE)]),
-                WriteDescriptorSet::default()
-                    .dst_set(descriptor_sets[0].handle)
-                    .dst_binding(get_dst_binding(
-                        &pool_desc_types,
-                        DescriptorType::UNIFORM_BUFFER_DYNAMIC,
-                    ))
-                    .descriptor_type(DescriptorType::UNIFORM_BUFFER_DYNAMIC)
-                    .buffer_info(&[DescriptorBufferInfo::default()
-                        .buffer(uniform_buffer.handle)
-                        .offset(0)
-                        .range(vk::WHOLE_SIZE)]),
-                WriteDescriptorSet::default()
-                    .dst_set(descriptor_sets[0].handle)
-                    .dst_binding(get_dst_binding(
-                        &pool_desc_types,
-                        DescriptorType::STORAGE_BUFFER_DYNAMIC,
-                    ))
-                    .descriptor_type(DescriptorType::STORAGE_BUFFER_DYNAMIC)
-                    .buffer_info(&[DescriptorBufferInfo::default()
-                        .buffer(storage_buffer.handle)
-                        .offset(0)
-                        .range(vk::WHOLE_SIZE)]),
-                WriteDescriptorSet::default()
-                    .dst_set(descriptor_sets[0].handle)
-                    .dst_binding(get_dst_binding(
-                        &pool_desc_types,
-                        DescriptorType::INPUT_ATTACHMENT,
-                    ))
-                    .descriptor_type(DescriptorType::INPUT_ATTACHMENT)
-                    .image_info(&[DescriptorImageInfo::default()
-                        .image_layout(ImageLayout::GENERAL)
-                        .image_view(image_view.handle)
-                        .sampler(Sampler::null())]),
-            ],
-            &[],
-        );
-    }
+    descriptor_sets[0]
+        .write()
+        .sampler(
+            get_dst_binding(&pool_desc_types, DescriptorType::SAMPLER),
+            &sampler,
+        )
+        .combined_image_sampler(
+            get_dst_binding(&pool_desc_types, DescriptorType::COMBINED_IMAGE_SAMPLER),
+            &image_view,
+            ImageLayout::GENERAL,
+            &sampler,
+        )
+        .image(
+            get_dst_binding(&pool_desc_types, DescriptorType::SAMPLED_IMAGE),
+            DescriptorType::SAMPLED_IMAGE,
+            &image_view,
+            ImageLayout::GENERAL,
+        )
+        .image(
+            get_dst_binding(&pool_desc_types, DescriptorType::STORAGE_IMAGE),
+            DescriptorType::STORAGE_IMAGE,
+            &image_view,
+            ImageLayout::GENERAL,
+        )
+        .texel_buffer(
+            get_dst_binding(&pool_desc_types, DescriptorType::UNIFORM_TEXEL_BUFFER),
+            DescriptorType::UNIFORM_TEXEL_BUFFER,
+            &uniform_buffer_view,
+        )
+        .texel_buffer(
+            get_dst_binding(&pool_desc_types, DescriptorType::STORAGE_TEXEL_BUFFER),
+            DescriptorType::STORAGE_TEXEL_BUFFER,
+            &storage_buffer_view,
+        )
+        .buffer(
+            get_dst_binding(&pool_desc_types, DescriptorType::UNIFORM_BUFFER),
+            DescriptorType::UNIFORM_BUFFER,
+            &uniform_buffer,
+            0,
+            vk::WHOLE_SIZE,
+        )
+        .buffer(
+            get_dst_binding(&pool_desc_types, DescriptorType::STORAGE_BUFFER),
+            DescriptorType::STORAGE_BUFFER,
+            &storage_buffer,
+            0,
+            vk::WHOLE_SIZE,
+        )
+        .buffer(
+            get_dst_binding(&pool_desc_types, DescriptorType::UNIFORM_BUFFER_DYNAMIC),
+            DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+            &uniform_buffer,
+            0,
+            vk::WHOLE_SIZE,
+        )
+        .buffer(
+            get_dst_binding(&pool_desc_types, DescriptorType::STORAGE_BUFFER_DYNAMIC),
+            DescriptorType::STORAGE_BUFFER_DYNAMIC,
+            &storage_buffer,
+            0,
+            vk::WHOLE_SIZE,
+        )
+        .image(
+            get_dst_binding(&pool_desc_types, DescriptorType::INPUT_ATTACHMENT),
+            DescriptorType::INPUT_ATTACHMENT,
+            &image_view,
+            ImageLayout::GENERAL,
+        )
+        .update();
 
     // FIXME: Right now panvk use a common DestroyBufferView entrypoint that is wrong and cause a crash on drop.
     std::mem::forget(uniform_buffer_view);
@@ -325,7 +279,7 @@ fn test_all_types(device: &Arc<UsamiDevice>) -> VkResult<()> {
     Ok(())
 }
 
-fn main() -> VkResult<()> {
+fn main() -> UsamiResult<()> {
     let extensions = ["VK_EXT_debug_utils".into()];
 
     let instance = UsamiInstance::new(
@@ -335,17 +289,10 @@ fn main() -> VkResult<()> {
         &extensions,
         true,
     )?;
-    let device = UsamiDevice::new_by_filter(
+    let device = UsamiDevice::new_by_selector(
         instance,
         &[],
-        Box::new(|physical_device| {
-            physical_device
-                .queue_familiy_properties
-                .iter()
-                .enumerate()
-                .find_map(|(i, _)| Some(i as u32))
-                .map(|x| (physical_device, x))
-        }),
+        UsamiPhysicalDeviceSelector::new().queue_flags(vk::QueueFlags::empty()),
     )?;
 
     test_all_types(&device)?;
@@ -1,21 +1,19 @@
-use std::ffi::CString;
-
-use ash::{
-    prelude::VkResult,
-    vk::{
-        self, AccessFlags, BorderColor, BufferCreateFlags, BufferUsageFlags, CommandBufferLevel,
-        CommandBufferUsageFlags, CommandPoolCreateFlags, CommandPoolCreateInfo, CompareOp,
-        ComponentMapping, ComponentSwizzle, ComputePipelineCreateInfo, DescriptorImageInfo,
-        DescriptorPoolCreateInfo, DescriptorSetLayoutCreateInfo, DescriptorType, Extent3D,
-        FenceCreateFlags, Filter, Format, ImageAspectFlags, ImageCreateInfo, ImageLayout,
-        ImageSubresourceRange, ImageTiling, ImageType, ImageUsageFlags, ImageViewCreateFlags,
-        ImageViewType, MemoryPropertyFlags, PipelineBindPoint, PipelineCache,
-        PipelineShaderStageCreateInfo, PipelineStageFlags, QueueFlags, SampleCountFlags,
-        SamplerAddressMode, SamplerCreateInfo, SamplerMipmapMode, ShaderStageFlags, SharingMode,
-        SubmitInfo, WriteDescriptorSet,
-    },
+use std::{ffi::CString, sync::Arc};
+
+use ash::vk::{
+    self, AccessFlags, BorderColor, BufferCreateFlags, BufferUsageFlags, CommandBufferLevel,
+    CommandBufferUsageFlags, CommandPoolCreateFlags, CommandPoolCreateInfo, CompareOp,
+    ComponentMapping, ComponentSwizzle, ComputePipelineCreateInfo, Extent3D, FenceCreateFlags,
+    Filter, Format, ImageAspectFlags, ImageCreateInfo, ImageLayout, ImageSubresourceRange,
+    ImageTiling, ImageType, ImageUsageFlags, ImageViewCreateFlags, ImageViewType,
+    MemoryPropertyFlags, PipelineBindPoint, PipelineCache, PipelineShaderStageCreateInfo,
+    PipelineStageFlags, PushConstantRange, QueueFlags, SampleCountFlags, SamplerAddressMode,
+    SamplerCreateInfo, SamplerMipmapMode, ShaderStageFlags, SharingMode, SubmitInfo,
+};
+use usami::{
+    image::RawImageData, UsamiAccess, UsamiBindlessCapacities, UsamiBindlessTable, UsamiDevice,
+    UsamiInstance, UsamiPhysicalDeviceSelector, UsamiResult,
 };
-use usami::{image::RawImageData, UsamiDevice, UsamiInstance};
 
 #[derive(Clone, Debug, Copy, Default)]
 #[repr(packed(1))]
@@ -29,7 +27,7 @@ struct UniformBlock {
     color_bias: [f32; 4],
 }
 
-fn main() -> VkResult<()> {
+fn main() -> UsamiResult<()> {
     let extensions = [
         "VK_EXT_debug_utils".into(),
         "VK_KHR_get_physical_device_properties2".into(),
@@ -41,30 +39,19 @@ fn main() -> VkResult<()> {
     let instance = UsamiInstance::new(
         "image_robustness_texarray",
         "usami",
-        vk::API_VERSION_1_0,
+        vk::API_VERSION_1_2,
         &extensions,
         true,
     )?;
-    let device = UsamiDevice::new_by_filter(
-        instance,
-        &[ash::ext::image_robustness::NAME
-            .to_string_lossy()
-            .to_string()],
-        Box::new(|physical_device| {
-            physical_device
-                .queue_familiy_properties
-                .iter()
-                .enumerate()
-                .find_map(|(i, x)| {
-                    if x.queue_flags.contains(QueueFlags::COMPUTE) {
-                        Some(i as u32)
-                    } else {
-                        None
-                    }
-                })
-                .map(|x| (physical_device, x))
-        }),
-    )?;
+    let device = UsamiDevice::builder()
+        .required_extension(ash::ext::image_robustness::NAME.to_string_lossy())
+        .required_features(|features| {
+            features.union(&UsamiBindlessTable::required_features());
+        })
+        .physical_device_selector(
+            UsamiPhysicalDeviceSelector::new().queue_flags(QueueFlags::COMPUTE),
+        )
+        .build(instance)?;
 
     let command_pool = UsamiDevice::create_command_pool(
         &device,
@@ -94,24 +81,26 @@ fn main() -> VkResult<()> {
         ImageLayout::GENERAL,
     )?;
 
-    let white_image_view = white_image.create_simple_image_view(
-        "presentation_image_view".into(),
-        ImageViewType::TYPE_2D_ARRAY,
-        ImageSubresourceRange::default()
-            .aspect_mask(ImageAspectFlags::COLOR)
-            .base_mip_level(0)
-            .level_count(white_image.mip_levels)
-            .base_array_layer(0)
-            .layer_count(white_image.array_layers),
-        ComponentMapping::default()
-            .r(ComponentSwizzle::IDENTITY)
-            .g(ComponentSwizzle::IDENTITY)
-            .b(ComponentSwizzle::IDENTITY)
-            .a(ComponentSwizzle::IDENTITY),
-        ImageViewCreateFlags::empty(),
-    )?;
-
-    let white_image_sampler = UsamiDevice::create_sampler(
+    let white_image_view = Arc::new(
+        white_image.create_simple_image_view(
+            "presentation_image_view".into(),
+            ImageViewType::TYPE_2D_ARRAY,
+            ImageSubresourceRange::default()
+                .aspect_mask(ImageAspectFlags::COLOR)
+                .base_mip_level(0)
+                .level_count(white_image.mip_levels)
+                .base_array_layer(0)
+                .layer_count(white_image.array_layers),
+            ComponentMapping::default()
+                .r(ComponentSwizzle::IDENTITY)
+                .g(ComponentSwizzle::IDENTITY)
+                .b(ComponentSwizzle::IDENTITY)
+                .a(ComponentSwizzle::IDENTITY),
+            ImageViewCreateFlags::empty(),
+        )?,
+    );
+
+    let white_image_sampler = Arc::new(UsamiDevice::create_sampler(
         &device,
         "sampler".into(),
         SamplerCreateInfo::default()
@@ -124,7 +113,7 @@ fn main() -> VkResult<()> {
             .max_anisotropy(1.0)
             .border_color(BorderColor::FLOAT_TRANSPARENT_BLACK)
             .compare_op(CompareOp::NEVER),
-    )?;
+    )?);
 
     let output_image_info = ImageCreateInfo::default()
         .image_type(ImageType::TYPE_1D)
@@ -146,22 +135,24 @@ fn main() -> VkResult<()> {
         output_image_info,
         MemoryPropertyFlags::empty(),
     )?;
-    let output_image_view = output_image.create_simple_image_view(
-        "output_image_view".into(),
-        ImageViewType::TYPE_1D,
-        ImageSubresourceRange::default()
-            .aspect_mask(ImageAspectFlags::COLOR)
-            .base_mip_level(0)
-            .level_count(1)
-            .base_array_layer(0)
-            .layer_count(output_image.array_layers),
-        ComponentMapping::default()
-            .r(ComponentSwizzle::IDENTITY)
-            .g(ComponentSwizzle::IDENTITY)
-            .b(ComponentSwizzle::IDENTITY)
-            .a(ComponentSwizzle::IDENTITY),
-        ImageViewCreateFlags::empty(),
-    )?;
+    let output_image_view = Arc::new(
+        output_image.create_simple_image_view(
+            "output_image_view".into(),
+            ImageViewType::TYPE_1D,
+            ImageSubresourceRange::default()
+                .aspect_mask(ImageAspectFlags::COLOR)
+                .base_mip_level(0)
+                .level_count(1)
+                .base_array_layer(0)
+                .layer_count(output_image.array_layers),
+            ComponentMapping::default()
+                .r(ComponentSwizzle::IDENTITY)
+                .g(ComponentSwizzle::IDENTITY)
+                .b(ComponentSwizzle::IDENTITY)
+                .a(ComponentSwizzle::IDENTITY),
+            ImageViewCreateFlags::empty(),
+        )?,
+    );
 
     let output_readback_buffer = UsamiDevice::create_buffer_with_size(
         &device,
@@ -173,75 +164,27 @@ fn main() -> VkResult<()> {
         MemoryPropertyFlags::HOST_VISIBLE,
     )?;
 
-    let descriptor_pool_sizes = [
-        vk::DescriptorPoolSize {
-            ty: vk::DescriptorType::STORAGE_IMAGE,
-            descriptor_count: 1,
-        },
-        vk::DescriptorPoolSize {
-            ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-            descriptor_count: 1,
-        },
-    ];
-    let descriptor_pool_create_info = DescriptorPoolCreateInfo::default()
-        .pool_sizes(&descriptor_pool_sizes)
-        .max_sets(1);
-
-    let descriptor_pool = UsamiDevice::create_descriptor_pool(
+    let bindless_table = UsamiDevice::create_bindless_table(
         &device,
-        "descriptor_pool".into(),
-        descriptor_pool_create_info,
+        "bindless_table".into(),
+        ShaderStageFlags::COMPUTE,
+        UsamiBindlessCapacities::default(),
     )?;
-
-    let desc_layout_bindings = [
-        vk::DescriptorSetLayoutBinding::default()
-            .binding(0)
-            .descriptor_type(DescriptorType::STORAGE_IMAGE)
-            .descriptor_count(1)
-            .stage_flags(ShaderStageFlags::COMPUTE),
-        vk::DescriptorSetLayoutBinding::default()
-            .binding(1)
-            .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
-            .descriptor_count(1)
-            .stage_flags(ShaderStageFlags::COMPUTE),
+    // Slots of the output image, texture and sampler, as read by the shader from push constants.
+    let bindless_indices = [
+        bindless_table.add_storage_image(&output_image_view, ImageLayout::GENERAL)?,
+        bindless_table.add_sampled_image(&white_image_view, ImageLayout::GENERAL)?,
+        bindless_table.add_sampler(&white_image_sampler)?,
     ];
-    let descriptor_set_layout = UsamiDevice::create_descriptor_set_layout(
-        &device,
-        "descriptor_set_layout".into(),
-        DescriptorSetLayoutCreateInfo::default().bindings(&desc_layout_bindings),
-    )?;
-
-    let descriptor_sets = descriptor_pool
-        .allocate_descriptor_sets("descriptor_set".into(), &[descriptor_set_layout.handle])?;
-
-    unsafe {
-        device.handle.update_descriptor_sets(
-            &[
-                WriteDescriptorSet::default()
-                    .dst_set(descriptor_sets[0].handle)
-                    .dst_binding(0)
-                    .descriptor_type(DescriptorType::STORAGE_IMAGE)
-                    .image_info(&[DescriptorImageInfo::default()
-                        .image_layout(ImageLayout::GENERAL)
-                        .image_view(output_image_view.handle)]),
-                WriteDescriptorSet::default()
-                    .dst_set(descriptor_sets[0].handle)
-                    .dst_binding(1)
-                    .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
-                    .image_info(&[DescriptorImageInfo::default()
-                        .image_layout(ImageLayout::GENERAL)
-                        .image_view(white_image_view.handle)
-                        .sampler(white_image_sampler.handle)]),
-            ],
-            &[],
-        );
-    }
 
     let pipeline_layout = UsamiDevice::create_pipeline_layout(
         &device,
         "base_pipeline_layout".into(),
-        &[descriptor_set_layout.handle],
-        &[],
+        &[bindless_table.layout.handle],
+        &[PushConstantRange::default()
+            .stage_flags(ShaderStageFlags::COMPUTE)
+            .offset(0)
+            .size(std::mem::size_of_val(&bindless_indices) as u32)],
     )?;
 
     let shader_entrypoint_name = CString::new("main").unwrap();
@@ -292,21 +235,19 @@ fn main() -> VkResult<()> {
                     PipelineBindPoint::COMPUTE,
                     pipeline_layout.handle,
                     0,
-                    &[descriptor_sets[0].handle],
+                    &[bindless_table.set.handle],
                     &[],
                 );
             }
 
-            command_buffer.add_image_barrier(
-                &output_image,
-                None,
-                PipelineStageFlags::HOST,
-                PipelineStageFlags::COMPUTE_SHADER,
-                AccessFlags::empty(),
-                AccessFlags::SHADER_WRITE,
-                ImageLayout::UNDEFINED,
-                ImageLayout::GENERAL,
-            )?;
+            command_buffer.push_constants_slice(
+                &pipeline_layout,
+                ShaderStageFlags::COMPUTE,
+                0,
+                &bindless_indices,
+            );
+
+            command_buffer.transition_image(&output_image, None, UsamiAccess::ComputeWrite)?;
 
             unsafe {
                 vk_device.cmd_dispatch(
@@ -343,14 +284,12 @@ fn main() -> VkResult<()> {
     fence.wait(u64::MAX)?;
     fence.reset()?;
 
-    let output_readback_raw_buffer = output_readback_buffer.device_memory.read_to_vec()?;
+    let output_readback = output_readback_buffer.mapped_slice::<[f32; 4]>()?;
 
     let vec_count = 4;
 
-    for vec_idx in 0..vec_count {
-        let base_data = &output_readback_raw_buffer[vec_idx * 16..(vec_idx + 1) * 16];
-        for i in 0..4 {
-            let val = f32::from_le_bytes(base_data[(i) * 4..(i + 1) * 4].try_into().unwrap());
+    for (vec_idx, base_data) in output_readback.iter().take(vec_count).enumerate() {
+        for (i, val) in base_data.iter().enumerate() {
             println!("vec[{vec_idx}][{i}]: {val}");
         }
         println!();
@@ -1,11 +1,10 @@
 use ash::{
     khr::cooperative_matrix::Instance as CooperativeMatrix,
-    prelude::VkResult,
     vk::{self},
 };
-use usami::{UsamiDevice, UsamiInstance};
+use usami::{UsamiDevice, UsamiInstance, UsamiPhysicalDeviceSelector, UsamiResult};
 
-fn main() -> VkResult<()> {
+fn main() -> UsamiResult<()> {
     let extensions = ["VK_EXT_debug_utils".into()];
 
     let instance = UsamiInstance::new(
@@ -15,25 +14,12 @@ fn main() -> VkResult<()> {
         &extensions,
         true,
     )?;
-    let device = UsamiDevice::new_by_filter(
+    let device = UsamiDevice::new_by_selector(
         instance,
         &[ash::khr::cooperative_matrix::NAME
             .to_string_lossy()
             .to_string()],
-        Box::new(|physical_device| {
-            physical_device
-                .queue_familiy_properties
-                .iter()
-                .enumerate()
-                .find_map(|(i, x)| {
-                    if x.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
-                        Some(i as u32)
-                    } else {
-                        None
-                    }
-                })
-                .map(|x| (physical_device, x))
-        }),
+        UsamiPhysicalDeviceSelector::new(),
     )?;
 
     let cooperative_matrix =
@@ -1,27 +1,21 @@
-use std::ffi::CString;
-
 use ash::{
     ext::mesh_shader::Device as MeshShader,
-    prelude::VkResult,
     vk::{
         self, AccessFlags, AttachmentDescription, AttachmentLoadOp, AttachmentReference,
-        AttachmentStoreOp, BlendFactor, BlendOp, BufferCreateFlags, BufferUsageFlags, ClearValue,
-        ColorComponentFlags, CommandBufferLevel, CommandPoolCreateFlags, CommandPoolCreateInfo,
-        CompareOp, DescriptorBufferInfo, DescriptorPoolCreateInfo, DescriptorSetLayoutCreateInfo,
-        DynamicState, FenceCreateFlags, FrontFace, GraphicsPipelineCreateInfo, ImageLayout,
-        LogicOp, MemoryPropertyFlags, PhysicalDeviceType, PipelineBindPoint, PipelineCache,
-        PipelineColorBlendAttachmentState, PipelineColorBlendStateCreateInfo,
-        PipelineDepthStencilStateCreateInfo, PipelineDynamicStateCreateInfo,
-        PipelineMultisampleStateCreateInfo, PipelineRasterizationStateCreateInfo,
-        PipelineShaderStageCreateInfo, PipelineStageFlags, PipelineViewportStateCreateInfo,
-        PolygonMode, QueueFlags, RenderPassBeginInfo, RenderPassCreateInfo, SampleCountFlags,
-        ShaderStageFlags, SharingMode, StencilOp, StencilOpState, SubmitInfo, SubpassContents,
-        SubpassDependency, SubpassDescription, WriteDescriptorSet,
+        AttachmentStoreOp, BufferCreateFlags, BufferUsageFlags, ClearValue, CommandBufferLevel,
+        CommandPoolCreateFlags, CommandPoolCreateInfo, DescriptorPoolCreateFlags,
+        DescriptorSetLayoutCreateInfo, FenceCreateFlags, ImageLayout, MemoryPropertyFlags,
+        PhysicalDeviceType, PipelineBindPoint, PipelineStageFlags, RenderPassBeginInfo,
+        RenderPassCreateInfo, ShaderStageFlags, SharingMode, SubmitInfo, SubpassContents,
+        SubpassDependency, SubpassDescription,
     },
 };
-use usami::{UsamiDevice, UsamiInstance, UsamiPresentation};
+use usami::{
+    ResultExt, UsamiDevice, UsamiGraphicsPipelineBuilder, UsamiInstance,
+    UsamiPhysicalDeviceSelector, UsamiPresentation, UsamiResult,
+};
 
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
 use argh::FromArgs;
 
@@ -57,7 +51,7 @@ struct Args {
     output_buffer_file: Option<PathBuf>,
 }
 
-fn main() -> VkResult<()> {
+fn main() -> UsamiResult<()> {
     let args: Args = argh::from_env();
 
     let extensions = ["VK_EXT_debug_utils".into()];
@@ -72,45 +66,19 @@ fn main() -> VkResult<()> {
         &extensions,
         true,
     )?;
-    let device = UsamiDevice::new_by_filter(
+    let device = UsamiDevice::new_by_selector(
         instance,
         &["VK_EXT_mesh_shader".into(), "VK_KHR_spirv_1_4".into()],
-        Box::new(|physical_device| {
-            physical_device
-                .queue_familiy_properties
-                .iter()
-                .enumerate()
-                .find_map(|(i, x)| {
-                    if physical_device.properties.device_type != PhysicalDeviceType::DISCRETE_GPU {
-                        return None;
-                    }
-
-                    if x.queue_flags.contains(QueueFlags::GRAPHICS) {
-                        Some(i as u32)
-                    } else {
-                        None
-                    }
-                })
-                .map(|x| (physical_device, x))
-        }),
+        UsamiPhysicalDeviceSelector::new().preferred_device_type(PhysicalDeviceType::DISCRETE_GPU),
     )?;
     let presentation = UsamiPresentation::new(&device, width, height)?;
 
-    let shader_entrypoint_name = CString::new("main").unwrap();
-
-    let descriptor_pool_sizes = [vk::DescriptorPoolSize {
-        ty: vk::DescriptorType::STORAGE_BUFFER,
-        descriptor_count: 1,
-    }];
-    let descriptor_pool_create_info = DescriptorPoolCreateInfo::default()
-        .pool_sizes(&descriptor_pool_sizes)
-        .max_sets(1);
-
-    let descriptor_pool = UsamiDevice::create_descriptor_pool(
+    let mut descriptor_allocator = UsamiDevice::create_descriptor_allocator(
         &device,
-        "descriptor_pool".into(),
-        descriptor_pool_create_info,
-    )?;
+        "descriptor_allocator".into(),
+        DescriptorPoolCreateFlags::empty(),
+        1,
+    );
 
     let desc_layout_bindings = [vk::DescriptorSetLayoutBinding::default()
         .binding(0)
@@ -123,8 +91,8 @@ fn main() -> VkResult<()> {
         DescriptorSetLayoutCreateInfo::default().bindings(&desc_layout_bindings),
     )?;
 
-    let descriptor_sets = descriptor_pool
-        .allocate_descriptor_sets("descriptor_set".into(), &[descriptor_set_layout.handle])?;
+    let descriptor_sets =
+        descriptor_allocator.allocate("descriptor_set".into(), &[&descriptor_set_layout])?;
 
     let pipeline_layout = UsamiDevice::create_pipeline_layout(
         &device,
@@ -133,55 +101,37 @@ fn main() -> VkResult<()> {
         &[],
     )?;
 
-    let mut active_shaders = Vec::new();
-    let mut shader_stage_create_infos = Vec::new();
+    let task_shader = match &args.task_path {
+        Some(task_shader_path) => {
+            let shader_code = usami::utils::read_spv_file(task_shader_path)?;
 
-    if let Some(task_shader_path) = &args.task_path {
-        let shader_code = usami::utils::read_spv_file(task_shader_path);
-        let shader = UsamiDevice::create_shader(&device, "task_shader".into(), &shader_code)?;
+            Some(UsamiDevice::create_shader(
+                &device,
+                "task_shader".into(),
+                &shader_code,
+            )?)
+        }
+        None => None,
+    };
 
-        shader_stage_create_infos.push(
-            PipelineShaderStageCreateInfo::default()
-                .module(shader.handle)
-                .name(shader_entrypoint_name.as_c_str())
-                .stage(ShaderStageFlags::TASK_EXT),
-        );
-        active_shaders.push(shader);
-    }
+    let mesh_shader = {
+        let shader_code = usami::utils::read_spv_file(&args.mesh_path)?;
 
-    {
-        let shader_code = usami::utils::read_spv_file(&args.mesh_path);
-        let shader = UsamiDevice::create_shader(&device, "mesh_shader".into(), &shader_code)?;
-
-        shader_stage_create_infos.push(
-            PipelineShaderStageCreateInfo::default()
-                .module(shader.handle)
-                .name(shader_entrypoint_name.as_c_str())
-                .stage(ShaderStageFlags::MESH_EXT),
-        );
-        active_shaders.push(shader);
-    }
+        UsamiDevice::create_shader(&device, "mesh_shader".into(), &shader_code)?
+    };
 
-    {
+    let frag_shader = {
         let default_shader_code =
             usami::utils::as_u32_vec(include_bytes!("../../resources/mesh_tester/main.frag.spv"));
 
         let shader_code = if let Some(frag_shader_path) = &args.frag_path {
-            usami::utils::read_spv_file(frag_shader_path)
+            usami::utils::read_spv_file(frag_shader_path)?
         } else {
             default_shader_code
         };
 
-        let shader = UsamiDevice::create_shader(&device, "frag_shader".into(), &shader_code)?;
-
-        shader_stage_create_infos.push(
-            PipelineShaderStageCreateInfo::default()
-                .module(shader.handle)
-                .name(shader_entrypoint_name.as_c_str())
-                .stage(ShaderStageFlags::FRAGMENT),
-        );
-        active_shaders.push(shader);
-    }
+        UsamiDevice::create_shader(&device, "frag_shader".into(), &shader_code)?
+    };
 
     let group_count_x = args.group_count_x.unwrap_or(1);
     let group_count_y = args.group_count_y.unwrap_or(1);
@@ -190,49 +140,6 @@ fn main() -> VkResult<()> {
     let scissors = [presentation.rect2d()];
     let viewports = [presentation.viewport()];
 
-    let viewport_state_create_info = PipelineViewportStateCreateInfo::default()
-        .scissors(&scissors)
-        .viewports(&viewports);
-
-    let rasterization_create_info = PipelineRasterizationStateCreateInfo::default()
-        .front_face(FrontFace::COUNTER_CLOCKWISE)
-        .polygon_mode(PolygonMode::FILL)
-        .line_width(1.0);
-
-    let multisample_state_create_info = PipelineMultisampleStateCreateInfo::default()
-        .rasterization_samples(SampleCountFlags::TYPE_1);
-
-    let noop_stencil = StencilOpState::default()
-        .fail_op(StencilOp::KEEP)
-        .pass_op(StencilOp::KEEP)
-        .depth_fail_op(StencilOp::KEEP)
-        .compare_op(CompareOp::ALWAYS);
-
-    let depth_state_create_info = PipelineDepthStencilStateCreateInfo::default()
-        .depth_test_enable(true)
-        .depth_write_enable(true)
-        .depth_compare_op(CompareOp::LESS_OR_EQUAL)
-        .front(noop_stencil)
-        .back(noop_stencil)
-        .max_depth_bounds(1.0);
-
-    let attachements = [PipelineColorBlendAttachmentState::default()
-        .blend_enable(false)
-        .src_color_blend_factor(BlendFactor::SRC_COLOR)
-        .dst_color_blend_factor(BlendFactor::ONE_MINUS_DST_COLOR)
-        .color_blend_op(BlendOp::ADD)
-        .src_alpha_blend_factor(BlendFactor::ZERO)
-        .dst_alpha_blend_factor(BlendFactor::ZERO)
-        .alpha_blend_op(BlendOp::ADD)
-        .color_write_mask(ColorComponentFlags::RGBA)];
-
-    let color_blend_create_state = PipelineColorBlendStateCreateInfo::default()
-        .logic_op(LogicOp::CLEAR)
-        .attachments(&attachements);
-
-    let dynamic_state_create_info = PipelineDynamicStateCreateInfo::default()
-        .dynamic_states(&[DynamicState::VIEWPORT, DynamicState::SCISSOR]);
-
     let renderpass_attachments = [AttachmentDescription::default()
         .format(presentation.image.format)
         .samples(presentation.image.samples)
@@ -261,25 +168,17 @@ fn main() -> VkResult<()> {
     let render_pass =
         UsamiDevice::create_render_pass(&device, "render_pass".into(), render_pass_create_info)?;
 
-    let graphics_pipeline_create_info = GraphicsPipelineCreateInfo::default()
-        .stages(&shader_stage_create_infos)
-        .viewport_state(&viewport_state_create_info)
-        .rasterization_state(&rasterization_create_info)
-        .multisample_state(&multisample_state_create_info)
-        .depth_stencil_state(&depth_state_create_info)
-        .color_blend_state(&color_blend_create_state)
-        .dynamic_state(&dynamic_state_create_info)
-        .layout(pipeline_layout.handle)
-        .render_pass(render_pass.handle);
-
-    let pipelines = UsamiDevice::create_graphics_pipelines(
-        &device,
-        "pipeline".into(),
-        PipelineCache::null(),
-        &[graphics_pipeline_create_info],
-    )?;
+    let mut pipeline_builder = UsamiGraphicsPipelineBuilder::new();
 
-    let graphic_pipeline = &pipelines[0];
+    if let Some(task_shader) = &task_shader {
+        pipeline_builder = pipeline_builder.task_shader(task_shader);
+    }
+
+    let graphic_pipeline = pipeline_builder
+        .mesh_shader(&mesh_shader)
+        .fragment_shader(&frag_shader)
+        .render_pass(&render_pass, 0)
+        .build(&device, "pipeline".into(), &pipeline_layout)?;
 
     let framebuffer =
         presentation.create_framebuffer(&device, "framebuffer".into(), &render_pass)?;
@@ -298,7 +197,7 @@ fn main() -> VkResult<()> {
         1,
     )?;
 
-    let data_buffer = UsamiDevice::create_buffer_with_size(
+    let data_buffer = Arc::new(UsamiDevice::create_buffer_with_size(
         &device,
         "data_buffer".into(),
         BufferCreateFlags::empty(),
@@ -306,26 +205,22 @@ fn main() -> VkResult<()> {
         BufferUsageFlags::STORAGE_BUFFER,
         0x1000,
         MemoryPropertyFlags::HOST_VISIBLE,
-    )?;
-
-    unsafe {
-        device.handle.update_descriptor_sets(
-            &[WriteDescriptorSet::default()
-                .dst_set(descriptor_sets[0].handle)
-                .dst_binding(0)
-                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
-                .buffer_info(&[DescriptorBufferInfo::default()
-                    .buffer(data_buffer.handle)
-                    .offset(0)
-                    .range(vk::WHOLE_SIZE)])],
-            &[],
-        );
-    }
+    )?);
+
+    descriptor_sets[0]
+        .write()
+        .buffer(
+            0,
+            vk::DescriptorType::STORAGE_BUFFER,
+            &data_buffer,
+            0,
+            vk::WHOLE_SIZE,
+        )
+        .update();
 
     usami::utils::record_command_buffer_with_image_dep(
         &command_buffers[0],
         &presentation.image,
-        &presentation.buffer_readback,
         |device, command_buffer, _image| {
             let vk_instance = &device.instance.vk_instance;
             let vk_device = &device.handle;
@@ -388,7 +283,7 @@ fn main() -> VkResult<()> {
     fence.wait(u64::MAX)?;
     fence.reset()?;
 
-    let res = presentation.buffer_readback.device_memory.read_to_vec()?;
+    let res = presentation.read_back(&command_pool)?;
 
     image::save_buffer_with_format(
         "output.bmp",
@@ -400,10 +295,11 @@ fn main() -> VkResult<()> {
     )
     .unwrap();
 
-    let data_buffer_readback = data_buffer.device_memory.read_to_vec().unwrap();
+    let data_buffer_readback = data_buffer.allocation.read_to_vec()?;
 
     if let Some(output_buffer_file) = args.output_buffer_file {
-        std::fs::write(output_buffer_file, &data_buffer_readback).unwrap();
+        std::fs::write(output_buffer_file, &data_buffer_readback)
+            .context("Cannot write output buffer")?;
     }
     println!("{data_buffer_readback:?}");
     Ok(())
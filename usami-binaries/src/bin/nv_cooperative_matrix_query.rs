@@ -1,12 +1,11 @@
 use ash::{
     nv::cooperative_matrix::Instance as NvCooperativeMatrix,
-    prelude::VkResult,
     vk::{self},
 };
-use usami::{UsamiDevice, UsamiInstance};
+use usami::{UsamiDevice, UsamiInstance, UsamiPhysicalDeviceSelector, UsamiResult};
 use usami_binaries::ash_ext::get_physical_device_cooperative_matrix_properties_nv;
 
-fn main() -> VkResult<()> {
+fn main() -> UsamiResult<()> {
     let extensions = ["VK_EXT_debug_utils".into()];
 
     let instance = UsamiInstance::new(
@@ -16,30 +15,22 @@ fn main() -> VkResult<()> {
         &extensions,
         true,
     )?;
-    let device = UsamiDevice::new_by_filter(
+    let device = UsamiDevice::new_by_selector(
         instance,
-        &[ash::nv::cooperative_matrix::NAME.to_string_lossy().to_string()],
-        Box::new(|physical_device| {
-            physical_device
-                .queue_familiy_properties
-                .iter()
-                .enumerate()
-                .find_map(|(i, x)| {
-                    if x.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
-                        Some(i as u32)
-                    } else {
-                        None
-                    }
-                })
-                .map(|x| (physical_device, x))
-        }),
+        &[ash::nv::cooperative_matrix::NAME
+            .to_string_lossy()
+            .to_string()],
+        UsamiPhysicalDeviceSelector::new(),
     )?;
 
     let cooperative_matrix =
         NvCooperativeMatrix::new(&device.instance.vk_entry, &device.instance.vk_instance);
 
     let cooperative_matrix_props = unsafe {
-        get_physical_device_cooperative_matrix_properties_nv(&cooperative_matrix, device.physical_device.handle)
+        get_physical_device_cooperative_matrix_properties_nv(
+            &cooperative_matrix,
+            device.physical_device.handle,
+        )
     }?;
 
     for (idx, prop) in cooperative_matrix_props.iter().enumerate() {
@@ -2,7 +2,6 @@ use std::ffi::CString;
 
 use ash::{
     ext::mesh_shader::Device as MeshShader,
-    prelude::VkResult,
     vk::{
         self, AccessFlags, AttachmentDescription, AttachmentLoadOp, AttachmentReference,
         AttachmentStoreOp, BlendFactor, BlendOp, BufferCreateFlags, BufferUsageFlags, ClearValue,
@@ -13,12 +12,14 @@ use ash::{
         PipelineDepthStencilStateCreateInfo, PipelineDynamicStateCreateInfo,
         PipelineMultisampleStateCreateInfo, PipelineRasterizationStateCreateInfo,
         PipelineShaderStageCreateInfo, PipelineStageFlags, PipelineViewportStateCreateInfo,
-        PolygonMode, QueueFlags, RenderPassBeginInfo, RenderPassCreateInfo, SampleCountFlags,
-        ShaderStageFlags, SharingMode, StencilOp, StencilOpState, SubmitInfo, SubpassContents,
-        SubpassDependency, SubpassDescription,
+        PolygonMode, RenderPassBeginInfo, RenderPassCreateInfo, SampleCountFlags, ShaderStageFlags,
+        SharingMode, StencilOp, StencilOpState, SubmitInfo, SubpassContents, SubpassDependency,
+        SubpassDescription,
     },
 };
-use usami::{UsamiDevice, UsamiInstance, UsamiPresentation};
+use usami::{
+    UsamiDevice, UsamiInstance, UsamiPhysicalDeviceSelector, UsamiPresentation, UsamiResult,
+};
 
 #[derive(Clone, Debug, Copy)]
 #[repr(C)]
@@ -27,7 +28,7 @@ struct Vertex {
     color: [f32; 4],
 }
 
-fn main() -> VkResult<()> {
+fn main() -> UsamiResult<()> {
     let extensions = ["VK_EXT_debug_utils".into()];
 
     let width = 1920;
@@ -40,27 +41,10 @@ fn main() -> VkResult<()> {
         &extensions,
         true,
     )?;
-    let device = UsamiDevice::new_by_filter(
+    let device = UsamiDevice::new_by_selector(
         instance,
         &["VK_EXT_mesh_shader".into()],
-        Box::new(|physical_device| {
-            physical_device
-                .queue_familiy_properties
-                .iter()
-                .enumerate()
-                .find_map(|(i, x)| {
-                    if physical_device.properties.device_type != PhysicalDeviceType::DISCRETE_GPU {
-                        return None;
-                    }
-
-                    if x.queue_flags.contains(QueueFlags::GRAPHICS) {
-                        Some(i as u32)
-                    } else {
-                        None
-                    }
-                })
-                .map(|x| (physical_device, x))
-        }),
+        UsamiPhysicalDeviceSelector::new().preferred_device_type(PhysicalDeviceType::DISCRETE_GPU),
     )?;
     let presentation = UsamiPresentation::new(&device, width, height)?;
 
@@ -224,7 +208,6 @@ fn main() -> VkResult<()> {
     usami::utils::record_command_buffer_with_image_dep(
         &command_buffers[0],
         &presentation.image,
-        &presentation.buffer_readback,
         |device, command_buffer, _image| {
             let vk_instance = &device.instance.vk_instance;
             let vk_device = &device.handle;
@@ -280,7 +263,7 @@ fn main() -> VkResult<()> {
     fence.wait(u64::MAX)?;
     fence.reset()?;
 
-    let res = presentation.buffer_readback.device_memory.read_to_vec()?;
+    let res = presentation.read_back(&command_pool)?;
 
     image::save_buffer_with_format(
         "output.bmp",
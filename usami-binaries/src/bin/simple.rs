@@ -1,38 +1,20 @@
-use ash::{
-    prelude::VkResult,
-    vk::{
-        self, AccessFlags, ClearColorValue, CommandBufferLevel, CommandPoolCreateFlags,
-        CommandPoolCreateInfo, DependencyFlags, FenceCreateFlags, ImageAspectFlags, ImageLayout,
-        ImageMemoryBarrier, ImageSubresourceRange, PipelineStageFlags, SubmitInfo,
-    },
+use ash::vk::{
+    self, AccessFlags, ClearColorValue, CommandBufferLevel, CommandPoolCreateFlags,
+    CommandPoolCreateInfo, DependencyFlags, FenceCreateFlags, ImageAspectFlags, ImageLayout,
+    ImageMemoryBarrier, ImageSubresourceRange, PipelineStageFlags, SubmitInfo,
+};
+use usami::{
+    UsamiDevice, UsamiInstance, UsamiPhysicalDeviceSelector, UsamiPresentation, UsamiResult,
 };
-use usami::{UsamiDevice, UsamiInstance, UsamiPresentation};
 
-fn main() -> VkResult<()> {
+fn main() -> UsamiResult<()> {
     let extensions = ["VK_EXT_debug_utils".into()];
 
     let width = 500;
     let height = 500;
 
     let instance = UsamiInstance::new("simple", "usami", vk::API_VERSION_1_1, &extensions, true)?;
-    let device = UsamiDevice::new_by_filter(
-        instance,
-        &[],
-        Box::new(|physical_device| {
-            physical_device
-                .queue_familiy_properties
-                .iter()
-                .enumerate()
-                .find_map(|(i, x)| {
-                    if x.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
-                        Some(i as u32)
-                    } else {
-                        None
-                    }
-                })
-                .map(|x| (physical_device, x))
-        }),
-    )?;
+    let device = UsamiDevice::new_by_selector(instance, &[], UsamiPhysicalDeviceSelector::new())?;
     let presentation = UsamiPresentation::new(&device, width, height)?;
 
     let command_pool = UsamiDevice::create_command_pool(
@@ -52,7 +34,6 @@ fn main() -> VkResult<()> {
     usami::utils::record_command_buffer_with_image_dep(
         &command_buffers[0],
         &presentation.image,
-        &presentation.buffer_readback,
         |device, command_buffer, image| {
             let image_subresource_range = ImageSubresourceRange::default()
                 .base_array_layer(0)
@@ -106,7 +87,7 @@ fn main() -> VkResult<()> {
     fence.wait(u64::MAX)?;
     fence.reset()?;
 
-    let res = presentation.buffer_readback.device_memory.read_to_vec()?;
+    let res = presentation.read_back(&command_pool)?;
 
     image::save_buffer_with_format(
         "output.bmp",
@@ -1,29 +1,20 @@
-use std::ffi::CString;
-
-use ash::{
-    prelude::VkResult,
-    vk::{
-        self, AccessFlags, AttachmentDescription, AttachmentLoadOp, AttachmentReference,
-        AttachmentStoreOp, BlendFactor, BlendOp, BorderColor, BufferCreateFlags, BufferUsageFlags,
-        ClearValue, ColorComponentFlags, CommandBufferLevel, CommandPoolCreateFlags,
-        CommandPoolCreateInfo, CompareOp, ComponentMapping, ComponentSwizzle, DescriptorImageInfo,
-        DescriptorPoolCreateInfo, DescriptorSetLayoutCreateInfo, DescriptorType, DynamicState,
-        FenceCreateFlags, Filter, Format, FrontFace, GraphicsPipelineCreateInfo, ImageAspectFlags,
-        ImageLayout, ImageSubresourceRange, ImageUsageFlags, ImageViewCreateFlags, ImageViewType,
-        IndexType, LogicOp, PipelineBindPoint, PipelineCache, PipelineColorBlendAttachmentState,
-        PipelineColorBlendStateCreateInfo, PipelineDepthStencilStateCreateInfo,
-        PipelineDynamicStateCreateInfo, PipelineInputAssemblyStateCreateInfo,
-        PipelineMultisampleStateCreateInfo, PipelineRasterizationStateCreateInfo,
-        PipelineShaderStageCreateInfo, PipelineStageFlags, PipelineVertexInputStateCreateInfo,
-        PipelineViewportStateCreateInfo, PolygonMode, PrimitiveTopology, QueueFlags,
-        RenderPassBeginInfo, RenderPassCreateInfo, SampleCountFlags, SamplerAddressMode,
-        SamplerCreateInfo, SamplerMipmapMode, ShaderStageFlags, SharingMode, StencilOp,
-        StencilOpState, SubmitInfo, SubpassContents, SubpassDependency, SubpassDescription,
-        VertexInputAttributeDescription, VertexInputBindingDescription, VertexInputRate,
-        WriteDescriptorSet,
-    },
+use std::sync::Arc;
+
+use ash::vk::{
+    self, AccessFlags, AttachmentDescription, AttachmentLoadOp, AttachmentReference,
+    AttachmentStoreOp, BorderColor, BufferCreateFlags, BufferUsageFlags, ClearValue,
+    CommandBufferLevel, CommandPoolCreateFlags, CommandPoolCreateInfo, CompareOp, ComponentMapping,
+    ComponentSwizzle, DescriptorPoolCreateFlags, DescriptorSetLayoutCreateInfo, DescriptorType,
+    FenceCreateFlags, Filter, Format, ImageAspectFlags, ImageLayout, ImageSubresourceRange,
+    ImageUsageFlags, ImageViewCreateFlags, ImageViewType, IndexType, PipelineBindPoint,
+    PipelineStageFlags, RenderPassBeginInfo, RenderPassCreateInfo, SamplerAddressMode,
+    SamplerCreateInfo, SamplerMipmapMode, ShaderStageFlags, SharingMode, SubmitInfo,
+    SubpassContents, SubpassDependency, SubpassDescription, VertexInputRate,
+};
+use usami::{
+    offset_of, Error, UsamiDevice, UsamiGraphicsPipelineBuilder, UsamiInstance,
+    UsamiPhysicalDeviceSelector, UsamiPresentation, UsamiResult,
 };
-use usami::{offset_of, UsamiDevice, UsamiInstance, UsamiPresentation};
 
 #[derive(Clone, Debug, Copy)]
 #[repr(C)]
@@ -32,31 +23,14 @@ struct Vertex {
     uv: [f32; 2],
 }
 
-fn main() -> VkResult<()> {
+fn main() -> UsamiResult<()> {
     let extensions = ["VK_EXT_debug_utils".into()];
 
     let width = 1920;
     let height = 1080;
 
     let instance = UsamiInstance::new("triangle", "usami", vk::API_VERSION_1_1, &extensions, true)?;
-    let device = UsamiDevice::new_by_filter(
-        instance,
-        &[],
-        Box::new(|physical_device| {
-            physical_device
-                .queue_familiy_properties
-                .iter()
-                .enumerate()
-                .find_map(|(i, x)| {
-                    if x.queue_flags.contains(QueueFlags::GRAPHICS) {
-                        Some(i as u32)
-                    } else {
-                        None
-                    }
-                })
-                .map(|x| (physical_device, x))
-        }),
-    )?;
+    let device = UsamiDevice::new_by_selector(instance, &[], UsamiPhysicalDeviceSelector::new())?;
     let presentation = UsamiPresentation::new(&device, width, height)?;
 
     let command_pool = UsamiDevice::create_command_pool(
@@ -125,21 +99,13 @@ fn main() -> VkResult<()> {
     let vertex_shader =
         UsamiDevice::create_shader(&device, "vertex_shader".into(), &vertex_shader_code)?;
     let frag_shader = UsamiDevice::create_shader(&device, "frag_shader".into(), &frag_shader_code)?;
-    let shader_entrypoint_name = CString::new("main").unwrap();
 
-    let descriptor_pool_sizes = [vk::DescriptorPoolSize {
-        ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-        descriptor_count: 1,
-    }];
-    let descriptor_pool_create_info = DescriptorPoolCreateInfo::default()
-        .pool_sizes(&descriptor_pool_sizes)
-        .max_sets(1);
-
-    let descriptor_pool = UsamiDevice::create_descriptor_pool(
+    let mut descriptor_allocator = UsamiDevice::create_descriptor_allocator(
         &device,
-        "descriptor_pool".into(),
-        descriptor_pool_create_info,
-    )?;
+        "descriptor_allocator".into(),
+        DescriptorPoolCreateFlags::empty(),
+        1,
+    );
 
     let desc_layout_bindings = [vk::DescriptorSetLayoutBinding::default()
         .binding(0)
@@ -152,27 +118,29 @@ fn main() -> VkResult<()> {
         DescriptorSetLayoutCreateInfo::default().bindings(&desc_layout_bindings),
     )?;
 
-    let descriptor_sets = descriptor_pool
-        .allocate_descriptor_sets("descriptor_set".into(), &[descriptor_set_layout.handle])?;
-
-    let white_image_view = white_image.create_simple_image_view(
-        "presentation_image_view".into(),
-        ImageViewType::TYPE_2D,
-        ImageSubresourceRange::default()
-            .aspect_mask(ImageAspectFlags::COLOR)
-            .base_mip_level(0)
-            .level_count(1)
-            .base_array_layer(0)
-            .layer_count(1),
-        ComponentMapping::default()
-            .r(ComponentSwizzle::IDENTITY)
-            .g(ComponentSwizzle::IDENTITY)
-            .b(ComponentSwizzle::IDENTITY)
-            .a(ComponentSwizzle::IDENTITY),
-        ImageViewCreateFlags::empty(),
-    )?;
-
-    let sampler = UsamiDevice::create_sampler(
+    let descriptor_sets =
+        descriptor_allocator.allocate("descriptor_set".into(), &[&descriptor_set_layout])?;
+
+    let white_image_view = Arc::new(
+        white_image.create_simple_image_view(
+            "presentation_image_view".into(),
+            ImageViewType::TYPE_2D,
+            ImageSubresourceRange::default()
+                .aspect_mask(ImageAspectFlags::COLOR)
+                .base_mip_level(0)
+                .level_count(1)
+                .base_array_layer(0)
+                .layer_count(1),
+            ComponentMapping::default()
+                .r(ComponentSwizzle::IDENTITY)
+                .g(ComponentSwizzle::IDENTITY)
+                .b(ComponentSwizzle::IDENTITY)
+                .a(ComponentSwizzle::IDENTITY),
+            ImageViewCreateFlags::empty(),
+        )?,
+    );
+
+    let sampler = Arc::new(UsamiDevice::create_sampler(
         &device,
         "sampler".into(),
         SamplerCreateInfo::default()
@@ -185,21 +153,12 @@ fn main() -> VkResult<()> {
             .max_anisotropy(1.0)
             .border_color(BorderColor::FLOAT_TRANSPARENT_BLACK)
             .compare_op(CompareOp::NEVER),
-    )?;
+    )?);
 
-    unsafe {
-        device.handle.update_descriptor_sets(
-            &[WriteDescriptorSet::default()
-                .dst_set(descriptor_sets[0].handle)
-                .dst_binding(0)
-                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
-                .image_info(&[DescriptorImageInfo::default()
-                    .image_layout(ImageLayout::GENERAL)
-                    .image_view(white_image_view.handle)
-                    .sampler(sampler.handle)])],
-            &[],
-        );
-    }
+    descriptor_sets[0]
+        .write()
+        .combined_image_sampler(0, &white_image_view, ImageLayout::GENERAL, &sampler)
+        .update();
 
     let pipeline_layout = UsamiDevice::create_pipeline_layout(
         &device,
@@ -208,88 +167,9 @@ fn main() -> VkResult<()> {
         &[],
     )?;
 
-    let shader_stage_create_infos = [
-        PipelineShaderStageCreateInfo::default()
-            .module(vertex_shader.handle)
-            .name(shader_entrypoint_name.as_c_str())
-            .stage(ShaderStageFlags::VERTEX),
-        PipelineShaderStageCreateInfo::default()
-            .module(frag_shader.handle)
-            .name(shader_entrypoint_name.as_c_str())
-            .stage(ShaderStageFlags::FRAGMENT),
-    ];
-
-    let vertex_input_binding_descriptions = [VertexInputBindingDescription::default()
-        .binding(0)
-        .stride(std::mem::size_of::<Vertex>() as u32)
-        .input_rate(VertexInputRate::VERTEX)];
-
-    let vertex_input_attribute_descriptions = [
-        VertexInputAttributeDescription::default()
-            .location(0)
-            .binding(0)
-            .offset(offset_of!(Vertex, pos) as u32)
-            .format(Format::R32G32B32A32_SFLOAT),
-        VertexInputAttributeDescription::default()
-            .location(1)
-            .binding(0)
-            .offset(offset_of!(Vertex, uv) as u32)
-            .format(Format::R32G32_SFLOAT),
-    ];
-
-    let vertex_input_state_create_info = PipelineVertexInputStateCreateInfo::default()
-        .vertex_attribute_descriptions(&vertex_input_attribute_descriptions)
-        .vertex_binding_descriptions(&vertex_input_binding_descriptions);
-
-    let vertex_input_assembly_state_create_info =
-        PipelineInputAssemblyStateCreateInfo::default().topology(PrimitiveTopology::TRIANGLE_LIST);
-
     let scissors = [presentation.rect2d()];
     let viewports = [presentation.viewport()];
 
-    let viewport_state_create_info = PipelineViewportStateCreateInfo::default()
-        .scissors(&scissors)
-        .viewports(&viewports);
-
-    let rasterization_create_info = PipelineRasterizationStateCreateInfo::default()
-        .front_face(FrontFace::COUNTER_CLOCKWISE)
-        .polygon_mode(PolygonMode::FILL)
-        .line_width(1.0);
-
-    let multisample_state_create_info = PipelineMultisampleStateCreateInfo::default()
-        .rasterization_samples(SampleCountFlags::TYPE_1);
-
-    let noop_stencil = StencilOpState::default()
-        .fail_op(StencilOp::KEEP)
-        .pass_op(StencilOp::KEEP)
-        .depth_fail_op(StencilOp::KEEP)
-        .compare_op(CompareOp::ALWAYS);
-
-    let depth_state_create_info = PipelineDepthStencilStateCreateInfo::default()
-        .depth_test_enable(true)
-        .depth_write_enable(true)
-        .depth_compare_op(CompareOp::LESS_OR_EQUAL)
-        .front(noop_stencil)
-        .back(noop_stencil)
-        .max_depth_bounds(1.0);
-
-    let attachements = [PipelineColorBlendAttachmentState::default()
-        .blend_enable(false)
-        .src_color_blend_factor(BlendFactor::SRC_COLOR)
-        .dst_color_blend_factor(BlendFactor::ONE_MINUS_DST_COLOR)
-        .color_blend_op(BlendOp::ADD)
-        .src_alpha_blend_factor(BlendFactor::ZERO)
-        .dst_alpha_blend_factor(BlendFactor::ZERO)
-        .alpha_blend_op(BlendOp::ADD)
-        .color_write_mask(ColorComponentFlags::RGBA)];
-
-    let color_blend_create_state = PipelineColorBlendStateCreateInfo::default()
-        .logic_op(LogicOp::CLEAR)
-        .attachments(&attachements);
-
-    let dynamic_state_create_info = PipelineDynamicStateCreateInfo::default()
-        .dynamic_states(&[DynamicState::VIEWPORT, DynamicState::SCISSOR]);
-
     let renderpass_attachments = [AttachmentDescription::default()
         .format(presentation.image.format)
         .samples(presentation.image.samples)
@@ -318,27 +198,24 @@ fn main() -> VkResult<()> {
     let render_pass =
         UsamiDevice::create_render_pass(&device, "render_pass".into(), render_pass_create_info)?;
 
-    let graphics_pipeline_create_info = GraphicsPipelineCreateInfo::default()
-        .stages(&shader_stage_create_infos)
-        .vertex_input_state(&vertex_input_state_create_info)
-        .input_assembly_state(&vertex_input_assembly_state_create_info)
-        .viewport_state(&viewport_state_create_info)
-        .rasterization_state(&rasterization_create_info)
-        .multisample_state(&multisample_state_create_info)
-        .depth_stencil_state(&depth_state_create_info)
-        .color_blend_state(&color_blend_create_state)
-        .dynamic_state(&dynamic_state_create_info)
-        .layout(pipeline_layout.handle)
-        .render_pass(render_pass.handle);
-
-    let pipelines = UsamiDevice::create_graphics_pipelines(
-        &device,
-        "pipeline".into(),
-        PipelineCache::null(),
-        &[graphics_pipeline_create_info],
-    )?;
-
-    let graphic_pipeline = &pipelines[0];
+    let graphic_pipeline = UsamiGraphicsPipelineBuilder::new()
+        .vertex_shader(&vertex_shader)
+        .fragment_shader(&frag_shader)
+        .vertex_binding(
+            0,
+            std::mem::size_of::<Vertex>() as u32,
+            VertexInputRate::VERTEX,
+        )
+        .vertex_attribute(
+            0,
+            0,
+            Format::R32G32B32A32_SFLOAT,
+            offset_of!(Vertex, pos) as u32,
+        )
+        .vertex_attribute(1, 0, Format::R32G32_SFLOAT, offset_of!(Vertex, uv) as u32)
+        .depth(true, true, CompareOp::LESS_OR_EQUAL)
+        .render_pass(&render_pass, 0)
+        .build(&device, "pipeline".into(), &pipeline_layout)?;
 
     let framebuffer =
         presentation.create_framebuffer(&device, "framebuffer".into(), &render_pass)?;
@@ -352,7 +229,6 @@ fn main() -> VkResult<()> {
     usami::utils::record_command_buffer_with_image_dep(
         &command_buffers[0],
         &presentation.image,
-        &presentation.buffer_readback,
         |device, command_buffer, _image| {
             let vk_device = &device.handle;
             let clear_values = [ClearValue {
@@ -425,16 +301,29 @@ fn main() -> VkResult<()> {
     fence.wait(u64::MAX)?;
     fence.reset()?;
 
-    let res = presentation.buffer_readback.device_memory.read_to_vec()?;
-
-    image::save_buffer_with_format(
-        "output.bmp",
-        &res,
-        width,
-        height,
-        image::ColorType::Rgba8,
-        image::ImageFormat::Bmp,
-    )
-    .unwrap();
+    for readback in presentation.image.read_back(
+        &command_pool,
+        presentation
+            .image
+            .whole_subresource_range(ImageAspectFlags::COLOR),
+        None,
+    )? {
+        readback.save_png("output.png")?;
+
+        // The presentation image is RGBA8, so its texels can be saved as they are.
+        image::save_buffer_with_format(
+            "output.bmp",
+            &readback.data,
+            readback.extent.width,
+            readback.extent.height,
+            image::ColorType::Rgba8,
+            image::ImageFormat::Bmp,
+        )
+        .map_err(|source| Error::Image {
+            path: "output.bmp".into(),
+            source,
+        })?;
+    }
+
     Ok(())
 }
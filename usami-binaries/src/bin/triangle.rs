@@ -1,25 +1,12 @@
-use std::ffi::CString;
-
-use ash::{
-    prelude::VkResult,
-    vk::{
-        self, AccessFlags, AttachmentDescription, AttachmentLoadOp, AttachmentReference,
-        AttachmentStoreOp, BlendFactor, BlendOp, BufferCreateFlags, BufferUsageFlags, ClearValue,
-        ColorComponentFlags, CommandBufferLevel, CommandPoolCreateFlags, CommandPoolCreateInfo,
-        CompareOp, DynamicState, FenceCreateFlags, Format, FrontFace, GraphicsPipelineCreateInfo,
-        ImageLayout, LogicOp, PhysicalDeviceType, PipelineBindPoint, PipelineCache,
-        PipelineColorBlendAttachmentState, PipelineColorBlendStateCreateInfo,
-        PipelineDepthStencilStateCreateInfo, PipelineDynamicStateCreateInfo,
-        PipelineInputAssemblyStateCreateInfo, PipelineMultisampleStateCreateInfo,
-        PipelineRasterizationStateCreateInfo, PipelineShaderStageCreateInfo, PipelineStageFlags,
-        PipelineVertexInputStateCreateInfo, PipelineViewportStateCreateInfo, PolygonMode,
-        PrimitiveTopology, QueueFlags, RenderPassBeginInfo, RenderPassCreateInfo, SampleCountFlags,
-        ShaderStageFlags, SharingMode, StencilOp, StencilOpState, SubmitInfo, SubpassContents,
-        SubpassDependency, SubpassDescription, VertexInputAttributeDescription,
-        VertexInputBindingDescription, VertexInputRate,
-    },
+use ash::vk::{
+    self, AttachmentLoadOp, BufferCreateFlags, BufferUsageFlags, CommandBufferLevel,
+    CommandBufferUsageFlags, CommandPoolCreateFlags, CommandPoolCreateInfo, FenceCreateFlags,
+    Format, ImageAspectFlags, PhysicalDeviceType, SharingMode, SubmitInfo, VertexInputRate,
+};
+use usami::{
+    offset_of, Error, UsamiDevice, UsamiGraphicsPipelineBuilder, UsamiInstance,
+    UsamiPhysicalDeviceSelector, UsamiPresentation, UsamiResult,
 };
-use usami::{offset_of, UsamiDevice, UsamiInstance, UsamiPresentation};
 
 #[derive(Clone, Debug, Copy)]
 #[repr(C)]
@@ -28,34 +15,17 @@ struct Vertex {
     color: [f32; 4],
 }
 
-fn main() -> VkResult<()> {
+fn main() -> UsamiResult<()> {
     let extensions = ["VK_EXT_debug_utils".into()];
 
     let width = 1920;
     let height = 1080;
 
-    let instance = UsamiInstance::new("triangle", "usami", vk::API_VERSION_1_1, &extensions, true)?;
-    let device = UsamiDevice::new_by_filter(
+    let instance = UsamiInstance::new("triangle", "usami", vk::API_VERSION_1_3, &extensions, true)?;
+    let device = UsamiDevice::new_by_selector(
         instance,
         &[],
-        Box::new(|physical_device| {
-            physical_device
-                .queue_familiy_properties
-                .iter()
-                .enumerate()
-                .find_map(|(i, x)| {
-                    if physical_device.properties.device_type != PhysicalDeviceType::DISCRETE_GPU {
-                        return None;
-                    }
-
-                    if x.queue_flags.contains(QueueFlags::GRAPHICS) {
-                        Some(i as u32)
-                    } else {
-                        None
-                    }
-                })
-                .map(|x| (physical_device, x))
-        }),
+        UsamiPhysicalDeviceSelector::new().preferred_device_type(PhysicalDeviceType::DISCRETE_GPU),
     )?;
     let presentation = UsamiPresentation::new(&device, width, height)?;
 
@@ -101,145 +71,38 @@ fn main() -> VkResult<()> {
     let vertex_shader =
         UsamiDevice::create_shader(&device, "vertex_shader".into(), &vertex_shader_code)?;
     let frag_shader = UsamiDevice::create_shader(&device, "frag_shader".into(), &frag_shader_code)?;
-    let shader_entrypoint_name = CString::new("main").unwrap();
-
     let pipeline_layout =
         UsamiDevice::create_pipeline_layout(&device, "base_pipeline_layout".into(), &[], &[])?;
 
-    let shader_stage_create_infos = [
-        PipelineShaderStageCreateInfo::default()
-            .module(vertex_shader.handle)
-            .name(shader_entrypoint_name.as_c_str())
-            .stage(ShaderStageFlags::VERTEX),
-        PipelineShaderStageCreateInfo::default()
-            .module(frag_shader.handle)
-            .name(shader_entrypoint_name.as_c_str())
-            .stage(ShaderStageFlags::FRAGMENT),
-    ];
-
-    let vertex_input_binding_descriptions = [VertexInputBindingDescription::default()
-        .binding(0)
-        .stride(std::mem::size_of::<Vertex>() as u32)
-        .input_rate(VertexInputRate::VERTEX)];
-
-    let vertex_input_attribute_descriptions = [
-        VertexInputAttributeDescription::default()
-            .location(0)
-            .binding(0)
-            .offset(offset_of!(Vertex, pos) as u32)
-            .format(Format::R32G32B32A32_SFLOAT),
-        VertexInputAttributeDescription::default()
-            .location(1)
-            .binding(0)
-            .offset(offset_of!(Vertex, color) as u32)
-            .format(Format::R32G32B32A32_SFLOAT),
-    ];
-
-    let vertex_input_state_create_info = PipelineVertexInputStateCreateInfo::default()
-        .vertex_attribute_descriptions(&vertex_input_attribute_descriptions)
-        .vertex_binding_descriptions(&vertex_input_binding_descriptions);
-
-    let vertex_input_assembly_state_create_info =
-        PipelineInputAssemblyStateCreateInfo::default().topology(PrimitiveTopology::TRIANGLE_LIST);
-
     let scissors = [presentation.rect2d()];
     let viewports = [presentation.viewport()];
 
-    let viewport_state_create_info = PipelineViewportStateCreateInfo::default()
-        .scissors(&scissors)
-        .viewports(&viewports);
-
-    let rasterization_create_info = PipelineRasterizationStateCreateInfo::default()
-        .front_face(FrontFace::COUNTER_CLOCKWISE)
-        .polygon_mode(PolygonMode::FILL)
-        .line_width(1.0);
-
-    let multisample_state_create_info = PipelineMultisampleStateCreateInfo::default()
-        .rasterization_samples(SampleCountFlags::TYPE_1);
-
-    let noop_stencil = StencilOpState::default()
-        .fail_op(StencilOp::KEEP)
-        .pass_op(StencilOp::KEEP)
-        .depth_fail_op(StencilOp::KEEP)
-        .compare_op(CompareOp::ALWAYS);
-
-    let depth_state_create_info = PipelineDepthStencilStateCreateInfo::default()
-        .depth_test_enable(true)
-        .depth_write_enable(true)
-        .depth_compare_op(CompareOp::LESS_OR_EQUAL)
-        .front(noop_stencil)
-        .back(noop_stencil)
-        .max_depth_bounds(1.0);
-
-    let attachements = [PipelineColorBlendAttachmentState::default()
-        .blend_enable(false)
-        .src_color_blend_factor(BlendFactor::SRC_COLOR)
-        .dst_color_blend_factor(BlendFactor::ONE_MINUS_DST_COLOR)
-        .color_blend_op(BlendOp::ADD)
-        .src_alpha_blend_factor(BlendFactor::ZERO)
-        .dst_alpha_blend_factor(BlendFactor::ZERO)
-        .alpha_blend_op(BlendOp::ADD)
-        .color_write_mask(ColorComponentFlags::RGBA)];
-
-    let color_blend_create_state = PipelineColorBlendStateCreateInfo::default()
-        .logic_op(LogicOp::CLEAR)
-        .attachments(&attachements);
-
-    let dynamic_state_create_info = PipelineDynamicStateCreateInfo::default()
-        .dynamic_states(&[DynamicState::VIEWPORT, DynamicState::SCISSOR]);
-
-    let renderpass_attachments = [AttachmentDescription::default()
-        .format(presentation.image.format)
-        .samples(presentation.image.samples)
-        .load_op(AttachmentLoadOp::CLEAR)
-        .store_op(AttachmentStoreOp::STORE)
-        .final_layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)];
-    let color_attachment_refs = [AttachmentReference::default()
-        .attachment(0)
-        .layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)];
-
-    let dependencies = [SubpassDependency::default()
-        .src_subpass(vk::SUBPASS_EXTERNAL)
-        .src_stage_mask(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-        .dst_access_mask(AccessFlags::COLOR_ATTACHMENT_READ | AccessFlags::COLOR_ATTACHMENT_WRITE)
-        .dst_stage_mask(PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)];
-
-    let renderpass_subpasses = [SubpassDescription::default()
-        .color_attachments(&color_attachment_refs)
-        .pipeline_bind_point(PipelineBindPoint::GRAPHICS)];
-
-    let render_pass_create_info = RenderPassCreateInfo::default()
-        .attachments(&renderpass_attachments)
-        .subpasses(&renderpass_subpasses)
-        .dependencies(&dependencies);
-
-    let render_pass =
-        UsamiDevice::create_render_pass(&device, "render_pass".into(), render_pass_create_info)?;
-
-    let graphics_pipeline_create_info = GraphicsPipelineCreateInfo::default()
-        .stages(&shader_stage_create_infos)
-        .vertex_input_state(&vertex_input_state_create_info)
-        .input_assembly_state(&vertex_input_assembly_state_create_info)
-        .viewport_state(&viewport_state_create_info)
-        .rasterization_state(&rasterization_create_info)
-        .multisample_state(&multisample_state_create_info)
-        .depth_stencil_state(&depth_state_create_info)
-        .color_blend_state(&color_blend_create_state)
-        .dynamic_state(&dynamic_state_create_info)
-        .layout(pipeline_layout.handle)
-        .render_pass(render_pass.handle);
-
-    let pipelines = UsamiDevice::create_graphics_pipelines(
-        &device,
-        "pipeline".into(),
-        PipelineCache::null(),
-        &[graphics_pipeline_create_info],
-    )?;
-
-    let graphic_pipeline = &pipelines[0];
-
-    let framebuffer =
-        presentation.create_framebuffer(&device, "framebuffer".into(), &render_pass)?;
+    let graphic_pipeline = UsamiGraphicsPipelineBuilder::new()
+        .vertex_shader(&vertex_shader)
+        .fragment_shader(&frag_shader)
+        .vertex_binding(
+            0,
+            std::mem::size_of::<Vertex>() as u32,
+            VertexInputRate::VERTEX,
+        )
+        .vertex_attribute(
+            0,
+            0,
+            Format::R32G32B32A32_SFLOAT,
+            offset_of!(Vertex, pos) as u32,
+        )
+        .vertex_attribute(
+            1,
+            0,
+            Format::R32G32B32A32_SFLOAT,
+            offset_of!(Vertex, color) as u32,
+        )
+        .rendering_formats(
+            &[presentation.image.format],
+            Format::UNDEFINED,
+            Format::UNDEFINED,
+        )
+        .build(&device, "pipeline".into(), &pipeline_layout)?;
 
     let command_pool = UsamiDevice::create_command_pool(
         &device,
@@ -255,30 +118,18 @@ fn main() -> VkResult<()> {
         1,
     )?;
 
-    usami::utils::record_command_buffer_with_image_dep(
-        &command_buffers[0],
-        &presentation.image,
-        &presentation.buffer_readback,
-        |device, command_buffer, _image| {
+    command_buffers[0].record(
+        CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+        |device, command_buffer| {
             let vk_device = &device.handle;
-            let clear_values = [ClearValue {
-                color: vk::ClearColorValue {
-                    float32: [0.0, 0.0, 0.0, 0.0],
-                },
-            }];
 
-            let render_pass_begin_info = RenderPassBeginInfo::default()
-                .render_pass(render_pass.handle)
-                .framebuffer(framebuffer.handle)
-                .render_area(presentation.rect2d())
-                .clear_values(&clear_values);
+            presentation.begin_rendering(
+                command_buffer,
+                AttachmentLoadOp::CLEAR,
+                [0.0, 0.0, 0.0, 0.0],
+            )?;
 
             unsafe {
-                vk_device.cmd_begin_render_pass(
-                    command_buffer.handle,
-                    &render_pass_begin_info,
-                    SubpassContents::INLINE,
-                );
                 vk_device.cmd_bind_pipeline(
                     command_buffer.handle,
                     vk::PipelineBindPoint::GRAPHICS,
@@ -306,11 +157,11 @@ fn main() -> VkResult<()> {
                     0,
                     1,
                 );
-                //vk_device.cmd_draw(command_buffer.handle, 3, 2, 0, 0);
-                vk_device.cmd_end_render_pass(command_buffer.handle);
-
-                ImageLayout::COLOR_ATTACHMENT_OPTIMAL
             }
+
+            command_buffer.end_rendering();
+
+            Ok(())
         },
     )?;
 
@@ -324,16 +175,29 @@ fn main() -> VkResult<()> {
     fence.wait(u64::MAX)?;
     fence.reset()?;
 
-    let res = presentation.buffer_readback.device_memory.read_to_vec()?;
+    for readback in presentation.image.read_back(
+        &command_pool,
+        presentation
+            .image
+            .whole_subresource_range(ImageAspectFlags::COLOR),
+        None,
+    )? {
+        readback.save_png("output.png")?;
+
+        // The presentation image is RGBA8, so its texels can be saved as they are.
+        image::save_buffer_with_format(
+            "output.bmp",
+            &readback.data,
+            readback.extent.width,
+            readback.extent.height,
+            image::ColorType::Rgba8,
+            image::ImageFormat::Bmp,
+        )
+        .map_err(|source| Error::Image {
+            path: "output.bmp".into(),
+            source,
+        })?;
+    }
 
-    image::save_buffer_with_format(
-        "output.bmp",
-        &res,
-        width,
-        height,
-        image::ColorType::Rgba8,
-        image::ImageFormat::Bmp,
-    )
-    .unwrap();
     Ok(())
 }
@@ -3,7 +3,6 @@ use std::ffi::CString;
 use ash::{
     ext::conditional_rendering::Device as ConditionalRendering,
     ext::transform_feedback::Device as TransformFeedback,
-    prelude::VkResult,
     vk::{
         self, AccessFlags, AttachmentDescription, AttachmentLoadOp, AttachmentReference,
         AttachmentStoreOp, BlendFactor, BlendOp, BufferCreateFlags, BufferUsageFlags,
@@ -17,13 +16,16 @@ use ash::{
         PipelineRasterizationStateCreateInfo, PipelineShaderStageCreateInfo, PipelineStageFlags,
         PipelineVertexInputStateCreateInfo, PipelineViewportStateCreateInfo, PolygonMode,
         PrimitiveTopology, PushConstantRange, QueryControlFlags, QueryPoolCreateInfo,
-        QueryResultFlags, QueryType, QueueFlags, RenderPassBeginInfo, RenderPassCreateInfo,
-        SampleCountFlags, ShaderStageFlags, SharingMode, StencilOp, StencilOpState, SubmitInfo,
-        SubpassContents, SubpassDescription, VertexInputAttributeDescription,
-        VertexInputBindingDescription, VertexInputRate,
+        QueryResultFlags, QueryType, RenderPassBeginInfo, RenderPassCreateInfo, SampleCountFlags,
+        ShaderStageFlags, SharingMode, StencilOp, StencilOpState, SubmitInfo, SubpassContents,
+        SubpassDescription, VertexInputAttributeDescription, VertexInputBindingDescription,
+        VertexInputRate,
     },
 };
-use usami::{offset_of, UsamiDevice, UsamiInstance, UsamiPresentation};
+use usami::{
+    offset_of, UsamiDevice, UsamiInstance, UsamiPhysicalDeviceSelector, UsamiPresentation,
+    UsamiResult,
+};
 use usami_binaries::ash_ext::{
     begin_conditional_rendering, begin_transform_feedback, bind_transform_feedback_buffers,
     end_conditional_rendering, end_transform_feedback,
@@ -37,7 +39,7 @@ struct VertexElementData {
     vertex_index: i32,
 }
 
-fn main() -> VkResult<()> {
+fn main() -> UsamiResult<()> {
     let extensions = ["VK_EXT_debug_utils".into()];
 
     let width = 256;
@@ -51,31 +53,14 @@ fn main() -> VkResult<()> {
         true,
     )?;
 
-    let device = UsamiDevice::new_by_filter(
+    let device = UsamiDevice::new_by_selector(
         instance,
         &[
             "VK_EXT_conditional_rendering".into(),
             "VK_EXT_transform_feedback".into(),
             "VK_EXT_host_query_reset".into(),
         ],
-        Box::new(|physical_device| {
-            physical_device
-                .queue_familiy_properties
-                .iter()
-                .enumerate()
-                .find_map(|(i, x)| {
-                    if physical_device.properties.device_type != PhysicalDeviceType::DISCRETE_GPU {
-                        return None;
-                    }
-
-                    if x.queue_flags.contains(QueueFlags::GRAPHICS) {
-                        Some(i as u32)
-                    } else {
-                        None
-                    }
-                })
-                .map(|x| (physical_device, x))
-        }),
+        UsamiPhysicalDeviceSelector::new().preferred_device_type(PhysicalDeviceType::DISCRETE_GPU),
     )?;
 
     let xfb = TransformFeedback::new(&device.instance.vk_instance, &device.handle);
@@ -430,6 +415,8 @@ fn main() -> VkResult<()> {
         device.cmd_draw(command_buffer.handle, 6, 1, 6 * index, 0);
     };
 
+    // Conditional rendering and transform feedback accesses cannot be described with UsamiAccess,
+    // so barriers are recorded by hand instead of using a UsamiRenderGraph.
     command_buffer.record(
         CommandBufferUsageFlags::empty(),
         |device, command_buffer| {
@@ -579,19 +566,18 @@ fn main() -> VkResult<()> {
     fence.wait(u64::MAX)?;
     fence.reset()?;
 
-    let xfb_buffer_readback = xfb_buffer.device_memory.read_to_vec().unwrap();
+    let xfb_buffer_readback = xfb_buffer.allocation.read_to_vec().unwrap();
     std::fs::write("output.hex", &xfb_buffer_readback).unwrap();
 
-    let query_bufer_readback = query_buffer.device_memory.read_to_vec().unwrap();
+    let query_bufer_readback = query_buffer.allocation.read_to_vec().unwrap();
 
     println!("query_results_raw = {query_bufer_readback:?}");
-    for query_index in 0..2 {
-        let value = u32::from_le_bytes(
-            query_bufer_readback[query_index * 4..(query_index + 1) * 4]
-                .try_into()
-                .unwrap(),
-        );
-
+    for (query_index, value) in query_buffer
+        .mapped_slice::<u32>()?
+        .iter()
+        .take(2)
+        .enumerate()
+    {
         println!("query_results[{query_index}] = {value}");
     }
 
@@ -612,7 +598,7 @@ fn main() -> VkResult<()> {
         println!("xfb_stream[{stream}] = {values:?}");
     }
 
-    let res = presentation.buffer_readback.device_memory.read_to_vec()?;
+    let res = presentation.read_back(&command_pool)?;
 
     image::save_buffer_with_format(
         "output.bmp",
@@ -0,0 +1,321 @@
+use std::sync::{Arc, Mutex};
+
+use ash::vk::{
+    self, DescriptorBindingFlags, DescriptorPoolCreateFlags, DescriptorPoolCreateInfo,
+    DescriptorPoolSize, DescriptorSetLayoutBinding, DescriptorSetLayoutBindingFlagsCreateInfo,
+    DescriptorSetLayoutCreateFlags, DescriptorSetLayoutCreateInfo, DescriptorType, DeviceSize,
+    ImageLayout, ShaderStageFlags,
+};
+
+use crate::{
+    descriptor::UsamiDescriptorSetLayout, Error, ResultExt, UsamiBuffer, UsamiDescriptorPool,
+    UsamiDescriptorSet, UsamiDevice, UsamiDeviceFeatures, UsamiImageView, UsamiResult,
+    UsamiSampler,
+};
+
+/// Kind of resource stored in a [UsamiBindlessTable], each kind is an array at its own binding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UsamiBindlessKind {
+    Sampler,
+    SampledImage,
+    StorageImage,
+    StorageBuffer,
+}
+
+impl UsamiBindlessKind {
+    pub const ALL: [Self; 4] = [
+        Self::Sampler,
+        Self::SampledImage,
+        Self::StorageImage,
+        Self::StorageBuffer,
+    ];
+
+    pub fn binding(self) -> u32 {
+        self as u32
+    }
+
+    pub fn descriptor_type(self) -> DescriptorType {
+        match self {
+            Self::Sampler => DescriptorType::SAMPLER,
+            Self::SampledImage => DescriptorType::SAMPLED_IMAGE,
+            Self::StorageImage => DescriptorType::STORAGE_IMAGE,
+            Self::StorageBuffer => DescriptorType::STORAGE_BUFFER,
+        }
+    }
+}
+
+/// Number of slots of each kind in a [UsamiBindlessTable].
+#[derive(Clone, Copy, Debug)]
+pub struct UsamiBindlessCapacities {
+    pub samplers: u32,
+    pub sampled_images: u32,
+    pub storage_images: u32,
+    pub storage_buffers: u32,
+}
+
+impl UsamiBindlessCapacities {
+    pub fn get(&self, kind: UsamiBindlessKind) -> u32 {
+        match kind {
+            UsamiBindlessKind::Sampler => self.samplers,
+            UsamiBindlessKind::SampledImage => self.sampled_images,
+            UsamiBindlessKind::StorageImage => self.storage_images,
+            UsamiBindlessKind::StorageBuffer => self.storage_buffers,
+        }
+    }
+}
+
+impl Default for UsamiBindlessCapacities {
+    fn default() -> Self {
+        Self {
+            samplers: 256,
+            sampled_images: 4096,
+            storage_images: 1024,
+            storage_buffers: 4096,
+        }
+    }
+}
+
+#[derive(Default)]
+struct BindlessSlots {
+    next_index: u32,
+    free_indices: Vec<u32>,
+}
+
+/// Single descriptor set holding partially bound arrays of samplers, images and buffers indexed from shaders.
+///
+/// Slots can be written while the set is bound (update-after-bind) as long as the slot itself is not used by pending work.
+/// The storage buffer array is the last binding and is allocated with a variable descriptor count.
+pub struct UsamiBindlessTable {
+    pub name: String,
+    pub capacities: UsamiBindlessCapacities,
+    pub set: UsamiDescriptorSet,
+    pub pool: UsamiDescriptorPool,
+    pub layout: UsamiDescriptorSetLayout,
+    slots: Mutex<[BindlessSlots; 4]>,
+}
+
+impl UsamiBindlessTable {
+    /// Descriptor indexing features required by a bindless table.
+    pub fn required_features() -> UsamiDeviceFeatures {
+        let mut features = UsamiDeviceFeatures::default();
+
+        features.core.shader_sampled_image_array_dynamic_indexing = vk::TRUE;
+        features.core.shader_storage_image_array_dynamic_indexing = vk::TRUE;
+        features.core.shader_storage_buffer_array_dynamic_indexing = vk::TRUE;
+
+        let vulkan12 = &mut features.vulkan12;
+
+        vulkan12.descriptor_indexing = vk::TRUE;
+        vulkan12.runtime_descriptor_array = vk::TRUE;
+        vulkan12.descriptor_binding_partially_bound = vk::TRUE;
+        vulkan12.descriptor_binding_variable_descriptor_count = vk::TRUE;
+        vulkan12.descriptor_binding_update_unused_while_pending = vk::TRUE;
+        vulkan12.descriptor_binding_sampled_image_update_after_bind = vk::TRUE;
+        vulkan12.descriptor_binding_storage_image_update_after_bind = vk::TRUE;
+        vulkan12.descriptor_binding_storage_buffer_update_after_bind = vk::TRUE;
+
+        features
+    }
+
+    pub fn new(
+        device: &Arc<UsamiDevice>,
+        name: String,
+        stage_flags: ShaderStageFlags,
+        capacities: UsamiBindlessCapacities,
+    ) -> UsamiResult<Self> {
+        let missing_features = Self::required_features().missing_from(&device.enabled_features);
+
+        if !missing_features.is_empty() {
+            return Err(Error::MissingFeatures(missing_features));
+        }
+
+        let bindings: Vec<DescriptorSetLayoutBinding> = UsamiBindlessKind::ALL
+            .iter()
+            .map(|kind| {
+                DescriptorSetLayoutBinding::default()
+                    .binding(kind.binding())
+                    .descriptor_type(kind.descriptor_type())
+                    .descriptor_count(capacities.get(*kind))
+                    .stage_flags(stage_flags)
+            })
+            .collect();
+
+        let common_flags = DescriptorBindingFlags::PARTIALLY_BOUND
+            | DescriptorBindingFlags::UPDATE_AFTER_BIND
+            | DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING;
+        let binding_flags = [
+            common_flags,
+            common_flags,
+            common_flags,
+            common_flags | DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT,
+        ];
+        let mut binding_flags_info =
+            DescriptorSetLayoutBindingFlagsCreateInfo::default().binding_flags(&binding_flags);
+
+        let layout = UsamiDevice::create_descriptor_set_layout(
+            device,
+            format!("{name}_layout"),
+            DescriptorSetLayoutCreateInfo::default()
+                .flags(DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL)
+                .bindings(&bindings)
+                .push_next(&mut binding_flags_info),
+        )?;
+
+        let pool_sizes: Vec<DescriptorPoolSize> = UsamiBindlessKind::ALL
+            .iter()
+            .map(|kind| DescriptorPoolSize {
+                ty: kind.descriptor_type(),
+                descriptor_count: capacities.get(*kind),
+            })
+            .filter(|x| x.descriptor_count != 0)
+            .collect();
+
+        let pool = UsamiDevice::create_descriptor_pool(
+            device,
+            format!("{name}_pool"),
+            DescriptorPoolCreateInfo::default()
+                .flags(DescriptorPoolCreateFlags::UPDATE_AFTER_BIND)
+                .pool_sizes(&pool_sizes)
+                .max_sets(1),
+        )?;
+
+        let set = pool
+            .allocate_descriptor_sets_with_variable_counts(
+                format!("{name}_set"),
+                &[layout.handle],
+                &[capacities.storage_buffers],
+            )?
+            .remove(0);
+
+        Ok(Self {
+            name,
+            capacities,
+            set,
+            pool,
+            layout,
+            slots: Mutex::new(Default::default()),
+        })
+    }
+
+    fn allocate_slot(&self, kind: UsamiBindlessKind) -> UsamiResult<u32> {
+        let mut slots = self.slots.lock().unwrap();
+        let slots = &mut slots[kind.binding() as usize];
+
+        if let Some(index) = slots.free_indices.pop() {
+            return Ok(index);
+        }
+
+        if slots.next_index >= self.capacities.get(kind) {
+            return Err(Error::Vulkan(vk::Result::ERROR_OUT_OF_POOL_MEMORY))
+                .context(format!("{}_{kind:?}", self.name));
+        }
+
+        slots.next_index += 1;
+
+        Ok(slots.next_index - 1)
+    }
+
+    /// Write a sampler to a free slot and return its index.
+    pub fn add_sampler(&self, sampler: &Arc<UsamiSampler>) -> UsamiResult<u32> {
+        let kind = UsamiBindlessKind::Sampler;
+        let index = self.allocate_slot(kind)?;
+
+        self.set
+            .write()
+            .sampler_at(kind.binding(), index, sampler)
+            .update();
+
+        Ok(index)
+    }
+
+    /// Write a sampled image to a free slot and return its index.
+    pub fn add_sampled_image(
+        &self,
+        image_view: &Arc<UsamiImageView>,
+        layout: ImageLayout,
+    ) -> UsamiResult<u32> {
+        self.add_image(UsamiBindlessKind::SampledImage, image_view, layout)
+    }
+
+    /// Write a storage image to a free slot and return its index.
+    pub fn add_storage_image(
+        &self,
+        image_view: &Arc<UsamiImageView>,
+        layout: ImageLayout,
+    ) -> UsamiResult<u32> {
+        self.add_image(UsamiBindlessKind::StorageImage, image_view, layout)
+    }
+
+    fn add_image(
+        &self,
+        kind: UsamiBindlessKind,
+        image_view: &Arc<UsamiImageView>,
+        layout: ImageLayout,
+    ) -> UsamiResult<u32> {
+        let index = self.allocate_slot(kind)?;
+
+        self.set
+            .write()
+            .image_at(
+                kind.binding(),
+                index,
+                kind.descriptor_type(),
+                image_view,
+                layout,
+            )
+            .update();
+
+        Ok(index)
+    }
+
+    /// Write a storage buffer range to a free slot and return its index.
+    pub fn add_storage_buffer(
+        &self,
+        buffer: &Arc<UsamiBuffer>,
+        offset: DeviceSize,
+        range: DeviceSize,
+    ) -> UsamiResult<u32> {
+        let kind = UsamiBindlessKind::StorageBuffer;
+        let index = self.allocate_slot(kind)?;
+
+        self.set
+            .write()
+            .buffer_at(
+                kind.binding(),
+                index,
+                kind.descriptor_type(),
+                buffer,
+                offset,
+                range,
+            )
+            .update();
+
+        Ok(index)
+    }
+
+    /// Release a slot and the resource written to it, the slot must not be used by pending work.
+    pub fn remove(&self, kind: UsamiBindlessKind, index: u32) -> UsamiResult<()> {
+        let mut slots = self.slots.lock().unwrap();
+        let slots = &mut slots[kind.binding() as usize];
+
+        if index >= slots.next_index || slots.free_indices.contains(&index) {
+            return Err(Error::UnallocatedBindlessSlot { kind, index });
+        }
+
+        self.set.release_resources(kind.binding(), index);
+        slots.free_indices.push(index);
+
+        Ok(())
+    }
+}
+
+impl UsamiDevice {
+    pub fn create_bindless_table(
+        device: &Arc<UsamiDevice>,
+        name: String,
+        stage_flags: ShaderStageFlags,
+        capacities: UsamiBindlessCapacities,
+    ) -> UsamiResult<UsamiBindlessTable> {
+        UsamiBindlessTable::new(device, name.clone(), stage_flags, capacities).context(&name)
+    }
+}
@@ -1,21 +1,27 @@
 use std::sync::Arc;
 
 use ash::{
-    prelude::*,
     vk::{
-        Buffer, BufferCreateFlags, BufferCreateInfo, BufferUsageFlags, BufferView,
-        BufferViewCreateFlags, BufferViewCreateInfo, DeviceSize, Format, MemoryPropertyFlags,
-        SharingMode,
+        Buffer, BufferCreateFlags, BufferCreateInfo, BufferDeviceAddressInfo, BufferUsageFlags,
+        BufferView, BufferViewCreateFlags, BufferViewCreateInfo, DeviceAddress, DeviceSize, Format,
+        MemoryAllocateFlags, MemoryPropertyFlags, SharingMode,
     },
     Device,
 };
 
-use crate::{UsamiDevice, UsamiDeviceMemory};
+use bytemuck::Pod;
+
+use crate::{
+    memory::{MappedSlice, MappedSliceMut},
+    ResultExt, UsamiAllocation, UsamiDevice, UsamiResult, UsamiStagingBelt,
+};
 
 pub struct UsamiBuffer {
     device: Arc<UsamiDevice>,
     pub handle: Buffer,
-    pub device_memory: UsamiDeviceMemory,
+    pub size: DeviceSize,
+    pub usage: BufferUsageFlags,
+    pub allocation: UsamiAllocation,
 }
 
 impl UsamiBuffer {
@@ -23,37 +29,52 @@ impl UsamiBuffer {
         device: &Arc<UsamiDevice>,
         create_info: BufferCreateInfo,
         memory_flags: MemoryPropertyFlags,
-    ) -> VkResult<Self> {
+    ) -> UsamiResult<Self> {
         let vk_device: &Device = &device.handle;
 
         let handle = unsafe { vk_device.create_buffer(&create_info, None)? };
         let req = unsafe { vk_device.get_buffer_memory_requirements(handle) };
-        let device_memory = UsamiDeviceMemory::new(device, req, memory_flags)?;
+        let allocate_flags = if create_info
+            .usage
+            .contains(BufferUsageFlags::SHADER_DEVICE_ADDRESS)
+        {
+            MemoryAllocateFlags::DEVICE_ADDRESS
+        } else {
+            MemoryAllocateFlags::empty()
+        };
+        let allocation = UsamiAllocation::new(device, req, memory_flags, allocate_flags, true)?;
         unsafe {
-            vk_device.bind_buffer_memory(handle, device_memory.handle, 0)?;
+            vk_device.bind_buffer_memory(handle, allocation.memory, allocation.offset)?;
         }
 
         Ok(Self {
             device: device.clone(),
             handle,
-            device_memory,
+            size: create_info.size,
+            usage: create_info.usage,
+            allocation,
         })
     }
 
-    pub fn copy_from_slice<T: Copy>(&self, data: &[T]) -> VkResult<()> {
+    /// Return the device address of the buffer, it must have been created with [BufferUsageFlags::SHADER_DEVICE_ADDRESS].
+    pub fn device_address(&self) -> DeviceAddress {
         unsafe {
-            let dst_slice =
-                std::slice::from_raw_parts_mut(self.device_memory.map()? as *mut T, data.len());
-
-            dst_slice.copy_from_slice(data);
+            self.device
+                .handle
+                .get_buffer_device_address(&BufferDeviceAddressInfo::default().buffer(self.handle))
+        }
+    }
 
-            self.device_memory
-                .flush(0, std::mem::size_of_val(dst_slice) as u64)?;
+    pub fn mapped_slice<T: Pod>(&self) -> UsamiResult<MappedSlice<'_, T>> {
+        self.allocation.mapped_slice()
+    }
 
-            self.device_memory.unmap();
-        }
+    pub fn mapped_slice_mut<T: Pod>(&mut self) -> UsamiResult<MappedSliceMut<'_, T>> {
+        self.allocation.mapped_slice_mut()
+    }
 
-        Ok(())
+    pub fn copy_from_slice<T: Copy>(&mut self, data: &[T]) -> UsamiResult<()> {
+        self.allocation.write_from_slice(data)
     }
 
     pub fn create_view(
@@ -63,7 +84,7 @@ impl UsamiBuffer {
         format: Format,
         offset: DeviceSize,
         range: DeviceSize,
-    ) -> VkResult<UsamiBufferView> {
+    ) -> UsamiResult<UsamiBufferView> {
         UsamiDevice::create_buffer_view(
             &self.device,
             name,
@@ -89,7 +110,7 @@ pub struct UsamiBufferView {
 }
 
 impl UsamiBufferView {
-    pub fn new(device: &Arc<UsamiDevice>, create_info: BufferViewCreateInfo) -> VkResult<Self> {
+    pub fn new(device: &Arc<UsamiDevice>, create_info: BufferViewCreateInfo) -> UsamiResult<Self> {
         let vk_device: &Device = &device.handle;
 
         let handle = unsafe { vk_device.create_buffer_view(&create_info, None)? };
@@ -115,9 +136,9 @@ impl UsamiDevice {
         sharing_mode: SharingMode,
         usage: BufferUsageFlags,
         data: &[T],
-    ) -> VkResult<UsamiBuffer> {
+    ) -> UsamiResult<UsamiBuffer> {
         let size = std::mem::size_of_val(data) as u64;
-        let buffer = Self::create_buffer_with_size(
+        let mut buffer = Self::create_buffer_with_size(
             device,
             name,
             flags,
@@ -140,7 +161,7 @@ impl UsamiDevice {
         usage: BufferUsageFlags,
         size: DeviceSize,
         memory_flags: MemoryPropertyFlags,
-    ) -> VkResult<UsamiBuffer> {
+    ) -> UsamiResult<UsamiBuffer> {
         let queue_family_indices = &[device.vk_queue_index];
         let create_info = BufferCreateInfo::default()
             .flags(flags)
@@ -148,19 +169,44 @@ impl UsamiDevice {
             .usage(usage)
             .size(size)
             .queue_family_indices(queue_family_indices);
-        let buffer = UsamiBuffer::new(device, create_info, memory_flags)?;
+        let buffer = UsamiBuffer::new(device, create_info, memory_flags).context(&name)?;
 
         device.set_debug_name(name, buffer.handle)?;
 
         Ok(buffer)
     }
 
+    /// Create a device local buffer whose initial data is uploaded on the next flush of `staging_belt`.
+    pub fn create_device_local_buffer<T: Pod>(
+        device: &Arc<UsamiDevice>,
+        name: String,
+        flags: BufferCreateFlags,
+        sharing_mode: SharingMode,
+        usage: BufferUsageFlags,
+        data: &[T],
+        staging_belt: &mut UsamiStagingBelt,
+    ) -> UsamiResult<UsamiBuffer> {
+        let buffer = Self::create_buffer_with_size(
+            device,
+            name,
+            flags,
+            sharing_mode,
+            usage | BufferUsageFlags::TRANSFER_DST,
+            std::mem::size_of_val(data) as u64,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        staging_belt.upload_buffer(&buffer, 0, data)?;
+
+        Ok(buffer)
+    }
+
     pub fn create_buffer_view(
         device: &Arc<UsamiDevice>,
         name: String,
         create_info: BufferViewCreateInfo,
-    ) -> VkResult<UsamiBufferView> {
-        let buffer_view = UsamiBufferView::new(device, create_info)?;
+    ) -> UsamiResult<UsamiBufferView> {
+        let buffer_view = UsamiBufferView::new(device, create_info).context(&name)?;
 
         device.set_debug_name(name, buffer_view.handle)?;
 
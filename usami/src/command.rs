@@ -1,30 +1,35 @@
 use std::sync::Arc;
 
-use ash::{
-    prelude::*,
-    vk::{
-        self, AccessFlags, BufferImageCopy, BufferMemoryBarrier, ClearColorValue, CommandBuffer,
-        CommandBufferAllocateInfo, CommandBufferBeginInfo, CommandBufferInheritanceInfo,
-        CommandBufferLevel, CommandBufferUsageFlags, CommandPool, CommandPoolCreateInfo,
-        DependencyFlags, ImageAspectFlags, ImageLayout, ImageMemoryBarrier, ImageSubresourceRange,
-        MemoryBarrier, PipelineStageFlags,
-    },
+use ash::vk::{
+    self, AccessFlags, BufferImageCopy, BufferMemoryBarrier, BufferMemoryBarrier2, ClearColorValue,
+    CommandBuffer, CommandBufferAllocateInfo, CommandBufferBeginInfo, CommandBufferInheritanceInfo,
+    CommandBufferLevel, CommandBufferUsageFlags, CommandPool, CommandPoolCreateInfo,
+    DependencyFlags, DependencyInfo, DescriptorBufferBindingInfoEXT, DeviceSize, ImageAspectFlags,
+    ImageLayout, ImageMemoryBarrier, ImageMemoryBarrier2, ImageSubresourceRange, MemoryBarrier,
+    MemoryBarrier2, PipelineBindPoint, PipelineStageFlags, Rect2D, RenderingAttachmentInfo,
+    RenderingInfo, ShaderStageFlags,
 };
+use bytemuck::Pod;
 
-use crate::{utils, UsamiBuffer, UsamiDevice, UsamiImage};
+use crate::{
+    format::format_info, utils, Error, ResultExt, UsamiAccess, UsamiBuffer, UsamiDescriptorBuffer,
+    UsamiDevice, UsamiImage, UsamiPipelineLayout, UsamiResourceState, UsamiResult,
+};
 
 pub struct UsamiCommandPool {
     device: Arc<UsamiDevice>,
     pub handle: CommandPool,
+    pub queue_family_index: u32,
 }
 
 impl UsamiCommandPool {
-    pub fn new(device: &Arc<UsamiDevice>, create_info: CommandPoolCreateInfo) -> VkResult<Self> {
+    pub fn new(device: &Arc<UsamiDevice>, create_info: CommandPoolCreateInfo) -> UsamiResult<Self> {
         let handle = unsafe { device.handle.create_command_pool(&create_info, None)? };
 
         Ok(Self {
             device: device.clone(),
             handle,
+            queue_family_index: create_info.queue_family_index,
         })
     }
 
@@ -33,14 +38,15 @@ impl UsamiCommandPool {
         name: String,
         level: CommandBufferLevel,
         command_buffer_count: u32,
-    ) -> VkResult<Vec<UsamiCommandBuffer>> {
+    ) -> UsamiResult<Vec<UsamiCommandBuffer>> {
         let command_buffers = UsamiCommandBuffer::new(
             &self.device,
             CommandBufferAllocateInfo::default()
                 .command_pool(self.handle)
                 .level(level)
                 .command_buffer_count(command_buffer_count),
-        )?;
+        )
+        .context(&name)?;
 
         for (idx, command_buffer) in command_buffers.iter().enumerate() {
             self.device
@@ -67,7 +73,7 @@ impl UsamiCommandBuffer {
     pub fn new(
         device: &Arc<UsamiDevice>,
         allocate_info: CommandBufferAllocateInfo,
-    ) -> VkResult<Vec<Self>> {
+    ) -> UsamiResult<Vec<Self>> {
         let result = unsafe { device.handle.allocate_command_buffers(&allocate_info)? };
 
         Ok(result
@@ -80,22 +86,22 @@ impl UsamiCommandBuffer {
             .collect())
     }
 
-    pub fn record<F: Fn(&Arc<UsamiDevice>, &UsamiCommandBuffer) -> VkResult<()>>(
+    pub fn record<F: Fn(&Arc<UsamiDevice>, &UsamiCommandBuffer) -> UsamiResult<()>>(
         &self,
         flags: CommandBufferUsageFlags,
         callback: F,
-    ) -> VkResult<()> {
+    ) -> UsamiResult<()> {
         self.record_with_inheritance_info(&CommandBufferInheritanceInfo::default(), flags, callback)
     }
 
     pub fn record_with_inheritance_info<
-        F: Fn(&Arc<UsamiDevice>, &UsamiCommandBuffer) -> VkResult<()>,
+        F: Fn(&Arc<UsamiDevice>, &UsamiCommandBuffer) -> UsamiResult<()>,
     >(
         &self,
         inheritance_info: &CommandBufferInheritanceInfo,
         flags: CommandBufferUsageFlags,
         callback: F,
-    ) -> VkResult<()> {
+    ) -> UsamiResult<()> {
         unsafe {
             self.device.handle.begin_command_buffer(
                 self.handle,
@@ -122,7 +128,7 @@ impl UsamiCommandBuffer {
         dst_access_mask: AccessFlags,
         old_layout: ImageLayout,
         new_layout: ImageLayout,
-    ) -> VkResult<()> {
+    ) -> UsamiResult<()> {
         let image_subresource_range = image_subresource_range_opt.unwrap_or(
             ImageSubresourceRange::default()
                 .base_array_layer(0)
@@ -152,6 +158,398 @@ impl UsamiCommandBuffer {
             );
         }
 
+        image.set_state(
+            image_subresource_range,
+            UsamiResourceState::from_legacy(dst_stage_mask, dst_access_mask, new_layout),
+        );
+
+        Ok(())
+    }
+
+    /// Barrier between two uses of an image, the whole image is used if no range is given.
+    ///
+    /// The old layout of every subresource is the tracked one, unless `previous` is
+    /// [UsamiAccess::Nothing] in which case the content is discarded.
+    pub fn image_barrier2(
+        &self,
+        image: &UsamiImage,
+        subresource_range: Option<ImageSubresourceRange>,
+        previous: UsamiAccess,
+        next: UsamiAccess,
+    ) -> UsamiResult<()> {
+        self.record_image_transition(image, subresource_range, Some(previous), next.state())
+    }
+
+    /// Barrier from the tracked state of every subresource of an image to `next`, the whole
+    /// image is used if no range is given.
+    ///
+    /// Tracking happens at record time, command buffers are assumed to execute in the order
+    /// they were recorded.
+    pub fn transition_image(
+        &self,
+        image: &UsamiImage,
+        subresource_range: Option<ImageSubresourceRange>,
+        next: UsamiAccess,
+    ) -> UsamiResult<()> {
+        self.record_image_transition(image, subresource_range, None, next.state())
+    }
+
+    fn record_image_transition(
+        &self,
+        image: &UsamiImage,
+        subresource_range: Option<ImageSubresourceRange>,
+        previous: Option<UsamiAccess>,
+        next: UsamiResourceState,
+    ) -> UsamiResult<()> {
+        let subresource_range = match subresource_range {
+            Some(subresource_range) => image.resolve_subresource_range(subresource_range),
+            None => image.whole_subresource_range(
+                format_info(image.format).map_or(ImageAspectFlags::COLOR, |x| x.aspect_mask),
+            ),
+        };
+        let mut barriers = Vec::new();
+
+        for mip_level in subresource_range.base_mip_level
+            ..subresource_range.base_mip_level + subresource_range.level_count
+        {
+            let layer_end = subresource_range.base_array_layer + subresource_range.layer_count;
+            let mut array_layer = subresource_range.base_array_layer;
+
+            // Merge consecutive layers sharing the same state in a single barrier.
+            while array_layer < layer_end {
+                let mut tracked = image.tracked_state(mip_level, array_layer);
+                let mut layer_count = 1;
+
+                while array_layer + layer_count < layer_end
+                    && image.tracked_state(mip_level, array_layer + layer_count) == tracked
+                {
+                    layer_count += 1;
+                }
+
+                let layout = tracked.layout();
+                // Reads already covered by the barrier that followed the last write need none.
+                let src = match (previous, tracked.access(next)) {
+                    (Some(UsamiAccess::Nothing), _) => Some(UsamiAccess::Nothing.state()),
+                    (Some(previous), _) => Some(UsamiResourceState {
+                        layout,
+                        ..previous.state()
+                    }),
+                    (None, src) => src,
+                };
+
+                let range = subresource_range
+                    .base_mip_level(mip_level)
+                    .level_count(1)
+                    .base_array_layer(array_layer)
+                    .layer_count(layer_count);
+
+                image.set_tracked_state(range, tracked);
+
+                if let Some(src) = src {
+                    barriers.push(
+                        ImageMemoryBarrier2::default()
+                            .src_stage_mask(src.stage_mask)
+                            .src_access_mask(src.access_mask)
+                            .dst_stage_mask(next.stage_mask)
+                            .dst_access_mask(next.access_mask)
+                            .old_layout(src.layout)
+                            .new_layout(next.layout)
+                            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                            .image(image.handle)
+                            .subresource_range(range),
+                    );
+                }
+
+                array_layer += layer_count;
+            }
+        }
+
+        self.pipeline_barrier2(&[], &[], &barriers);
+
+        Ok(())
+    }
+
+    /// Barrier between two uses of a buffer range.
+    pub fn buffer_barrier2(
+        &self,
+        buffer: &UsamiBuffer,
+        previous: UsamiAccess,
+        next: UsamiAccess,
+        offset: u64,
+        size: u64,
+    ) -> UsamiResult<()> {
+        self.pipeline_barrier2(
+            &[],
+            &[BufferMemoryBarrier2::default()
+                .src_stage_mask(previous.stage_mask())
+                .src_access_mask(previous.access_mask())
+                .dst_stage_mask(next.stage_mask())
+                .dst_access_mask(next.access_mask())
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .buffer(buffer.handle)
+                .offset(offset)
+                .size(size)],
+            &[],
+        );
+
+        Ok(())
+    }
+
+    /// Global barrier between two uses of any resource.
+    pub fn memory_barrier2(&self, previous: UsamiAccess, next: UsamiAccess) -> UsamiResult<()> {
+        self.pipeline_barrier2(
+            &[MemoryBarrier2::default()
+                .src_stage_mask(previous.stage_mask())
+                .src_access_mask(previous.access_mask())
+                .dst_stage_mask(next.stage_mask())
+                .dst_access_mask(next.access_mask())],
+            &[],
+            &[],
+        );
+
+        Ok(())
+    }
+
+    /// Record barriers with `vkCmdPipelineBarrier2` when synchronization2 is enabled, otherwise
+    /// this falls back to `vkCmdPipelineBarrier` with the union of all stage masks.
+    pub fn pipeline_barrier2(
+        &self,
+        memory_barriers: &[MemoryBarrier2],
+        buffer_memory_barriers: &[BufferMemoryBarrier2],
+        image_memory_barriers: &[ImageMemoryBarrier2],
+    ) {
+        if memory_barriers.is_empty()
+            && buffer_memory_barriers.is_empty()
+            && image_memory_barriers.is_empty()
+        {
+            return;
+        }
+
+        let vk_device = &self.device.handle;
+
+        if self.device.enabled_features.vulkan13.synchronization2 == vk::TRUE {
+            unsafe {
+                vk_device.cmd_pipeline_barrier2(
+                    self.handle,
+                    &DependencyInfo::default()
+                        .memory_barriers(memory_barriers)
+                        .buffer_memory_barriers(buffer_memory_barriers)
+                        .image_memory_barriers(image_memory_barriers),
+                );
+            }
+
+            return;
+        }
+
+        let mut src_stage_mask = PipelineStageFlags::empty();
+        let mut dst_stage_mask = PipelineStageFlags::empty();
+        let mut legacy = |src_stage, src_access, dst_stage, dst_access| {
+            let src = UsamiResourceState::new(src_stage, src_access, ImageLayout::UNDEFINED);
+            let dst = UsamiResourceState::new(dst_stage, dst_access, ImageLayout::UNDEFINED);
+
+            src_stage_mask |= src.legacy_stage_mask();
+            dst_stage_mask |= dst.legacy_stage_mask();
+
+            (src.legacy_access_mask(), dst.legacy_access_mask())
+        };
+
+        let memory_barriers: Vec<_> = memory_barriers
+            .iter()
+            .map(|x| {
+                let (src_access_mask, dst_access_mask) = legacy(
+                    x.src_stage_mask,
+                    x.src_access_mask,
+                    x.dst_stage_mask,
+                    x.dst_access_mask,
+                );
+
+                MemoryBarrier::default()
+                    .src_access_mask(src_access_mask)
+                    .dst_access_mask(dst_access_mask)
+            })
+            .collect();
+        let buffer_memory_barriers: Vec<_> = buffer_memory_barriers
+            .iter()
+            .map(|x| {
+                let (src_access_mask, dst_access_mask) = legacy(
+                    x.src_stage_mask,
+                    x.src_access_mask,
+                    x.dst_stage_mask,
+                    x.dst_access_mask,
+                );
+
+                BufferMemoryBarrier::default()
+                    .src_access_mask(src_access_mask)
+                    .dst_access_mask(dst_access_mask)
+                    .src_queue_family_index(x.src_queue_family_index)
+                    .dst_queue_family_index(x.dst_queue_family_index)
+                    .buffer(x.buffer)
+                    .offset(x.offset)
+                    .size(x.size)
+            })
+            .collect();
+        let image_memory_barriers: Vec<_> = image_memory_barriers
+            .iter()
+            .map(|x| {
+                let (src_access_mask, dst_access_mask) = legacy(
+                    x.src_stage_mask,
+                    x.src_access_mask,
+                    x.dst_stage_mask,
+                    x.dst_access_mask,
+                );
+
+                ImageMemoryBarrier::default()
+                    .src_access_mask(src_access_mask)
+                    .dst_access_mask(dst_access_mask)
+                    .old_layout(x.old_layout)
+                    .new_layout(x.new_layout)
+                    .src_queue_family_index(x.src_queue_family_index)
+                    .dst_queue_family_index(x.dst_queue_family_index)
+                    .image(x.image)
+                    .subresource_range(x.subresource_range)
+            })
+            .collect();
+
+        // The legacy path does not accept empty stage masks.
+        if src_stage_mask.is_empty() {
+            src_stage_mask = PipelineStageFlags::TOP_OF_PIPE;
+        }
+
+        if dst_stage_mask.is_empty() {
+            dst_stage_mask = PipelineStageFlags::BOTTOM_OF_PIPE;
+        }
+
+        unsafe {
+            vk_device.cmd_pipeline_barrier(
+                self.handle,
+                src_stage_mask,
+                dst_stage_mask,
+                DependencyFlags::empty(),
+                &memory_barriers,
+                &buffer_memory_barriers,
+                &image_memory_barriers,
+            );
+        }
+    }
+
+    /// Begin a dynamic rendering instance, this requires the `dynamic_rendering` feature.
+    pub fn begin_rendering(
+        &self,
+        render_area: Rect2D,
+        layer_count: u32,
+        color_attachments: &[RenderingAttachmentInfo],
+        depth_attachment: Option<&RenderingAttachmentInfo>,
+        stencil_attachment: Option<&RenderingAttachmentInfo>,
+    ) -> UsamiResult<()> {
+        if self.device.enabled_features.vulkan13.dynamic_rendering != vk::TRUE {
+            return Err(Error::MissingFeatures(vec!["dynamic_rendering"]));
+        }
+
+        let mut rendering_info = RenderingInfo::default()
+            .render_area(render_area)
+            .layer_count(layer_count)
+            .color_attachments(color_attachments);
+
+        if let Some(depth_attachment) = depth_attachment {
+            rendering_info = rendering_info.depth_attachment(depth_attachment);
+        }
+
+        if let Some(stencil_attachment) = stencil_attachment {
+            rendering_info = rendering_info.stencil_attachment(stencil_attachment);
+        }
+
+        unsafe {
+            self.device
+                .handle
+                .cmd_begin_rendering(self.handle, &rendering_info);
+        }
+
+        Ok(())
+    }
+
+    pub fn end_rendering(&self) {
+        unsafe { self.device.handle.cmd_end_rendering(self.handle) }
+    }
+
+    /// Update the push constants at `offset` with a value whose layout matches the shader block.
+    pub fn push_constants<T: Pod>(
+        &self,
+        layout: &UsamiPipelineLayout,
+        stage_flags: ShaderStageFlags,
+        offset: u32,
+        value: &T,
+    ) {
+        self.push_constants_slice(layout, stage_flags, offset, std::slice::from_ref(value))
+    }
+
+    pub fn push_constants_slice<T: Pod>(
+        &self,
+        layout: &UsamiPipelineLayout,
+        stage_flags: ShaderStageFlags,
+        offset: u32,
+        values: &[T],
+    ) {
+        let data: &[u8] = bytemuck::cast_slice(values);
+
+        assert!(
+            offset.is_multiple_of(4) && data.len().is_multiple_of(4),
+            "Push constant offset and size must be multiples of 4"
+        );
+
+        unsafe {
+            self.device.handle.cmd_push_constants(
+                self.handle,
+                layout.handle,
+                stage_flags,
+                offset,
+                data,
+            );
+        }
+    }
+
+    /// Bind descriptor buffers, their index in `descriptor_buffers` is the buffer index used by [Self::set_descriptor_buffer_offsets].
+    pub fn bind_descriptor_buffers(
+        &self,
+        descriptor_buffers: &[&UsamiDescriptorBuffer],
+    ) -> UsamiResult<()> {
+        let binding_infos: Vec<DescriptorBufferBindingInfoEXT> = descriptor_buffers
+            .iter()
+            .map(|descriptor_buffer| descriptor_buffer.binding_info())
+            .collect();
+
+        unsafe {
+            self.device
+                .descriptor_buffer_device()?
+                .cmd_bind_descriptor_buffers(self.handle, &binding_infos);
+        }
+
+        Ok(())
+    }
+
+    /// Point the sets starting at `first_set` to an offset in one of the bound descriptor buffers.
+    pub fn set_descriptor_buffer_offsets(
+        &self,
+        pipeline_bind_point: PipelineBindPoint,
+        layout: &UsamiPipelineLayout,
+        first_set: u32,
+        buffer_indices: &[u32],
+        offsets: &[DeviceSize],
+    ) -> UsamiResult<()> {
+        unsafe {
+            self.device
+                .descriptor_buffer_device()?
+                .cmd_set_descriptor_buffer_offsets(
+                    self.handle,
+                    pipeline_bind_point,
+                    layout.handle,
+                    first_set,
+                    buffer_indices,
+                    offsets,
+                );
+        }
+
         Ok(())
     }
 
@@ -164,7 +562,7 @@ impl UsamiCommandBuffer {
         dst_access_mask: AccessFlags,
         offset: u64,
         size: u64,
-    ) -> VkResult<()> {
+    ) -> UsamiResult<()> {
         unsafe {
             self.device.handle.cmd_pipeline_barrier(
                 self.handle,
@@ -193,7 +591,7 @@ impl UsamiCommandBuffer {
         dst_stage_mask: PipelineStageFlags,
         src_access_mask: AccessFlags,
         dst_access_mask: AccessFlags,
-    ) -> VkResult<()> {
+    ) -> UsamiResult<()> {
         unsafe {
             self.device.handle.cmd_pipeline_barrier(
                 self.handle,
@@ -211,23 +609,21 @@ impl UsamiCommandBuffer {
         Ok(())
     }
 
-    pub fn clear_image(&self, image: &UsamiImage, r: f32, g: f32, b: f32, a: f32) -> VkResult<()> {
-        self.add_image_barrier(
-            image,
-            None,
-            PipelineStageFlags::TOP_OF_PIPE,
-            PipelineStageFlags::TRANSFER,
-            AccessFlags::empty(),
-            AccessFlags::TRANSFER_WRITE,
-            ImageLayout::UNDEFINED,
-            ImageLayout::GENERAL,
-        )?;
+    pub fn clear_image(
+        &self,
+        image: &UsamiImage,
+        r: f32,
+        g: f32,
+        b: f32,
+        a: f32,
+    ) -> UsamiResult<()> {
+        self.transition_image(image, None, UsamiAccess::TransferWrite)?;
 
         unsafe {
             self.device.handle.cmd_clear_color_image(
                 self.handle,
                 image.handle,
-                ImageLayout::GENERAL,
+                ImageLayout::TRANSFER_DST_OPTIMAL,
                 &ClearColorValue {
                     float32: [r, g, b, a],
                 },
@@ -238,12 +634,7 @@ impl UsamiCommandBuffer {
             );
         }
 
-        self.add_memory_barrier(
-            PipelineStageFlags::TRANSFER,
-            PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-            AccessFlags::TRANSFER_WRITE,
-            AccessFlags::COLOR_ATTACHMENT_READ | AccessFlags::COLOR_ATTACHMENT_WRITE,
-        )?;
+        self.transition_image(image, None, UsamiAccess::General)?;
 
         Ok(())
     }
@@ -261,7 +652,7 @@ impl UsamiCommandBuffer {
         dest_image_dst_stage_flags: PipelineStageFlags,
         dest_image_dst_access_mask: AccessFlags,
         base_mip_level: u32,
-    ) -> VkResult<()> {
+    ) -> UsamiResult<()> {
         let subresource_range = ImageSubresourceRange::default()
             .aspect_mask(image_aspect_flags)
             .base_mip_level(base_mip_level)
@@ -269,32 +660,20 @@ impl UsamiCommandBuffer {
             .base_array_layer(0)
             .layer_count(array_layers);
 
-        unsafe {
-            self.device.handle.cmd_pipeline_barrier(
-                self.handle,
-                PipelineStageFlags::HOST,
-                PipelineStageFlags::TRANSFER,
-                DependencyFlags::empty(),
-                &[],
-                &[BufferMemoryBarrier::default()
-                    .src_access_mask(AccessFlags::HOST_WRITE)
-                    .dst_access_mask(AccessFlags::TRANSFER_READ)
-                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-                    .buffer(buffer.handle)
-                    .offset(0)
-                    .size(buffer_size)],
-                &[ImageMemoryBarrier::default()
-                    .src_access_mask(AccessFlags::empty())
-                    .dst_access_mask(AccessFlags::TRANSFER_WRITE)
-                    .old_layout(ImageLayout::UNDEFINED)
-                    .new_layout(ImageLayout::TRANSFER_DST_OPTIMAL)
-                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-                    .image(dest_image.handle)
-                    .subresource_range(subresource_range)],
-            );
+        self.buffer_barrier2(
+            buffer,
+            UsamiAccess::HostWrite,
+            UsamiAccess::TransferRead,
+            0,
+            buffer_size,
+        )?;
+        self.transition_image(
+            dest_image,
+            Some(subresource_range),
+            UsamiAccess::TransferWrite,
+        )?;
 
+        unsafe {
             self.device.handle.cmd_copy_buffer_to_image(
                 self.handle,
                 buffer.handle,
@@ -302,19 +681,19 @@ impl UsamiCommandBuffer {
                 ImageLayout::TRANSFER_DST_OPTIMAL,
                 copy_regions,
             );
-
-            self.add_image_barrier(
-                dest_image,
-                Some(subresource_range),
-                PipelineStageFlags::TRANSFER,
-                dest_image_dst_stage_flags,
-                AccessFlags::TRANSFER_WRITE,
-                dest_image_dst_access_mask,
-                ImageLayout::TRANSFER_DST_OPTIMAL,
-                dest_image_layout,
-            )?;
         }
 
+        self.add_image_barrier(
+            dest_image,
+            Some(subresource_range),
+            PipelineStageFlags::TRANSFER,
+            dest_image_dst_stage_flags,
+            AccessFlags::TRANSFER_WRITE,
+            dest_image_dst_access_mask,
+            ImageLayout::TRANSFER_DST_OPTIMAL,
+            dest_image_layout,
+        )?;
+
         Ok(())
     }
 
@@ -329,7 +708,7 @@ impl UsamiCommandBuffer {
         level_count: u32,
         barrier_aspect: ImageAspectFlags,
         src_stage_mask: PipelineStageFlags,
-    ) -> VkResult<()> {
+    ) -> UsamiResult<()> {
         let image_subresource_range = ImageSubresourceRange::default()
             .base_array_layer(0)
             .layer_count(layer_count)
@@ -386,8 +765,8 @@ impl UsamiDevice {
         device: &Arc<UsamiDevice>,
         name: String,
         create_info: CommandPoolCreateInfo,
-    ) -> VkResult<UsamiCommandPool> {
-        let shader = UsamiCommandPool::new(device, create_info)?;
+    ) -> UsamiResult<UsamiCommandPool> {
+        let shader = UsamiCommandPool::new(device, create_info).context(&name)?;
 
         device.set_debug_name(name, shader.handle)?;
 
@@ -401,7 +780,7 @@ impl UsamiDevice {
         image: &UsamiImage,
         new_layout: ImageLayout,
         copy_regions: &[BufferImageCopy],
-    ) -> VkResult<()> {
+    ) -> UsamiResult<()> {
         utils::record_and_execute_command_buffer(
             device,
             command_pool,
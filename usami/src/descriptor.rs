@@ -1,34 +1,69 @@
-use std::sync::Arc;
-
-use ash::{
-    prelude::*,
-    vk::{
-        DescriptorPool, DescriptorPoolCreateFlags, DescriptorPoolCreateInfo, DescriptorSet,
-        DescriptorSetAllocateInfo, DescriptorSetLayout, DescriptorSetLayoutCreateInfo,
-    },
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{Arc, Mutex},
 };
 
-use crate::UsamiDevice;
+use ash::vk::{
+    self, BufferView, DescriptorBufferInfo, DescriptorImageInfo, DescriptorPool,
+    DescriptorPoolCreateFlags, DescriptorPoolCreateInfo, DescriptorPoolResetFlags,
+    DescriptorPoolSize, DescriptorSet, DescriptorSetAllocateInfo, DescriptorSetLayout,
+    DescriptorSetLayoutBinding, DescriptorSetLayoutCreateFlags, DescriptorSetLayoutCreateInfo,
+    DescriptorSetVariableDescriptorCountAllocateInfo, DescriptorType, DeviceSize, ImageLayout,
+    WriteDescriptorSet,
+};
+
+use crate::{
+    Error, ResultExt, UsamiBuffer, UsamiBufferView, UsamiDevice, UsamiImageView,
+    UsamiPipelineLayout, UsamiResult, UsamiSampler,
+};
+
+type BoundResource = Arc<dyn Any + Send + Sync>;
 
 pub struct UsamiDescriptorSetLayout {
     device: Arc<UsamiDevice>,
     pub handle: DescriptorSetLayout,
+    pub flags: DescriptorSetLayoutCreateFlags,
+    /// Bindings of the layout, without their immutable samplers.
+    pub bindings: Vec<DescriptorSetLayoutBinding<'static>>,
 }
 
 impl UsamiDescriptorSetLayout {
     pub fn new(
         device: &Arc<UsamiDevice>,
         create_info: DescriptorSetLayoutCreateInfo,
-    ) -> VkResult<Self> {
+    ) -> UsamiResult<Self> {
         let handle = unsafe {
             device
                 .handle
                 .create_descriptor_set_layout(&create_info, None)?
         };
 
+        let bindings = if create_info.binding_count == 0 {
+            Vec::new()
+        } else {
+            unsafe {
+                std::slice::from_raw_parts(
+                    create_info.p_bindings,
+                    create_info.binding_count as usize,
+                )
+            }
+            .iter()
+            .map(|x| {
+                DescriptorSetLayoutBinding::default()
+                    .binding(x.binding)
+                    .descriptor_type(x.descriptor_type)
+                    .descriptor_count(x.descriptor_count)
+                    .stage_flags(x.stage_flags)
+            })
+            .collect()
+        };
+
         Ok(Self {
             device: device.clone(),
             handle,
+            flags: create_info.flags,
+            bindings,
         })
     }
 }
@@ -50,7 +85,10 @@ pub struct UsamiDescriptorPool {
 }
 
 impl UsamiDescriptorPool {
-    pub fn new(device: &Arc<UsamiDevice>, create_info: DescriptorPoolCreateInfo) -> VkResult<Self> {
+    pub fn new(
+        device: &Arc<UsamiDevice>,
+        create_info: DescriptorPoolCreateInfo,
+    ) -> UsamiResult<Self> {
         let handle = unsafe { device.handle.create_descriptor_pool(&create_info, None)? };
 
         Ok(Self {
@@ -66,14 +104,30 @@ impl UsamiDescriptorPool {
         &self,
         name: String,
         layouts: &[DescriptorSetLayout],
-    ) -> VkResult<Vec<UsamiDescriptorSet>> {
-        let command_buffers = UsamiDescriptorSet::new(
-            &self.device,
-            DescriptorSetAllocateInfo::default()
-                .descriptor_pool(self.handle)
-                .set_layouts(layouts),
-            self.should_free_sets,
-        )?;
+    ) -> UsamiResult<Vec<UsamiDescriptorSet>> {
+        self.allocate_descriptor_sets_with_variable_counts(name, layouts, &[])
+    }
+
+    /// Allocate sets whose last binding has a variable descriptor count, given for each layout.
+    pub fn allocate_descriptor_sets_with_variable_counts(
+        &self,
+        name: String,
+        layouts: &[DescriptorSetLayout],
+        variable_descriptor_counts: &[u32],
+    ) -> UsamiResult<Vec<UsamiDescriptorSet>> {
+        let mut variable_count_info = DescriptorSetVariableDescriptorCountAllocateInfo::default()
+            .descriptor_counts(variable_descriptor_counts);
+        let mut allocate_info = DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.handle)
+            .set_layouts(layouts);
+
+        if !variable_descriptor_counts.is_empty() {
+            allocate_info = allocate_info.push_next(&mut variable_count_info);
+        }
+
+        let command_buffers =
+            UsamiDescriptorSet::new(&self.device, allocate_info, self.should_free_sets)
+                .context(&name)?;
 
         for (idx, command_buffer) in command_buffers.iter().enumerate() {
             self.device
@@ -82,6 +136,17 @@ impl UsamiDescriptorPool {
 
         Ok(command_buffers)
     }
+
+    /// Return every set allocated from this pool to it, those sets must not be used anymore.
+    pub fn reset(&self) -> UsamiResult<()> {
+        unsafe {
+            self.device
+                .handle
+                .reset_descriptor_pool(self.handle, DescriptorPoolResetFlags::empty())?;
+        }
+
+        Ok(())
+    }
 }
 
 impl Drop for UsamiDescriptorPool {
@@ -99,6 +164,8 @@ pub struct UsamiDescriptorSet {
     descriptor_pool: DescriptorPool,
     pub handle: DescriptorSet,
     pub should_free_on_drop: bool,
+    /// Resources written to the set, by binding and array element.
+    bound_resources: Mutex<HashMap<(u32, u32), Vec<BoundResource>>>,
 }
 
 impl UsamiDescriptorSet {
@@ -106,7 +173,7 @@ impl UsamiDescriptorSet {
         device: &Arc<UsamiDevice>,
         allocate_info: DescriptorSetAllocateInfo,
         should_free_on_drop: bool,
-    ) -> VkResult<Vec<Self>> {
+    ) -> UsamiResult<Vec<Self>> {
         let result = unsafe { device.handle.allocate_descriptor_sets(&allocate_info)? };
 
         Ok(result
@@ -116,9 +183,247 @@ impl UsamiDescriptorSet {
                 descriptor_pool: allocate_info.descriptor_pool,
                 handle: *handle,
                 should_free_on_drop,
+                bound_resources: Mutex::new(HashMap::new()),
             })
             .collect())
     }
+
+    /// Stop keeping alive the resources written at the given binding and array element.
+    ///
+    /// The descriptor must not be accessed anymore unless it is written again.
+    pub fn release_resources(&self, binding: u32, array_element: u32) {
+        self.bound_resources
+            .lock()
+            .unwrap()
+            .remove(&(binding, array_element));
+    }
+
+    /// Start writing descriptors to this set, the written resources are kept alive by the set until overwritten.
+    pub fn write(&self) -> UsamiDescriptorWriter<'_> {
+        UsamiDescriptorWriter {
+            set: self,
+            writes: Vec::new(),
+        }
+    }
+}
+
+enum DescriptorInfo {
+    Buffer(DescriptorBufferInfo),
+    Image(DescriptorImageInfo),
+    TexelBuffer(BufferView),
+}
+
+struct PendingDescriptorWrite {
+    binding: u32,
+    array_element: u32,
+    descriptor_type: DescriptorType,
+    info: DescriptorInfo,
+    resources: Vec<BoundResource>,
+}
+
+/// Batch of descriptor writes applied by [UsamiDescriptorWriter::update].
+pub struct UsamiDescriptorWriter<'a> {
+    set: &'a UsamiDescriptorSet,
+    writes: Vec<PendingDescriptorWrite>,
+}
+
+impl<'a> UsamiDescriptorWriter<'a> {
+    fn push(
+        mut self,
+        binding: u32,
+        array_element: u32,
+        descriptor_type: DescriptorType,
+        info: DescriptorInfo,
+        resources: Vec<BoundResource>,
+    ) -> Self {
+        self.writes.push(PendingDescriptorWrite {
+            binding,
+            array_element,
+            descriptor_type,
+            info,
+            resources,
+        });
+
+        self
+    }
+
+    pub fn buffer(
+        self,
+        binding: u32,
+        descriptor_type: DescriptorType,
+        buffer: &Arc<UsamiBuffer>,
+        offset: DeviceSize,
+        range: DeviceSize,
+    ) -> Self {
+        self.buffer_at(binding, 0, descriptor_type, buffer, offset, range)
+    }
+
+    pub fn buffer_at(
+        self,
+        binding: u32,
+        array_element: u32,
+        descriptor_type: DescriptorType,
+        buffer: &Arc<UsamiBuffer>,
+        offset: DeviceSize,
+        range: DeviceSize,
+    ) -> Self {
+        let info = DescriptorBufferInfo::default()
+            .buffer(buffer.handle)
+            .offset(offset)
+            .range(range);
+
+        self.push(
+            binding,
+            array_element,
+            descriptor_type,
+            DescriptorInfo::Buffer(info),
+            vec![buffer.clone()],
+        )
+    }
+
+    pub fn image(
+        self,
+        binding: u32,
+        descriptor_type: DescriptorType,
+        image_view: &Arc<UsamiImageView>,
+        layout: ImageLayout,
+    ) -> Self {
+        self.image_at(binding, 0, descriptor_type, image_view, layout)
+    }
+
+    pub fn image_at(
+        self,
+        binding: u32,
+        array_element: u32,
+        descriptor_type: DescriptorType,
+        image_view: &Arc<UsamiImageView>,
+        layout: ImageLayout,
+    ) -> Self {
+        let info = DescriptorImageInfo::default()
+            .image_view(image_view.handle)
+            .image_layout(layout);
+
+        self.push(
+            binding,
+            array_element,
+            descriptor_type,
+            DescriptorInfo::Image(info),
+            vec![image_view.clone()],
+        )
+    }
+
+    pub fn sampler(self, binding: u32, sampler: &Arc<UsamiSampler>) -> Self {
+        self.sampler_at(binding, 0, sampler)
+    }
+
+    pub fn sampler_at(self, binding: u32, array_element: u32, sampler: &Arc<UsamiSampler>) -> Self {
+        let info = DescriptorImageInfo::default().sampler(sampler.handle);
+
+        self.push(
+            binding,
+            array_element,
+            DescriptorType::SAMPLER,
+            DescriptorInfo::Image(info),
+            vec![sampler.clone()],
+        )
+    }
+
+    pub fn combined_image_sampler(
+        self,
+        binding: u32,
+        image_view: &Arc<UsamiImageView>,
+        layout: ImageLayout,
+        sampler: &Arc<UsamiSampler>,
+    ) -> Self {
+        self.combined_image_sampler_at(binding, 0, image_view, layout, sampler)
+    }
+
+    pub fn combined_image_sampler_at(
+        self,
+        binding: u32,
+        array_element: u32,
+        image_view: &Arc<UsamiImageView>,
+        layout: ImageLayout,
+        sampler: &Arc<UsamiSampler>,
+    ) -> Self {
+        let info = DescriptorImageInfo::default()
+            .image_view(image_view.handle)
+            .image_layout(layout)
+            .sampler(sampler.handle);
+
+        self.push(
+            binding,
+            array_element,
+            DescriptorType::COMBINED_IMAGE_SAMPLER,
+            DescriptorInfo::Image(info),
+            vec![image_view.clone(), sampler.clone()],
+        )
+    }
+
+    pub fn texel_buffer(
+        self,
+        binding: u32,
+        descriptor_type: DescriptorType,
+        buffer_view: &Arc<UsamiBufferView>,
+    ) -> Self {
+        self.texel_buffer_at(binding, 0, descriptor_type, buffer_view)
+    }
+
+    pub fn texel_buffer_at(
+        self,
+        binding: u32,
+        array_element: u32,
+        descriptor_type: DescriptorType,
+        buffer_view: &Arc<UsamiBufferView>,
+    ) -> Self {
+        self.push(
+            binding,
+            array_element,
+            descriptor_type,
+            DescriptorInfo::TexelBuffer(buffer_view.handle),
+            vec![buffer_view.clone()],
+        )
+    }
+
+    /// Apply all writes to the descriptor set.
+    pub fn update(self) {
+        let descriptor_writes: Vec<WriteDescriptorSet> = self
+            .writes
+            .iter()
+            .map(|write| {
+                let descriptor_write = WriteDescriptorSet::default()
+                    .dst_set(self.set.handle)
+                    .dst_binding(write.binding)
+                    .dst_array_element(write.array_element)
+                    .descriptor_type(write.descriptor_type);
+
+                match &write.info {
+                    DescriptorInfo::Buffer(info) => {
+                        descriptor_write.buffer_info(std::slice::from_ref(info))
+                    }
+                    DescriptorInfo::Image(info) => {
+                        descriptor_write.image_info(std::slice::from_ref(info))
+                    }
+                    DescriptorInfo::TexelBuffer(view) => {
+                        descriptor_write.texel_buffer_view(std::slice::from_ref(view))
+                    }
+                }
+            })
+            .collect();
+
+        unsafe {
+            self.set
+                .device
+                .handle
+                .update_descriptor_sets(&descriptor_writes, &[]);
+        }
+
+        let mut bound_resources = self.set.bound_resources.lock().unwrap();
+
+        for write in self.writes {
+            bound_resources.insert((write.binding, write.array_element), write.resources);
+        }
+    }
 }
 
 impl Drop for UsamiDescriptorSet {
@@ -134,13 +439,204 @@ impl Drop for UsamiDescriptorSet {
     }
 }
 
+struct PoolEntry {
+    pool: UsamiDescriptorPool,
+    pool_sizes: HashMap<DescriptorType, u32>,
+    max_sets: u32,
+}
+
+impl PoolEntry {
+    /// Whether an empty pool can hold `set_count` sets using `sizes` descriptors.
+    fn can_hold(&self, sizes: &HashMap<DescriptorType, u32>, set_count: u32) -> bool {
+        self.max_sets >= set_count
+            && sizes
+                .iter()
+                .all(|(ty, count)| self.pool_sizes.get(ty).copied().unwrap_or(0) >= *count)
+    }
+}
+
+/// Descriptor set allocator creating pools on demand.
+///
+/// Pool sizes are derived from the layouts of the allocated sets and a new pool is created when the current one is exhausted.
+/// Sets are never freed individually, [UsamiDescriptorAllocator::reset] returns all of them at once (for example once per frame).
+/// [DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET] is ignored, so dropping a set after a reset or after the allocator never frees it into a reset or destroyed pool.
+pub struct UsamiDescriptorAllocator {
+    device: Arc<UsamiDevice>,
+    name: String,
+    flags: DescriptorPoolCreateFlags,
+    sets_per_pool: u32,
+    /// Highest number of descriptors of each type used by a single set so far.
+    descriptor_counts: HashMap<DescriptorType, u32>,
+    current_pool: Option<PoolEntry>,
+    full_pools: Vec<PoolEntry>,
+    free_pools: Vec<PoolEntry>,
+}
+
+impl UsamiDescriptorAllocator {
+    pub fn new(
+        device: &Arc<UsamiDevice>,
+        name: String,
+        flags: DescriptorPoolCreateFlags,
+        sets_per_pool: u32,
+    ) -> Self {
+        Self {
+            device: device.clone(),
+            name,
+            flags: flags & !DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET,
+            sets_per_pool: sets_per_pool.max(1),
+            descriptor_counts: HashMap::new(),
+            current_pool: None,
+            full_pools: Vec::new(),
+            free_pools: Vec::new(),
+        }
+    }
+
+    fn create_pool(
+        &self,
+        minimum_sizes: &HashMap<DescriptorType, u32>,
+        set_count: u32,
+    ) -> UsamiResult<PoolEntry> {
+        let mut pool_sizes: Vec<DescriptorPoolSize> = self
+            .descriptor_counts
+            .iter()
+            .map(|(ty, count)| DescriptorPoolSize {
+                ty: *ty,
+                descriptor_count: (count * self.sets_per_pool)
+                    .max(minimum_sizes.get(ty).copied().unwrap_or(0)),
+            })
+            .filter(|x| x.descriptor_count != 0)
+            .collect();
+
+        // Vulkan 1.0 requires at least one pool size.
+        if pool_sizes.is_empty() {
+            pool_sizes.push(DescriptorPoolSize {
+                ty: DescriptorType::STORAGE_BUFFER,
+                descriptor_count: 1,
+            });
+        }
+
+        let pool_index = self.full_pools.len() + self.free_pools.len();
+        let max_sets = self.sets_per_pool.max(set_count);
+
+        let pool = UsamiDevice::create_descriptor_pool(
+            &self.device,
+            format!("{}_pool_{pool_index}", self.name),
+            DescriptorPoolCreateInfo::default()
+                .flags(self.flags)
+                .pool_sizes(&pool_sizes)
+                .max_sets(max_sets),
+        )?;
+
+        Ok(PoolEntry {
+            pool,
+            pool_sizes: pool_sizes
+                .iter()
+                .map(|x| (x.ty, x.descriptor_count))
+                .collect(),
+            max_sets,
+        })
+    }
+
+    /// Allocate a set for each of the given layouts.
+    pub fn allocate(
+        &mut self,
+        name: String,
+        layouts: &[&UsamiDescriptorSetLayout],
+    ) -> UsamiResult<Vec<UsamiDescriptorSet>> {
+        let mut request_sizes: HashMap<DescriptorType, u32> = HashMap::new();
+
+        for layout in layouts {
+            let mut set_sizes: HashMap<DescriptorType, u32> = HashMap::new();
+
+            for binding in &layout.bindings {
+                *set_sizes.entry(binding.descriptor_type).or_default() += binding.descriptor_count;
+            }
+
+            for (ty, count) in set_sizes {
+                let descriptor_count = self.descriptor_counts.entry(ty).or_default();
+                *descriptor_count = (*descriptor_count).max(count);
+
+                *request_sizes.entry(ty).or_default() += count;
+            }
+        }
+
+        let set_count = layouts.len() as u32;
+
+        if self.current_pool.is_none() {
+            let free_pool_index = self
+                .free_pools
+                .iter()
+                .position(|x| x.can_hold(&request_sizes, set_count));
+
+            self.current_pool = match free_pool_index {
+                Some(index) => Some(self.free_pools.swap_remove(index)),
+                None => Some(self.create_pool(&request_sizes, set_count)?),
+            };
+        }
+
+        let handles: Vec<DescriptorSetLayout> = layouts.iter().map(|x| x.handle).collect();
+
+        let result = self
+            .current_pool
+            .as_ref()
+            .unwrap()
+            .pool
+            .allocate_descriptor_sets(name.clone(), &handles);
+
+        match result.as_ref().map_err(Error::vk_result) {
+            Err(Some(vk::Result::ERROR_OUT_OF_POOL_MEMORY | vk::Result::ERROR_FRAGMENTED_POOL)) => {
+                // Retire the exhausted pool and retry once with a fresh one.
+                self.full_pools.extend(self.current_pool.take());
+                self.current_pool = Some(self.create_pool(&request_sizes, set_count)?);
+
+                self.current_pool
+                    .as_ref()
+                    .unwrap()
+                    .pool
+                    .allocate_descriptor_sets(name, &handles)
+            }
+            _ => result,
+        }
+    }
+
+    /// Allocate the sets of a pipeline layout created from reflection.
+    pub fn allocate_for_pipeline_layout(
+        &mut self,
+        name: String,
+        pipeline_layout: &UsamiPipelineLayout,
+    ) -> UsamiResult<Vec<UsamiDescriptorSet>> {
+        let layouts: Vec<&UsamiDescriptorSetLayout> = pipeline_layout.set_layouts.iter().collect();
+
+        if layouts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.allocate(name, &layouts)
+    }
+
+    /// Reset every pool, all sets previously allocated must not be used anymore.
+    pub fn reset(&mut self) -> UsamiResult<()> {
+        for pool in self
+            .current_pool
+            .take()
+            .into_iter()
+            .chain(self.full_pools.drain(..))
+        {
+            pool.pool.reset()?;
+            self.free_pools.push(pool);
+        }
+
+        Ok(())
+    }
+}
+
 impl UsamiDevice {
     pub fn create_descriptor_pool(
         device: &Arc<UsamiDevice>,
         name: String,
         create_info: DescriptorPoolCreateInfo,
-    ) -> VkResult<UsamiDescriptorPool> {
-        let shader = UsamiDescriptorPool::new(device, create_info)?;
+    ) -> UsamiResult<UsamiDescriptorPool> {
+        let shader = UsamiDescriptorPool::new(device, create_info).context(&name)?;
 
         device.set_debug_name(name, shader.handle)?;
 
@@ -151,11 +647,20 @@ impl UsamiDevice {
         device: &Arc<UsamiDevice>,
         name: String,
         create_info: DescriptorSetLayoutCreateInfo,
-    ) -> VkResult<UsamiDescriptorSetLayout> {
-        let layout = UsamiDescriptorSetLayout::new(device, create_info)?;
+    ) -> UsamiResult<UsamiDescriptorSetLayout> {
+        let layout = UsamiDescriptorSetLayout::new(device, create_info).context(&name)?;
 
         device.set_debug_name(name, layout.handle)?;
 
         Ok(layout)
     }
+
+    pub fn create_descriptor_allocator(
+        device: &Arc<UsamiDevice>,
+        name: String,
+        flags: DescriptorPoolCreateFlags,
+        sets_per_pool: u32,
+    ) -> UsamiDescriptorAllocator {
+        UsamiDescriptorAllocator::new(device, name, flags, sets_per_pool)
+    }
 }
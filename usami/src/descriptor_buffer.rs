@@ -0,0 +1,634 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt::Write as _,
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use ash::{
+    ext::descriptor_buffer::Device as DescriptorBufferDevice,
+    vk::{
+        self, BufferCreateFlags, BufferUsageFlags, DescriptorAddressInfoEXT,
+        DescriptorBufferBindingInfoEXT, DescriptorDataEXT, DescriptorGetInfoEXT,
+        DescriptorImageInfo, DescriptorType, DeviceAddress, DeviceSize, Format, ImageLayout,
+        MemoryPropertyFlags, PhysicalDeviceDescriptorBufferPropertiesEXT,
+        PhysicalDeviceProperties2, SharingMode, WHOLE_SIZE,
+    },
+};
+
+use crate::{
+    descriptor::UsamiDescriptorSetLayout, Error, ResultExt, UsamiBuffer, UsamiDevice,
+    UsamiImageView, UsamiResult, UsamiSampler,
+};
+
+type BoundResource = Arc<dyn Any + Send + Sync>;
+
+/// Descriptor types that can be written to a descriptor buffer, in dump order.
+const DESCRIPTOR_TYPES: [DescriptorType; 10] = [
+    DescriptorType::SAMPLER,
+    DescriptorType::COMBINED_IMAGE_SAMPLER,
+    DescriptorType::SAMPLED_IMAGE,
+    DescriptorType::STORAGE_IMAGE,
+    DescriptorType::UNIFORM_TEXEL_BUFFER,
+    DescriptorType::STORAGE_TEXEL_BUFFER,
+    DescriptorType::UNIFORM_BUFFER,
+    DescriptorType::STORAGE_BUFFER,
+    DescriptorType::INPUT_ATTACHMENT,
+    DescriptorType::ACCELERATION_STRUCTURE_KHR,
+];
+
+/// Placement of a set allocated by [UsamiDescriptorBuffer::allocate_set].
+#[derive(Clone, Debug)]
+pub struct UsamiDescriptorBufferSet {
+    pub offset: DeviceSize,
+    pub size: DeviceSize,
+    /// Bindings of the set layout as (binding, descriptor type, descriptor count, offset in the set).
+    pub bindings: Vec<(u32, DescriptorType, u32, DeviceSize)>,
+}
+
+/// Host visible buffer holding descriptors written with `vkGetDescriptorEXT` (`VK_EXT_descriptor_buffer`).
+///
+/// Sets are sub-allocated linearly and only released all at once by [UsamiDescriptorBuffer::reset].
+pub struct UsamiDescriptorBuffer {
+    device: Arc<UsamiDevice>,
+    pub name: String,
+    pub buffer: UsamiBuffer,
+    pub address: DeviceAddress,
+    pub properties: PhysicalDeviceDescriptorBufferPropertiesEXT<'static>,
+    sets: Mutex<Vec<UsamiDescriptorBufferSet>>,
+    bound_resources: Mutex<HashMap<(DeviceSize, u32, u32), Vec<BoundResource>>>,
+}
+
+impl UsamiDescriptorBuffer {
+    pub fn new(
+        device: &Arc<UsamiDevice>,
+        name: String,
+        usage: BufferUsageFlags,
+        size: DeviceSize,
+    ) -> UsamiResult<Self> {
+        device.descriptor_buffer_device()?;
+
+        let buffer = UsamiDevice::create_buffer_with_size(
+            device,
+            name.clone(),
+            BufferCreateFlags::empty(),
+            SharingMode::EXCLUSIVE,
+            usage | BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            size,
+            MemoryPropertyFlags::HOST_VISIBLE,
+        )?;
+        let address = buffer.device_address();
+
+        Ok(Self {
+            device: device.clone(),
+            name,
+            buffer,
+            address,
+            properties: device.descriptor_buffer_properties(),
+            sets: Mutex::new(Vec::new()),
+            bound_resources: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn loader(&self) -> &DescriptorBufferDevice {
+        self.device
+            .vk_descriptor_buffer_device
+            .as_ref()
+            .expect("Descriptor buffer created without VK_EXT_descriptor_buffer")
+    }
+
+    /// Size of a single descriptor of the given type, [None] if it cannot be stored in a descriptor buffer.
+    pub fn descriptor_size(&self, descriptor_type: DescriptorType) -> Option<usize> {
+        let properties = &self.properties;
+        let robust = self.device.enabled_features.core.robust_buffer_access != vk::FALSE;
+
+        let size = match descriptor_type {
+            DescriptorType::SAMPLER => properties.sampler_descriptor_size,
+            DescriptorType::COMBINED_IMAGE_SAMPLER => {
+                properties.combined_image_sampler_descriptor_size
+            }
+            DescriptorType::SAMPLED_IMAGE => properties.sampled_image_descriptor_size,
+            DescriptorType::STORAGE_IMAGE => properties.storage_image_descriptor_size,
+            DescriptorType::UNIFORM_TEXEL_BUFFER if robust => {
+                properties.robust_uniform_texel_buffer_descriptor_size
+            }
+            DescriptorType::UNIFORM_TEXEL_BUFFER => properties.uniform_texel_buffer_descriptor_size,
+            DescriptorType::STORAGE_TEXEL_BUFFER if robust => {
+                properties.robust_storage_texel_buffer_descriptor_size
+            }
+            DescriptorType::STORAGE_TEXEL_BUFFER => properties.storage_texel_buffer_descriptor_size,
+            DescriptorType::UNIFORM_BUFFER if robust => {
+                properties.robust_uniform_buffer_descriptor_size
+            }
+            DescriptorType::UNIFORM_BUFFER => properties.uniform_buffer_descriptor_size,
+            DescriptorType::STORAGE_BUFFER if robust => {
+                properties.robust_storage_buffer_descriptor_size
+            }
+            DescriptorType::STORAGE_BUFFER => properties.storage_buffer_descriptor_size,
+            DescriptorType::INPUT_ATTACHMENT => properties.input_attachment_descriptor_size,
+            DescriptorType::ACCELERATION_STRUCTURE_KHR => {
+                properties.acceleration_structure_descriptor_size
+            }
+            _ => return None,
+        };
+
+        Some(size)
+    }
+
+    /// Size of a set using the given layout, which must have been created with [vk::DescriptorSetLayoutCreateFlags::DESCRIPTOR_BUFFER_EXT].
+    pub fn set_layout_size(&self, layout: &UsamiDescriptorSetLayout) -> DeviceSize {
+        unsafe { self.loader().get_descriptor_set_layout_size(layout.handle) }
+    }
+
+    /// Offset of a binding from the start of a set using the given layout.
+    pub fn binding_offset(&self, layout: &UsamiDescriptorSetLayout, binding: u32) -> DeviceSize {
+        unsafe {
+            self.loader()
+                .get_descriptor_set_layout_binding_offset(layout.handle, binding)
+        }
+    }
+
+    /// Reserve space for a set using the given layout and return its offset in the buffer.
+    pub fn allocate_set(&self, layout: &UsamiDescriptorSetLayout) -> UsamiResult<DeviceSize> {
+        let size = self.set_layout_size(layout);
+        let mut sets = self.sets.lock().unwrap();
+
+        let offset = sets
+            .last()
+            .map(|set| set.offset + set.size)
+            .unwrap_or(0)
+            .next_multiple_of(self.properties.descriptor_buffer_offset_alignment);
+
+        if offset + size > self.buffer.size {
+            return Err(Error::Vulkan(vk::Result::ERROR_OUT_OF_POOL_MEMORY)).context(&self.name);
+        }
+
+        let bindings = layout
+            .bindings
+            .iter()
+            .map(|binding| {
+                (
+                    binding.binding,
+                    binding.descriptor_type,
+                    binding.descriptor_count,
+                    self.binding_offset(layout, binding.binding),
+                )
+            })
+            .collect();
+
+        sets.push(UsamiDescriptorBufferSet {
+            offset,
+            size,
+            bindings,
+        });
+
+        Ok(offset)
+    }
+
+    /// Return the sets allocated so far.
+    pub fn sets(&self) -> Vec<UsamiDescriptorBufferSet> {
+        self.sets.lock().unwrap().clone()
+    }
+
+    /// Release all the sets and the resources they were keeping alive.
+    pub fn reset(&self) {
+        self.sets.lock().unwrap().clear();
+        self.bound_resources.lock().unwrap().clear();
+    }
+
+    pub fn write<'a>(
+        &'a mut self,
+        set_offset: DeviceSize,
+        layout: &'a UsamiDescriptorSetLayout,
+    ) -> UsamiDescriptorBufferWriter<'a> {
+        UsamiDescriptorBufferWriter {
+            descriptor_buffer: self,
+            set_offset,
+            layout,
+            writes: Vec::new(),
+        }
+    }
+
+    /// Binding info to pass to [crate::UsamiCommandBuffer::bind_descriptor_buffers].
+    pub fn binding_info(&self) -> DescriptorBufferBindingInfoEXT<'static> {
+        DescriptorBufferBindingInfoEXT::default()
+            .address(self.address)
+            .usage(self.buffer.usage)
+    }
+
+    /// Return the raw descriptor bytes.
+    pub fn read_to_vec(&self) -> UsamiResult<Vec<u8>> {
+        let mut data = self.buffer.allocation.read_to_vec()?;
+
+        data.truncate(self.buffer.size as usize);
+
+        Ok(data)
+    }
+
+    /// Save the raw descriptor bytes along with a JSON sidecar (same path with a `json` extension)
+    /// describing the descriptor sizes and the placement of every set.
+    pub fn save_raw<P: AsRef<Path>>(&self, path: P) -> UsamiResult<()> {
+        let path = path.as_ref();
+        let sidecar_path = path.with_extension("json");
+
+        fs::write(path, self.read_to_vec()?).map_err(|source| Error::Io {
+            path: Some(path.to_path_buf()),
+            source,
+        })?;
+        fs::write(&sidecar_path, self.sidecar_json()).map_err(|source| Error::Io {
+            path: Some(sidecar_path),
+            source,
+        })
+    }
+
+    fn sidecar_json(&self) -> String {
+        let descriptor_sizes = DESCRIPTOR_TYPES
+            .iter()
+            .filter_map(|ty| {
+                self.descriptor_size(*ty)
+                    .map(|size| format!("    \"{ty:?}\": {size}"))
+            })
+            .collect::<Vec<String>>()
+            .join(",\n");
+
+        let sets = self
+            .sets()
+            .iter()
+            .map(|set| {
+                let mut result = format!(
+                    "    {{\n      \"offset\": {},\n      \"size\": {},\n      \"bindings\": [",
+                    set.offset, set.size
+                );
+
+                for (index, (binding, ty, count, offset)) in set.bindings.iter().enumerate() {
+                    let separator = if index == 0 { "" } else { "," };
+
+                    write!(
+                        result,
+                        "{separator}\n        {{ \"binding\": {binding}, \"type\": \"{ty:?}\", \"count\": {count}, \"offset\": {offset} }}"
+                    )
+                    .unwrap();
+                }
+
+                result.push_str("\n      ]\n    }");
+                result
+            })
+            .collect::<Vec<String>>()
+            .join(",\n");
+
+        format!(
+            concat!(
+                "{{\n",
+                "  \"usage\": \"{:?}\",\n",
+                "  \"size\": {},\n",
+                "  \"offset_alignment\": {},\n",
+                "  \"descriptor_sizes\": {{\n{}\n  }},\n",
+                "  \"sets\": [\n{}\n  ]\n",
+                "}}\n"
+            ),
+            self.buffer.usage,
+            self.buffer.size,
+            self.properties.descriptor_buffer_offset_alignment,
+            descriptor_sizes,
+            sets
+        )
+    }
+}
+
+enum DescriptorBufferInfo {
+    Sampler(vk::Sampler),
+    Image(DescriptorImageInfo),
+    Address(DescriptorAddressInfoEXT<'static>),
+}
+
+struct PendingDescriptorBufferWrite {
+    binding: u32,
+    array_element: u32,
+    descriptor_type: DescriptorType,
+    info: DescriptorBufferInfo,
+    resources: Vec<BoundResource>,
+}
+
+/// Batch of descriptors written to a descriptor buffer set by [UsamiDescriptorBufferWriter::update].
+pub struct UsamiDescriptorBufferWriter<'a> {
+    descriptor_buffer: &'a mut UsamiDescriptorBuffer,
+    set_offset: DeviceSize,
+    layout: &'a UsamiDescriptorSetLayout,
+    writes: Vec<PendingDescriptorBufferWrite>,
+}
+
+impl<'a> UsamiDescriptorBufferWriter<'a> {
+    fn push(
+        mut self,
+        binding: u32,
+        array_element: u32,
+        descriptor_type: DescriptorType,
+        info: DescriptorBufferInfo,
+        resources: Vec<BoundResource>,
+    ) -> Self {
+        self.writes.push(PendingDescriptorBufferWrite {
+            binding,
+            array_element,
+            descriptor_type,
+            info,
+            resources,
+        });
+
+        self
+    }
+
+    pub fn buffer(
+        self,
+        binding: u32,
+        descriptor_type: DescriptorType,
+        buffer: &Arc<UsamiBuffer>,
+        offset: DeviceSize,
+        range: DeviceSize,
+    ) -> Self {
+        self.buffer_at(binding, 0, descriptor_type, buffer, offset, range)
+    }
+
+    pub fn buffer_at(
+        self,
+        binding: u32,
+        array_element: u32,
+        descriptor_type: DescriptorType,
+        buffer: &Arc<UsamiBuffer>,
+        offset: DeviceSize,
+        range: DeviceSize,
+    ) -> Self {
+        self.texel_buffer_at(
+            binding,
+            array_element,
+            descriptor_type,
+            buffer,
+            offset,
+            range,
+            Format::UNDEFINED,
+        )
+    }
+
+    pub fn texel_buffer(
+        self,
+        binding: u32,
+        descriptor_type: DescriptorType,
+        buffer: &Arc<UsamiBuffer>,
+        offset: DeviceSize,
+        range: DeviceSize,
+        format: Format,
+    ) -> Self {
+        self.texel_buffer_at(binding, 0, descriptor_type, buffer, offset, range, format)
+    }
+
+    pub fn texel_buffer_at(
+        self,
+        binding: u32,
+        array_element: u32,
+        descriptor_type: DescriptorType,
+        buffer: &Arc<UsamiBuffer>,
+        offset: DeviceSize,
+        range: DeviceSize,
+        format: Format,
+    ) -> Self {
+        let range = if range == WHOLE_SIZE {
+            buffer.size - offset
+        } else {
+            range
+        };
+        let info = DescriptorAddressInfoEXT::default()
+            .address(buffer.device_address() + offset)
+            .range(range)
+            .format(format);
+
+        self.push(
+            binding,
+            array_element,
+            descriptor_type,
+            DescriptorBufferInfo::Address(info),
+            vec![buffer.clone()],
+        )
+    }
+
+    pub fn image(
+        self,
+        binding: u32,
+        descriptor_type: DescriptorType,
+        image_view: &Arc<UsamiImageView>,
+        layout: ImageLayout,
+    ) -> Self {
+        self.image_at(binding, 0, descriptor_type, image_view, layout)
+    }
+
+    pub fn image_at(
+        self,
+        binding: u32,
+        array_element: u32,
+        descriptor_type: DescriptorType,
+        image_view: &Arc<UsamiImageView>,
+        layout: ImageLayout,
+    ) -> Self {
+        let info = DescriptorImageInfo::default()
+            .image_view(image_view.handle)
+            .image_layout(layout);
+
+        self.push(
+            binding,
+            array_element,
+            descriptor_type,
+            DescriptorBufferInfo::Image(info),
+            vec![image_view.clone()],
+        )
+    }
+
+    pub fn sampler(self, binding: u32, sampler: &Arc<UsamiSampler>) -> Self {
+        self.sampler_at(binding, 0, sampler)
+    }
+
+    pub fn sampler_at(self, binding: u32, array_element: u32, sampler: &Arc<UsamiSampler>) -> Self {
+        self.push(
+            binding,
+            array_element,
+            DescriptorType::SAMPLER,
+            DescriptorBufferInfo::Sampler(sampler.handle),
+            vec![sampler.clone()],
+        )
+    }
+
+    pub fn combined_image_sampler(
+        self,
+        binding: u32,
+        image_view: &Arc<UsamiImageView>,
+        layout: ImageLayout,
+        sampler: &Arc<UsamiSampler>,
+    ) -> Self {
+        self.combined_image_sampler_at(binding, 0, image_view, layout, sampler)
+    }
+
+    pub fn combined_image_sampler_at(
+        self,
+        binding: u32,
+        array_element: u32,
+        image_view: &Arc<UsamiImageView>,
+        layout: ImageLayout,
+        sampler: &Arc<UsamiSampler>,
+    ) -> Self {
+        let info = DescriptorImageInfo::default()
+            .image_view(image_view.handle)
+            .image_layout(layout)
+            .sampler(sampler.handle);
+
+        self.push(
+            binding,
+            array_element,
+            DescriptorType::COMBINED_IMAGE_SAMPLER,
+            DescriptorBufferInfo::Image(info),
+            vec![image_view.clone(), sampler.clone()],
+        )
+    }
+
+    /// Write all the descriptors to the buffer memory.
+    pub fn update(self) -> UsamiResult<()> {
+        let descriptor_buffer = self.descriptor_buffer;
+        let mut placements = Vec::with_capacity(self.writes.len());
+
+        for write in &self.writes {
+            let descriptor_size = descriptor_buffer
+                .descriptor_size(write.descriptor_type)
+                .ok_or(Error::UnsupportedDescriptorType(write.descriptor_type))
+                .context(&descriptor_buffer.name)?;
+
+            // A binding missing from the layout has no valid array element.
+            let descriptor_count = self
+                .layout
+                .bindings
+                .iter()
+                .find(|x| x.binding == write.binding)
+                .map(|x| x.descriptor_count)
+                .unwrap_or(0);
+
+            if write.array_element >= descriptor_count {
+                return Err(Error::OutOfBounds {
+                    start: write.array_element.into(),
+                    end: u64::from(write.array_element) + 1,
+                    limit: descriptor_count.into(),
+                })
+                .context(&descriptor_buffer.name);
+            }
+
+            let offset = self.set_offset
+                + descriptor_buffer.binding_offset(self.layout, write.binding)
+                + write.array_element as DeviceSize * descriptor_size as DeviceSize;
+            let end = offset + descriptor_size as DeviceSize;
+
+            if end > descriptor_buffer.buffer.size {
+                return Err(Error::OutOfBounds {
+                    start: offset,
+                    end,
+                    limit: descriptor_buffer.buffer.size,
+                })
+                .context(&descriptor_buffer.name);
+            }
+
+            placements.push(offset as usize..end as usize);
+        }
+
+        let loader = descriptor_buffer.device.descriptor_buffer_device()?;
+        let mut mapped = descriptor_buffer.buffer.mapped_slice_mut::<u8>()?;
+
+        for (write, placement) in self.writes.iter().zip(placements) {
+            let data = match &write.info {
+                DescriptorBufferInfo::Sampler(sampler) => DescriptorDataEXT { p_sampler: sampler },
+                DescriptorBufferInfo::Image(info) => match write.descriptor_type {
+                    DescriptorType::COMBINED_IMAGE_SAMPLER => DescriptorDataEXT {
+                        p_combined_image_sampler: info,
+                    },
+                    DescriptorType::STORAGE_IMAGE => DescriptorDataEXT {
+                        p_storage_image: info,
+                    },
+                    DescriptorType::INPUT_ATTACHMENT => DescriptorDataEXT {
+                        p_input_attachment_image: info,
+                    },
+                    _ => DescriptorDataEXT {
+                        p_sampled_image: info,
+                    },
+                },
+                DescriptorBufferInfo::Address(info) => match write.descriptor_type {
+                    DescriptorType::UNIFORM_TEXEL_BUFFER => DescriptorDataEXT {
+                        p_uniform_texel_buffer: info,
+                    },
+                    DescriptorType::STORAGE_TEXEL_BUFFER => DescriptorDataEXT {
+                        p_storage_texel_buffer: info,
+                    },
+                    DescriptorType::UNIFORM_BUFFER => DescriptorDataEXT {
+                        p_uniform_buffer: info,
+                    },
+                    _ => DescriptorDataEXT {
+                        p_storage_buffer: info,
+                    },
+                },
+            };
+
+            unsafe {
+                loader.get_descriptor(
+                    &DescriptorGetInfoEXT::default()
+                        .ty(write.descriptor_type)
+                        .data(data),
+                    &mut mapped[placement],
+                );
+            }
+        }
+
+        mapped.flush()?;
+        drop(mapped);
+
+        let mut bound_resources = descriptor_buffer.bound_resources.lock().unwrap();
+
+        for write in self.writes {
+            bound_resources.insert(
+                (self.set_offset, write.binding, write.array_element),
+                write.resources,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl UsamiDevice {
+    /// Return the `VK_EXT_descriptor_buffer` device functions if the extension is enabled.
+    pub fn descriptor_buffer_device(&self) -> UsamiResult<&DescriptorBufferDevice> {
+        self.vk_descriptor_buffer_device
+            .as_ref()
+            .ok_or(Error::MissingExtension("VK_EXT_descriptor_buffer".into()))
+    }
+
+    pub fn descriptor_buffer_properties(
+        &self,
+    ) -> PhysicalDeviceDescriptorBufferPropertiesEXT<'static> {
+        let mut properties = PhysicalDeviceDescriptorBufferPropertiesEXT::default();
+
+        {
+            let mut properties2 = PhysicalDeviceProperties2::default().push_next(&mut properties);
+
+            unsafe {
+                self.instance
+                    .vk_instance
+                    .get_physical_device_properties2(self.physical_device.handle, &mut properties2);
+            }
+        }
+
+        properties.p_next = std::ptr::null_mut();
+        properties
+    }
+
+    pub fn create_descriptor_buffer(
+        device: &Arc<UsamiDevice>,
+        name: String,
+        usage: BufferUsageFlags,
+        size: DeviceSize,
+    ) -> UsamiResult<UsamiDescriptorBuffer> {
+        UsamiDescriptorBuffer::new(device, name.clone(), usage, size).context(&name)
+    }
+}
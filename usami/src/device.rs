@@ -1,19 +1,31 @@
 use std::{
-    ffi::{c_char, CString},
+    ffi::{c_char, CStr, CString},
     sync::Arc,
 };
 
 use ash::{
-    ext::{debug_utils::Device as DebugUtilsDevice, image_robustness},
-    prelude::*,
+    ext::{
+        debug_utils::Device as DebugUtilsDevice,
+        descriptor_buffer::Device as DescriptorBufferDevice,
+    },
+    khr::pipeline_executable_properties::Device as PipelineExecutablePropertiesDevice,
     vk::{
-        self, BufferCreateFlags, BufferUsageFlags, ComponentMapping, ComponentSwizzle, DebugUtilsObjectNameInfoEXT, DeviceCreateInfo, DeviceQueueCreateInfo, Extent2D, Extent3D, Format, FramebufferCreateInfo, ImageAspectFlags, ImageCreateInfo, ImageSubresourceRange, ImageTiling, ImageType, ImageUsageFlags, ImageViewCreateFlags, ImageViewType, MemoryPropertyFlags, PhysicalDevice, PhysicalDeviceConditionalRenderingFeaturesEXT, PhysicalDeviceCooperativeMatrixFeaturesKHR, PhysicalDeviceCooperativeMatrixFeaturesNV, PhysicalDeviceFeatures, PhysicalDeviceFeatures2, PhysicalDeviceImageRobustnessFeatures, PhysicalDeviceMemoryProperties, PhysicalDeviceMeshShaderFeaturesEXT, PhysicalDeviceProperties, PhysicalDeviceShaderObjectFeaturesEXT, PhysicalDeviceTransformFeedbackFeaturesEXT, PhysicalDeviceVulkan11Features, PhysicalDeviceVulkan12Features, PhysicalDeviceVulkan13Features, QueueFamilyProperties, Rect2D, SampleCountFlags, SharingMode, Viewport
+        self, AttachmentLoadOp, AttachmentStoreOp, ClearColorValue, ClearValue, ComponentMapping,
+        ComponentSwizzle, DebugUtilsLabelEXT, DebugUtilsObjectNameInfoEXT, DeviceCreateInfo,
+        DeviceQueueCreateInfo, Extent2D, Extent3D, Format, FramebufferCreateInfo, ImageAspectFlags,
+        ImageCreateInfo, ImageLayout, ImageSubresourceRange, ImageTiling, ImageType,
+        ImageUsageFlags, ImageViewCreateFlags, ImageViewType, MemoryPropertyFlags, PhysicalDevice,
+        PhysicalDeviceFeatures, PhysicalDeviceMemoryProperties, PhysicalDeviceProperties,
+        PhysicalDeviceType, QueueFamilyProperties, QueueFlags, Rect2D, RenderingAttachmentInfo,
+        SampleCountFlags, Viewport,
     },
 };
 
 use crate::{
-    utils, UsamiBuffer, UsamiFramebuffer, UsamiImage, UsamiImageView, UsamiInstance,
-    UsamiRenderPass,
+    memory::{UsamiAllocator, DEFAULT_MEMORY_BLOCK_SIZE},
+    queue::{UsamiQueueKind, UsamiQueueRegistry, UsamiQueueSlot},
+    Error, UsamiAccess, UsamiCommandBuffer, UsamiCommandPool, UsamiDeviceFeatures,
+    UsamiFramebuffer, UsamiImage, UsamiImageView, UsamiInstance, UsamiRenderPass, UsamiResult,
 };
 
 pub struct UsamiPhysicalDevice {
@@ -22,224 +34,666 @@ pub struct UsamiPhysicalDevice {
     pub features: PhysicalDeviceFeatures,
     pub memory_properties: PhysicalDeviceMemoryProperties,
     pub queue_familiy_properties: Vec<QueueFamilyProperties>,
+    pub extensions: Vec<String>,
+    pub supported_features: UsamiDeviceFeatures,
 }
 
-pub struct UsamiDevice {
-    pub instance: UsamiInstance,
-    pub physical_device: UsamiPhysicalDevice,
-    pub handle: ash::Device,
-    pub vk_debug_utils_device: DebugUtilsDevice,
-    pub vk_queue_index: u32,
-}
+impl UsamiPhysicalDevice {
+    pub fn new(instance: &UsamiInstance, handle: PhysicalDevice) -> UsamiResult<Self> {
+        let vk_instance = &instance.vk_instance;
+
+        let properties = unsafe { vk_instance.get_physical_device_properties(handle) };
+        let features = unsafe { vk_instance.get_physical_device_features(handle) };
+        let memory_properties =
+            unsafe { vk_instance.get_physical_device_memory_properties(handle) };
+        let queue_familiy_properties =
+            unsafe { vk_instance.get_physical_device_queue_family_properties(handle) };
+        let extensions_cstring =
+            unsafe { vk_instance.enumerate_device_extension_properties(handle)? }
+                .iter()
+                .filter_map(|x| x.extension_name_as_c_str().ok().map(CString::from))
+                .collect::<Vec<CString>>();
+        let extensions_cstr = extensions_cstring
+            .iter()
+            .map(CString::as_c_str)
+            .collect::<Vec<&CStr>>();
 
-impl UsamiDevice {
-    pub fn new_by_filter(
-        instance: UsamiInstance,
-        extensions: &[String],
-        should_grab: Box<dyn FnMut(UsamiPhysicalDevice) -> Option<(UsamiPhysicalDevice, u32)>>,
-    ) -> VkResult<Arc<Self>> {
-        let (physical_device, vk_queue_index) =
-            unsafe { instance.vk_instance.enumerate_physical_devices()? }
+        let api_version = instance.vk_version.min(properties.api_version);
+        let mut supported_features = UsamiDeviceFeatures {
+            core: features,
+            ..Default::default()
+        };
+
+        if api_version >= vk::API_VERSION_1_1 {
+            let mut features2 = supported_features.chain(api_version, &extensions_cstr);
+
+            unsafe { vk_instance.get_physical_device_features2(handle, &mut features2) };
+
+            supported_features.core = features2.features;
+        }
+
+        // Ensure the next pointers are NULL as get_physical_device_features2 could create garbage...
+        supported_features.clear_chain();
+        supported_features.resolve_promoted(api_version, &extensions_cstr);
+
+        Ok(Self {
+            handle,
+            properties,
+            features,
+            memory_properties,
+            queue_familiy_properties,
+            extensions: extensions_cstring
                 .iter()
-                .map(|x| {
-                    let prop = unsafe { instance.vk_instance.get_physical_device_properties(*x) };
-                    let feat = unsafe { instance.vk_instance.get_physical_device_features(*x) };
-                    let memory_prop = unsafe {
-                        instance
-                            .vk_instance
-                            .get_physical_device_memory_properties(*x)
-                    };
-                    let queues = unsafe {
-                        instance
-                            .vk_instance
-                            .get_physical_device_queue_family_properties(*x)
-                    };
-                    UsamiPhysicalDevice {
-                        handle: *x,
-                        features: feat,
-                        memory_properties: memory_prop,
-                        properties: prop,
-                        queue_familiy_properties: queues,
-                    }
-                })
-                .find_map(should_grab)
-                .expect("Cannot find a device that match requirement!");
+                .map(|x| x.to_string_lossy().into_owned())
+                .collect(),
+            supported_features,
+        })
+    }
 
-        let extensions_cstring: Vec<CString> = extensions
-            .iter()
-            .map(|name| CString::new(name.as_str()).unwrap())
-            .collect();
+    pub fn name(&self) -> String {
+        self.properties
+            .device_name_as_c_str()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
 
-        let mut has_shader_object_extension = false;
-        let mut has_mesh_shader_extension = false;
-        let mut has_conditional_rendering_extension = false;
-        let mut has_xfb_extension = false;
-        let mut has_cooperative_matrix = false;
-        let mut has_cooperative_matrix_nv = false;
-        let mut has_image_robustness = false;
-
-        for extension in &extensions_cstring {
-            if ash::ext::shader_object::NAME == extension.as_c_str() {
-                has_shader_object_extension = true;
-            }
+    pub fn supports_extension(&self, name: &str) -> bool {
+        self.extensions.iter().any(|x| x == name)
+    }
 
-            if ash::ext::mesh_shader::NAME == extension.as_c_str() {
-                has_mesh_shader_extension = true;
-            }
+    pub fn api_version(&self, instance: &UsamiInstance) -> u32 {
+        instance.vk_version.min(self.properties.api_version)
+    }
+}
+
+/// Environment variable overriding the physical device selection.
+///
+/// It accepts a comma separated list of `key=value` pairs with the following keys:
+/// `index`, `name` (case insensitive substring), `vendor`, `device` and `type`
+/// (`discrete`, `integrated`, `virtual`, `cpu` or `other`).
+/// A value without a key is an index if it is a number or a name otherwise.
+pub const DEVICE_SELECTOR_ENV: &str = "USAMI_DEVICE";
+
+/// Criteria used to pick a physical device and its queue family.
+#[derive(Clone, Debug)]
+pub struct UsamiPhysicalDeviceSelector {
+    pub vendor_id: Option<u32>,
+    pub device_id: Option<u32>,
+    pub name: Option<String>,
+    pub index: Option<usize>,
+    pub preferred_device_type: Option<PhysicalDeviceType>,
+    pub queue_flags: QueueFlags,
+}
+
+impl Default for UsamiPhysicalDeviceSelector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UsamiPhysicalDeviceSelector {
+    pub fn new() -> Self {
+        Self {
+            vendor_id: None,
+            device_id: None,
+            name: None,
+            index: None,
+            preferred_device_type: None,
+            queue_flags: QueueFlags::GRAPHICS,
+        }
+    }
+
+    pub fn vendor_id(mut self, vendor_id: u32) -> Self {
+        self.vendor_id = Some(vendor_id);
+        self
+    }
+
+    pub fn device_id(mut self, device_id: u32) -> Self {
+        self.device_id = Some(device_id);
+        self
+    }
+
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn index(mut self, index: usize) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// Devices of this type are tried first, other devices are still considered.
+    pub fn preferred_device_type(mut self, device_type: PhysicalDeviceType) -> Self {
+        self.preferred_device_type = Some(device_type);
+        self
+    }
+
+    pub fn queue_flags(mut self, queue_flags: QueueFlags) -> Self {
+        self.queue_flags = queue_flags;
+        self
+    }
+
+    /// Override the criteria with the ones given in [DEVICE_SELECTOR_ENV] if set.
+    pub fn with_env(self) -> UsamiResult<Self> {
+        match std::env::var(DEVICE_SELECTOR_ENV) {
+            Ok(value) => self.with_spec(&value),
+            Err(_) => Ok(self),
+        }
+    }
+
+    /// Override the criteria with the ones given in `spec` (see [DEVICE_SELECTOR_ENV] for the syntax).
+    pub fn with_spec(mut self, spec: &str) -> UsamiResult<Self> {
+        let invalid = || Error::InvalidDeviceSelector(spec.into());
 
-            if ash::ext::conditional_rendering::NAME == extension.as_c_str() {
-                has_conditional_rendering_extension = true;
+        for entry in spec.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+            let (key, value) = match entry.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None if entry.parse::<usize>().is_ok() => ("index", entry),
+                None => ("name", entry),
+            };
+
+            match key {
+                "index" => self.index = Some(value.parse().map_err(|_| invalid())?),
+                "name" => self.name = Some(value.into()),
+                "vendor" => self.vendor_id = Some(parse_id(value).ok_or_else(invalid)?),
+                "device" => self.device_id = Some(parse_id(value).ok_or_else(invalid)?),
+                "type" => {
+                    self.preferred_device_type = Some(match value {
+                        "discrete" => PhysicalDeviceType::DISCRETE_GPU,
+                        "integrated" => PhysicalDeviceType::INTEGRATED_GPU,
+                        "virtual" => PhysicalDeviceType::VIRTUAL_GPU,
+                        "cpu" => PhysicalDeviceType::CPU,
+                        "other" => PhysicalDeviceType::OTHER,
+                        _ => return Err(invalid()),
+                    })
+                }
+                _ => return Err(invalid()),
             }
+        }
+
+        Ok(self)
+    }
+
+    pub fn matches(&self, index: usize, physical_device: &UsamiPhysicalDevice) -> bool {
+        let properties = &physical_device.properties;
 
-            if ash::ext::transform_feedback::NAME == extension.as_c_str() {
-                has_xfb_extension = true;
+        if let Some(expected_index) = self.index {
+            if expected_index != index {
+                return false;
             }
+        }
 
-            if ash::khr::cooperative_matrix::NAME == extension.as_c_str() {
-                has_cooperative_matrix = true;
+        if let Some(vendor_id) = self.vendor_id {
+            if properties.vendor_id != vendor_id {
+                return false;
             }
+        }
 
-            if ash::nv::cooperative_matrix::NAME == extension.as_c_str() {
-                has_cooperative_matrix_nv = true;
+        if let Some(device_id) = self.device_id {
+            if properties.device_id != device_id {
+                return false;
             }
+        }
 
-            if ash::ext::image_robustness::NAME == extension.as_c_str() {
-                has_image_robustness = true;
+        if let Some(name) = &self.name {
+            if !physical_device
+                .name()
+                .to_lowercase()
+                .contains(&name.to_lowercase())
+            {
+                return false;
             }
         }
 
-        let extensions_raw: Vec<*const c_char> = extensions_cstring
+        true
+    }
+
+    /// Return the first queue family supporting the requested queue flags.
+    pub fn find_queue_family_index(&self, physical_device: &UsamiPhysicalDevice) -> Option<u32> {
+        physical_device
+            .queue_familiy_properties
             .iter()
-            .map(|raw_name| raw_name.as_ptr())
-            .collect();
+            .position(|x| x.queue_flags.contains(self.queue_flags))
+            .map(|x| x as u32)
+    }
 
-        let enabled_features = PhysicalDeviceFeatures::default()
-            .geometry_shader(physical_device.features.geometry_shader != 0)
-            .shader_tessellation_and_geometry_point_size(
-                physical_device
-                    .features
-                    .shader_tessellation_and_geometry_point_size
-                    != 0,
-            );
+    /// Keep the matching physical devices, the preferred device type first.
+    pub fn select(&self, physical_devices: Vec<UsamiPhysicalDevice>) -> Vec<UsamiPhysicalDevice> {
+        let mut result = physical_devices
+            .into_iter()
+            .enumerate()
+            .filter(|(index, physical_device)| self.matches(*index, physical_device))
+            .map(|(_, physical_device)| physical_device)
+            .collect::<Vec<UsamiPhysicalDevice>>();
+
+        if let Some(device_type) = self.preferred_device_type {
+            result.sort_by_key(|x| x.properties.device_type != device_type);
+        }
 
-        let device_queue_create_info = [DeviceQueueCreateInfo::default()
-            .queue_family_index(vk_queue_index)
-            .queue_priorities(&[1.0])];
+        result
+    }
+}
 
-        let mut shader_object_features =
-            PhysicalDeviceShaderObjectFeaturesEXT::default().shader_object(true);
+fn parse_id(value: &str) -> Option<u32> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
 
-        let mut mesh_shader_features = PhysicalDeviceMeshShaderFeaturesEXT::default()
-            .mesh_shader(true)
-            .task_shader(true);
+pub type UsamiPhysicalDeviceFilter =
+    Box<dyn FnMut(UsamiPhysicalDevice) -> Option<(UsamiPhysicalDevice, u32)>>;
+
+/// Create a [UsamiDevice] enabling only the extensions and features supported by the physical device.
+pub struct UsamiDeviceBuilder {
+    required_extensions: Vec<String>,
+    optional_extensions: Vec<String>,
+    required_features: UsamiDeviceFeatures,
+    optional_features: UsamiDeviceFeatures,
+    selector: UsamiPhysicalDeviceSelector,
+    selector_from_env: bool,
+    filter: Option<UsamiPhysicalDeviceFilter>,
+    queues: Vec<(UsamiQueueKind, u32)>,
+}
 
-        let mut conditional_rendering_features =
-            PhysicalDeviceConditionalRenderingFeaturesEXT::default().conditional_rendering(true);
+impl Default for UsamiDeviceBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        let mut xfb_features = PhysicalDeviceTransformFeedbackFeaturesEXT::default()
-            .transform_feedback(true)
-            .geometry_streams(true);
+impl UsamiDeviceBuilder {
+    pub fn new() -> Self {
+        Self {
+            required_extensions: Vec::new(),
+            optional_extensions: Vec::new(),
+            required_features: UsamiDeviceFeatures::default(),
+            optional_features: UsamiDeviceFeatures::default(),
+            selector: UsamiPhysicalDeviceSelector::new(),
+            selector_from_env: true,
+            filter: None,
+            queues: Vec::new(),
+        }
+    }
 
-        let mut cooperative_matrix_features =
-            PhysicalDeviceCooperativeMatrixFeaturesKHR::default().cooperative_matrix(true);
+    pub fn required_extension<S: Into<String>>(mut self, name: S) -> Self {
+        self.required_extensions.push(name.into());
+        self
+    }
 
-        let mut cooperative_matrix_nv_features =
-            PhysicalDeviceCooperativeMatrixFeaturesNV::default().cooperative_matrix(true);
+    pub fn required_extensions(mut self, names: &[String]) -> Self {
+        self.required_extensions.extend_from_slice(names);
+        self
+    }
 
-        let mut image_robustness_features =
-            PhysicalDeviceImageRobustnessFeatures::default().robust_image_access(true);
+    pub fn optional_extension<S: Into<String>>(mut self, name: S) -> Self {
+        self.optional_extensions.push(name.into());
+        self
+    }
 
-        let mut create_info = DeviceCreateInfo::default()
-            .queue_create_infos(&device_queue_create_info)
-            .enabled_features(&enabled_features)
-            .enabled_extension_names(&extensions_raw);
+    pub fn optional_extensions(mut self, names: &[String]) -> Self {
+        self.optional_extensions.extend_from_slice(names);
+        self
+    }
 
-        if has_shader_object_extension {
-            create_info = create_info.push_next(&mut shader_object_features);
-        }
+    /// Features that must be supported, device creation fails otherwise.
+    pub fn required_features<F: FnOnce(&mut UsamiDeviceFeatures)>(mut self, callback: F) -> Self {
+        callback(&mut self.required_features);
+        self
+    }
 
-        if has_mesh_shader_extension {
-            create_info = create_info.push_next(&mut mesh_shader_features);
-        }
+    /// Features that are enabled only if supported.
+    pub fn optional_features<F: FnOnce(&mut UsamiDeviceFeatures)>(mut self, callback: F) -> Self {
+        callback(&mut self.optional_features);
+        self
+    }
 
-        if has_conditional_rendering_extension {
-            create_info = create_info.push_next(&mut conditional_rendering_features);
-        }
+    pub fn physical_device_selector(mut self, selector: UsamiPhysicalDeviceSelector) -> Self {
+        self.selector = selector;
+        self
+    }
 
-        if has_xfb_extension {
-            create_info = create_info.push_next(&mut xfb_features);
-        }
+    /// Do not let [DEVICE_SELECTOR_ENV] override the physical device selector.
+    pub fn ignore_selector_env(mut self) -> Self {
+        self.selector_from_env = false;
+        self
+    }
+
+    /// Select the physical device and the queue family index to use, replacing the selector queue family lookup.
+    pub fn physical_device_filter(mut self, filter: UsamiPhysicalDeviceFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Request `count` queues for the given kind of work, registered in [UsamiDevice::queues].
+    ///
+    /// A queue family dedicated to that kind of work is preferred, creating the device fails when the family does not have enough queues.
+    /// Kinds without requested queues use the main queue, see [UsamiQueueRegistry::get].
+    pub fn queues(mut self, kind: UsamiQueueKind, count: u32) -> Self {
+        self.queues.push((kind, count));
+        self
+    }
 
-        if has_cooperative_matrix {
-            create_info = create_info.push_next(&mut cooperative_matrix_features);
+    /// Resolve the queue family and count of every requested queue kind.
+    pub fn resolve_queues(
+        &self,
+        physical_device: &UsamiPhysicalDevice,
+    ) -> UsamiResult<Vec<(UsamiQueueKind, u32, u32)>> {
+        self.queues
+            .iter()
+            .enumerate()
+            .map(|(request_index, (kind, count))| {
+                // Queues of the same kind requested several times are indexed one after the other.
+                let index: u32 = self.queues[..request_index]
+                    .iter()
+                    .filter(|(other_kind, _)| other_kind == kind)
+                    .map(|(_, other_count)| *other_count)
+                    .sum();
+
+                kind.find_queue_family_index(&physical_device.queue_familiy_properties)
+                    .map(|family_index| (*kind, family_index, *count))
+                    .ok_or(Error::NoSuitableQueue {
+                        kind: *kind,
+                        index: index as usize,
+                    })
+            })
+            .collect()
+    }
+
+    /// Resolve the extensions and features to enable on the given physical device.
+    pub fn negotiate(
+        &self,
+        physical_device: &UsamiPhysicalDevice,
+    ) -> UsamiResult<(Vec<String>, UsamiDeviceFeatures)> {
+        let mut extensions = Vec::new();
+
+        for name in &self.required_extensions {
+            if !physical_device.supports_extension(name) {
+                return Err(Error::MissingExtension(name.clone()));
+            }
+
+            if !extensions.contains(name) {
+                extensions.push(name.clone());
+            }
         }
 
-        if has_cooperative_matrix_nv {
-            create_info = create_info.push_next(&mut cooperative_matrix_nv_features);
+        for name in &self.optional_extensions {
+            if physical_device.supports_extension(name) && !extensions.contains(name) {
+                extensions.push(name.clone());
+            }
         }
 
-        if has_image_robustness {
-            create_info = create_info.push_next(&mut image_robustness_features);
+        let missing_features = self
+            .required_features
+            .missing_from(&physical_device.supported_features);
+
+        if !missing_features.is_empty() {
+            return Err(Error::MissingFeatures(missing_features));
         }
 
-        let mut vk11_features = PhysicalDeviceVulkan11Features::default();
-        let mut vk12_features = PhysicalDeviceVulkan12Features::default();
-        let mut vk13_features = PhysicalDeviceVulkan13Features::default();
+        let mut features = self.optional_features;
+        features.intersect(&physical_device.supported_features);
+        features.union(&self.required_features);
 
-        if instance.vk_version >= vk::API_VERSION_1_2 {
-            let mut tmp_feat = PhysicalDeviceFeatures2::default();
-            tmp_feat = tmp_feat.push_next(&mut vk11_features);
-            tmp_feat = tmp_feat.push_next(&mut vk12_features);
+        Ok((extensions, features))
+    }
 
-            if instance.vk_version >= vk::API_VERSION_1_3 {
-                tmp_feat = tmp_feat.push_next(&mut vk13_features);
-            }
+    pub fn build(mut self, instance: UsamiInstance) -> UsamiResult<Arc<UsamiDevice>> {
+        let selector = if self.selector_from_env {
+            self.selector.clone().with_env()?
+        } else {
+            self.selector.clone()
+        };
+
+        let physical_devices = unsafe { instance.vk_instance.enumerate_physical_devices()? }
+            .into_iter()
+            .map(|handle| UsamiPhysicalDevice::new(&instance, handle))
+            .collect::<UsamiResult<Vec<UsamiPhysicalDevice>>>()?;
+
+        let mut last_error = Error::NoMatchingPhysicalDevice;
+
+        for physical_device in selector.select(physical_devices) {
+            let selected = match &mut self.filter {
+                Some(filter) => filter(physical_device),
+                None => selector
+                    .find_queue_family_index(&physical_device)
+                    .map(|x| (physical_device, x)),
+            };
 
-            unsafe {
-                instance
-                    .vk_instance
-                    .get_physical_device_features2(physical_device.handle, &mut tmp_feat)
+            let Some((physical_device, vk_queue_index)) = selected else {
+                continue;
             };
 
-            // Ensure the next pointer is NULL as get_physical_device_features2 could create garbage...
-            vk11_features.p_next = core::ptr::null_mut();
-            vk12_features.p_next = core::ptr::null_mut();
-            vk13_features.p_next = core::ptr::null_mut();
+            let negotiated = self
+                .negotiate(&physical_device)
+                .and_then(|(extensions, features)| {
+                    Ok((extensions, features, self.resolve_queues(&physical_device)?))
+                });
+
+            match negotiated {
+                Ok((extensions, features, queues)) => {
+                    return UsamiDevice::new(
+                        instance,
+                        physical_device,
+                        vk_queue_index,
+                        queues,
+                        extensions,
+                        features,
+                    )
+                }
+                Err(error) => last_error = error,
+            }
+        }
+
+        Err(last_error)
+    }
+}
+
+pub struct UsamiDevice {
+    pub instance: UsamiInstance,
+    pub physical_device: UsamiPhysicalDevice,
+    pub handle: ash::Device,
+    pub vk_debug_utils_device: DebugUtilsDevice,
+    /// Only loaded when `VK_EXT_descriptor_buffer` is enabled.
+    pub vk_descriptor_buffer_device: Option<DescriptorBufferDevice>,
+    /// Only loaded when `VK_KHR_pipeline_executable_properties` is enabled.
+    pub vk_pipeline_executable_properties_device: Option<PipelineExecutablePropertiesDevice>,
+    pub vk_queue_index: u32,
+    pub queues: UsamiQueueRegistry,
+    pub allocator: UsamiAllocator,
+    pub enabled_extensions: Vec<String>,
+    pub enabled_features: UsamiDeviceFeatures,
+}
+
+impl UsamiDevice {
+    pub fn builder() -> UsamiDeviceBuilder {
+        UsamiDeviceBuilder::new()
+    }
+
+    pub fn new_by_filter(
+        instance: UsamiInstance,
+        extensions: &[String],
+        should_grab: UsamiPhysicalDeviceFilter,
+    ) -> UsamiResult<Arc<Self>> {
+        Self::builder()
+            .required_extensions(extensions)
+            .optional_features(|features| *features = UsamiDeviceFeatures::usami_defaults())
+            .physical_device_filter(should_grab)
+            .build(instance)
+    }
+
+    pub fn new_by_selector(
+        instance: UsamiInstance,
+        extensions: &[String],
+        selector: UsamiPhysicalDeviceSelector,
+    ) -> UsamiResult<Arc<Self>> {
+        Self::builder()
+            .required_extensions(extensions)
+            .optional_features(|features| *features = UsamiDeviceFeatures::usami_defaults())
+            .physical_device_selector(selector)
+            .build(instance)
+    }
+
+    fn new(
+        instance: UsamiInstance,
+        physical_device: UsamiPhysicalDevice,
+        vk_queue_index: u32,
+        queues: Vec<(UsamiQueueKind, u32, u32)>,
+        enabled_extensions: Vec<String>,
+        mut enabled_features: UsamiDeviceFeatures,
+    ) -> UsamiResult<Arc<Self>> {
+        let extensions_cstring = enabled_extensions
+            .iter()
+            .map(|name| CString::new(name.as_str()))
+            .collect::<Result<Vec<CString>, _>>()?;
+        let extensions_cstr: Vec<&CStr> =
+            extensions_cstring.iter().map(CString::as_c_str).collect();
+        let extensions_raw: Vec<*const c_char> = extensions_cstring
+            .iter()
+            .map(|raw_name| raw_name.as_ptr())
+            .collect();
+
+        // The main queue always takes the first queue of its family, queue handles are never shared as they are externally synchronized.
+        let queue_families = &physical_device.queue_familiy_properties;
+        let mut queue_counts = vec![0u32; queue_families.len()];
+        queue_counts[vk_queue_index as usize] = 1;
+
+        let mut queue_slots = Vec::new();
+
+        for (kind, family_index, count) in queues {
+            let family_queue_count = queue_families[family_index as usize].queue_count;
 
-            create_info = create_info.push_next(&mut vk11_features);
-            create_info = create_info.push_next(&mut vk12_features);
+            for _ in 0..count {
+                let used_count = &mut queue_counts[family_index as usize];
 
-            if instance.vk_version >= vk::API_VERSION_1_3 {
-                create_info = create_info.push_next(&mut vk13_features);
+                if *used_count >= family_queue_count {
+                    return Err(Error::NoSuitableQueue {
+                        kind,
+                        index: queue_slots
+                            .iter()
+                            .filter(|(slot_kind, _, _)| *slot_kind == kind)
+                            .count(),
+                    });
+                }
+
+                queue_slots.push((kind, family_index, *used_count));
+                *used_count += 1;
             }
         }
 
+        let queue_priorities =
+            vec![1.0f32; queue_counts.iter().copied().max().unwrap_or(1) as usize];
+        let device_queue_create_info = queue_counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count != 0)
+            .map(|(family_index, count)| {
+                DeviceQueueCreateInfo::default()
+                    .queue_family_index(family_index as u32)
+                    .queue_priorities(&queue_priorities[..*count as usize])
+            })
+            .collect::<Vec<DeviceQueueCreateInfo>>();
+
+        let api_version = physical_device.api_version(&instance);
+        let core_features = enabled_features.core;
+        let mut features2 = enabled_features.chain(api_version, &extensions_cstr);
+
+        let mut create_info = DeviceCreateInfo::default()
+            .queue_create_infos(&device_queue_create_info)
+            .enabled_extension_names(&extensions_raw);
+
+        if api_version >= vk::API_VERSION_1_1 {
+            create_info = create_info.push_next(&mut features2);
+        } else {
+            create_info = create_info.enabled_features(&core_features);
+        }
+
         let handle = unsafe {
             instance
                 .vk_instance
                 .create_device(physical_device.handle, &create_info, None)?
         };
 
+        enabled_features.clear_chain();
+
         let vk_debug_utils_device = DebugUtilsDevice::new(&instance.vk_instance, &handle);
+        let vk_descriptor_buffer_device = extensions_cstr
+            .contains(&ash::ext::descriptor_buffer::NAME)
+            .then(|| DescriptorBufferDevice::new(&instance.vk_instance, &handle));
+        let vk_pipeline_executable_properties_device = extensions_cstr
+            .contains(&ash::khr::pipeline_executable_properties::NAME)
+            .then(|| PipelineExecutablePropertiesDevice::new(&instance.vk_instance, &handle));
+
+        let get_queue_slot = |family_index: u32, queue_index: u32| UsamiQueueSlot {
+            family_index,
+            queue_index,
+            handle: unsafe { handle.get_device_queue(family_index, queue_index) },
+        };
+
+        let queues = UsamiQueueRegistry::new(
+            get_queue_slot(vk_queue_index, 0),
+            queue_families[vk_queue_index as usize].queue_flags,
+            queue_slots
+                .into_iter()
+                .map(|(kind, family_index, queue_index)| {
+                    (kind, get_queue_slot(family_index, queue_index))
+                })
+                .collect(),
+        );
 
         Ok(Arc::new(Self {
             instance,
             physical_device,
             handle,
             vk_debug_utils_device,
+            vk_descriptor_buffer_device,
+            vk_pipeline_executable_properties_device,
             vk_queue_index,
+            queues,
+            allocator: UsamiAllocator::new(DEFAULT_MEMORY_BLOCK_SIZE),
+            enabled_extensions,
+            enabled_features,
         }))
     }
 
-    pub fn set_debug_name<T: vk::Handle>(&self, name: String, object_handle: T) -> VkResult<()> {
-        unsafe {
-            let name = CString::new(name).unwrap();
+    pub fn set_debug_name<T: vk::Handle>(&self, name: String, object_handle: T) -> UsamiResult<()> {
+        let name = CString::new(name)?;
 
+        unsafe {
             self.vk_debug_utils_device.set_debug_utils_object_name(
                 &DebugUtilsObjectNameInfoEXT::default()
                     .object_handle(object_handle)
                     .object_name(name.as_c_str()),
-            )
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Open a debug label region in a command buffer.
+    pub fn begin_debug_label(
+        &self,
+        command_buffer: &UsamiCommandBuffer,
+        label: String,
+    ) -> UsamiResult<()> {
+        let label = CString::new(label)?;
+
+        unsafe {
+            self.vk_debug_utils_device.cmd_begin_debug_utils_label(
+                command_buffer.handle,
+                &DebugUtilsLabelEXT::default().label_name(label.as_c_str()),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Close the last debug label region opened in a command buffer.
+    pub fn end_debug_label(&self, command_buffer: &UsamiCommandBuffer) {
+        unsafe {
+            self.vk_debug_utils_device
+                .cmd_end_debug_utils_label(command_buffer.handle);
         }
     }
 }
@@ -247,6 +701,7 @@ impl UsamiDevice {
 impl Drop for UsamiDevice {
     fn drop(&mut self) {
         unsafe {
+            self.allocator.destroy(&self.handle);
             self.handle.destroy_device(None);
         }
     }
@@ -255,11 +710,10 @@ impl Drop for UsamiDevice {
 pub struct UsamiPresentation {
     pub image: UsamiImage,
     pub image_view: UsamiImageView,
-    pub buffer_readback: UsamiBuffer,
 }
 
 impl UsamiPresentation {
-    pub fn new(device: &Arc<UsamiDevice>, width: u32, height: u32) -> VkResult<Self> {
+    pub fn new(device: &Arc<UsamiDevice>, width: u32, height: u32) -> UsamiResult<Self> {
         let format = Format::R8G8B8A8_UNORM;
         let presentation_image_info = ImageCreateInfo::default()
             .image_type(ImageType::TYPE_2D)
@@ -304,21 +758,7 @@ impl UsamiPresentation {
             ImageViewCreateFlags::empty(),
         )?;
 
-        let buffer_readback = UsamiDevice::create_buffer_with_size(
-            device,
-            "presentation_buffer_readback".into(),
-            BufferCreateFlags::empty(),
-            SharingMode::EXCLUSIVE,
-            BufferUsageFlags::TRANSFER_DST,
-            u64::from(width * height * utils::get_format_size(format)),
-            MemoryPropertyFlags::HOST_VISIBLE,
-        )?;
-
-        Ok(Self {
-            image,
-            image_view,
-            buffer_readback,
-        })
+        Ok(Self { image, image_view })
     }
 
     pub fn dimensions(&self) -> Extent2D {
@@ -345,12 +785,58 @@ impl UsamiPresentation {
         }
     }
 
+    /// Download the content of the presentation image, which is left in its tracked layout.
+    pub fn read_back(&self, command_pool: &UsamiCommandPool) -> UsamiResult<Vec<u8>> {
+        let mut readbacks = self.image.read_back(
+            command_pool,
+            self.image.whole_subresource_range(ImageAspectFlags::COLOR),
+            None,
+        )?;
+
+        Ok(readbacks.remove(0).data)
+    }
+
+    /// Describe the presentation image as a dynamic rendering color attachment.
+    pub fn color_attachment(
+        &self,
+        load_op: AttachmentLoadOp,
+        clear_color: [f32; 4],
+    ) -> RenderingAttachmentInfo<'static> {
+        self.image_view.rendering_attachment(
+            ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            load_op,
+            AttachmentStoreOp::STORE,
+            ClearValue {
+                color: ClearColorValue {
+                    float32: clear_color,
+                },
+            },
+        )
+    }
+
+    /// Transition the presentation image to a color attachment and begin dynamic rendering on it.
+    pub fn begin_rendering(
+        &self,
+        command_buffer: &UsamiCommandBuffer,
+        load_op: AttachmentLoadOp,
+        clear_color: [f32; 4],
+    ) -> UsamiResult<()> {
+        command_buffer.transition_image(&self.image, None, UsamiAccess::ColorAttachmentWrite)?;
+        command_buffer.begin_rendering(
+            self.rect2d(),
+            1,
+            &[self.color_attachment(load_op, clear_color)],
+            None,
+            None,
+        )
+    }
+
     pub fn create_framebuffer(
         &self,
         device: &Arc<UsamiDevice>,
         name: String,
         render_pass: &UsamiRenderPass,
-    ) -> VkResult<UsamiFramebuffer> {
+    ) -> UsamiResult<UsamiFramebuffer> {
         let dimensions = self.dimensions();
 
         UsamiDevice::create_framebuffer(
@@ -0,0 +1,201 @@
+use std::{ffi::NulError, fmt, io, path::PathBuf};
+
+use ash::{
+    vk::{self, DescriptorType, Format, MemoryPropertyFlags},
+    LoadingError,
+};
+use bytemuck::PodCastError;
+use image::ImageError;
+
+use crate::{bindless::UsamiBindlessKind, queue::UsamiQueueKind};
+
+pub type UsamiResult<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// A Vulkan entrypoint returned an error.
+    Vulkan(vk::Result),
+    /// The Vulkan loader could not be found or loaded.
+    LoaderUnavailable(LoadingError),
+    /// No physical device matched the given filter.
+    NoMatchingPhysicalDevice,
+    /// The physical device selector is malformed.
+    InvalidDeviceSelector(String),
+    /// No queue family can provide the requested queue.
+    NoSuitableQueue { kind: UsamiQueueKind, index: usize },
+    /// A required device extension is not supported by the physical device.
+    MissingExtension(String),
+    /// Some required device features are not supported by the physical device.
+    MissingFeatures(Vec<&'static str>),
+    /// No memory type matched the given requirements.
+    NoSuitableMemoryType {
+        memory_type_bits: u32,
+        flags: MemoryPropertyFlags,
+    },
+    /// The memory cannot be accessed from the host.
+    MemoryNotHostVisible,
+    /// Mapped memory cannot be seen as the requested type.
+    InvalidMappedCast(PodCastError),
+    /// The given format is not supported by usami.
+    UnsupportedFormat(Format),
+    /// A string given to Vulkan contained an interior nul byte.
+    InvalidString(NulError),
+    /// An I/O operation failed.
+    Io {
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+    /// An image could not be encoded or saved.
+    Image { path: PathBuf, source: ImageError },
+    /// A SPIR-V module could not be reflected.
+    Reflection(String),
+    /// A range or index is outside of the object it refers to.
+    OutOfBounds { start: u64, end: u64, limit: u64 },
+    /// The descriptor type cannot be stored in a descriptor buffer.
+    UnsupportedDescriptorType(DescriptorType),
+    /// A slot of a bindless table was released while it was not allocated.
+    UnallocatedBindlessSlot { kind: UsamiBindlessKind, index: u32 },
+    /// The number of color blend attachments of a pipeline differs from its number of color attachments.
+    ColorAttachmentCountMismatch {
+        blend_attachments: usize,
+        color_attachments: usize,
+    },
+    /// Another error with a description of what was being done when it happened (usually the debug name of the object being created).
+    Context { context: String, source: Box<Error> },
+}
+
+impl Error {
+    pub fn with_context<S: Into<String>>(self, context: S) -> Self {
+        Self::Context {
+            context: context.into(),
+            source: Box::new(self),
+        }
+    }
+
+    /// Return the underlying Vulkan result if this error originated from Vulkan.
+    pub fn vk_result(&self) -> Option<vk::Result> {
+        match self {
+            Self::Vulkan(result) => Some(*result),
+            Self::Context { source, .. } => source.vk_result(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Vulkan(result) => write!(f, "Vulkan error: {result}"),
+            Self::LoaderUnavailable(error) => write!(f, "Cannot load Vulkan library: {error}"),
+            Self::NoMatchingPhysicalDevice => {
+                write!(f, "Cannot find a physical device that match requirements")
+            }
+            Self::NoSuitableMemoryType {
+                memory_type_bits,
+                flags,
+            } => write!(
+                f,
+                "Cannot find a memory type matching bits {memory_type_bits:#x} with flags {flags:?}"
+            ),
+            Self::InvalidDeviceSelector(spec) => write!(f, "Invalid device selector \"{spec}\""),
+            Self::NoSuitableQueue { kind, index } => {
+                write!(f, "Cannot find a {kind:?} queue at index {index}")
+            }
+            Self::MissingExtension(name) => write!(f, "Missing device extension {name}"),
+            Self::MissingFeatures(names) => {
+                write!(f, "Missing device features {}", names.join(", "))
+            }
+            Self::MemoryNotHostVisible => write!(f, "Memory is not host visible"),
+            Self::InvalidMappedCast(error) => write!(f, "Invalid mapped memory cast: {error}"),
+            Self::UnsupportedFormat(format) => write!(f, "Unsupported format {format:?}"),
+            Self::InvalidString(error) => write!(f, "Invalid string: {error}"),
+            Self::Io {
+                path: Some(path),
+                source,
+            } => write!(f, "I/O error on {}: {source}", path.display()),
+            Self::Io { path: None, source } => write!(f, "I/O error: {source}"),
+            Self::Image { path, source } => {
+                write!(f, "Cannot save image {}: {source}", path.display())
+            }
+            Self::Reflection(error) => write!(f, "SPIR-V reflection error: {error}"),
+            Self::OutOfBounds { start, end, limit } => {
+                write!(
+                    f,
+                    "Range {start}..{end} is out of bounds (limit is {limit})"
+                )
+            }
+            Self::UnsupportedDescriptorType(descriptor_type) => {
+                write!(f, "Unsupported descriptor type {descriptor_type:?}")
+            }
+            Self::UnallocatedBindlessSlot { kind, index } => {
+                write!(f, "{kind:?} slot {index} is not allocated")
+            }
+            Self::ColorAttachmentCountMismatch {
+                blend_attachments,
+                color_attachments,
+            } => write!(
+                f,
+                "Pipeline has {blend_attachments} color blend attachments for {color_attachments} color attachments"
+            ),
+            Self::Context { context, source } => write!(f, "{context}: {source}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Vulkan(result) => Some(result),
+            Self::LoaderUnavailable(error) => Some(error),
+            Self::InvalidString(error) => Some(error),
+            Self::Io { source, .. } => Some(source),
+            Self::Image { source, .. } => Some(source),
+            Self::Context { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<vk::Result> for Error {
+    fn from(value: vk::Result) -> Self {
+        Self::Vulkan(value)
+    }
+}
+
+impl From<LoadingError> for Error {
+    fn from(value: LoadingError) -> Self {
+        Self::LoaderUnavailable(value)
+    }
+}
+
+impl From<PodCastError> for Error {
+    fn from(value: PodCastError) -> Self {
+        Self::InvalidMappedCast(value)
+    }
+}
+
+impl From<NulError> for Error {
+    fn from(value: NulError) -> Self {
+        Self::InvalidString(value)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(value: io::Error) -> Self {
+        Self::Io {
+            path: None,
+            source: value,
+        }
+    }
+}
+
+pub trait ResultExt<T> {
+    /// Attach a context (usually the debug name of the object being created) to an error.
+    fn context<S: Into<String>>(self, context: S) -> UsamiResult<T>;
+}
+
+impl<T, E: Into<Error>> ResultExt<T> for std::result::Result<T, E> {
+    fn context<S: Into<String>>(self, context: S) -> UsamiResult<T> {
+        self.map_err(|error| error.into().with_context(context))
+    }
+}
@@ -0,0 +1,383 @@
+use std::ffi::CStr;
+
+use ash::vk::{self, Bool32, PhysicalDeviceFeatures, PhysicalDeviceFeatures2};
+
+/// Condition under which a feature structure is part of the `pNext` chain.
+#[derive(Clone, Copy, Debug)]
+enum FeatureRequirement {
+    ApiVersion(u32),
+    Extension(&'static CStr),
+}
+
+impl FeatureRequirement {
+    fn is_satisfied(&self, api_version: u32, extensions: &[&CStr]) -> bool {
+        match self {
+            Self::ApiVersion(version) => api_version >= *version,
+            Self::Extension(name) => extensions.contains(name),
+        }
+    }
+}
+
+/// Define [UsamiDeviceFeatures] from a list of feature structures.
+///
+/// Supporting a new extension only requires adding an entry here.
+/// Extensions promoted to core name the core structure replacing them, they are not chained once it is.
+macro_rules! device_features {
+    (@promote [] $($rest:tt)*) => {
+        false
+    };
+    (@promote [$requirement:expr, $core:expr] $extension:expr, $api_version:expr, $extensions:expr; $($feature:ident),*) => {{
+        let is_promoted = $requirement.is_satisfied($api_version, $extensions);
+
+        if is_promoted {
+            $(
+                if $extension.$feature != vk::FALSE {
+                    $core.$feature = vk::TRUE;
+                }
+            )*
+        }
+
+        is_promoted
+    }};
+    (@demote [] $($rest:tt)*) => {};
+    (@demote [$requirement:expr, $core:expr] $extension:expr, $api_version:expr, $extensions:expr; $($feature:ident),*) => {
+        if $requirement.is_satisfied($api_version, $extensions) {
+            $($extension.$feature = $core.$feature;)*
+        }
+    };
+    (
+        core { $($core_feature:ident),* $(,)? }
+        $(
+            $field:ident: $ty:ident if $requirement:expr
+                $(, promoted to $promoted:ident if $promoted_requirement:expr)?
+                => { $($feature:ident),* $(,)? }
+        )*
+    ) => {
+        /// All the features known by usami, core and extensions.
+        #[derive(Clone, Copy, Debug, Default)]
+        pub struct UsamiDeviceFeatures {
+            pub core: PhysicalDeviceFeatures,
+            $(pub $field: vk::$ty<'static>,)*
+        }
+
+        impl UsamiDeviceFeatures {
+            /// Visit every feature of `self` along with the matching feature of `other`.
+            pub fn zip_mut<F: FnMut(&'static str, &mut Bool32, Bool32)>(
+                &mut self,
+                other: &Self,
+                mut callback: F,
+            ) {
+                $(
+                    callback(
+                        concat!("core.", stringify!($core_feature)),
+                        &mut self.core.$core_feature,
+                        other.core.$core_feature,
+                    );
+                )*
+                $($(
+                    callback(
+                        concat!(stringify!($field), ".", stringify!($feature)),
+                        &mut self.$field.$feature,
+                        other.$field.$feature,
+                    );
+                )*)*
+            }
+
+            /// Clear all the `pNext` pointers left over by a previous chain.
+            pub fn clear_chain(&mut self) {
+                $(self.$field.p_next = std::ptr::null_mut();)*
+            }
+
+            /// Build a [PhysicalDeviceFeatures2] chaining every structure relevant to the given API version and extensions.
+            ///
+            /// Features of promoted extensions are enabled in the core structure replacing them instead.
+            pub fn chain<'a>(
+                &'a mut self,
+                api_version: u32,
+                extensions: &[&CStr],
+            ) -> PhysicalDeviceFeatures2<'a> {
+                self.clear_chain();
+
+                $(
+                    let $field = $requirement.is_satisfied(api_version, extensions)
+                        && !device_features!(
+                            @promote [$($promoted_requirement, self.$promoted)?]
+                            self.$field, api_version, extensions; $($feature),*
+                        );
+                )*
+
+                let mut features2 = PhysicalDeviceFeatures2::default().features(self.core);
+
+                $(
+                    if $field {
+                        features2 = features2.push_next(&mut self.$field);
+                    }
+                )*
+
+                features2
+            }
+
+            /// Copy the features of core structures back to the promoted extensions they replaced in [Self::chain].
+            pub fn resolve_promoted(&mut self, api_version: u32, extensions: &[&CStr]) {
+                $(
+                    device_features!(
+                        @demote [$($promoted_requirement, self.$promoted)?]
+                        self.$field, api_version, extensions; $($feature),*
+                    );
+                )*
+            }
+        }
+    };
+}
+
+device_features! {
+    core {
+        robust_buffer_access,
+        full_draw_index_uint32,
+        image_cube_array,
+        independent_blend,
+        geometry_shader,
+        tessellation_shader,
+        sample_rate_shading,
+        dual_src_blend,
+        logic_op,
+        multi_draw_indirect,
+        draw_indirect_first_instance,
+        depth_clamp,
+        depth_bias_clamp,
+        fill_mode_non_solid,
+        depth_bounds,
+        wide_lines,
+        large_points,
+        alpha_to_one,
+        multi_viewport,
+        sampler_anisotropy,
+        texture_compression_etc2,
+        texture_compression_astc_ldr,
+        texture_compression_bc,
+        occlusion_query_precise,
+        pipeline_statistics_query,
+        vertex_pipeline_stores_and_atomics,
+        fragment_stores_and_atomics,
+        shader_tessellation_and_geometry_point_size,
+        shader_image_gather_extended,
+        shader_storage_image_extended_formats,
+        shader_storage_image_multisample,
+        shader_storage_image_read_without_format,
+        shader_storage_image_write_without_format,
+        shader_uniform_buffer_array_dynamic_indexing,
+        shader_sampled_image_array_dynamic_indexing,
+        shader_storage_buffer_array_dynamic_indexing,
+        shader_storage_image_array_dynamic_indexing,
+        shader_clip_distance,
+        shader_cull_distance,
+        shader_float64,
+        shader_int64,
+        shader_int16,
+        shader_resource_residency,
+        shader_resource_min_lod,
+        sparse_binding,
+        sparse_residency_buffer,
+        sparse_residency_image2_d,
+        sparse_residency_image3_d,
+        sparse_residency2_samples,
+        sparse_residency4_samples,
+        sparse_residency8_samples,
+        sparse_residency16_samples,
+        sparse_residency_aliased,
+        variable_multisample_rate,
+        inherited_queries,
+    }
+    vulkan11: PhysicalDeviceVulkan11Features if FeatureRequirement::ApiVersion(vk::API_VERSION_1_2) => {
+        storage_buffer16_bit_access,
+        uniform_and_storage_buffer16_bit_access,
+        storage_push_constant16,
+        storage_input_output16,
+        multiview,
+        multiview_geometry_shader,
+        multiview_tessellation_shader,
+        variable_pointers_storage_buffer,
+        variable_pointers,
+        protected_memory,
+        sampler_ycbcr_conversion,
+        shader_draw_parameters,
+    }
+    vulkan12: PhysicalDeviceVulkan12Features if FeatureRequirement::ApiVersion(vk::API_VERSION_1_2) => {
+        sampler_mirror_clamp_to_edge,
+        draw_indirect_count,
+        storage_buffer8_bit_access,
+        uniform_and_storage_buffer8_bit_access,
+        storage_push_constant8,
+        shader_buffer_int64_atomics,
+        shader_shared_int64_atomics,
+        shader_float16,
+        shader_int8,
+        descriptor_indexing,
+        shader_input_attachment_array_dynamic_indexing,
+        shader_uniform_texel_buffer_array_dynamic_indexing,
+        shader_storage_texel_buffer_array_dynamic_indexing,
+        shader_uniform_buffer_array_non_uniform_indexing,
+        shader_sampled_image_array_non_uniform_indexing,
+        shader_storage_buffer_array_non_uniform_indexing,
+        shader_storage_image_array_non_uniform_indexing,
+        shader_input_attachment_array_non_uniform_indexing,
+        shader_uniform_texel_buffer_array_non_uniform_indexing,
+        shader_storage_texel_buffer_array_non_uniform_indexing,
+        descriptor_binding_uniform_buffer_update_after_bind,
+        descriptor_binding_sampled_image_update_after_bind,
+        descriptor_binding_storage_image_update_after_bind,
+        descriptor_binding_storage_buffer_update_after_bind,
+        descriptor_binding_uniform_texel_buffer_update_after_bind,
+        descriptor_binding_storage_texel_buffer_update_after_bind,
+        descriptor_binding_update_unused_while_pending,
+        descriptor_binding_partially_bound,
+        descriptor_binding_variable_descriptor_count,
+        runtime_descriptor_array,
+        sampler_filter_minmax,
+        scalar_block_layout,
+        imageless_framebuffer,
+        uniform_buffer_standard_layout,
+        shader_subgroup_extended_types,
+        separate_depth_stencil_layouts,
+        host_query_reset,
+        timeline_semaphore,
+        buffer_device_address,
+        buffer_device_address_capture_replay,
+        buffer_device_address_multi_device,
+        vulkan_memory_model,
+        vulkan_memory_model_device_scope,
+        vulkan_memory_model_availability_visibility_chains,
+        shader_output_viewport_index,
+        shader_output_layer,
+        subgroup_broadcast_dynamic_id,
+    }
+    vulkan13: PhysicalDeviceVulkan13Features if FeatureRequirement::ApiVersion(vk::API_VERSION_1_3) => {
+        robust_image_access,
+        inline_uniform_block,
+        descriptor_binding_inline_uniform_block_update_after_bind,
+        pipeline_creation_cache_control,
+        private_data,
+        shader_demote_to_helper_invocation,
+        shader_terminate_invocation,
+        subgroup_size_control,
+        compute_full_subgroups,
+        synchronization2,
+        texture_compression_astc_hdr,
+        shader_zero_initialize_workgroup_memory,
+        dynamic_rendering,
+        shader_integer_dot_product,
+        maintenance4,
+    }
+    shader_object: PhysicalDeviceShaderObjectFeaturesEXT if FeatureRequirement::Extension(ash::ext::shader_object::NAME) => {
+        shader_object,
+    }
+    mesh_shader: PhysicalDeviceMeshShaderFeaturesEXT if FeatureRequirement::Extension(ash::ext::mesh_shader::NAME) => {
+        task_shader,
+        mesh_shader,
+        multiview_mesh_shader,
+        primitive_fragment_shading_rate_mesh_shader,
+        mesh_shader_queries,
+    }
+    conditional_rendering: PhysicalDeviceConditionalRenderingFeaturesEXT if FeatureRequirement::Extension(ash::ext::conditional_rendering::NAME) => {
+        conditional_rendering,
+        inherited_conditional_rendering,
+    }
+    transform_feedback: PhysicalDeviceTransformFeedbackFeaturesEXT if FeatureRequirement::Extension(ash::ext::transform_feedback::NAME) => {
+        transform_feedback,
+        geometry_streams,
+    }
+    cooperative_matrix: PhysicalDeviceCooperativeMatrixFeaturesKHR if FeatureRequirement::Extension(ash::khr::cooperative_matrix::NAME) => {
+        cooperative_matrix,
+        cooperative_matrix_robust_buffer_access,
+    }
+    cooperative_matrix_nv: PhysicalDeviceCooperativeMatrixFeaturesNV if FeatureRequirement::Extension(ash::nv::cooperative_matrix::NAME) => {
+        cooperative_matrix,
+        cooperative_matrix_robust_buffer_access,
+    }
+    image_robustness: PhysicalDeviceImageRobustnessFeatures if FeatureRequirement::Extension(ash::ext::image_robustness::NAME),
+        promoted to vulkan13 if FeatureRequirement::ApiVersion(vk::API_VERSION_1_3) => {
+        robust_image_access,
+    }
+    descriptor_buffer: PhysicalDeviceDescriptorBufferFeaturesEXT if FeatureRequirement::Extension(ash::ext::descriptor_buffer::NAME) => {
+        descriptor_buffer,
+        descriptor_buffer_capture_replay,
+        descriptor_buffer_image_layout_ignored,
+        descriptor_buffer_push_descriptors,
+    }
+    pipeline_executable_properties: PhysicalDevicePipelineExecutablePropertiesFeaturesKHR if FeatureRequirement::Extension(ash::khr::pipeline_executable_properties::NAME) => {
+        pipeline_executable_info,
+    }
+}
+
+impl UsamiDeviceFeatures {
+    /// Every feature known by usami enabled.
+    pub fn all() -> Self {
+        let mut result = Self::default();
+
+        result.zip_mut(&Self::default(), |_, value, _| *value = vk::TRUE);
+
+        result
+    }
+
+    /// Features enabled when supported by [crate::UsamiDevice::new_by_filter] and [crate::UsamiDevice::new_by_selector].
+    pub fn usami_defaults() -> Self {
+        let all_features = Self::all();
+        let mut features = Self::default();
+
+        features.core.geometry_shader = vk::TRUE;
+        features.core.shader_tessellation_and_geometry_point_size = vk::TRUE;
+
+        features.vulkan11 = all_features.vulkan11;
+        features.vulkan12 = all_features.vulkan12;
+        features.vulkan13 = all_features.vulkan13;
+
+        features.shader_object.shader_object = vk::TRUE;
+        features.mesh_shader.mesh_shader = vk::TRUE;
+        features.mesh_shader.task_shader = vk::TRUE;
+        features.conditional_rendering.conditional_rendering = vk::TRUE;
+        features.transform_feedback.transform_feedback = vk::TRUE;
+        features.transform_feedback.geometry_streams = vk::TRUE;
+        features.cooperative_matrix.cooperative_matrix = vk::TRUE;
+        features.cooperative_matrix_nv.cooperative_matrix = vk::TRUE;
+        features.image_robustness.robust_image_access = vk::TRUE;
+        features.descriptor_buffer.descriptor_buffer = vk::TRUE;
+        features
+            .pipeline_executable_properties
+            .pipeline_executable_info = vk::TRUE;
+
+        features
+    }
+
+    /// Keep only the features that are also enabled in `other`.
+    pub fn intersect(&mut self, other: &Self) {
+        self.zip_mut(other, |_, value, other_value| {
+            if other_value == vk::FALSE {
+                *value = vk::FALSE;
+            }
+        });
+    }
+
+    /// Enable all the features enabled in `other`.
+    pub fn union(&mut self, other: &Self) {
+        self.zip_mut(other, |_, value, other_value| {
+            if other_value != vk::FALSE {
+                *value = vk::TRUE;
+            }
+        });
+    }
+
+    /// Return the names of the features enabled in `self` but not in `supported`.
+    pub fn missing_from(&self, supported: &Self) -> Vec<&'static str> {
+        let mut result = Vec::new();
+
+        let mut features = *self;
+
+        features.zip_mut(supported, |name, value, supported_value| {
+            if *value != vk::FALSE && supported_value == vk::FALSE {
+                result.push(name);
+            }
+        });
+
+        result
+    }
+}
@@ -1,11 +1,8 @@
 use std::sync::Arc;
 
-use ash::{
-    prelude::*,
-    vk::{Fence, FenceCreateFlags, FenceCreateInfo},
-};
+use ash::vk::{Fence, FenceCreateFlags, FenceCreateInfo};
 
-use crate::UsamiDevice;
+use crate::{ResultExt, UsamiDevice, UsamiResult};
 
 pub struct UsamiFence {
     device: Arc<UsamiDevice>,
@@ -13,7 +10,7 @@ pub struct UsamiFence {
 }
 
 impl UsamiFence {
-    pub fn new(device: &Arc<UsamiDevice>, flags: FenceCreateFlags) -> VkResult<Self> {
+    pub fn new(device: &Arc<UsamiDevice>, flags: FenceCreateFlags) -> UsamiResult<Self> {
         let create_info = FenceCreateInfo::default().flags(flags);
 
         let handle = unsafe { device.handle.create_fence(&create_info, None)? };
@@ -24,16 +21,20 @@ impl UsamiFence {
         })
     }
 
-    pub fn wait(&self, timeout: u64) -> VkResult<()> {
+    pub fn wait(&self, timeout: u64) -> UsamiResult<()> {
         unsafe {
             self.device
                 .handle
-                .wait_for_fences(&[self.handle], true, timeout)
+                .wait_for_fences(&[self.handle], true, timeout)?;
         }
+
+        Ok(())
     }
 
-    pub fn reset(&self) -> VkResult<()> {
-        unsafe { self.device.handle.reset_fences(&[self.handle]) }
+    pub fn reset(&self) -> UsamiResult<()> {
+        unsafe { self.device.handle.reset_fences(&[self.handle])? };
+
+        Ok(())
     }
 }
 
@@ -48,8 +49,8 @@ impl UsamiDevice {
         device: &Arc<UsamiDevice>,
         name: String,
         flags: FenceCreateFlags,
-    ) -> VkResult<UsamiFence> {
-        let pipeline_layout = UsamiFence::new(device, flags)?;
+    ) -> UsamiResult<UsamiFence> {
+        let pipeline_layout = UsamiFence::new(device, flags).context(&name)?;
 
         device.set_debug_name(name, pipeline_layout.handle)?;
 
@@ -0,0 +1,560 @@
+use ash::vk::{Extent3D, Format, ImageAspectFlags};
+
+use crate::{Error, UsamiResult};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UsamiNumericFormat {
+    UNorm,
+    SNorm,
+    UScaled,
+    SScaled,
+    UInt,
+    SInt,
+    UFloat,
+    SFloat,
+    Srgb,
+}
+
+impl UsamiNumericFormat {
+    pub fn is_float(self) -> bool {
+        matches!(self, Self::UFloat | Self::SFloat)
+    }
+
+    pub fn is_integer(self) -> bool {
+        matches!(self, Self::UInt | Self::SInt)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UsamiComponentKind {
+    R,
+    G,
+    B,
+    A,
+    /// Depth.
+    D,
+    /// Stencil.
+    S,
+    /// Shared exponent.
+    E,
+    /// Unused bits.
+    X,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct UsamiFormatComponent {
+    pub kind: UsamiComponentKind,
+    /// Size in bits, 0 for compressed formats.
+    pub bits: u32,
+    pub numeric_format: UsamiNumericFormat,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct UsamiFormatPlane {
+    pub block_size: u32,
+    pub width_divisor: u32,
+    pub height_divisor: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct UsamiFormatInfo {
+    pub format: Format,
+    /// Size in bytes of a texel block, 0 for multi-planar formats.
+    pub block_size: u32,
+    pub block_extent: Extent3D,
+    pub aspect_mask: ImageAspectFlags,
+    pub compressed: bool,
+    /// Size in bytes of the words components are packed in for `*_PACK*` formats, 0 when
+    /// every component is stored in its own bytes.
+    pub pack_size: u32,
+    /// Components in the order of the format name.
+    pub components: &'static [UsamiFormatComponent],
+    /// Planes of multi-planar formats, empty otherwise.
+    pub planes: &'static [UsamiFormatPlane],
+}
+
+impl UsamiFormatInfo {
+    /// Numeric format of the first component that is not padding.
+    pub fn numeric_format(&self) -> UsamiNumericFormat {
+        self.components
+            .iter()
+            .find(|x| x.kind != UsamiComponentKind::X)
+            .map(|x| x.numeric_format)
+            .unwrap_or(UsamiNumericFormat::UNorm)
+    }
+
+    pub fn has_component(&self, kind: UsamiComponentKind) -> bool {
+        self.components.iter().any(|x| x.kind == kind)
+    }
+
+    pub fn is_depth_stencil(&self) -> bool {
+        self.aspect_mask
+            .intersects(ImageAspectFlags::DEPTH | ImageAspectFlags::STENCIL)
+    }
+
+    /// Size in bytes of a texel block of the given aspect once copied to a buffer.
+    pub fn aspect_block_size(&self, aspect_mask: ImageAspectFlags) -> u32 {
+        if aspect_mask == ImageAspectFlags::STENCIL {
+            1
+        } else if aspect_mask == ImageAspectFlags::DEPTH {
+            match self.format {
+                Format::D16_UNORM | Format::D16_UNORM_S8_UINT => 2,
+                _ => 4,
+            }
+        } else {
+            self.block_size
+        }
+    }
+
+    /// Whether a texel block covers more than one texel (compressed and `*_422` formats).
+    pub fn has_multi_texel_blocks(&self) -> bool {
+        self.block_extent.width * self.block_extent.height * self.block_extent.depth > 1
+    }
+
+    /// Number of texel blocks needed to cover the given extent.
+    pub fn block_count(&self, extent: Extent3D) -> u64 {
+        u64::from(extent.width.div_ceil(self.block_extent.width))
+            * u64::from(extent.height.div_ceil(self.block_extent.height))
+            * u64::from(extent.depth.div_ceil(self.block_extent.depth))
+    }
+
+    /// Size in bytes of tightly packed data of the given aspect covering the extent.
+    pub fn size_for_extent(&self, extent: Extent3D, aspect_mask: ImageAspectFlags) -> u64 {
+        self.block_count(extent) * u64::from(self.aspect_block_size(aspect_mask))
+    }
+}
+
+mod aspects {
+    use ash::vk::ImageAspectFlags;
+
+    pub const COLOR: ImageAspectFlags = ImageAspectFlags::COLOR;
+    pub const DEPTH: ImageAspectFlags = ImageAspectFlags::DEPTH;
+    pub const STENCIL: ImageAspectFlags = ImageAspectFlags::STENCIL;
+    pub const DEPTH_STENCIL: ImageAspectFlags = ImageAspectFlags::from_raw(
+        ImageAspectFlags::DEPTH.as_raw() | ImageAspectFlags::STENCIL.as_raw(),
+    );
+}
+
+macro_rules! compressed {
+    (compressed) => {
+        true
+    };
+    (uncompressed) => {
+        false
+    };
+}
+
+macro_rules! format_table {
+    ($(
+        $format:ident => $block_size:literal, [$width:literal, $height:literal, $depth:literal], $aspect:ident, $compressed:ident, $pack_size:literal,
+        [$($kind:ident $bits:literal $numeric_format:ident),*]
+        $(planes [$(($plane_block_size:literal, $width_divisor:literal, $height_divisor:literal)),*])?;
+    )*) => {
+        /// Return the description of a core format, [None] for [Format::UNDEFINED] and extension formats.
+        pub fn format_info(format: Format) -> Option<&'static UsamiFormatInfo> {
+            match format {
+                $(
+                    Format::$format => {
+                        const INFO: UsamiFormatInfo = UsamiFormatInfo {
+                            format: Format::$format,
+                            block_size: $block_size,
+                            block_extent: Extent3D {
+                                width: $width,
+                                height: $height,
+                                depth: $depth,
+                            },
+                            aspect_mask: aspects::$aspect,
+                            compressed: compressed!($compressed),
+                            pack_size: $pack_size,
+                            components: &[$(UsamiFormatComponent {
+                                kind: UsamiComponentKind::$kind,
+                                bits: $bits,
+                                numeric_format: UsamiNumericFormat::$numeric_format,
+                            }),*],
+                            planes: &[$($(UsamiFormatPlane {
+                                block_size: $plane_block_size,
+                                width_divisor: $width_divisor,
+                                height_divisor: $height_divisor,
+                            }),*)?],
+                        };
+
+                        Some(&INFO)
+                    }
+                )*
+                _ => None,
+            }
+        }
+    };
+}
+
+pub fn get_format_info(format: Format) -> UsamiResult<&'static UsamiFormatInfo> {
+    format_info(format).ok_or(Error::UnsupportedFormat(format))
+}
+
+#[rustfmt::skip]
+format_table! {
+    R4G4_UNORM_PACK8 => 1, [1, 1, 1], COLOR, uncompressed, 1, [R 4 UNorm, G 4 UNorm];
+    R4G4B4A4_UNORM_PACK16 => 2, [1, 1, 1], COLOR, uncompressed, 2, [R 4 UNorm, G 4 UNorm, B 4 UNorm, A 4 UNorm];
+    B4G4R4A4_UNORM_PACK16 => 2, [1, 1, 1], COLOR, uncompressed, 2, [B 4 UNorm, G 4 UNorm, R 4 UNorm, A 4 UNorm];
+    R5G6B5_UNORM_PACK16 => 2, [1, 1, 1], COLOR, uncompressed, 2, [R 5 UNorm, G 6 UNorm, B 5 UNorm];
+    B5G6R5_UNORM_PACK16 => 2, [1, 1, 1], COLOR, uncompressed, 2, [B 5 UNorm, G 6 UNorm, R 5 UNorm];
+    R5G5B5A1_UNORM_PACK16 => 2, [1, 1, 1], COLOR, uncompressed, 2, [R 5 UNorm, G 5 UNorm, B 5 UNorm, A 1 UNorm];
+    B5G5R5A1_UNORM_PACK16 => 2, [1, 1, 1], COLOR, uncompressed, 2, [B 5 UNorm, G 5 UNorm, R 5 UNorm, A 1 UNorm];
+    A1R5G5B5_UNORM_PACK16 => 2, [1, 1, 1], COLOR, uncompressed, 2, [A 1 UNorm, R 5 UNorm, G 5 UNorm, B 5 UNorm];
+    R8_UNORM => 1, [1, 1, 1], COLOR, uncompressed, 0, [R 8 UNorm];
+    R8_SNORM => 1, [1, 1, 1], COLOR, uncompressed, 0, [R 8 SNorm];
+    R8_USCALED => 1, [1, 1, 1], COLOR, uncompressed, 0, [R 8 UScaled];
+    R8_SSCALED => 1, [1, 1, 1], COLOR, uncompressed, 0, [R 8 SScaled];
+    R8_UINT => 1, [1, 1, 1], COLOR, uncompressed, 0, [R 8 UInt];
+    R8_SINT => 1, [1, 1, 1], COLOR, uncompressed, 0, [R 8 SInt];
+    R8_SRGB => 1, [1, 1, 1], COLOR, uncompressed, 0, [R 8 Srgb];
+    R8G8_UNORM => 2, [1, 1, 1], COLOR, uncompressed, 0, [R 8 UNorm, G 8 UNorm];
+    R8G8_SNORM => 2, [1, 1, 1], COLOR, uncompressed, 0, [R 8 SNorm, G 8 SNorm];
+    R8G8_USCALED => 2, [1, 1, 1], COLOR, uncompressed, 0, [R 8 UScaled, G 8 UScaled];
+    R8G8_SSCALED => 2, [1, 1, 1], COLOR, uncompressed, 0, [R 8 SScaled, G 8 SScaled];
+    R8G8_UINT => 2, [1, 1, 1], COLOR, uncompressed, 0, [R 8 UInt, G 8 UInt];
+    R8G8_SINT => 2, [1, 1, 1], COLOR, uncompressed, 0, [R 8 SInt, G 8 SInt];
+    R8G8_SRGB => 2, [1, 1, 1], COLOR, uncompressed, 0, [R 8 Srgb, G 8 Srgb];
+    R8G8B8_UNORM => 3, [1, 1, 1], COLOR, uncompressed, 0, [R 8 UNorm, G 8 UNorm, B 8 UNorm];
+    R8G8B8_SNORM => 3, [1, 1, 1], COLOR, uncompressed, 0, [R 8 SNorm, G 8 SNorm, B 8 SNorm];
+    R8G8B8_USCALED => 3, [1, 1, 1], COLOR, uncompressed, 0, [R 8 UScaled, G 8 UScaled, B 8 UScaled];
+    R8G8B8_SSCALED => 3, [1, 1, 1], COLOR, uncompressed, 0, [R 8 SScaled, G 8 SScaled, B 8 SScaled];
+    R8G8B8_UINT => 3, [1, 1, 1], COLOR, uncompressed, 0, [R 8 UInt, G 8 UInt, B 8 UInt];
+    R8G8B8_SINT => 3, [1, 1, 1], COLOR, uncompressed, 0, [R 8 SInt, G 8 SInt, B 8 SInt];
+    R8G8B8_SRGB => 3, [1, 1, 1], COLOR, uncompressed, 0, [R 8 Srgb, G 8 Srgb, B 8 Srgb];
+    B8G8R8_UNORM => 3, [1, 1, 1], COLOR, uncompressed, 0, [B 8 UNorm, G 8 UNorm, R 8 UNorm];
+    B8G8R8_SNORM => 3, [1, 1, 1], COLOR, uncompressed, 0, [B 8 SNorm, G 8 SNorm, R 8 SNorm];
+    B8G8R8_USCALED => 3, [1, 1, 1], COLOR, uncompressed, 0, [B 8 UScaled, G 8 UScaled, R 8 UScaled];
+    B8G8R8_SSCALED => 3, [1, 1, 1], COLOR, uncompressed, 0, [B 8 SScaled, G 8 SScaled, R 8 SScaled];
+    B8G8R8_UINT => 3, [1, 1, 1], COLOR, uncompressed, 0, [B 8 UInt, G 8 UInt, R 8 UInt];
+    B8G8R8_SINT => 3, [1, 1, 1], COLOR, uncompressed, 0, [B 8 SInt, G 8 SInt, R 8 SInt];
+    B8G8R8_SRGB => 3, [1, 1, 1], COLOR, uncompressed, 0, [B 8 Srgb, G 8 Srgb, R 8 Srgb];
+    R8G8B8A8_UNORM => 4, [1, 1, 1], COLOR, uncompressed, 0, [R 8 UNorm, G 8 UNorm, B 8 UNorm, A 8 UNorm];
+    R8G8B8A8_SNORM => 4, [1, 1, 1], COLOR, uncompressed, 0, [R 8 SNorm, G 8 SNorm, B 8 SNorm, A 8 SNorm];
+    R8G8B8A8_USCALED => 4, [1, 1, 1], COLOR, uncompressed, 0, [R 8 UScaled, G 8 UScaled, B 8 UScaled, A 8 UScaled];
+    R8G8B8A8_SSCALED => 4, [1, 1, 1], COLOR, uncompressed, 0, [R 8 SScaled, G 8 SScaled, B 8 SScaled, A 8 SScaled];
+    R8G8B8A8_UINT => 4, [1, 1, 1], COLOR, uncompressed, 0, [R 8 UInt, G 8 UInt, B 8 UInt, A 8 UInt];
+    R8G8B8A8_SINT => 4, [1, 1, 1], COLOR, uncompressed, 0, [R 8 SInt, G 8 SInt, B 8 SInt, A 8 SInt];
+    R8G8B8A8_SRGB => 4, [1, 1, 1], COLOR, uncompressed, 0, [R 8 Srgb, G 8 Srgb, B 8 Srgb, A 8 UNorm];
+    B8G8R8A8_UNORM => 4, [1, 1, 1], COLOR, uncompressed, 0, [B 8 UNorm, G 8 UNorm, R 8 UNorm, A 8 UNorm];
+    B8G8R8A8_SNORM => 4, [1, 1, 1], COLOR, uncompressed, 0, [B 8 SNorm, G 8 SNorm, R 8 SNorm, A 8 SNorm];
+    B8G8R8A8_USCALED => 4, [1, 1, 1], COLOR, uncompressed, 0, [B 8 UScaled, G 8 UScaled, R 8 UScaled, A 8 UScaled];
+    B8G8R8A8_SSCALED => 4, [1, 1, 1], COLOR, uncompressed, 0, [B 8 SScaled, G 8 SScaled, R 8 SScaled, A 8 SScaled];
+    B8G8R8A8_UINT => 4, [1, 1, 1], COLOR, uncompressed, 0, [B 8 UInt, G 8 UInt, R 8 UInt, A 8 UInt];
+    B8G8R8A8_SINT => 4, [1, 1, 1], COLOR, uncompressed, 0, [B 8 SInt, G 8 SInt, R 8 SInt, A 8 SInt];
+    B8G8R8A8_SRGB => 4, [1, 1, 1], COLOR, uncompressed, 0, [B 8 Srgb, G 8 Srgb, R 8 Srgb, A 8 UNorm];
+    A8B8G8R8_UNORM_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [A 8 UNorm, B 8 UNorm, G 8 UNorm, R 8 UNorm];
+    A8B8G8R8_SNORM_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [A 8 SNorm, B 8 SNorm, G 8 SNorm, R 8 SNorm];
+    A8B8G8R8_USCALED_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [A 8 UScaled, B 8 UScaled, G 8 UScaled, R 8 UScaled];
+    A8B8G8R8_SSCALED_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [A 8 SScaled, B 8 SScaled, G 8 SScaled, R 8 SScaled];
+    A8B8G8R8_UINT_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [A 8 UInt, B 8 UInt, G 8 UInt, R 8 UInt];
+    A8B8G8R8_SINT_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [A 8 SInt, B 8 SInt, G 8 SInt, R 8 SInt];
+    A8B8G8R8_SRGB_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [A 8 UNorm, B 8 Srgb, G 8 Srgb, R 8 Srgb];
+    A2R10G10B10_UNORM_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [A 2 UNorm, R 10 UNorm, G 10 UNorm, B 10 UNorm];
+    A2R10G10B10_SNORM_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [A 2 SNorm, R 10 SNorm, G 10 SNorm, B 10 SNorm];
+    A2R10G10B10_USCALED_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [A 2 UScaled, R 10 UScaled, G 10 UScaled, B 10 UScaled];
+    A2R10G10B10_SSCALED_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [A 2 SScaled, R 10 SScaled, G 10 SScaled, B 10 SScaled];
+    A2R10G10B10_UINT_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [A 2 UInt, R 10 UInt, G 10 UInt, B 10 UInt];
+    A2R10G10B10_SINT_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [A 2 SInt, R 10 SInt, G 10 SInt, B 10 SInt];
+    A2B10G10R10_UNORM_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [A 2 UNorm, B 10 UNorm, G 10 UNorm, R 10 UNorm];
+    A2B10G10R10_SNORM_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [A 2 SNorm, B 10 SNorm, G 10 SNorm, R 10 SNorm];
+    A2B10G10R10_USCALED_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [A 2 UScaled, B 10 UScaled, G 10 UScaled, R 10 UScaled];
+    A2B10G10R10_SSCALED_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [A 2 SScaled, B 10 SScaled, G 10 SScaled, R 10 SScaled];
+    A2B10G10R10_UINT_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [A 2 UInt, B 10 UInt, G 10 UInt, R 10 UInt];
+    A2B10G10R10_SINT_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [A 2 SInt, B 10 SInt, G 10 SInt, R 10 SInt];
+    R16_UNORM => 2, [1, 1, 1], COLOR, uncompressed, 0, [R 16 UNorm];
+    R16_SNORM => 2, [1, 1, 1], COLOR, uncompressed, 0, [R 16 SNorm];
+    R16_USCALED => 2, [1, 1, 1], COLOR, uncompressed, 0, [R 16 UScaled];
+    R16_SSCALED => 2, [1, 1, 1], COLOR, uncompressed, 0, [R 16 SScaled];
+    R16_UINT => 2, [1, 1, 1], COLOR, uncompressed, 0, [R 16 UInt];
+    R16_SINT => 2, [1, 1, 1], COLOR, uncompressed, 0, [R 16 SInt];
+    R16_SFLOAT => 2, [1, 1, 1], COLOR, uncompressed, 0, [R 16 SFloat];
+    R16G16_UNORM => 4, [1, 1, 1], COLOR, uncompressed, 0, [R 16 UNorm, G 16 UNorm];
+    R16G16_SNORM => 4, [1, 1, 1], COLOR, uncompressed, 0, [R 16 SNorm, G 16 SNorm];
+    R16G16_USCALED => 4, [1, 1, 1], COLOR, uncompressed, 0, [R 16 UScaled, G 16 UScaled];
+    R16G16_SSCALED => 4, [1, 1, 1], COLOR, uncompressed, 0, [R 16 SScaled, G 16 SScaled];
+    R16G16_UINT => 4, [1, 1, 1], COLOR, uncompressed, 0, [R 16 UInt, G 16 UInt];
+    R16G16_SINT => 4, [1, 1, 1], COLOR, uncompressed, 0, [R 16 SInt, G 16 SInt];
+    R16G16_SFLOAT => 4, [1, 1, 1], COLOR, uncompressed, 0, [R 16 SFloat, G 16 SFloat];
+    R16G16B16_UNORM => 6, [1, 1, 1], COLOR, uncompressed, 0, [R 16 UNorm, G 16 UNorm, B 16 UNorm];
+    R16G16B16_SNORM => 6, [1, 1, 1], COLOR, uncompressed, 0, [R 16 SNorm, G 16 SNorm, B 16 SNorm];
+    R16G16B16_USCALED => 6, [1, 1, 1], COLOR, uncompressed, 0, [R 16 UScaled, G 16 UScaled, B 16 UScaled];
+    R16G16B16_SSCALED => 6, [1, 1, 1], COLOR, uncompressed, 0, [R 16 SScaled, G 16 SScaled, B 16 SScaled];
+    R16G16B16_UINT => 6, [1, 1, 1], COLOR, uncompressed, 0, [R 16 UInt, G 16 UInt, B 16 UInt];
+    R16G16B16_SINT => 6, [1, 1, 1], COLOR, uncompressed, 0, [R 16 SInt, G 16 SInt, B 16 SInt];
+    R16G16B16_SFLOAT => 6, [1, 1, 1], COLOR, uncompressed, 0, [R 16 SFloat, G 16 SFloat, B 16 SFloat];
+    R16G16B16A16_UNORM => 8, [1, 1, 1], COLOR, uncompressed, 0, [R 16 UNorm, G 16 UNorm, B 16 UNorm, A 16 UNorm];
+    R16G16B16A16_SNORM => 8, [1, 1, 1], COLOR, uncompressed, 0, [R 16 SNorm, G 16 SNorm, B 16 SNorm, A 16 SNorm];
+    R16G16B16A16_USCALED => 8, [1, 1, 1], COLOR, uncompressed, 0, [R 16 UScaled, G 16 UScaled, B 16 UScaled, A 16 UScaled];
+    R16G16B16A16_SSCALED => 8, [1, 1, 1], COLOR, uncompressed, 0, [R 16 SScaled, G 16 SScaled, B 16 SScaled, A 16 SScaled];
+    R16G16B16A16_UINT => 8, [1, 1, 1], COLOR, uncompressed, 0, [R 16 UInt, G 16 UInt, B 16 UInt, A 16 UInt];
+    R16G16B16A16_SINT => 8, [1, 1, 1], COLOR, uncompressed, 0, [R 16 SInt, G 16 SInt, B 16 SInt, A 16 SInt];
+    R16G16B16A16_SFLOAT => 8, [1, 1, 1], COLOR, uncompressed, 0, [R 16 SFloat, G 16 SFloat, B 16 SFloat, A 16 SFloat];
+    R32_UINT => 4, [1, 1, 1], COLOR, uncompressed, 0, [R 32 UInt];
+    R32_SINT => 4, [1, 1, 1], COLOR, uncompressed, 0, [R 32 SInt];
+    R32_SFLOAT => 4, [1, 1, 1], COLOR, uncompressed, 0, [R 32 SFloat];
+    R32G32_UINT => 8, [1, 1, 1], COLOR, uncompressed, 0, [R 32 UInt, G 32 UInt];
+    R32G32_SINT => 8, [1, 1, 1], COLOR, uncompressed, 0, [R 32 SInt, G 32 SInt];
+    R32G32_SFLOAT => 8, [1, 1, 1], COLOR, uncompressed, 0, [R 32 SFloat, G 32 SFloat];
+    R32G32B32_UINT => 12, [1, 1, 1], COLOR, uncompressed, 0, [R 32 UInt, G 32 UInt, B 32 UInt];
+    R32G32B32_SINT => 12, [1, 1, 1], COLOR, uncompressed, 0, [R 32 SInt, G 32 SInt, B 32 SInt];
+    R32G32B32_SFLOAT => 12, [1, 1, 1], COLOR, uncompressed, 0, [R 32 SFloat, G 32 SFloat, B 32 SFloat];
+    R32G32B32A32_UINT => 16, [1, 1, 1], COLOR, uncompressed, 0, [R 32 UInt, G 32 UInt, B 32 UInt, A 32 UInt];
+    R32G32B32A32_SINT => 16, [1, 1, 1], COLOR, uncompressed, 0, [R 32 SInt, G 32 SInt, B 32 SInt, A 32 SInt];
+    R32G32B32A32_SFLOAT => 16, [1, 1, 1], COLOR, uncompressed, 0, [R 32 SFloat, G 32 SFloat, B 32 SFloat, A 32 SFloat];
+    R64_UINT => 8, [1, 1, 1], COLOR, uncompressed, 0, [R 64 UInt];
+    R64_SINT => 8, [1, 1, 1], COLOR, uncompressed, 0, [R 64 SInt];
+    R64_SFLOAT => 8, [1, 1, 1], COLOR, uncompressed, 0, [R 64 SFloat];
+    R64G64_UINT => 16, [1, 1, 1], COLOR, uncompressed, 0, [R 64 UInt, G 64 UInt];
+    R64G64_SINT => 16, [1, 1, 1], COLOR, uncompressed, 0, [R 64 SInt, G 64 SInt];
+    R64G64_SFLOAT => 16, [1, 1, 1], COLOR, uncompressed, 0, [R 64 SFloat, G 64 SFloat];
+    R64G64B64_UINT => 24, [1, 1, 1], COLOR, uncompressed, 0, [R 64 UInt, G 64 UInt, B 64 UInt];
+    R64G64B64_SINT => 24, [1, 1, 1], COLOR, uncompressed, 0, [R 64 SInt, G 64 SInt, B 64 SInt];
+    R64G64B64_SFLOAT => 24, [1, 1, 1], COLOR, uncompressed, 0, [R 64 SFloat, G 64 SFloat, B 64 SFloat];
+    R64G64B64A64_UINT => 32, [1, 1, 1], COLOR, uncompressed, 0, [R 64 UInt, G 64 UInt, B 64 UInt, A 64 UInt];
+    R64G64B64A64_SINT => 32, [1, 1, 1], COLOR, uncompressed, 0, [R 64 SInt, G 64 SInt, B 64 SInt, A 64 SInt];
+    R64G64B64A64_SFLOAT => 32, [1, 1, 1], COLOR, uncompressed, 0, [R 64 SFloat, G 64 SFloat, B 64 SFloat, A 64 SFloat];
+    B10G11R11_UFLOAT_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [B 10 UFloat, G 11 UFloat, R 11 UFloat];
+    E5B9G9R9_UFLOAT_PACK32 => 4, [1, 1, 1], COLOR, uncompressed, 4, [E 5 UFloat, B 9 UFloat, G 9 UFloat, R 9 UFloat];
+    D16_UNORM => 2, [1, 1, 1], DEPTH, uncompressed, 0, [D 16 UNorm];
+    X8_D24_UNORM_PACK32 => 4, [1, 1, 1], DEPTH, uncompressed, 4, [X 8 UNorm, D 24 UNorm];
+    D32_SFLOAT => 4, [1, 1, 1], DEPTH, uncompressed, 0, [D 32 SFloat];
+    S8_UINT => 1, [1, 1, 1], STENCIL, uncompressed, 0, [S 8 UInt];
+    D16_UNORM_S8_UINT => 3, [1, 1, 1], DEPTH_STENCIL, uncompressed, 0, [D 16 UNorm, S 8 UInt];
+    D24_UNORM_S8_UINT => 4, [1, 1, 1], DEPTH_STENCIL, uncompressed, 0, [D 24 UNorm, S 8 UInt];
+    D32_SFLOAT_S8_UINT => 5, [1, 1, 1], DEPTH_STENCIL, uncompressed, 0, [D 32 SFloat, S 8 UInt];
+    BC1_RGB_UNORM_BLOCK => 8, [4, 4, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm];
+    BC1_RGB_SRGB_BLOCK => 8, [4, 4, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb];
+    BC1_RGBA_UNORM_BLOCK => 8, [4, 4, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    BC1_RGBA_SRGB_BLOCK => 8, [4, 4, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    BC2_UNORM_BLOCK => 16, [4, 4, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    BC2_SRGB_BLOCK => 16, [4, 4, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    BC3_UNORM_BLOCK => 16, [4, 4, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    BC3_SRGB_BLOCK => 16, [4, 4, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    BC4_UNORM_BLOCK => 8, [4, 4, 1], COLOR, compressed, 0, [R 0 UNorm];
+    BC4_SNORM_BLOCK => 8, [4, 4, 1], COLOR, compressed, 0, [R 0 SNorm];
+    BC5_UNORM_BLOCK => 16, [4, 4, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm];
+    BC5_SNORM_BLOCK => 16, [4, 4, 1], COLOR, compressed, 0, [R 0 SNorm, G 0 SNorm];
+    BC6H_UFLOAT_BLOCK => 16, [4, 4, 1], COLOR, compressed, 0, [R 0 UFloat, G 0 UFloat, B 0 UFloat];
+    BC6H_SFLOAT_BLOCK => 16, [4, 4, 1], COLOR, compressed, 0, [R 0 SFloat, G 0 SFloat, B 0 SFloat];
+    BC7_UNORM_BLOCK => 16, [4, 4, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    BC7_SRGB_BLOCK => 16, [4, 4, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    ETC2_R8G8B8_UNORM_BLOCK => 8, [4, 4, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm];
+    ETC2_R8G8B8_SRGB_BLOCK => 8, [4, 4, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb];
+    ETC2_R8G8B8A1_UNORM_BLOCK => 8, [4, 4, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    ETC2_R8G8B8A1_SRGB_BLOCK => 8, [4, 4, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    ETC2_R8G8B8A8_UNORM_BLOCK => 16, [4, 4, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    ETC2_R8G8B8A8_SRGB_BLOCK => 16, [4, 4, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    EAC_R11_UNORM_BLOCK => 8, [4, 4, 1], COLOR, compressed, 0, [R 0 UNorm];
+    EAC_R11_SNORM_BLOCK => 8, [4, 4, 1], COLOR, compressed, 0, [R 0 SNorm];
+    EAC_R11G11_UNORM_BLOCK => 16, [4, 4, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm];
+    EAC_R11G11_SNORM_BLOCK => 16, [4, 4, 1], COLOR, compressed, 0, [R 0 SNorm, G 0 SNorm];
+    ASTC_4X4_UNORM_BLOCK => 16, [4, 4, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    ASTC_4X4_SRGB_BLOCK => 16, [4, 4, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    ASTC_5X4_UNORM_BLOCK => 16, [5, 4, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    ASTC_5X4_SRGB_BLOCK => 16, [5, 4, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    ASTC_5X5_UNORM_BLOCK => 16, [5, 5, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    ASTC_5X5_SRGB_BLOCK => 16, [5, 5, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    ASTC_6X5_UNORM_BLOCK => 16, [6, 5, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    ASTC_6X5_SRGB_BLOCK => 16, [6, 5, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    ASTC_6X6_UNORM_BLOCK => 16, [6, 6, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    ASTC_6X6_SRGB_BLOCK => 16, [6, 6, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    ASTC_8X5_UNORM_BLOCK => 16, [8, 5, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    ASTC_8X5_SRGB_BLOCK => 16, [8, 5, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    ASTC_8X6_UNORM_BLOCK => 16, [8, 6, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    ASTC_8X6_SRGB_BLOCK => 16, [8, 6, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    ASTC_8X8_UNORM_BLOCK => 16, [8, 8, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    ASTC_8X8_SRGB_BLOCK => 16, [8, 8, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    ASTC_10X5_UNORM_BLOCK => 16, [10, 5, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    ASTC_10X5_SRGB_BLOCK => 16, [10, 5, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    ASTC_10X6_UNORM_BLOCK => 16, [10, 6, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    ASTC_10X6_SRGB_BLOCK => 16, [10, 6, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    ASTC_10X8_UNORM_BLOCK => 16, [10, 8, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    ASTC_10X8_SRGB_BLOCK => 16, [10, 8, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    ASTC_10X10_UNORM_BLOCK => 16, [10, 10, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    ASTC_10X10_SRGB_BLOCK => 16, [10, 10, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    ASTC_12X10_UNORM_BLOCK => 16, [12, 10, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    ASTC_12X10_SRGB_BLOCK => 16, [12, 10, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    ASTC_12X12_UNORM_BLOCK => 16, [12, 12, 1], COLOR, compressed, 0, [R 0 UNorm, G 0 UNorm, B 0 UNorm, A 0 UNorm];
+    ASTC_12X12_SRGB_BLOCK => 16, [12, 12, 1], COLOR, compressed, 0, [R 0 Srgb, G 0 Srgb, B 0 Srgb, A 0 UNorm];
+    G8B8G8R8_422_UNORM => 4, [2, 1, 1], COLOR, uncompressed, 0, [G 8 UNorm, B 8 UNorm, G 8 UNorm, R 8 UNorm];
+    B8G8R8G8_422_UNORM => 4, [2, 1, 1], COLOR, uncompressed, 0, [B 8 UNorm, G 8 UNorm, R 8 UNorm, G 8 UNorm];
+    G8_B8_R8_3PLANE_420_UNORM => 0, [1, 1, 1], COLOR, uncompressed, 0, [G 8 UNorm, B 8 UNorm, R 8 UNorm] planes [(1, 1, 1), (1, 2, 2), (1, 2, 2)];
+    G8_B8R8_2PLANE_420_UNORM => 0, [1, 1, 1], COLOR, uncompressed, 0, [G 8 UNorm, B 8 UNorm, R 8 UNorm] planes [(1, 1, 1), (2, 2, 2)];
+    G8_B8_R8_3PLANE_422_UNORM => 0, [1, 1, 1], COLOR, uncompressed, 0, [G 8 UNorm, B 8 UNorm, R 8 UNorm] planes [(1, 1, 1), (1, 2, 1), (1, 2, 1)];
+    G8_B8R8_2PLANE_422_UNORM => 0, [1, 1, 1], COLOR, uncompressed, 0, [G 8 UNorm, B 8 UNorm, R 8 UNorm] planes [(1, 1, 1), (2, 2, 1)];
+    G8_B8_R8_3PLANE_444_UNORM => 0, [1, 1, 1], COLOR, uncompressed, 0, [G 8 UNorm, B 8 UNorm, R 8 UNorm] planes [(1, 1, 1), (1, 1, 1), (1, 1, 1)];
+    R10X6_UNORM_PACK16 => 2, [1, 1, 1], COLOR, uncompressed, 2, [R 10 UNorm, X 6 UNorm];
+    R10X6G10X6_UNORM_2PACK16 => 4, [1, 1, 1], COLOR, uncompressed, 2, [R 10 UNorm, X 6 UNorm, G 10 UNorm, X 6 UNorm];
+    R10X6G10X6B10X6A10X6_UNORM_4PACK16 => 8, [1, 1, 1], COLOR, uncompressed, 2, [R 10 UNorm, X 6 UNorm, G 10 UNorm, X 6 UNorm, B 10 UNorm, X 6 UNorm, A 10 UNorm, X 6 UNorm];
+    G10X6B10X6G10X6R10X6_422_UNORM_4PACK16 => 8, [2, 1, 1], COLOR, uncompressed, 2, [G 10 UNorm, X 6 UNorm, B 10 UNorm, X 6 UNorm, G 10 UNorm, X 6 UNorm, R 10 UNorm, X 6 UNorm];
+    B10X6G10X6R10X6G10X6_422_UNORM_4PACK16 => 8, [2, 1, 1], COLOR, uncompressed, 2, [B 10 UNorm, X 6 UNorm, G 10 UNorm, X 6 UNorm, R 10 UNorm, X 6 UNorm, G 10 UNorm, X 6 UNorm];
+    G10X6_B10X6_R10X6_3PLANE_420_UNORM_3PACK16 => 0, [1, 1, 1], COLOR, uncompressed, 2, [G 10 UNorm, X 6 UNorm, B 10 UNorm, X 6 UNorm, R 10 UNorm, X 6 UNorm] planes [(2, 1, 1), (2, 2, 2), (2, 2, 2)];
+    G10X6_B10X6R10X6_2PLANE_420_UNORM_3PACK16 => 0, [1, 1, 1], COLOR, uncompressed, 2, [G 10 UNorm, X 6 UNorm, B 10 UNorm, X 6 UNorm, R 10 UNorm, X 6 UNorm] planes [(2, 1, 1), (4, 2, 2)];
+    G10X6_B10X6_R10X6_3PLANE_422_UNORM_3PACK16 => 0, [1, 1, 1], COLOR, uncompressed, 2, [G 10 UNorm, X 6 UNorm, B 10 UNorm, X 6 UNorm, R 10 UNorm, X 6 UNorm] planes [(2, 1, 1), (2, 2, 1), (2, 2, 1)];
+    G10X6_B10X6R10X6_2PLANE_422_UNORM_3PACK16 => 0, [1, 1, 1], COLOR, uncompressed, 2, [G 10 UNorm, X 6 UNorm, B 10 UNorm, X 6 UNorm, R 10 UNorm, X 6 UNorm] planes [(2, 1, 1), (4, 2, 1)];
+    G10X6_B10X6_R10X6_3PLANE_444_UNORM_3PACK16 => 0, [1, 1, 1], COLOR, uncompressed, 2, [G 10 UNorm, X 6 UNorm, B 10 UNorm, X 6 UNorm, R 10 UNorm, X 6 UNorm] planes [(2, 1, 1), (2, 1, 1), (2, 1, 1)];
+    R12X4_UNORM_PACK16 => 2, [1, 1, 1], COLOR, uncompressed, 2, [R 12 UNorm, X 4 UNorm];
+    R12X4G12X4_UNORM_2PACK16 => 4, [1, 1, 1], COLOR, uncompressed, 2, [R 12 UNorm, X 4 UNorm, G 12 UNorm, X 4 UNorm];
+    R12X4G12X4B12X4A12X4_UNORM_4PACK16 => 8, [1, 1, 1], COLOR, uncompressed, 2, [R 12 UNorm, X 4 UNorm, G 12 UNorm, X 4 UNorm, B 12 UNorm, X 4 UNorm, A 12 UNorm, X 4 UNorm];
+    G12X4B12X4G12X4R12X4_422_UNORM_4PACK16 => 8, [2, 1, 1], COLOR, uncompressed, 2, [G 12 UNorm, X 4 UNorm, B 12 UNorm, X 4 UNorm, G 12 UNorm, X 4 UNorm, R 12 UNorm, X 4 UNorm];
+    B12X4G12X4R12X4G12X4_422_UNORM_4PACK16 => 8, [2, 1, 1], COLOR, uncompressed, 2, [B 12 UNorm, X 4 UNorm, G 12 UNorm, X 4 UNorm, R 12 UNorm, X 4 UNorm, G 12 UNorm, X 4 UNorm];
+    G12X4_B12X4_R12X4_3PLANE_420_UNORM_3PACK16 => 0, [1, 1, 1], COLOR, uncompressed, 2, [G 12 UNorm, X 4 UNorm, B 12 UNorm, X 4 UNorm, R 12 UNorm, X 4 UNorm] planes [(2, 1, 1), (2, 2, 2), (2, 2, 2)];
+    G12X4_B12X4R12X4_2PLANE_420_UNORM_3PACK16 => 0, [1, 1, 1], COLOR, uncompressed, 2, [G 12 UNorm, X 4 UNorm, B 12 UNorm, X 4 UNorm, R 12 UNorm, X 4 UNorm] planes [(2, 1, 1), (4, 2, 2)];
+    G12X4_B12X4_R12X4_3PLANE_422_UNORM_3PACK16 => 0, [1, 1, 1], COLOR, uncompressed, 2, [G 12 UNorm, X 4 UNorm, B 12 UNorm, X 4 UNorm, R 12 UNorm, X 4 UNorm] planes [(2, 1, 1), (2, 2, 1), (2, 2, 1)];
+    G12X4_B12X4R12X4_2PLANE_422_UNORM_3PACK16 => 0, [1, 1, 1], COLOR, uncompressed, 2, [G 12 UNorm, X 4 UNorm, B 12 UNorm, X 4 UNorm, R 12 UNorm, X 4 UNorm] planes [(2, 1, 1), (4, 2, 1)];
+    G12X4_B12X4_R12X4_3PLANE_444_UNORM_3PACK16 => 0, [1, 1, 1], COLOR, uncompressed, 2, [G 12 UNorm, X 4 UNorm, B 12 UNorm, X 4 UNorm, R 12 UNorm, X 4 UNorm] planes [(2, 1, 1), (2, 1, 1), (2, 1, 1)];
+    G16B16G16R16_422_UNORM => 8, [2, 1, 1], COLOR, uncompressed, 0, [G 16 UNorm, B 16 UNorm, G 16 UNorm, R 16 UNorm];
+    B16G16R16G16_422_UNORM => 8, [2, 1, 1], COLOR, uncompressed, 0, [B 16 UNorm, G 16 UNorm, R 16 UNorm, G 16 UNorm];
+    G16_B16_R16_3PLANE_420_UNORM => 0, [1, 1, 1], COLOR, uncompressed, 0, [G 16 UNorm, B 16 UNorm, R 16 UNorm] planes [(2, 1, 1), (2, 2, 2), (2, 2, 2)];
+    G16_B16R16_2PLANE_420_UNORM => 0, [1, 1, 1], COLOR, uncompressed, 0, [G 16 UNorm, B 16 UNorm, R 16 UNorm] planes [(2, 1, 1), (4, 2, 2)];
+    G16_B16_R16_3PLANE_422_UNORM => 0, [1, 1, 1], COLOR, uncompressed, 0, [G 16 UNorm, B 16 UNorm, R 16 UNorm] planes [(2, 1, 1), (2, 2, 1), (2, 2, 1)];
+    G16_B16R16_2PLANE_422_UNORM => 0, [1, 1, 1], COLOR, uncompressed, 0, [G 16 UNorm, B 16 UNorm, R 16 UNorm] planes [(2, 1, 1), (4, 2, 1)];
+    G16_B16_R16_3PLANE_444_UNORM => 0, [1, 1, 1], COLOR, uncompressed, 0, [G 16 UNorm, B 16 UNorm, R 16 UNorm] planes [(2, 1, 1), (2, 1, 1), (2, 1, 1)];
+    G8_B8R8_2PLANE_444_UNORM => 0, [1, 1, 1], COLOR, uncompressed, 0, [G 8 UNorm, B 8 UNorm, R 8 UNorm] planes [(1, 1, 1), (2, 1, 1)];
+    G10X6_B10X6R10X6_2PLANE_444_UNORM_3PACK16 => 0, [1, 1, 1], COLOR, uncompressed, 2, [G 10 UNorm, X 6 UNorm, B 10 UNorm, X 6 UNorm, R 10 UNorm, X 6 UNorm] planes [(2, 1, 1), (4, 1, 1)];
+    G12X4_B12X4R12X4_2PLANE_444_UNORM_3PACK16 => 0, [1, 1, 1], COLOR, uncompressed, 2, [G 12 UNorm, X 4 UNorm, B 12 UNorm, X 4 UNorm, R 12 UNorm, X 4 UNorm] planes [(2, 1, 1), (4, 1, 1)];
+    G16_B16R16_2PLANE_444_UNORM => 0, [1, 1, 1], COLOR, uncompressed, 0, [G 16 UNorm, B 16 UNorm, R 16 UNorm] planes [(2, 1, 1), (4, 1, 1)];
+    A4R4G4B4_UNORM_PACK16 => 2, [1, 1, 1], COLOR, uncompressed, 2, [A 4 UNorm, R 4 UNorm, G 4 UNorm, B 4 UNorm];
+    A4B4G4R4_UNORM_PACK16 => 2, [1, 1, 1], COLOR, uncompressed, 2, [A 4 UNorm, B 4 UNorm, G 4 UNorm, R 4 UNorm];
+    ASTC_4X4_SFLOAT_BLOCK => 16, [4, 4, 1], COLOR, compressed, 0, [R 0 SFloat, G 0 SFloat, B 0 SFloat, A 0 SFloat];
+    ASTC_5X4_SFLOAT_BLOCK => 16, [5, 4, 1], COLOR, compressed, 0, [R 0 SFloat, G 0 SFloat, B 0 SFloat, A 0 SFloat];
+    ASTC_5X5_SFLOAT_BLOCK => 16, [5, 5, 1], COLOR, compressed, 0, [R 0 SFloat, G 0 SFloat, B 0 SFloat, A 0 SFloat];
+    ASTC_6X5_SFLOAT_BLOCK => 16, [6, 5, 1], COLOR, compressed, 0, [R 0 SFloat, G 0 SFloat, B 0 SFloat, A 0 SFloat];
+    ASTC_6X6_SFLOAT_BLOCK => 16, [6, 6, 1], COLOR, compressed, 0, [R 0 SFloat, G 0 SFloat, B 0 SFloat, A 0 SFloat];
+    ASTC_8X5_SFLOAT_BLOCK => 16, [8, 5, 1], COLOR, compressed, 0, [R 0 SFloat, G 0 SFloat, B 0 SFloat, A 0 SFloat];
+    ASTC_8X6_SFLOAT_BLOCK => 16, [8, 6, 1], COLOR, compressed, 0, [R 0 SFloat, G 0 SFloat, B 0 SFloat, A 0 SFloat];
+    ASTC_8X8_SFLOAT_BLOCK => 16, [8, 8, 1], COLOR, compressed, 0, [R 0 SFloat, G 0 SFloat, B 0 SFloat, A 0 SFloat];
+    ASTC_10X5_SFLOAT_BLOCK => 16, [10, 5, 1], COLOR, compressed, 0, [R 0 SFloat, G 0 SFloat, B 0 SFloat, A 0 SFloat];
+    ASTC_10X6_SFLOAT_BLOCK => 16, [10, 6, 1], COLOR, compressed, 0, [R 0 SFloat, G 0 SFloat, B 0 SFloat, A 0 SFloat];
+    ASTC_10X8_SFLOAT_BLOCK => 16, [10, 8, 1], COLOR, compressed, 0, [R 0 SFloat, G 0 SFloat, B 0 SFloat, A 0 SFloat];
+    ASTC_10X10_SFLOAT_BLOCK => 16, [10, 10, 1], COLOR, compressed, 0, [R 0 SFloat, G 0 SFloat, B 0 SFloat, A 0 SFloat];
+    ASTC_12X10_SFLOAT_BLOCK => 16, [12, 10, 1], COLOR, compressed, 0, [R 0 SFloat, G 0 SFloat, B 0 SFloat, A 0 SFloat];
+    ASTC_12X12_SFLOAT_BLOCK => 16, [12, 12, 1], COLOR, compressed, 0, [R 0 SFloat, G 0 SFloat, B 0 SFloat, A 0 SFloat];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every format of the table: Vulkan 1.0 formats, promoted YCbCr and 4444 formats and ASTC HDR.
+    fn table_formats() -> impl Iterator<Item = &'static UsamiFormatInfo> {
+        (1..=184)
+            .chain(1_000_156_000..=1_000_156_033)
+            .chain(1_000_330_000..=1_000_330_003)
+            .chain(1_000_340_000..=1_000_340_001)
+            .chain(1_000_066_000..=1_000_066_013)
+            .map(|raw| {
+                let format = Format::from_raw(raw);
+
+                format_info(format).unwrap_or_else(|| panic!("Missing {format:?}"))
+            })
+    }
+
+    #[test]
+    fn table_is_consistent() {
+        for info in table_formats() {
+            let bits: u32 = info.components.iter().map(|x| x.bits).sum();
+
+            if info.compressed {
+                assert!(info.has_multi_texel_blocks(), "{:?}", info.format);
+                assert_eq!(bits, 0, "{:?}", info.format);
+            } else if info.planes.is_empty() {
+                assert_eq!(bits, info.block_size * 8, "{:?}", info.format);
+            } else {
+                assert_eq!(info.block_size, 0, "{:?}", info.format);
+            }
+
+            if info.pack_size != 0 && info.planes.is_empty() {
+                assert_eq!(info.block_size % info.pack_size, 0, "{:?}", info.format);
+            }
+
+            assert_eq!(
+                info.is_depth_stencil(),
+                info.has_component(UsamiComponentKind::D)
+                    || info.has_component(UsamiComponentKind::S),
+                "{:?}",
+                info.format
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_formats() {
+        assert!(format_info(Format::UNDEFINED).is_none());
+        assert!(matches!(
+            get_format_info(Format::PVRTC1_2BPP_UNORM_BLOCK_IMG),
+            Err(Error::UnsupportedFormat(
+                Format::PVRTC1_2BPP_UNORM_BLOCK_IMG
+            ))
+        ));
+    }
+
+    #[test]
+    fn sizes() {
+        let extent = Extent3D {
+            width: 5,
+            height: 5,
+            depth: 1,
+        };
+
+        let rgba8 = format_info(Format::R8G8B8A8_UNORM).unwrap();
+        assert_eq!(rgba8.block_count(extent), 25);
+        assert_eq!(rgba8.size_for_extent(extent, ImageAspectFlags::COLOR), 100);
+        assert_eq!(rgba8.numeric_format(), UsamiNumericFormat::UNorm);
+
+        let bc1 = format_info(Format::BC1_RGB_UNORM_BLOCK).unwrap();
+        assert_eq!(bc1.block_count(extent), 4);
+        assert_eq!(bc1.size_for_extent(extent, ImageAspectFlags::COLOR), 32);
+
+        let astc = format_info(Format::ASTC_12X10_SFLOAT_BLOCK).unwrap();
+        assert_eq!(astc.block_count(extent), 1);
+        assert!(astc.numeric_format().is_float());
+
+        let packed_422 = format_info(Format::G8B8G8R8_422_UNORM).unwrap();
+        assert!(packed_422.has_multi_texel_blocks());
+        assert_eq!(
+            packed_422.size_for_extent(extent, ImageAspectFlags::COLOR),
+            60
+        );
+
+        let shared_exponent = format_info(Format::E5B9G9R9_UFLOAT_PACK32).unwrap();
+        assert_eq!(shared_exponent.numeric_format(), UsamiNumericFormat::UFloat);
+
+        let padded = format_info(Format::X8_D24_UNORM_PACK32).unwrap();
+        assert_eq!(padded.numeric_format(), UsamiNumericFormat::UNorm);
+    }
+
+    #[test]
+    fn depth_stencil_aspects() {
+        for (format, depth_size) in [
+            (Format::D16_UNORM_S8_UINT, 2),
+            (Format::D24_UNORM_S8_UINT, 4),
+            (Format::D32_SFLOAT_S8_UINT, 4),
+        ] {
+            let info = format_info(format).unwrap();
+
+            assert_eq!(info.aspect_mask, aspects::DEPTH_STENCIL);
+            assert_eq!(info.aspect_block_size(ImageAspectFlags::DEPTH), depth_size);
+            assert_eq!(info.aspect_block_size(ImageAspectFlags::STENCIL), 1);
+        }
+
+        let uint = format_info(Format::S8_UINT).unwrap();
+        assert_eq!(uint.aspect_mask, ImageAspectFlags::STENCIL);
+        assert!(uint.numeric_format().is_integer());
+    }
+
+    #[test]
+    fn planes() {
+        let info = format_info(Format::G8_B8R8_2PLANE_420_UNORM).unwrap();
+
+        assert_eq!(info.planes.len(), 2);
+        assert_eq!(
+            info.planes
+                .iter()
+                .map(|x| (x.block_size, x.width_divisor, x.height_divisor))
+                .collect::<Vec<_>>(),
+            vec![(1, 1, 1), (2, 2, 2)]
+        );
+    }
+}
@@ -1,11 +1,8 @@
 use std::sync::Arc;
 
-use ash::{
-    prelude::*,
-    vk::{Framebuffer, FramebufferCreateInfo},
-};
+use ash::vk::{Framebuffer, FramebufferCreateInfo};
 
-use crate::UsamiDevice;
+use crate::{ResultExt, UsamiDevice, UsamiResult};
 
 pub struct UsamiFramebuffer {
     device: Arc<UsamiDevice>,
@@ -13,7 +10,7 @@ pub struct UsamiFramebuffer {
 }
 
 impl UsamiFramebuffer {
-    pub fn new(device: &Arc<UsamiDevice>, create_info: FramebufferCreateInfo) -> VkResult<Self> {
+    pub fn new(device: &Arc<UsamiDevice>, create_info: FramebufferCreateInfo) -> UsamiResult<Self> {
         let handle = unsafe { device.handle.create_framebuffer(&create_info, None)? };
 
         Ok(Self {
@@ -34,8 +31,8 @@ impl UsamiDevice {
         device: &Arc<UsamiDevice>,
         name: String,
         create_info: FramebufferCreateInfo,
-    ) -> VkResult<UsamiFramebuffer> {
-        let framebuffer = UsamiFramebuffer::new(device, create_info)?;
+    ) -> UsamiResult<UsamiFramebuffer> {
+        let framebuffer = UsamiFramebuffer::new(device, create_info).context(&name)?;
 
         device.set_debug_name(name, framebuffer.handle)?;
 
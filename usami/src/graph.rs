@@ -0,0 +1,516 @@
+use std::sync::Arc;
+
+use ash::vk::{
+    self, BufferMemoryBarrier2, BufferUsageFlags, DeviceSize, ImageCreateInfo, ImageLayout,
+    ImageSubresourceRange, ImageUsageFlags, MemoryPropertyFlags, SharingMode,
+};
+
+use crate::{
+    utils, UsamiAccess, UsamiBuffer, UsamiCommandBuffer, UsamiCommandPool, UsamiDevice, UsamiImage,
+    UsamiResourceState, UsamiResult, UsamiTrackedState,
+};
+
+/// Handle to an image of a [UsamiRenderGraph].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UsamiGraphImage(usize);
+
+/// Handle to a buffer of a [UsamiRenderGraph].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UsamiGraphBuffer(usize);
+
+/// A resource used by a pass and how it is used.
+#[derive(Clone, Copy, Debug)]
+pub enum UsamiGraphAccess {
+    Image {
+        image: UsamiGraphImage,
+        /// The whole image is used if no range is given.
+        subresource_range: Option<ImageSubresourceRange>,
+        access: UsamiAccess,
+    },
+    Buffer {
+        buffer: UsamiGraphBuffer,
+        access: UsamiAccess,
+    },
+}
+
+impl UsamiGraphAccess {
+    pub fn image(image: UsamiGraphImage, access: UsamiAccess) -> Self {
+        Self::Image {
+            image,
+            subresource_range: None,
+            access,
+        }
+    }
+
+    pub fn image_range(
+        image: UsamiGraphImage,
+        subresource_range: ImageSubresourceRange,
+        access: UsamiAccess,
+    ) -> Self {
+        Self::Image {
+            image,
+            subresource_range: Some(subresource_range),
+            access,
+        }
+    }
+
+    pub fn buffer(buffer: UsamiGraphBuffer, access: UsamiAccess) -> Self {
+        Self::Buffer { buffer, access }
+    }
+
+    fn access(&self) -> UsamiAccess {
+        match self {
+            Self::Image { access, .. } | Self::Buffer { access, .. } => *access,
+        }
+    }
+
+    fn is_write(&self) -> bool {
+        self.access().state().is_write()
+    }
+
+    fn resource(&self) -> (bool, usize) {
+        match self {
+            Self::Image { image, .. } => (true, image.0),
+            Self::Buffer { buffer, .. } => (false, buffer.0),
+        }
+    }
+
+    /// Whether both accesses must be ordered, images read in different layouts cannot be used at the same time.
+    fn conflicts(&self, other: &Self) -> bool {
+        let (is_image, _) = self.resource();
+
+        self.resource() == other.resource()
+            && (self.is_write()
+                || other.is_write()
+                || (is_image && self.access().image_layout() != other.access().image_layout()))
+    }
+}
+
+struct TransientImage {
+    name: String,
+    create_info: ImageCreateInfo<'static>,
+    memory_flags: MemoryPropertyFlags,
+    image: Option<UsamiImage>,
+}
+
+enum ImageEntry<'a> {
+    Imported(&'a UsamiImage),
+    Transient(Box<TransientImage>),
+}
+
+enum BufferEntry<'a> {
+    Imported(&'a UsamiBuffer),
+    Transient {
+        name: String,
+        usage: BufferUsageFlags,
+        size: DeviceSize,
+        memory_flags: MemoryPropertyFlags,
+        buffer: Option<UsamiBuffer>,
+    },
+}
+
+type PassCallback<'a> =
+    Box<dyn Fn(&UsamiRenderGraphResources, &UsamiCommandBuffer) -> UsamiResult<()> + 'a>;
+
+struct PassEntry<'a> {
+    name: String,
+    accesses: Vec<UsamiGraphAccess>,
+    callback: PassCallback<'a>,
+}
+
+/// Resources of a render graph as seen by passes while they are recorded.
+pub struct UsamiRenderGraphResources<'g, 'a> {
+    images: &'g [ImageEntry<'a>],
+    buffers: &'g [BufferEntry<'a>],
+}
+
+impl<'g, 'a> UsamiRenderGraphResources<'g, 'a> {
+    pub fn image(&self, image: UsamiGraphImage) -> &'g UsamiImage {
+        match &self.images[image.0] {
+            ImageEntry::Imported(image) => image,
+            ImageEntry::Transient(transient) => transient
+                .image
+                .as_ref()
+                .expect("transient images must be allocated before recording"),
+        }
+    }
+
+    pub fn buffer(&self, buffer: UsamiGraphBuffer) -> &'g UsamiBuffer {
+        match &self.buffers[buffer.0] {
+            BufferEntry::Imported(buffer) => buffer,
+            BufferEntry::Transient { buffer, .. } => buffer
+                .as_ref()
+                .expect("transient buffers must be allocated before recording"),
+        }
+    }
+}
+
+/// Graph of passes declaring the resources they read and write.
+///
+/// A pass depends on the earlier passes whose accesses conflict with its own. Passes whose
+/// writes only reach transient resources nobody reads afterward are culled, and the others are
+/// recorded in levels: every pass of a level only depends on passes of earlier levels, so the
+/// barriers of a whole level are recorded at once before its passes. Barriers are derived from
+/// the declared accesses and the tracked state of resources, and transient resources are created
+/// on the first recording and owned by the graph.
+pub struct UsamiRenderGraph<'a> {
+    device: Arc<UsamiDevice>,
+    name: String,
+    images: Vec<ImageEntry<'a>>,
+    buffers: Vec<BufferEntry<'a>>,
+    passes: Vec<PassEntry<'a>>,
+}
+
+impl<'a> UsamiRenderGraph<'a> {
+    pub fn new(device: &Arc<UsamiDevice>, name: String) -> Self {
+        Self {
+            device: device.clone(),
+            name,
+            images: Vec::new(),
+            buffers: Vec::new(),
+            passes: Vec::new(),
+        }
+    }
+
+    pub fn import_image(&mut self, image: &'a UsamiImage) -> UsamiGraphImage {
+        self.images.push(ImageEntry::Imported(image));
+
+        UsamiGraphImage(self.images.len() - 1)
+    }
+
+    pub fn import_buffer(&mut self, buffer: &'a UsamiBuffer) -> UsamiGraphBuffer {
+        self.buffers.push(BufferEntry::Imported(buffer));
+
+        UsamiGraphBuffer(self.buffers.len() - 1)
+    }
+
+    /// Declare an image owned by the graph, usage flags required by the passes are added to the create info.
+    pub fn create_image(
+        &mut self,
+        name: String,
+        create_info: ImageCreateInfo<'static>,
+        memory_flags: MemoryPropertyFlags,
+    ) -> UsamiGraphImage {
+        self.images
+            .push(ImageEntry::Transient(Box::new(TransientImage {
+                name,
+                create_info,
+                memory_flags,
+                image: None,
+            })));
+
+        UsamiGraphImage(self.images.len() - 1)
+    }
+
+    /// Declare a buffer owned by the graph, transfer usage flags required by the passes are added to `usage`.
+    pub fn create_buffer(
+        &mut self,
+        name: String,
+        usage: BufferUsageFlags,
+        size: DeviceSize,
+        memory_flags: MemoryPropertyFlags,
+    ) -> UsamiGraphBuffer {
+        self.buffers.push(BufferEntry::Transient {
+            name,
+            usage,
+            size,
+            memory_flags,
+            buffer: None,
+        });
+
+        UsamiGraphBuffer(self.buffers.len() - 1)
+    }
+
+    pub fn add_pass<
+        F: Fn(&UsamiRenderGraphResources, &UsamiCommandBuffer) -> UsamiResult<()> + 'a,
+    >(
+        &mut self,
+        name: String,
+        accesses: &[UsamiGraphAccess],
+        callback: F,
+    ) {
+        self.passes.push(PassEntry {
+            name,
+            accesses: accesses.to_vec(),
+            callback: Box::new(callback),
+        });
+    }
+
+    /// Return a resource of the graph, [None] for transient images that were not allocated yet.
+    pub fn image(&self, image: UsamiGraphImage) -> Option<&UsamiImage> {
+        match &self.images[image.0] {
+            ImageEntry::Imported(image) => Some(image),
+            ImageEntry::Transient(transient) => transient.image.as_ref(),
+        }
+    }
+
+    /// Return a resource of the graph, [None] for transient buffers that were not allocated yet.
+    pub fn buffer(&self, buffer: UsamiGraphBuffer) -> Option<&UsamiBuffer> {
+        match &self.buffers[buffer.0] {
+            BufferEntry::Imported(buffer) => Some(buffer),
+            BufferEntry::Transient { buffer, .. } => buffer.as_ref(),
+        }
+    }
+
+    /// Create the transient resources that were not created yet.
+    pub fn allocate(&mut self) -> UsamiResult<()> {
+        for (index, entry) in self.images.iter_mut().enumerate() {
+            let ImageEntry::Transient(transient) = entry else {
+                continue;
+            };
+
+            if transient.image.is_none() {
+                let usage = self
+                    .passes
+                    .iter()
+                    .flat_map(|pass| &pass.accesses)
+                    .filter_map(|access| match access {
+                        UsamiGraphAccess::Image {
+                            image,
+                            access: usage,
+                            ..
+                        } if image.0 == index => Some(image_usage(*usage)),
+                        _ => None,
+                    })
+                    .fold(transient.create_info.usage, |acc, x| acc | x);
+
+                transient.image = Some(UsamiDevice::create_image(
+                    &self.device,
+                    format!("{}_{}", self.name, transient.name),
+                    transient.create_info.usage(usage),
+                    transient.memory_flags,
+                )?);
+            }
+        }
+
+        for (index, entry) in self.buffers.iter_mut().enumerate() {
+            if let BufferEntry::Transient {
+                name,
+                usage,
+                size,
+                memory_flags,
+                buffer: buffer @ None,
+            } = entry
+            {
+                let usage = self
+                    .passes
+                    .iter()
+                    .flat_map(|pass| &pass.accesses)
+                    .filter_map(|access| match access {
+                        UsamiGraphAccess::Buffer {
+                            buffer,
+                            access: usage,
+                            ..
+                        } if buffer.0 == index => Some(buffer_usage(*usage)),
+                        _ => None,
+                    })
+                    .fold(*usage, |acc, x| acc | x);
+
+                *buffer = Some(UsamiDevice::create_buffer_with_size(
+                    &self.device,
+                    format!("{}_{name}", self.name),
+                    vk::BufferCreateFlags::empty(),
+                    SharingMode::EXCLUSIVE,
+                    usage,
+                    *size,
+                    *memory_flags,
+                )?);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Index of the passes to record, in order.
+    fn live_passes(&self) -> Vec<usize> {
+        let is_transient = |(is_image, index): (bool, usize)| {
+            if is_image {
+                matches!(self.images[index], ImageEntry::Transient(_))
+            } else {
+                matches!(self.buffers[index], BufferEntry::Transient { .. })
+            }
+        };
+
+        // Walk backward, a pass is alive if it has no write (side effects only), writes an
+        // imported resource or writes a resource read by a live pass after it.
+        let mut alive = vec![false; self.passes.len()];
+        let mut needed: Vec<(bool, usize)> = Vec::new();
+
+        for (index, pass) in self.passes.iter().enumerate().rev() {
+            let writes: Vec<_> = pass
+                .accesses
+                .iter()
+                .filter(|x| x.is_write())
+                .map(UsamiGraphAccess::resource)
+                .collect();
+
+            alive[index] = writes.is_empty()
+                || writes
+                    .iter()
+                    .any(|x| !is_transient(*x) || needed.contains(x));
+
+            // Writes may be partial, so every resource of a live pass is needed.
+            if alive[index] {
+                needed.extend(pass.accesses.iter().map(UsamiGraphAccess::resource));
+            }
+        }
+
+        (0..self.passes.len()).filter(|x| alive[*x]).collect()
+    }
+
+    /// Topologically sort the live passes in levels, each pass is placed in the level after the
+    /// last pass it depends on. Passes of a level keep their declaration order.
+    fn levels(&self) -> Vec<Vec<usize>> {
+        let live_passes = self.live_passes();
+        let mut pass_levels: Vec<usize> = Vec::with_capacity(live_passes.len());
+        let mut levels: Vec<Vec<usize>> = Vec::new();
+
+        for (position, index) in live_passes.iter().enumerate() {
+            let accesses = &self.passes[*index].accesses;
+            let level = live_passes[..position]
+                .iter()
+                .zip(&pass_levels)
+                .filter(|(dependency, _)| {
+                    self.passes[**dependency]
+                        .accesses
+                        .iter()
+                        .any(|dependency_access| {
+                            accesses
+                                .iter()
+                                .any(|access| access.conflicts(dependency_access))
+                        })
+                })
+                .map(|(_, level)| level + 1)
+                .max()
+                .unwrap_or(0);
+
+            pass_levels.push(level);
+
+            if level == levels.len() {
+                levels.push(Vec::new());
+            }
+
+            levels[level].push(*index);
+        }
+
+        levels
+    }
+
+    /// Record every live pass along with the barriers they need.
+    ///
+    /// [Self::allocate] must be called first if the graph has transient resources.
+    pub fn record(&self, command_buffer: &UsamiCommandBuffer) -> UsamiResult<()> {
+        let resources = UsamiRenderGraphResources {
+            images: &self.images,
+            buffers: &self.buffers,
+        };
+        let mut buffer_states = vec![
+            UsamiTrackedState::new(UsamiResourceState {
+                layout: ImageLayout::UNDEFINED,
+                ..UsamiAccess::Nothing.state()
+            });
+            self.buffers.len()
+        ];
+
+        for level in self.levels() {
+            let mut buffer_barriers = Vec::new();
+
+            for access in level.iter().flat_map(|index| &self.passes[*index].accesses) {
+                match access {
+                    UsamiGraphAccess::Image {
+                        image,
+                        subresource_range,
+                        access,
+                    } => {
+                        command_buffer.transition_image(
+                            resources.image(*image),
+                            *subresource_range,
+                            *access,
+                        )?;
+                    }
+                    UsamiGraphAccess::Buffer { buffer, access } => {
+                        // Buffers have no layout, only image uses give one.
+                        let dst = UsamiResourceState {
+                            layout: ImageLayout::UNDEFINED,
+                            ..access.state()
+                        };
+
+                        if let Some(src) = buffer_states[buffer.0].access(dst) {
+                            buffer_barriers.push(
+                                BufferMemoryBarrier2::default()
+                                    .src_stage_mask(src.stage_mask)
+                                    .src_access_mask(src.access_mask)
+                                    .dst_stage_mask(dst.stage_mask)
+                                    .dst_access_mask(dst.access_mask)
+                                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                                    .buffer(resources.buffer(*buffer).handle)
+                                    .offset(0)
+                                    .size(vk::WHOLE_SIZE),
+                            );
+                        }
+                    }
+                }
+            }
+
+            command_buffer.pipeline_barrier2(&[], &buffer_barriers, &[]);
+
+            for index in level {
+                let pass = &self.passes[index];
+
+                self.device
+                    .begin_debug_label(command_buffer, format!("{}_{}", self.name, pass.name))?;
+                (pass.callback)(&resources, command_buffer)?;
+                self.device.end_debug_label(command_buffer);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Allocate, record and submit the passes in a single command buffer, then wait for its completion.
+    pub fn execute(&mut self, command_pool: &UsamiCommandPool) -> UsamiResult<()> {
+        self.allocate()?;
+
+        utils::record_and_execute_command_buffer(
+            &self.device,
+            command_pool,
+            format!("{}_command_buffer", self.name),
+            |_, command_buffer| self.record(command_buffer),
+        )
+    }
+}
+
+/// Image usage flags required by an access.
+fn image_usage(access: UsamiAccess) -> ImageUsageFlags {
+    match access {
+        UsamiAccess::VertexShaderRead
+        | UsamiAccess::FragmentSampledRead
+        | UsamiAccess::ComputeSampledRead => ImageUsageFlags::SAMPLED,
+        UsamiAccess::FragmentStorageRead
+        | UsamiAccess::FragmentStorageWrite
+        | UsamiAccess::ComputeRead
+        | UsamiAccess::ComputeWrite => ImageUsageFlags::STORAGE,
+        UsamiAccess::ColorAttachmentRead | UsamiAccess::ColorAttachmentWrite => {
+            ImageUsageFlags::COLOR_ATTACHMENT
+        }
+        UsamiAccess::DepthStencilAttachmentRead | UsamiAccess::DepthStencilAttachmentWrite => {
+            ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+        }
+        UsamiAccess::TransferRead => ImageUsageFlags::TRANSFER_SRC,
+        UsamiAccess::TransferWrite => ImageUsageFlags::TRANSFER_DST,
+        _ => ImageUsageFlags::empty(),
+    }
+}
+
+/// Buffer usage flags required by an access, shader accesses are ambiguous and must be given explicitly.
+fn buffer_usage(access: UsamiAccess) -> BufferUsageFlags {
+    match access {
+        UsamiAccess::IndirectBuffer => BufferUsageFlags::INDIRECT_BUFFER,
+        UsamiAccess::IndexBuffer => BufferUsageFlags::INDEX_BUFFER,
+        UsamiAccess::VertexBuffer => BufferUsageFlags::VERTEX_BUFFER,
+        UsamiAccess::TransferRead => BufferUsageFlags::TRANSFER_SRC,
+        UsamiAccess::TransferWrite => BufferUsageFlags::TRANSFER_DST,
+        _ => BufferUsageFlags::empty(),
+    }
+}
@@ -1,29 +1,36 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use ash::{
-    prelude::*,
     vk::{
-        BufferCreateFlags, BufferImageCopy, BufferUsageFlags, ComponentMapping, Extent3D, Format,
-        Image, ImageAspectFlags, ImageCreateInfo, ImageLayout, ImageSubresourceLayers,
-        ImageSubresourceRange, ImageTiling, ImageType, ImageUsageFlags, ImageView,
-        ImageViewCreateFlags, ImageViewCreateInfo, ImageViewType, MemoryPropertyFlags,
-        SampleCountFlags, Sampler, SamplerCreateInfo, SharingMode,
+        AttachmentLoadOp, AttachmentStoreOp, BufferImageCopy, ClearValue, ComponentMapping,
+        Extent3D, Format, Image, ImageAspectFlags, ImageCreateInfo, ImageLayout,
+        ImageSubresourceLayers, ImageSubresourceRange, ImageTiling, ImageType, ImageUsageFlags,
+        ImageView, ImageViewCreateFlags, ImageViewCreateInfo, ImageViewType, MemoryAllocateFlags,
+        MemoryPropertyFlags, RenderingAttachmentInfo, SampleCountFlags, Sampler, SamplerCreateInfo,
+        REMAINING_ARRAY_LAYERS, REMAINING_MIP_LEVELS,
     },
     Device,
 };
 use image::{RgbImage, RgbaImage};
 
-use crate::{utils, UsamiCommandPool, UsamiDevice, UsamiDeviceMemory};
+use crate::{
+    format::get_format_info, Error, ResultExt, UsamiAccess, UsamiAllocation, UsamiCommandPool,
+    UsamiDevice, UsamiImageReadback, UsamiResourceState, UsamiResult, UsamiStagingBelt,
+    UsamiStagingDownload, UsamiTrackedState,
+};
 
 pub struct UsamiImage {
     device: Arc<UsamiDevice>,
+    pub image_type: ImageType,
     pub extent: Extent3D,
     pub format: Format,
     pub samples: SampleCountFlags,
     pub array_layers: u32,
     pub mip_levels: u32,
     pub handle: Image,
-    pub device_memory: UsamiDeviceMemory,
+    pub allocation: UsamiAllocation,
+    /// Tracked state of every mip level of every array layer.
+    states: Mutex<Vec<UsamiTrackedState>>,
 }
 
 impl UsamiImage {
@@ -31,28 +38,99 @@ impl UsamiImage {
         device: &Arc<UsamiDevice>,
         create_info: ImageCreateInfo,
         memory_flags: MemoryPropertyFlags,
-    ) -> VkResult<Self> {
+    ) -> UsamiResult<Self> {
         let vk_device: &Device = &device.handle;
 
         let handle = unsafe { vk_device.create_image(&create_info, None)? };
         let req = unsafe { vk_device.get_image_memory_requirements(handle) };
-        let device_memory = UsamiDeviceMemory::new(device, req, memory_flags)?;
+        let allocation = UsamiAllocation::new(
+            device,
+            req,
+            memory_flags,
+            MemoryAllocateFlags::empty(),
+            create_info.tiling == ImageTiling::LINEAR,
+        )?;
         unsafe {
-            vk_device.bind_image_memory(handle, device_memory.handle, 0)?;
+            vk_device.bind_image_memory(handle, allocation.memory, allocation.offset)?;
         }
 
         Ok(Self {
             device: device.clone(),
+            image_type: create_info.image_type,
             extent: create_info.extent,
             format: create_info.format,
             samples: create_info.samples,
             array_layers: create_info.array_layers,
             mip_levels: create_info.mip_levels,
             handle,
-            device_memory,
+            allocation,
+            states: Mutex::new(vec![
+                UsamiTrackedState::new(UsamiResourceState::initial(
+                    create_info.initial_layout
+                ));
+                (create_info.mip_levels * create_info.array_layers)
+                    as usize
+            ]),
         })
     }
 
+    /// Tracked state of a subresource, as of the last recorded barrier.
+    pub fn state(&self, mip_level: u32, array_layer: u32) -> UsamiResourceState {
+        self.tracked_state(mip_level, array_layer).src()
+    }
+
+    /// Tracked last write and reads of a subresource, as of the last recorded barrier.
+    pub fn tracked_state(&self, mip_level: u32, array_layer: u32) -> UsamiTrackedState {
+        self.states.lock().unwrap()[(array_layer * self.mip_levels + mip_level) as usize]
+    }
+
+    /// Tracked layout of a subresource, as of the last recorded barrier.
+    pub fn layout(&self, mip_level: u32, array_layer: u32) -> ImageLayout {
+        self.tracked_state(mip_level, array_layer).layout()
+    }
+
+    /// Override the tracked state of a range, for transitions done outside of usami barriers (e.g. by render passes).
+    pub fn set_state(&self, subresource_range: ImageSubresourceRange, state: UsamiResourceState) {
+        self.set_tracked_state(subresource_range, UsamiTrackedState::new(state));
+    }
+
+    pub fn set_tracked_state(
+        &self,
+        subresource_range: ImageSubresourceRange,
+        state: UsamiTrackedState,
+    ) {
+        let subresource_range = self.resolve_subresource_range(subresource_range);
+        let mut states = self.states.lock().unwrap();
+
+        for array_layer in subresource_range.base_array_layer
+            ..subresource_range.base_array_layer + subresource_range.layer_count
+        {
+            for mip_level in subresource_range.base_mip_level
+                ..subresource_range.base_mip_level + subresource_range.level_count
+            {
+                states[(array_layer * self.mip_levels + mip_level) as usize] = state;
+            }
+        }
+    }
+
+    /// Replace [REMAINING_MIP_LEVELS] and [REMAINING_ARRAY_LAYERS] by actual counts.
+    pub fn resolve_subresource_range(
+        &self,
+        subresource_range: ImageSubresourceRange,
+    ) -> ImageSubresourceRange {
+        let mut result = subresource_range;
+
+        if result.level_count == REMAINING_MIP_LEVELS {
+            result.level_count = self.mip_levels - result.base_mip_level;
+        }
+
+        if result.layer_count == REMAINING_ARRAY_LAYERS {
+            result.layer_count = self.array_layers - result.base_array_layer;
+        }
+
+        result
+    }
+
     pub fn create_simple_image_view(
         &self,
         name: String,
@@ -60,7 +138,7 @@ impl UsamiImage {
         subresource_range: ImageSubresourceRange,
         components: ComponentMapping,
         flags: ImageViewCreateFlags,
-    ) -> VkResult<UsamiImageView> {
+    ) -> UsamiResult<UsamiImageView> {
         UsamiDevice::create_image_view(
             &self.device,
             name,
@@ -74,6 +152,15 @@ impl UsamiImage {
         )
     }
 
+    /// Extent of the given mip level.
+    pub fn mip_extent(&self, mip_level: u32) -> Extent3D {
+        Extent3D {
+            width: (self.extent.width >> mip_level).max(1),
+            height: (self.extent.height >> mip_level).max(1),
+            depth: (self.extent.depth >> mip_level).max(1),
+        }
+    }
+
     pub fn buffer_copy(
         &self,
         aspect_mask: ImageAspectFlags,
@@ -92,6 +179,124 @@ impl UsamiImage {
             )
             .image_extent(self.extent)
     }
+
+    /// Download every mip level, array layer and aspect of the given range, one readback each.
+    ///
+    /// The image is left in its tracked layout, `layout` overrides it for images transitioned
+    /// outside of usami barriers (e.g. by render passes). Depth and stencil aspects are read back
+    /// separately.
+    pub fn read_back(
+        &self,
+        command_pool: &UsamiCommandPool,
+        subresource_range: ImageSubresourceRange,
+        layout: Option<ImageLayout>,
+    ) -> UsamiResult<Vec<UsamiImageReadback>> {
+        let format_info = get_format_info(self.format)?;
+
+        if !format_info.planes.is_empty() {
+            return Err(Error::UnsupportedFormat(self.format));
+        }
+
+        let subresource_range = self.resolve_subresource_range(subresource_range);
+
+        if let Some(layout) = layout {
+            self.set_state(
+                subresource_range.aspect_mask(format_info.aspect_mask),
+                UsamiResourceState {
+                    layout,
+                    ..UsamiAccess::General.state()
+                },
+            );
+        }
+        let level_count = subresource_range.level_count;
+        let layer_count = subresource_range.layer_count;
+        let aspects: Vec<ImageAspectFlags> = [
+            ImageAspectFlags::COLOR,
+            ImageAspectFlags::DEPTH,
+            ImageAspectFlags::STENCIL,
+        ]
+        .into_iter()
+        .filter(|x| subresource_range.aspect_mask.contains(*x))
+        .collect();
+
+        let mut downloads: Vec<(UsamiStagingDownload, UsamiImageReadback)> = Vec::new();
+        let mut total_size = 0;
+        let mut readbacks = Vec::new();
+
+        for mip_level in
+            subresource_range.base_mip_level..subresource_range.base_mip_level + level_count
+        {
+            for array_layer in
+                subresource_range.base_array_layer..subresource_range.base_array_layer + layer_count
+            {
+                for aspect_mask in &aspects {
+                    let extent = self.mip_extent(mip_level);
+                    total_size += format_info.size_for_extent(extent, *aspect_mask);
+
+                    readbacks.push(UsamiImageReadback {
+                        format: self.format,
+                        aspect_mask: *aspect_mask,
+                        mip_level,
+                        array_layer,
+                        extent,
+                        data: Vec::new(),
+                    });
+                }
+            }
+        }
+
+        // Leave room for the alignment of every download.
+        let mut staging_belt = UsamiStagingBelt::new(
+            &self.device,
+            "readback_staging_belt".into(),
+            command_pool,
+            total_size + 256 * readbacks.len() as u64,
+        );
+
+        for readback in readbacks {
+            let region = BufferImageCopy::default()
+                .image_subresource(
+                    ImageSubresourceLayers::default()
+                        .aspect_mask(readback.aspect_mask)
+                        .mip_level(readback.mip_level)
+                        .base_array_layer(readback.array_layer)
+                        .layer_count(1),
+                )
+                .image_extent(readback.extent);
+
+            // Layout transitions of depth/stencil images must cover both aspects.
+            let download = staging_belt.download_image(
+                self,
+                format_info.aspect_mask,
+                &[region],
+                format_info.size_for_extent(readback.extent, readback.aspect_mask),
+            )?;
+
+            downloads.push((download, readback));
+        }
+
+        staging_belt.flush()?;
+
+        Ok(downloads
+            .into_iter()
+            .map(|(download, mut readback)| {
+                readback.data = staging_belt
+                    .take_download(download)
+                    .expect("download must be available after flush");
+                readback
+            })
+            .collect())
+    }
+
+    /// Subresource range covering every mip level and array layer of the given aspects.
+    pub fn whole_subresource_range(&self, aspect_mask: ImageAspectFlags) -> ImageSubresourceRange {
+        ImageSubresourceRange::default()
+            .aspect_mask(aspect_mask)
+            .base_mip_level(0)
+            .level_count(self.mip_levels)
+            .base_array_layer(0)
+            .layer_count(self.array_layers)
+    }
 }
 
 impl Drop for UsamiImage {
@@ -102,18 +307,38 @@ impl Drop for UsamiImage {
 
 pub struct UsamiImageView {
     device: Arc<UsamiDevice>,
+    pub format: Format,
+    pub subresource_range: ImageSubresourceRange,
     pub handle: ImageView,
 }
 
 impl UsamiImageView {
-    pub fn new(device: &Arc<UsamiDevice>, create_info: ImageViewCreateInfo) -> VkResult<Self> {
+    pub fn new(device: &Arc<UsamiDevice>, create_info: ImageViewCreateInfo) -> UsamiResult<Self> {
         let handle = unsafe { device.handle.create_image_view(&create_info, None)? };
 
         Ok(Self {
             device: device.clone(),
+            format: create_info.format,
+            subresource_range: create_info.subresource_range,
             handle,
         })
     }
+
+    /// Describe this view as an attachment of [crate::UsamiCommandBuffer::begin_rendering].
+    pub fn rendering_attachment(
+        &self,
+        layout: ImageLayout,
+        load_op: AttachmentLoadOp,
+        store_op: AttachmentStoreOp,
+        clear_value: ClearValue,
+    ) -> RenderingAttachmentInfo<'static> {
+        RenderingAttachmentInfo::default()
+            .image_view(self.handle)
+            .image_layout(layout)
+            .load_op(load_op)
+            .store_op(store_op)
+            .clear_value(clear_value)
+    }
 }
 
 impl Drop for UsamiImageView {
@@ -128,7 +353,7 @@ pub struct UsamiSampler {
 }
 
 impl UsamiSampler {
-    pub fn new(device: &Arc<UsamiDevice>, create_info: SamplerCreateInfo) -> VkResult<Self> {
+    pub fn new(device: &Arc<UsamiDevice>, create_info: SamplerCreateInfo) -> UsamiResult<Self> {
         let handle = unsafe { device.handle.create_sampler(&create_info, None)? };
 
         Ok(Self {
@@ -150,8 +375,8 @@ impl UsamiDevice {
         name: String,
         create_info: ImageCreateInfo,
         memory_flags: MemoryPropertyFlags,
-    ) -> VkResult<UsamiImage> {
-        let image = UsamiImage::new(device, create_info, memory_flags)?;
+    ) -> UsamiResult<UsamiImage> {
+        let image = UsamiImage::new(device, create_info, memory_flags).context(&name)?;
 
         device.set_debug_name(name, image.handle)?;
 
@@ -162,8 +387,8 @@ impl UsamiDevice {
         device: &Arc<UsamiDevice>,
         name: String,
         create_info: ImageViewCreateInfo,
-    ) -> VkResult<UsamiImageView> {
-        let image_view = UsamiImageView::new(device, create_info)?;
+    ) -> UsamiResult<UsamiImageView> {
+        let image_view = UsamiImageView::new(device, create_info).context(&name)?;
 
         device.set_debug_name(name, image_view.handle)?;
 
@@ -174,8 +399,8 @@ impl UsamiDevice {
         device: &Arc<UsamiDevice>,
         name: String,
         create_info: SamplerCreateInfo,
-    ) -> VkResult<UsamiSampler> {
-        let image = UsamiSampler::new(device, create_info)?;
+    ) -> UsamiResult<UsamiSampler> {
+        let image = UsamiSampler::new(device, create_info).context(&name)?;
 
         device.set_debug_name(name, image.handle)?;
 
@@ -189,7 +414,7 @@ impl UsamiDevice {
         raw_image: &RawImageData,
         usage: ImageUsageFlags,
         layout: ImageLayout,
-    ) -> VkResult<UsamiImage> {
+    ) -> UsamiResult<UsamiImage> {
         let queue_family_indices = &[device.vk_queue_index];
         let image_create_info = ImageCreateInfo::default()
             .image_type(ImageType::TYPE_2D)
@@ -209,23 +434,21 @@ impl UsamiDevice {
             MemoryPropertyFlags::empty(),
         )?;
 
-        let temporary_buffer = Self::create_buffer(
-            device,
-            format!("{name}_temp_buffer"),
-            BufferCreateFlags::empty(),
-            SharingMode::EXCLUSIVE,
-            BufferUsageFlags::TRANSFER_SRC,
-            &raw_image.data,
-        )?;
-
-        UsamiDevice::copy_buffer_to_image(
+        let mut staging_belt = UsamiStagingBelt::new(
             device,
+            format!("{name}_staging_belt"),
             command_pool,
-            &temporary_buffer,
+            raw_image.data.len() as u64,
+        );
+
+        staging_belt.upload_image(
             &image,
-            layout,
+            ImageAspectFlags::COLOR,
+            &raw_image.data,
             &raw_image.copy_regions(ImageAspectFlags::COLOR),
+            layout,
         )?;
+        staging_belt.flush()?;
 
         Ok(image)
     }
@@ -256,10 +479,10 @@ impl RawImageArrayInfo {
             .image_extent(self.extent)
     }
 
-    pub fn size(&self, format: Format) -> u32 {
-        let pixel_count = self.extent.height * self.extent.width;
+    pub fn size(&self, format: Format) -> UsamiResult<u32> {
+        let format_info = get_format_info(format)?;
 
-        pixel_count * utils::get_format_size(format)
+        Ok(format_info.size_for_extent(self.extent, format_info.aspect_mask) as u32)
     }
 }
 
@@ -269,16 +492,14 @@ pub struct RawImageLevelInfo {
 }
 
 impl RawImageLevelInfo {
-    pub fn size(&self, format: Format) -> u32 {
+    pub fn size(&self, format: Format) -> UsamiResult<u32> {
         let mut size = 0;
 
         for layer in &self.layers {
-            let pixel_count = layer.extent.height * layer.extent.width;
-
-            size += pixel_count * utils::get_format_size(format);
+            size += layer.size(format)?;
         }
 
-        size
+        Ok(size)
     }
 }
 
@@ -310,7 +531,7 @@ impl RawImageData {
         self.level_infos.len()
     }
 
-    pub fn size(&self, level: u32) -> Option<u32> {
+    pub fn size(&self, level: u32) -> Option<UsamiResult<u32>> {
         self.level_infos
             .get(level as usize)
             .map(|level_info| level_info.size(self.format))
@@ -5,7 +5,6 @@ use std::{
 
 use ash::{
     ext::debug_utils::Instance as DebugUtilsInstance,
-    prelude::*,
     vk::{
         self, Bool32, DebugUtilsMessageSeverityFlagsEXT, DebugUtilsMessageTypeFlagsEXT,
         DebugUtilsMessengerCallbackDataEXT, DebugUtilsMessengerEXT,
@@ -13,6 +12,8 @@ use ash::{
     Entry,
 };
 
+use crate::UsamiResult;
+
 pub struct UsamiInstance {
     pub vk_entry: Entry,
     pub vk_instance: ash::Instance,
@@ -56,19 +57,19 @@ impl UsamiInstance {
         api_version: u32,
         extensions: &[String],
         enable_validation: bool,
-    ) -> VkResult<Self> {
-        let app_name = CString::new(app_name).expect("cannot create CString for application name");
-        let engine_name = CString::new(engine_name).expect("cannot create CString for engine name");
+    ) -> UsamiResult<Self> {
+        let app_name = CString::new(app_name)?;
+        let engine_name = CString::new(engine_name)?;
         let application_info = vk::ApplicationInfo::default()
             .application_name(app_name.as_c_str())
             .application_version(0)
             .engine_name(engine_name.as_c_str())
             .engine_version(0)
             .api_version(api_version);
-        let extensions_cstring: Vec<CString> = extensions
+        let extensions_cstring = extensions
             .iter()
-            .map(|name| CString::new(name.as_str()).unwrap())
-            .collect();
+            .map(|name| CString::new(name.as_str()))
+            .collect::<Result<Vec<CString>, _>>()?;
 
         let extensions_raw: Vec<*const c_char> = extensions_cstring
             .iter()
@@ -86,7 +87,7 @@ impl UsamiInstance {
             create_info = create_info.enabled_layer_names(validation_layers_slice);
         }
 
-        let vk_entry = unsafe { Entry::load().expect("Cannot load VK library") };
+        let vk_entry = unsafe { Entry::load()? };
         let vk_instance = unsafe { vk_entry.create_instance(&create_info, None)? };
 
         let vk_debug_info = vk::DebugUtilsMessengerCreateInfoEXT::default()
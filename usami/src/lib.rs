@@ -1,30 +1,71 @@
 #![allow(clippy::too_many_arguments, unstable_name_collisions)]
 
+pub mod bindless;
 pub mod buffer;
 pub mod command;
 pub mod descriptor;
+pub mod descriptor_buffer;
 pub mod device;
+pub mod error;
+pub mod features;
 pub mod fence;
+pub mod format;
 pub mod framebuffer;
+pub mod graph;
 pub mod image;
 pub mod instance;
 pub mod memory;
 pub mod pipeline;
 pub mod queue;
+pub mod readback;
+pub mod reflection;
 pub mod renderpass;
+pub mod semaphore;
 pub mod shader;
+pub mod staging;
+pub mod sync;
 pub mod utils;
 
+pub use crate::bindless::{UsamiBindlessCapacities, UsamiBindlessKind, UsamiBindlessTable};
 pub use crate::buffer::{UsamiBuffer, UsamiBufferView};
 pub use crate::command::{UsamiCommandBuffer, UsamiCommandPool};
-pub use crate::descriptor::{UsamiDescriptorPool, UsamiDescriptorSet};
-pub use crate::device::{UsamiDevice, UsamiPresentation};
+pub use crate::descriptor::{
+    UsamiDescriptorAllocator, UsamiDescriptorPool, UsamiDescriptorSet, UsamiDescriptorWriter,
+};
+pub use crate::descriptor_buffer::{
+    UsamiDescriptorBuffer, UsamiDescriptorBufferSet, UsamiDescriptorBufferWriter,
+};
+pub use crate::device::{
+    UsamiDevice, UsamiDeviceBuilder, UsamiPhysicalDeviceSelector, UsamiPresentation,
+};
+pub use crate::error::{Error, ResultExt, UsamiResult};
+pub use crate::features::UsamiDeviceFeatures;
 pub use crate::fence::UsamiFence;
+pub use crate::format::UsamiFormatInfo;
 pub use crate::framebuffer::UsamiFramebuffer;
+pub use crate::graph::{
+    UsamiGraphAccess, UsamiGraphBuffer, UsamiGraphImage, UsamiRenderGraph,
+    UsamiRenderGraphResources,
+};
 pub use crate::image::{UsamiImage, UsamiImageView, UsamiSampler};
 pub use crate::instance::UsamiInstance;
-pub use crate::memory::UsamiDeviceMemory;
-pub use crate::pipeline::{UsamiPipeline, UsamiPipelineLayout};
-pub use crate::queue::UsamiQueue;
+pub use crate::memory::{
+    MappedSlice, MappedSliceMut, UsamiAllocation, UsamiAllocator, UsamiDeviceMemory,
+};
+pub use crate::pipeline::{
+    UsamiGraphicsPipelineBuilder, UsamiPipeline, UsamiPipelineCache, UsamiPipelineExecutable,
+    UsamiPipelineExecutableInternalRepresentation, UsamiPipelineExecutableStatistic,
+    UsamiPipelineExecutableStatisticValue, UsamiPipelineLayout, UsamiSpecializationBuilder,
+    UsamiSpecializationValue,
+};
+pub use crate::queue::{UsamiQueue, UsamiQueueKind, UsamiQueueRegistry};
+pub use crate::readback::UsamiImageReadback;
+pub use crate::reflection::{
+    UsamiDescriptorBinding, UsamiEntryPoint, UsamiScalarKind, UsamiShaderReflection,
+    UsamiSpecializationConstant,
+};
 pub use crate::renderpass::UsamiRenderPass;
+pub use crate::semaphore::{UsamiSemaphore, UsamiSemaphoreSubmit, UsamiSubmit};
 pub use crate::shader::UsamiShader;
+pub use crate::staging::{UsamiStagingBelt, UsamiStagingDownload};
+pub use crate::sync::{UsamiAccess, UsamiResourceState, UsamiTrackedState};
@@ -1,14 +1,17 @@
-use std::{ffi::c_void, sync::Arc};
-
-use ash::{
-    prelude::*,
-    vk::{
-        self, DeviceMemory, MappedMemoryRange, MemoryAllocateInfo, MemoryMapFlags,
-        MemoryPropertyFlags, MemoryRequirements, WHOLE_SIZE,
-    },
+use std::{
+    ffi::c_void,
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
 };
 
-use crate::UsamiDevice;
+use ash::vk::{
+    self, DeviceMemory, MappedMemoryRange, MemoryAllocateFlags, MemoryAllocateFlagsInfo,
+    MemoryAllocateInfo, MemoryMapFlags, MemoryPropertyFlags, MemoryRequirements, WHOLE_SIZE,
+};
+
+use bytemuck::Pod;
+
+use crate::{Error, UsamiDevice, UsamiResult};
 
 pub struct UsamiDeviceMemory {
     device: Arc<UsamiDevice>,
@@ -22,7 +25,7 @@ impl UsamiDeviceMemory {
         device: &Arc<UsamiDevice>,
         requirements: MemoryRequirements,
         flags: MemoryPropertyFlags,
-    ) -> VkResult<Self> {
+    ) -> UsamiResult<Self> {
         let allocate_info = MemoryAllocateInfo::default()
             .allocation_size(requirements.size)
             .memory_type_index(device.find_memory_type(&requirements, flags)?);
@@ -40,13 +43,13 @@ impl UsamiDeviceMemory {
     /// # Safety
     ///
     /// Must be called once.
-    pub unsafe fn map(&self) -> VkResult<*mut c_void> {
-        self.device.handle.map_memory(
+    pub unsafe fn map(&self) -> UsamiResult<*mut c_void> {
+        Ok(self.device.handle.map_memory(
             self.handle,
             0,
             self.allocation_size,
             MemoryMapFlags::empty(),
-        )
+        )?)
     }
 
     /// # Safety
@@ -56,7 +59,7 @@ impl UsamiDeviceMemory {
         self.device.handle.unmap_memory(self.handle)
     }
 
-    pub fn flush(&self, offset: u64, size: u64) -> VkResult<()> {
+    pub fn flush(&self, offset: u64, size: u64) -> UsamiResult<()> {
         let alignment = self
             .device
             .physical_device
@@ -90,22 +93,26 @@ impl UsamiDeviceMemory {
                 .flush_mapped_memory_ranges(&[MappedMemoryRange::default()
                     .memory(self.handle)
                     .offset(aligned_offset)
-                    .size(aligned_size)])
+                    .size(aligned_size)])?;
         }
+
+        Ok(())
     }
 
-    pub fn invalidate(&self, offset: u64, size: u64) -> VkResult<()> {
+    pub fn invalidate(&self, offset: u64, size: u64) -> UsamiResult<()> {
         unsafe {
             self.device
                 .handle
                 .invalidate_mapped_memory_ranges(&[MappedMemoryRange::default()
                     .memory(self.handle)
                     .offset(offset)
-                    .size(size)])
+                    .size(size)])?;
         }
+
+        Ok(())
     }
 
-    pub fn read_to_vec(&self) -> VkResult<Vec<u8>> {
+    pub fn read_to_vec(&self) -> UsamiResult<Vec<u8>> {
         let mut res = Vec::new();
 
         let allocation_size = self.allocation_size as usize;
@@ -139,7 +146,7 @@ impl UsamiDevice {
         &self,
         req: &MemoryRequirements,
         flags: MemoryPropertyFlags,
-    ) -> VkResult<u32> {
+    ) -> UsamiResult<u32> {
         self.physical_device.memory_properties.memory_types
             [..self.physical_device.memory_properties.memory_type_count as _]
             .iter()
@@ -149,6 +156,563 @@ impl UsamiDevice {
                     && memory_type.property_flags & flags == flags
             })
             .map(|(index, _memory_type)| index as _)
-            .ok_or(vk::Result::ERROR_UNKNOWN) // TODO better error management
+            .ok_or(Error::NoSuitableMemoryType {
+                memory_type_bits: req.memory_type_bits,
+                flags,
+            })
+    }
+}
+
+/// Size of the memory blocks suballocated by [UsamiAllocator].
+pub const DEFAULT_MEMORY_BLOCK_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Pointer to persistently mapped memory.
+#[derive(Clone, Copy, Debug)]
+struct MappedPtr(*mut u8);
+
+// SAFETY: The pointer is only dereferenced through the allocation owning the range it points to.
+unsafe impl Send for MappedPtr {}
+unsafe impl Sync for MappedPtr {}
+
+struct UsamiMemoryBlock {
+    handle: DeviceMemory,
+    memory_type_index: u32,
+    allocate_flags: MemoryAllocateFlags,
+    linear: bool,
+    dedicated: bool,
+    size: u64,
+    mapped_ptr: Option<MappedPtr>,
+    /// Free ranges as (offset, size) sorted by offset.
+    free_ranges: Vec<(u64, u64)>,
+    allocation_count: usize,
+}
+
+impl UsamiMemoryBlock {
+    fn allocate(&mut self, size: u64, alignment: u64) -> Option<u64> {
+        let (index, offset) = self.free_ranges.iter().enumerate().find_map(
+            |(index, (range_offset, range_size))| {
+                let offset = range_offset.next_multiple_of(alignment);
+
+                if offset + size <= range_offset + range_size {
+                    Some((index, offset))
+                } else {
+                    None
+                }
+            },
+        )?;
+
+        let (range_offset, range_size) = self.free_ranges.remove(index);
+        let range_end = range_offset + range_size;
+
+        if offset + size < range_end {
+            self.free_ranges
+                .insert(index, (offset + size, range_end - offset - size));
+        }
+
+        if range_offset < offset {
+            self.free_ranges
+                .insert(index, (range_offset, offset - range_offset));
+        }
+
+        self.allocation_count += 1;
+
+        Some(offset)
+    }
+
+    fn free(&mut self, offset: u64, size: u64) {
+        let index = self
+            .free_ranges
+            .partition_point(|(range_offset, _)| *range_offset < offset);
+
+        self.free_ranges.insert(index, (offset, size));
+
+        // Merge with the next range and then with the previous one.
+        if index + 1 < self.free_ranges.len() {
+            let (next_offset, next_size) = self.free_ranges[index + 1];
+
+            if offset + size == next_offset {
+                self.free_ranges[index].1 += next_size;
+                self.free_ranges.remove(index + 1);
+            }
+        }
+
+        if index > 0 {
+            let (previous_offset, previous_size) = self.free_ranges[index - 1];
+
+            if previous_offset + previous_size == offset {
+                self.free_ranges[index - 1].1 += self.free_ranges[index].1;
+                self.free_ranges.remove(index);
+            }
+        }
+
+        self.allocation_count -= 1;
+    }
+
+    fn used_size(&self) -> u64 {
+        self.size - self.free_ranges.iter().map(|(_, size)| size).sum::<u64>()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UsamiAllocatorStatistics {
+    pub block_count: usize,
+    pub allocation_count: usize,
+    /// Total size of the memory allocated from Vulkan.
+    pub allocated_size: u64,
+    /// Total size handed out to allocations, including alignment padding.
+    pub used_size: u64,
+}
+
+/// First-fit free-list allocator suballocating memory blocks per memory type.
+///
+/// Linear and optimal resources never share a block so `bufferImageGranularity` is always respected.
+/// Blocks are also split by [MemoryAllocateFlags] so device addresses are only requested when needed.
+/// Host visible blocks are persistently mapped.
+pub struct UsamiAllocator {
+    block_size: u64,
+    blocks: Mutex<Vec<Option<UsamiMemoryBlock>>>,
+}
+
+impl UsamiAllocator {
+    pub fn new(block_size: u64) -> Self {
+        Self {
+            block_size,
+            blocks: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn statistics(&self) -> UsamiAllocatorStatistics {
+        let blocks = self.blocks.lock().unwrap();
+
+        blocks
+            .iter()
+            .flatten()
+            .fold(UsamiAllocatorStatistics::default(), |mut stats, block| {
+                stats.block_count += 1;
+                stats.allocation_count += block.allocation_count;
+                stats.allocated_size += block.size;
+                stats.used_size += block.used_size();
+                stats
+            })
+    }
+
+    fn allocate(
+        &self,
+        device: &UsamiDevice,
+        requirements: &MemoryRequirements,
+        flags: MemoryPropertyFlags,
+        allocate_flags: MemoryAllocateFlags,
+        linear: bool,
+    ) -> UsamiResult<UsamiBlockRange> {
+        let memory_type_index = device.find_memory_type(requirements, flags)?;
+        let memory_type =
+            device.physical_device.memory_properties.memory_types[memory_type_index as usize];
+        let property_flags = memory_type.property_flags;
+        let host_visible = property_flags.contains(MemoryPropertyFlags::HOST_VISIBLE);
+
+        // Keep non coherent allocations on their own atoms so flushes and invalidations never overlap.
+        let (size, alignment) =
+            if host_visible && !property_flags.contains(MemoryPropertyFlags::HOST_COHERENT) {
+                let atom_size = device
+                    .physical_device
+                    .properties
+                    .limits
+                    .non_coherent_atom_size;
+
+                (
+                    requirements.size.next_multiple_of(atom_size),
+                    requirements.alignment.max(atom_size),
+                )
+            } else {
+                (requirements.size, requirements.alignment)
+            };
+
+        let mut blocks = self.blocks.lock().unwrap();
+
+        for (block_index, block) in blocks.iter_mut().enumerate() {
+            let Some(block) = block else {
+                continue;
+            };
+
+            if block.dedicated
+                || block.memory_type_index != memory_type_index
+                || block.allocate_flags != allocate_flags
+                || block.linear != linear
+            {
+                continue;
+            }
+
+            if let Some(offset) = block.allocate(size, alignment) {
+                return Ok(UsamiBlockRange {
+                    block_index,
+                    offset,
+                    size,
+                    memory: block.handle,
+                    property_flags,
+                    mapped_ptr: block.mapped_ptr,
+                });
+            }
+        }
+
+        let heap_size = device.physical_device.memory_properties.memory_heaps
+            [memory_type.heap_index as usize]
+            .size;
+        let block_size = self.block_size.min(heap_size / 8).max(1);
+        let dedicated = size > block_size;
+        let block_size = block_size.max(size);
+
+        let mut allocate_flags_info = MemoryAllocateFlagsInfo::default().flags(allocate_flags);
+        let mut allocate_info = MemoryAllocateInfo::default()
+            .allocation_size(block_size)
+            .memory_type_index(memory_type_index);
+
+        if !allocate_flags.is_empty() {
+            allocate_info = allocate_info.push_next(&mut allocate_flags_info);
+        }
+
+        let handle = unsafe { device.handle.allocate_memory(&allocate_info, None)? };
+
+        let mapped_ptr = if host_visible {
+            let result = unsafe {
+                device
+                    .handle
+                    .map_memory(handle, 0, WHOLE_SIZE, MemoryMapFlags::empty())
+            };
+
+            match result {
+                Ok(ptr) => Some(MappedPtr(ptr as *mut u8)),
+                Err(error) => {
+                    unsafe { device.handle.free_memory(handle, None) };
+
+                    return Err(error.into());
+                }
+            }
+        } else {
+            None
+        };
+
+        let mut block = UsamiMemoryBlock {
+            handle,
+            memory_type_index,
+            allocate_flags,
+            linear,
+            dedicated,
+            size: block_size,
+            mapped_ptr,
+            free_ranges: vec![(0, block_size)],
+            allocation_count: 0,
+        };
+
+        let offset = block
+            .allocate(size, alignment)
+            .expect("New block cannot fit the allocation");
+
+        let block_index = match blocks.iter().position(Option::is_none) {
+            Some(index) => index,
+            None => {
+                blocks.push(None);
+                blocks.len() - 1
+            }
+        };
+
+        blocks[block_index] = Some(block);
+
+        Ok(UsamiBlockRange {
+            block_index,
+            offset,
+            size,
+            memory: handle,
+            property_flags,
+            mapped_ptr,
+        })
+    }
+
+    fn free(&self, device: &ash::Device, block_index: usize, offset: u64, size: u64) {
+        let mut blocks = self.blocks.lock().unwrap();
+        let slot = &mut blocks[block_index];
+        let block = slot
+            .as_mut()
+            .expect("Allocation freed from a destroyed block");
+
+        block.free(offset, size);
+
+        // Dedicated blocks are only used once, other blocks are kept around for future allocations.
+        if block.dedicated && block.allocation_count == 0 {
+            unsafe { device.free_memory(block.handle, None) };
+            *slot = None;
+        }
+    }
+
+    /// Free all the blocks that are not used by any allocation.
+    pub fn trim(&self, device: &ash::Device) {
+        let mut blocks = self.blocks.lock().unwrap();
+
+        for slot in blocks.iter_mut() {
+            if let Some(block) = slot {
+                if block.allocation_count == 0 {
+                    unsafe { device.free_memory(block.handle, None) };
+                    *slot = None;
+                }
+            }
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Must only be called when destroying the device, after every allocation was dropped.
+    pub unsafe fn destroy(&self, device: &ash::Device) {
+        for block in self.blocks.lock().unwrap().drain(..).flatten() {
+            device.free_memory(block.handle, None);
+        }
+    }
+}
+
+struct UsamiBlockRange {
+    block_index: usize,
+    offset: u64,
+    size: u64,
+    memory: DeviceMemory,
+    property_flags: MemoryPropertyFlags,
+    mapped_ptr: Option<MappedPtr>,
+}
+
+/// A range of a memory block handed out by [UsamiAllocator].
+pub struct UsamiAllocation {
+    device: Arc<UsamiDevice>,
+    block_index: usize,
+    range_size: u64,
+    pub memory: DeviceMemory,
+    pub offset: u64,
+    pub size: u64,
+    pub property_flags: MemoryPropertyFlags,
+    mapped_ptr: Option<MappedPtr>,
+}
+
+impl UsamiAllocation {
+    pub fn new(
+        device: &Arc<UsamiDevice>,
+        requirements: MemoryRequirements,
+        flags: MemoryPropertyFlags,
+        allocate_flags: MemoryAllocateFlags,
+        linear: bool,
+    ) -> UsamiResult<Self> {
+        let range =
+            device
+                .allocator
+                .allocate(device, &requirements, flags, allocate_flags, linear)?;
+
+        Ok(Self {
+            device: device.clone(),
+            block_index: range.block_index,
+            range_size: range.size,
+            memory: range.memory,
+            offset: range.offset,
+            size: requirements.size,
+            property_flags: range.property_flags,
+            mapped_ptr: range.mapped_ptr,
+        })
+    }
+
+    pub fn is_host_coherent(&self) -> bool {
+        self.property_flags
+            .contains(MemoryPropertyFlags::HOST_COHERENT)
+    }
+
+    /// Return the persistently mapped pointer of this allocation if it is host visible.
+    pub fn mapped_ptr(&self) -> Option<*mut u8> {
+        self.mapped_ptr
+            .map(|ptr| unsafe { ptr.0.add(self.offset as usize) })
+    }
+
+    fn mapped_range(&self, offset: u64, size: u64) -> MappedMemoryRange<'static> {
+        let atom_size = self
+            .device
+            .physical_device
+            .properties
+            .limits
+            .non_coherent_atom_size;
+        let size = if size == WHOLE_SIZE {
+            self.size - offset
+        } else {
+            size
+        };
+
+        // Allocations of non coherent memory are aligned on atoms so the aligned range stays inside the allocation.
+        let start = (self.offset + offset) / atom_size * atom_size;
+        let end = (self.offset + offset + size).next_multiple_of(atom_size);
+
+        MappedMemoryRange::default()
+            .memory(self.memory)
+            .offset(start)
+            .size(end - start)
+    }
+
+    /// Make host writes in the given range visible to the device, does nothing on coherent memory.
+    pub fn flush(&self, offset: u64, size: u64) -> UsamiResult<()> {
+        if self.is_host_coherent() {
+            return Ok(());
+        }
+
+        unsafe {
+            self.device
+                .handle
+                .flush_mapped_memory_ranges(&[self.mapped_range(offset, size)])?;
+        }
+
+        Ok(())
+    }
+
+    /// Make device writes in the given range visible to the host, does nothing on coherent memory.
+    pub fn invalidate(&self, offset: u64, size: u64) -> UsamiResult<()> {
+        if self.is_host_coherent() {
+            return Ok(());
+        }
+
+        unsafe {
+            self.device
+                .handle
+                .invalidate_mapped_memory_ranges(&[self.mapped_range(offset, size)])?;
+        }
+
+        Ok(())
+    }
+
+    fn mapped_bytes(&self) -> UsamiResult<*mut u8> {
+        self.mapped_ptr().ok_or(Error::MemoryNotHostVisible)
+    }
+
+    /// Return a typed view of the allocation, device writes are made visible before returning.
+    pub fn mapped_slice<T: Pod>(&self) -> UsamiResult<MappedSlice<'_, T>> {
+        let ptr = self.mapped_bytes()?;
+
+        self.invalidate(0, WHOLE_SIZE)?;
+
+        // SAFETY: The range is mapped for the lifetime of the allocation and not mutably borrowed.
+        let bytes = unsafe { std::slice::from_raw_parts(ptr, self.size as usize) };
+        let len = bytes.len() - bytes.len() % std::mem::size_of::<T>();
+
+        Ok(MappedSlice {
+            data: bytemuck::try_cast_slice(&bytes[..len])?,
+        })
+    }
+
+    /// Return a mutable typed view of the allocation, host writes must be flushed before it is dropped.
+    pub fn mapped_slice_mut<T: Pod>(&mut self) -> UsamiResult<MappedSliceMut<'_, T>> {
+        let ptr = self.mapped_bytes()?;
+
+        self.invalidate(0, WHOLE_SIZE)?;
+
+        // SAFETY: The range is mapped for the lifetime of the allocation and exclusively borrowed.
+        let bytes = unsafe { std::slice::from_raw_parts_mut(ptr, self.size as usize) };
+        let len = bytes.len() - bytes.len() % std::mem::size_of::<T>();
+
+        Ok(MappedSliceMut {
+            allocation: self,
+            data: bytemuck::try_cast_slice_mut(&mut bytes[..len])?,
+            dirty: false,
+        })
+    }
+
+    pub fn read_to_vec(&self) -> UsamiResult<Vec<u8>> {
+        let ptr = self.mapped_ptr().ok_or(Error::MemoryNotHostVisible)?;
+
+        self.invalidate(0, WHOLE_SIZE)?;
+
+        let mut res = vec![0; self.size as usize];
+
+        unsafe {
+            std::ptr::copy(ptr, res.as_mut_ptr(), res.len());
+        }
+
+        Ok(res)
+    }
+
+    pub fn write_from_slice<T: Copy>(&mut self, data: &[T]) -> UsamiResult<()> {
+        let ptr = self.mapped_ptr().ok_or(Error::MemoryNotHostVisible)?;
+        let size = std::mem::size_of_val(data);
+
+        if size as u64 > self.size {
+            return Err(Error::OutOfBounds {
+                start: 0,
+                end: size as u64,
+                limit: self.size,
+            });
+        }
+
+        unsafe {
+            std::ptr::copy(data.as_ptr() as *const u8, ptr, size);
+        }
+
+        self.flush(0, size as u64)
+    }
+}
+
+impl Drop for UsamiAllocation {
+    fn drop(&mut self) {
+        self.device.allocator.free(
+            &self.device.handle,
+            self.block_index,
+            self.offset,
+            self.range_size,
+        );
+    }
+}
+
+/// Persistently mapped memory seen as a slice of `T`.
+pub struct MappedSlice<'a, T: Pod> {
+    data: &'a [T],
+}
+
+impl<'a, T: Pod> Deref for MappedSlice<'a, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        self.data
+    }
+}
+
+/// Mutable persistently mapped memory seen as a slice of `T`.
+///
+/// Writes must be made visible to the device with [`MappedSliceMut::flush`] before dropping it.
+pub struct MappedSliceMut<'a, T: Pod> {
+    allocation: &'a UsamiAllocation,
+    data: &'a mut [T],
+    dirty: bool,
+}
+
+impl<'a, T: Pod> MappedSliceMut<'a, T> {
+    /// Make the host writes visible to the device.
+    pub fn flush(&mut self) -> UsamiResult<()> {
+        if self.dirty {
+            self.allocation
+                .flush(0, std::mem::size_of_val(self.data) as u64)?;
+            self.dirty = false;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, T: Pod> Deref for MappedSliceMut<'a, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        self.data
+    }
+}
+
+impl<'a, T: Pod> DerefMut for MappedSliceMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.dirty = true;
+        self.data
+    }
+}
+
+impl<'a, T: Pod> Drop for MappedSliceMut<'a, T> {
+    fn drop(&mut self) {
+        debug_assert!(
+            !self.dirty || std::thread::panicking(),
+            "Mapped memory dropped without being flushed"
+        );
     }
 }
@@ -1,19 +1,40 @@
-use std::sync::Arc;
+use std::{
+    ffi::{CStr, CString},
+    sync::Arc,
+};
 
 use ash::{
-    prelude::*,
+    khr::pipeline_executable_properties::Device as PipelineExecutablePropertiesDevice,
     vk::{
-        ComputePipelineCreateInfo, DescriptorSetLayout, GraphicsPipelineCreateInfo, Pipeline,
-        PipelineCache, PipelineLayout, PipelineLayoutCreateFlags, PipelineLayoutCreateInfo,
-        PushConstantRange,
+        self, BlendFactor, BlendOp, ColorComponentFlags, CompareOp, ComputePipelineCreateInfo,
+        CullModeFlags, DescriptorSetLayout, DescriptorSetLayoutBinding,
+        DescriptorSetLayoutCreateInfo, DynamicState, Format, FrontFace, GraphicsPipelineCreateInfo,
+        Pipeline, PipelineCache, PipelineCacheCreateInfo, PipelineColorBlendAttachmentState,
+        PipelineColorBlendStateCreateInfo, PipelineCreateFlags,
+        PipelineDepthStencilStateCreateInfo, PipelineDynamicStateCreateInfo,
+        PipelineExecutableInfoKHR, PipelineExecutableStatisticFormatKHR, PipelineInfoKHR,
+        PipelineInputAssemblyStateCreateInfo, PipelineLayout, PipelineLayoutCreateFlags,
+        PipelineLayoutCreateInfo, PipelineMultisampleStateCreateInfo,
+        PipelineRasterizationStateCreateInfo, PipelineRenderingCreateInfo,
+        PipelineShaderStageCreateInfo, PipelineTessellationStateCreateInfo,
+        PipelineVertexInputStateCreateInfo, PipelineViewportStateCreateInfo, PolygonMode,
+        PrimitiveTopology, PushConstantRange, Rect2D, RenderPass, SampleCountFlags,
+        ShaderStageFlags, SpecializationInfo, SpecializationMapEntry, StencilOp, StencilOpState,
+        VertexInputAttributeDescription, VertexInputBindingDescription, VertexInputRate, Viewport,
     },
 };
+use bytemuck::Pod;
 
-use crate::UsamiDevice;
+use crate::{
+    descriptor::UsamiDescriptorSetLayout, reflection, Error, ResultExt, UsamiDevice,
+    UsamiEntryPoint, UsamiRenderPass, UsamiResult, UsamiScalarKind, UsamiShader,
+};
 
 pub struct UsamiPipelineLayout {
     device: Arc<UsamiDevice>,
     pub handle: PipelineLayout,
+    /// Descriptor set layouts owned by this pipeline layout, empty when created from raw handles.
+    pub set_layouts: Vec<UsamiDescriptorSetLayout>,
 }
 
 impl UsamiPipelineLayout {
@@ -21,7 +42,7 @@ impl UsamiPipelineLayout {
         device: &Arc<UsamiDevice>,
         set_layouts: &[DescriptorSetLayout],
         push_constant_ranges: &[PushConstantRange],
-    ) -> VkResult<Self> {
+    ) -> UsamiResult<Self> {
         let create_info = PipelineLayoutCreateInfo::default()
             .set_layouts(set_layouts)
             .push_constant_ranges(push_constant_ranges)
@@ -32,6 +53,7 @@ impl UsamiPipelineLayout {
         Ok(Self {
             device: device.clone(),
             handle,
+            set_layouts: Vec::new(),
         })
     }
 }
@@ -56,7 +78,7 @@ impl UsamiPipeline {
         device: &Arc<UsamiDevice>,
         pipeline_cache: PipelineCache,
         create_infos: &[GraphicsPipelineCreateInfo],
-    ) -> VkResult<Vec<Self>> {
+    ) -> UsamiResult<Vec<Self>> {
         let result = unsafe {
             device
                 .handle
@@ -77,7 +99,7 @@ impl UsamiPipeline {
         device: &Arc<UsamiDevice>,
         pipeline_cache: PipelineCache,
         create_infos: &[ComputePipelineCreateInfo],
-    ) -> VkResult<Vec<Self>> {
+    ) -> UsamiResult<Vec<Self>> {
         let result = unsafe {
             device
                 .handle
@@ -95,33 +117,316 @@ impl UsamiPipeline {
     }
 }
 
+impl UsamiPipeline {
+    fn executable_info(&self, executable_index: u32) -> PipelineExecutableInfoKHR<'static> {
+        PipelineExecutableInfoKHR::default()
+            .pipeline(self.handle)
+            .executable_index(executable_index)
+    }
+
+    /// Return the executables compiled for this pipeline, the pipeline must be created with `CAPTURE_STATISTICS_KHR` or `CAPTURE_INTERNAL_REPRESENTATIONS_KHR`.
+    pub fn executables(&self) -> UsamiResult<Vec<UsamiPipelineExecutable>> {
+        let vk_device = self.device.pipeline_executable_properties_device()?;
+        let properties = unsafe {
+            vk_device.get_pipeline_executable_properties(
+                &PipelineInfoKHR::default().pipeline(self.handle),
+            )?
+        };
+
+        Ok(properties
+            .iter()
+            .enumerate()
+            .map(|(index, x)| UsamiPipelineExecutable {
+                index: index as u32,
+                name: c_str_to_string(x.name_as_c_str()),
+                description: c_str_to_string(x.description_as_c_str()),
+                stages: x.stages,
+                subgroup_size: x.subgroup_size,
+            })
+            .collect())
+    }
+
+    /// Return the statistics of an executable, the pipeline must be created with `CAPTURE_STATISTICS_KHR`.
+    pub fn statistics(
+        &self,
+        executable_index: u32,
+    ) -> UsamiResult<Vec<UsamiPipelineExecutableStatistic>> {
+        let vk_device = self.device.pipeline_executable_properties_device()?;
+        let statistics = unsafe {
+            vk_device.get_pipeline_executable_statistics(&self.executable_info(executable_index))?
+        };
+
+        Ok(statistics
+            .iter()
+            .map(|x| {
+                let value = unsafe {
+                    match x.format {
+                        PipelineExecutableStatisticFormatKHR::BOOL32 => {
+                            UsamiPipelineExecutableStatisticValue::Bool(x.value.b32 != vk::FALSE)
+                        }
+                        PipelineExecutableStatisticFormatKHR::INT64 => {
+                            UsamiPipelineExecutableStatisticValue::Int64(x.value.i64)
+                        }
+                        PipelineExecutableStatisticFormatKHR::FLOAT64 => {
+                            UsamiPipelineExecutableStatisticValue::Float64(x.value.f64)
+                        }
+                        _ => UsamiPipelineExecutableStatisticValue::Uint64(x.value.u64),
+                    }
+                };
+
+                UsamiPipelineExecutableStatistic {
+                    name: c_str_to_string(x.name_as_c_str()),
+                    description: c_str_to_string(x.description_as_c_str()),
+                    value,
+                }
+            })
+            .collect())
+    }
+
+    /// Return the internal representations of an executable, the pipeline must be created with `CAPTURE_INTERNAL_REPRESENTATIONS_KHR`.
+    pub fn internal_representations(
+        &self,
+        executable_index: u32,
+    ) -> UsamiResult<Vec<UsamiPipelineExecutableInternalRepresentation>> {
+        let vk_device = self.device.pipeline_executable_properties_device()?;
+        let executable_info = self.executable_info(executable_index);
+
+        // The first query only returns the size of each representation, the data is fetched by a second one.
+        let mut representations = unsafe {
+            vk_device.get_pipeline_executable_internal_representations(&executable_info)?
+        };
+        let mut representations_data: Vec<Vec<u8>> = representations
+            .iter()
+            .map(|x| vec![0; x.data_size])
+            .collect();
+
+        for (representation, data) in representations.iter_mut().zip(&mut representations_data) {
+            representation.p_data = data.as_mut_ptr().cast();
+        }
+
+        let mut count = representations.len() as u32;
+
+        unsafe {
+            (vk_device
+                .fp()
+                .get_pipeline_executable_internal_representations_khr)(
+                vk_device.device(),
+                &executable_info,
+                &mut count,
+                representations.as_mut_ptr(),
+            )
+            .result()?;
+        }
+
+        Ok(representations
+            .iter()
+            .zip(representations_data)
+            .map(|(x, mut data)| {
+                let is_text = x.is_text != vk::FALSE;
+
+                if is_text {
+                    while data.last() == Some(&0) {
+                        data.pop();
+                    }
+                }
+
+                UsamiPipelineExecutableInternalRepresentation {
+                    name: c_str_to_string(x.name_as_c_str()),
+                    description: c_str_to_string(x.description_as_c_str()),
+                    is_text,
+                    data,
+                }
+            })
+            .collect())
+    }
+}
+
 impl Drop for UsamiPipeline {
     fn drop(&mut self) {
         unsafe { self.device.handle.destroy_pipeline(self.handle, None) }
     }
 }
 
+fn c_str_to_string<E>(value: Result<&CStr, E>) -> String {
+    value
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Executable of a pipeline as reported by `VK_KHR_pipeline_executable_properties`, usually one per shader stage.
+#[derive(Clone, Debug)]
+pub struct UsamiPipelineExecutable {
+    pub index: u32,
+    pub name: String,
+    pub description: String,
+    pub stages: ShaderStageFlags,
+    pub subgroup_size: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UsamiPipelineExecutableStatisticValue {
+    Bool(bool),
+    Int64(i64),
+    Uint64(u64),
+    Float64(f64),
+}
+
+/// Driver reported statistic of a pipeline executable (register count, instruction count, ...).
+#[derive(Clone, Debug)]
+pub struct UsamiPipelineExecutableStatistic {
+    pub name: String,
+    pub description: String,
+    pub value: UsamiPipelineExecutableStatisticValue,
+}
+
+/// Driver internal representation of a pipeline executable (IR, ISA, ...).
+#[derive(Clone, Debug)]
+pub struct UsamiPipelineExecutableInternalRepresentation {
+    pub name: String,
+    pub description: String,
+    /// Textual representations have their null terminator stripped.
+    pub is_text: bool,
+    pub data: Vec<u8>,
+}
+
+pub struct UsamiPipelineCache {
+    device: Arc<UsamiDevice>,
+    pub handle: PipelineCache,
+}
+
+impl UsamiPipelineCache {
+    pub fn new(device: &Arc<UsamiDevice>, initial_data: &[u8]) -> UsamiResult<Self> {
+        let handle = unsafe {
+            device.handle.create_pipeline_cache(
+                &PipelineCacheCreateInfo::default().initial_data(initial_data),
+                None,
+            )?
+        };
+
+        Ok(Self {
+            device: device.clone(),
+            handle,
+        })
+    }
+
+    /// Return the opaque blob of the cache, including its header.
+    pub fn data(&self) -> UsamiResult<Vec<u8>> {
+        Ok(unsafe { self.device.handle.get_pipeline_cache_data(self.handle)? })
+    }
+}
+
+impl Drop for UsamiPipelineCache {
+    fn drop(&mut self) {
+        unsafe { self.device.handle.destroy_pipeline_cache(self.handle, None) }
+    }
+}
+
 impl UsamiDevice {
+    /// Return the `VK_KHR_pipeline_executable_properties` device functions if the extension is enabled.
+    pub fn pipeline_executable_properties_device(
+        &self,
+    ) -> UsamiResult<&PipelineExecutablePropertiesDevice> {
+        self.vk_pipeline_executable_properties_device
+            .as_ref()
+            .ok_or(Error::MissingExtension(
+                "VK_KHR_pipeline_executable_properties".into(),
+            ))
+    }
+
+    pub fn create_pipeline_cache(
+        device: &Arc<UsamiDevice>,
+        name: String,
+        initial_data: &[u8],
+    ) -> UsamiResult<UsamiPipelineCache> {
+        let pipeline_cache = UsamiPipelineCache::new(device, initial_data).context(&name)?;
+
+        device.set_debug_name(name, pipeline_cache.handle)?;
+
+        Ok(pipeline_cache)
+    }
+
     pub fn create_pipeline_layout(
         device: &Arc<UsamiDevice>,
         name: String,
         set_layouts: &[DescriptorSetLayout],
         push_constant_ranges: &[PushConstantRange],
-    ) -> VkResult<UsamiPipelineLayout> {
-        let pipeline_layout = UsamiPipelineLayout::new(device, set_layouts, push_constant_ranges)?;
+    ) -> UsamiResult<UsamiPipelineLayout> {
+        let pipeline_layout =
+            UsamiPipelineLayout::new(device, set_layouts, push_constant_ranges).context(&name)?;
 
         device.set_debug_name(name, pipeline_layout.handle)?;
 
         Ok(pipeline_layout)
     }
 
+    /// Create a pipeline layout and its descriptor set layouts from the merged reflection of the given entry points.
+    pub fn create_reflected_pipeline_layout(
+        device: &Arc<UsamiDevice>,
+        name: String,
+        entry_points: &[&UsamiEntryPoint],
+    ) -> UsamiResult<UsamiPipelineLayout> {
+        let descriptor_bindings =
+            reflection::merge_descriptor_bindings(entry_points).context(&name)?;
+        let push_constant_ranges = reflection::merge_push_constant_ranges(entry_points);
+
+        // Runtime arrays need a variable descriptor count, callers have to create those layouts themselves.
+        if let Some(binding) = descriptor_bindings.iter().find(|x| x.descriptor_count == 0) {
+            return Err(Error::Reflection(format!(
+                "Binding {} of set {} is a runtime array",
+                binding.binding, binding.set
+            ))
+            .with_context(name));
+        }
+
+        let set_count = descriptor_bindings
+            .iter()
+            .map(|x| x.set + 1)
+            .max()
+            .unwrap_or(0);
+
+        let mut set_layouts = Vec::new();
+
+        for set in 0..set_count {
+            let bindings: Vec<DescriptorSetLayoutBinding> = descriptor_bindings
+                .iter()
+                .filter(|x| x.set == set)
+                .map(|x| {
+                    DescriptorSetLayoutBinding::default()
+                        .binding(x.binding)
+                        .descriptor_type(x.descriptor_type)
+                        .descriptor_count(x.descriptor_count)
+                        .stage_flags(x.stage_flags)
+                })
+                .collect();
+
+            set_layouts.push(UsamiDevice::create_descriptor_set_layout(
+                device,
+                format!("{name}_set_{set}"),
+                DescriptorSetLayoutCreateInfo::default().bindings(&bindings),
+            )?);
+        }
+
+        let set_layout_handles: Vec<DescriptorSetLayout> =
+            set_layouts.iter().map(|x| x.handle).collect();
+        let mut pipeline_layout = UsamiDevice::create_pipeline_layout(
+            device,
+            name,
+            &set_layout_handles,
+            &push_constant_ranges,
+        )?;
+        pipeline_layout.set_layouts = set_layouts;
+
+        Ok(pipeline_layout)
+    }
+
     pub fn create_compute_pipelines(
         device: &Arc<UsamiDevice>,
         name: String,
         pipeline_cache: PipelineCache,
         create_infos: &[ComputePipelineCreateInfo],
-    ) -> VkResult<Vec<UsamiPipeline>> {
-        let pipelines = UsamiPipeline::new_compute(device, pipeline_cache, create_infos)?;
+    ) -> UsamiResult<Vec<UsamiPipeline>> {
+        let pipelines =
+            UsamiPipeline::new_compute(device, pipeline_cache, create_infos).context(&name)?;
 
         for (idx, pipeline) in pipelines.iter().enumerate() {
             device.set_debug_name(format!("{name}_{idx}"), pipeline.handle)?;
@@ -135,8 +440,9 @@ impl UsamiDevice {
         name: String,
         pipeline_cache: PipelineCache,
         create_infos: &[GraphicsPipelineCreateInfo],
-    ) -> VkResult<Vec<UsamiPipeline>> {
-        let pipelines = UsamiPipeline::new_graphics(device, pipeline_cache, create_infos)?;
+    ) -> UsamiResult<Vec<UsamiPipeline>> {
+        let pipelines =
+            UsamiPipeline::new_graphics(device, pipeline_cache, create_infos).context(&name)?;
 
         for (idx, pipeline) in pipelines.iter().enumerate() {
             device.set_debug_name(format!("{name}_{idx}"), pipeline.handle)?;
@@ -145,3 +451,654 @@ impl UsamiDevice {
         Ok(pipelines)
     }
 }
+
+/// Value of a specialization constant whose size is only known from the shader.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UsamiSpecializationValue {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+}
+
+impl UsamiSpecializationValue {
+    /// Scalar kind the value is encoded as when the constant is unknown to the shader.
+    pub fn kind(self) -> UsamiScalarKind {
+        match self {
+            Self::Bool(_) => UsamiScalarKind::Bool,
+            Self::Integer(_) => UsamiScalarKind::Int,
+            Self::Float(_) => UsamiScalarKind::Float,
+        }
+    }
+
+    /// Convert the value to `kind` and encode it on `size` bytes, booleans are always 32-bit.
+    pub fn encode(self, kind: UsamiScalarKind, size: usize) -> Vec<u8> {
+        let mut data = match (kind, self) {
+            (UsamiScalarKind::Bool, value) => {
+                u32::from(value.as_f64() != 0.0).to_le_bytes().to_vec()
+            }
+            (UsamiScalarKind::Int, Self::Integer(value)) => value.to_le_bytes().to_vec(),
+            (UsamiScalarKind::Int, value) => (value.as_f64() as i64).to_le_bytes().to_vec(),
+            (UsamiScalarKind::Float, value) => match size {
+                8 => value.as_f64().to_le_bytes().to_vec(),
+                2 => f32_to_f16(value.as_f64() as f32).to_le_bytes().to_vec(),
+                _ => (value.as_f64() as f32).to_le_bytes().to_vec(),
+            },
+        };
+
+        data.resize(size, 0);
+        data
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Self::Bool(value) => f64::from(u8::from(value)),
+            Self::Integer(value) => value as f64,
+            Self::Float(value) => value,
+        }
+    }
+}
+
+/// Convert a float to the bits of a half float, rounding to nearest even.
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x7F_FFFF;
+
+    if exponent == 0xFF {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7C00 | nan;
+    }
+
+    let exponent = exponent - 127 + 15;
+
+    if exponent >= 0x1F {
+        return sign | 0x7C00;
+    }
+
+    // Subnormal halves keep the implicit leading bit in their mantissa.
+    let (mantissa, shift) = if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+
+        (mantissa | 0x80_0000, (14 - exponent) as u32)
+    } else {
+        (mantissa, 13)
+    };
+
+    let half = mantissa >> shift;
+    let remainder = mantissa & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    let round = remainder > halfway || (remainder == halfway && half & 1 != 0);
+    let exponent_bits = (exponent.max(0) as u32) << 10;
+
+    // A carry out of the mantissa correctly bumps the exponent.
+    sign | (exponent_bits + half + u32::from(round)) as u16
+}
+
+/// Build the [SpecializationInfo] of a shader stage from typed constants.
+#[derive(Clone, Debug, Default)]
+pub struct UsamiSpecializationBuilder {
+    map_entries: Vec<SpecializationMapEntry>,
+    data: Vec<u8>,
+}
+
+impl UsamiSpecializationBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the value of a specialization constant, its type must match the size of the constant in the shader.
+    pub fn constant<T: Pod>(self, constant_id: u32, value: T) -> Self {
+        self.constant_bytes(constant_id, bytemuck::bytes_of(&value))
+    }
+
+    /// Set the raw value of a specialization constant, replacing any previous value.
+    pub fn constant_bytes(mut self, constant_id: u32, value: &[u8]) -> Self {
+        self.map_entries
+            .retain(|map_entry| map_entry.constant_id != constant_id);
+        self.map_entries.push(
+            SpecializationMapEntry::default()
+                .constant_id(constant_id)
+                .offset(self.data.len() as u32)
+                .size(value.len()),
+        );
+        self.data.extend_from_slice(value);
+
+        self
+    }
+
+    /// Set the value of a specialization constant converted to `kind` and encoded on `size` bytes.
+    pub fn constant_value(
+        self,
+        constant_id: u32,
+        value: UsamiSpecializationValue,
+        kind: UsamiScalarKind,
+        size: usize,
+    ) -> Self {
+        self.constant_bytes(constant_id, &value.encode(kind, size))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map_entries.is_empty()
+    }
+
+    pub fn info(&self) -> SpecializationInfo<'_> {
+        SpecializationInfo::default()
+            .map_entries(&self.map_entries)
+            .data(&self.data)
+    }
+}
+
+struct GraphicsPipelineStage<'a> {
+    stage: ShaderStageFlags,
+    shader: &'a UsamiShader,
+    entry_point: CString,
+}
+
+/// Build a single graphics pipeline with an opaque color blend state for every color attachment
+/// (a single one with render passes), no depth test and dynamic viewport and scissor by default.
+///
+/// Vertex input and input assembly are skipped for mesh shading pipelines.
+pub struct UsamiGraphicsPipelineBuilder<'a> {
+    flags: PipelineCreateFlags,
+    stages: Vec<GraphicsPipelineStage<'a>>,
+    specialization_constants: Vec<(ShaderStageFlags, u32, Vec<u8>)>,
+    vertex_bindings: Vec<VertexInputBindingDescription>,
+    vertex_attributes: Vec<VertexInputAttributeDescription>,
+    topology: PrimitiveTopology,
+    primitive_restart: bool,
+    patch_control_points: u32,
+    viewports: Vec<Viewport>,
+    scissors: Vec<Rect2D>,
+    polygon_mode: PolygonMode,
+    cull_mode: CullModeFlags,
+    front_face: FrontFace,
+    line_width: f32,
+    rasterization_samples: SampleCountFlags,
+    depth_test: bool,
+    depth_write: bool,
+    depth_compare_op: CompareOp,
+    stencil: Option<(StencilOpState, StencilOpState)>,
+    /// Derived from the color attachments when not given.
+    color_blend_attachments: Option<Vec<PipelineColorBlendAttachmentState>>,
+    dynamic_states: Vec<DynamicState>,
+    render_pass: RenderPass,
+    subpass: u32,
+    color_attachment_formats: Vec<Format>,
+    depth_attachment_format: Format,
+    stencil_attachment_format: Format,
+}
+
+impl<'a> Default for UsamiGraphicsPipelineBuilder<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> UsamiGraphicsPipelineBuilder<'a> {
+    pub fn new() -> Self {
+        Self {
+            flags: PipelineCreateFlags::empty(),
+            stages: Vec::new(),
+            specialization_constants: Vec::new(),
+            vertex_bindings: Vec::new(),
+            vertex_attributes: Vec::new(),
+            topology: PrimitiveTopology::TRIANGLE_LIST,
+            primitive_restart: false,
+            patch_control_points: 0,
+            viewports: vec![Viewport::default()],
+            scissors: vec![Rect2D::default()],
+            polygon_mode: PolygonMode::FILL,
+            cull_mode: CullModeFlags::NONE,
+            front_face: FrontFace::COUNTER_CLOCKWISE,
+            line_width: 1.0,
+            rasterization_samples: SampleCountFlags::TYPE_1,
+            depth_test: false,
+            depth_write: false,
+            depth_compare_op: CompareOp::LESS_OR_EQUAL,
+            stencil: None,
+            color_blend_attachments: None,
+            dynamic_states: vec![DynamicState::VIEWPORT, DynamicState::SCISSOR],
+            render_pass: RenderPass::null(),
+            subpass: 0,
+            color_attachment_formats: Vec::new(),
+            depth_attachment_format: Format::UNDEFINED,
+            stencil_attachment_format: Format::UNDEFINED,
+        }
+    }
+
+    /// Color blend state writing every component without blending.
+    pub fn opaque_attachment() -> PipelineColorBlendAttachmentState {
+        PipelineColorBlendAttachmentState::default()
+            .blend_enable(false)
+            .color_write_mask(ColorComponentFlags::RGBA)
+    }
+
+    /// Color blend state doing standard alpha blending.
+    pub fn alpha_blend_attachment() -> PipelineColorBlendAttachmentState {
+        PipelineColorBlendAttachmentState::default()
+            .blend_enable(true)
+            .src_color_blend_factor(BlendFactor::SRC_ALPHA)
+            .dst_color_blend_factor(BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .color_blend_op(BlendOp::ADD)
+            .src_alpha_blend_factor(BlendFactor::ONE)
+            .dst_alpha_blend_factor(BlendFactor::ZERO)
+            .alpha_blend_op(BlendOp::ADD)
+            .color_write_mask(ColorComponentFlags::RGBA)
+    }
+
+    pub fn flags(mut self, flags: PipelineCreateFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Add a shader stage using the given entry point.
+    pub fn stage_with_entry_point(
+        mut self,
+        stage: ShaderStageFlags,
+        shader: &'a UsamiShader,
+        entry_point: &str,
+    ) -> UsamiResult<Self> {
+        self.stages.push(GraphicsPipelineStage {
+            stage,
+            shader,
+            entry_point: CString::new(entry_point)?,
+        });
+
+        Ok(self)
+    }
+
+    /// Add a shader stage using the `main` entry point.
+    pub fn stage(mut self, stage: ShaderStageFlags, shader: &'a UsamiShader) -> Self {
+        self.stages.push(GraphicsPipelineStage {
+            stage,
+            shader,
+            entry_point: CString::new("main").unwrap(),
+        });
+
+        self
+    }
+
+    pub fn vertex_shader(self, shader: &'a UsamiShader) -> Self {
+        self.stage(ShaderStageFlags::VERTEX, shader)
+    }
+
+    pub fn geometry_shader(self, shader: &'a UsamiShader) -> Self {
+        self.stage(ShaderStageFlags::GEOMETRY, shader)
+    }
+
+    pub fn task_shader(self, shader: &'a UsamiShader) -> Self {
+        self.stage(ShaderStageFlags::TASK_EXT, shader)
+    }
+
+    pub fn mesh_shader(self, shader: &'a UsamiShader) -> Self {
+        self.stage(ShaderStageFlags::MESH_EXT, shader)
+    }
+
+    pub fn fragment_shader(self, shader: &'a UsamiShader) -> Self {
+        self.stage(ShaderStageFlags::FRAGMENT, shader)
+    }
+
+    /// Set a specialization constant of every stage in `stages`.
+    pub fn specialization_constant<T: Pod>(
+        self,
+        stages: ShaderStageFlags,
+        constant_id: u32,
+        value: T,
+    ) -> Self {
+        self.specialization_constant_bytes(stages, constant_id, bytemuck::bytes_of(&value))
+    }
+
+    /// Set a specialization constant of every stage in `stages` from its raw bytes.
+    pub fn specialization_constant_bytes(
+        mut self,
+        stages: ShaderStageFlags,
+        constant_id: u32,
+        value: &[u8],
+    ) -> Self {
+        self.specialization_constants
+            .push((stages, constant_id, value.to_vec()));
+
+        self
+    }
+
+    pub fn vertex_binding(
+        mut self,
+        binding: u32,
+        stride: u32,
+        input_rate: VertexInputRate,
+    ) -> Self {
+        self.vertex_bindings.push(
+            VertexInputBindingDescription::default()
+                .binding(binding)
+                .stride(stride)
+                .input_rate(input_rate),
+        );
+
+        self
+    }
+
+    pub fn vertex_attribute(
+        mut self,
+        location: u32,
+        binding: u32,
+        format: Format,
+        offset: u32,
+    ) -> Self {
+        self.vertex_attributes.push(
+            VertexInputAttributeDescription::default()
+                .location(location)
+                .binding(binding)
+                .format(format)
+                .offset(offset),
+        );
+
+        self
+    }
+
+    pub fn topology(mut self, topology: PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn primitive_restart(mut self, primitive_restart: bool) -> Self {
+        self.primitive_restart = primitive_restart;
+        self
+    }
+
+    /// Enable tessellation with patches of the given size.
+    pub fn patch_control_points(mut self, patch_control_points: u32) -> Self {
+        self.patch_control_points = patch_control_points;
+        self
+    }
+
+    /// Use a static viewport and scissor, removing them from the dynamic states.
+    pub fn viewport(mut self, viewport: Viewport, scissor: Rect2D) -> Self {
+        self.viewports = vec![viewport];
+        self.scissors = vec![scissor];
+        self.dynamic_states
+            .retain(|x| *x != DynamicState::VIEWPORT && *x != DynamicState::SCISSOR);
+        self
+    }
+
+    pub fn polygon_mode(mut self, polygon_mode: PolygonMode) -> Self {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: CullModeFlags, front_face: FrontFace) -> Self {
+        self.cull_mode = cull_mode;
+        self.front_face = front_face;
+        self
+    }
+
+    pub fn line_width(mut self, line_width: f32) -> Self {
+        self.line_width = line_width;
+        self
+    }
+
+    pub fn rasterization_samples(mut self, rasterization_samples: SampleCountFlags) -> Self {
+        self.rasterization_samples = rasterization_samples;
+        self
+    }
+
+    pub fn depth(
+        mut self,
+        depth_test: bool,
+        depth_write: bool,
+        depth_compare_op: CompareOp,
+    ) -> Self {
+        self.depth_test = depth_test;
+        self.depth_write = depth_write;
+        self.depth_compare_op = depth_compare_op;
+        self
+    }
+
+    pub fn stencil(mut self, front: StencilOpState, back: StencilOpState) -> Self {
+        self.stencil = Some((front, back));
+        self
+    }
+
+    /// Replace the color blend state of every color attachment.
+    pub fn color_blend_attachments(
+        mut self,
+        color_blend_attachments: &[PipelineColorBlendAttachmentState],
+    ) -> Self {
+        self.color_blend_attachments = Some(color_blend_attachments.to_vec());
+        self
+    }
+
+    /// Replace the dynamic states.
+    pub fn dynamic_states(mut self, dynamic_states: &[DynamicState]) -> Self {
+        self.dynamic_states = dynamic_states.to_vec();
+        self
+    }
+
+    pub fn render_pass(mut self, render_pass: &UsamiRenderPass, subpass: u32) -> Self {
+        self.render_pass = render_pass.handle;
+        self.subpass = subpass;
+        self
+    }
+
+    /// Attachment formats used with dynamic rendering, when no render pass is given.
+    pub fn rendering_formats(
+        mut self,
+        color_attachment_formats: &[Format],
+        depth_attachment_format: Format,
+        stencil_attachment_format: Format,
+    ) -> Self {
+        self.color_attachment_formats = color_attachment_formats.to_vec();
+        self.depth_attachment_format = depth_attachment_format;
+        self.stencil_attachment_format = stencil_attachment_format;
+        self
+    }
+
+    pub fn build(
+        &self,
+        device: &Arc<UsamiDevice>,
+        name: String,
+        layout: &UsamiPipelineLayout,
+    ) -> UsamiResult<UsamiPipeline> {
+        self.build_with_cache(device, name, layout, PipelineCache::null())
+    }
+
+    pub fn build_with_cache(
+        &self,
+        device: &Arc<UsamiDevice>,
+        name: String,
+        layout: &UsamiPipelineLayout,
+        pipeline_cache: PipelineCache,
+    ) -> UsamiResult<UsamiPipeline> {
+        // Pack the specialization constants of every stage first as stage infos borrow them.
+        let specializations: Vec<UsamiSpecializationBuilder> = self
+            .stages
+            .iter()
+            .map(|stage| {
+                self.specialization_constants
+                    .iter()
+                    .filter(|(stages, _, _)| stages.contains(stage.stage))
+                    .fold(
+                        UsamiSpecializationBuilder::new(),
+                        |specialization, (_, constant_id, value)| {
+                            specialization.constant_bytes(*constant_id, value)
+                        },
+                    )
+            })
+            .collect();
+        let specialization_infos: Vec<SpecializationInfo> = specializations
+            .iter()
+            .map(UsamiSpecializationBuilder::info)
+            .collect();
+        let stage_create_infos: Vec<PipelineShaderStageCreateInfo> = self
+            .stages
+            .iter()
+            .zip(&specialization_infos)
+            .map(|(stage, specialization_info)| {
+                let create_info = PipelineShaderStageCreateInfo::default()
+                    .stage(stage.stage)
+                    .module(stage.shader.handle)
+                    .name(stage.entry_point.as_c_str());
+
+                if specialization_info.map_entry_count == 0 {
+                    create_info
+                } else {
+                    create_info.specialization_info(specialization_info)
+                }
+            })
+            .collect();
+
+        let is_mesh_pipeline = self
+            .stages
+            .iter()
+            .any(|x| x.stage == ShaderStageFlags::MESH_EXT);
+
+        let vertex_input_state = PipelineVertexInputStateCreateInfo::default()
+            .vertex_binding_descriptions(&self.vertex_bindings)
+            .vertex_attribute_descriptions(&self.vertex_attributes);
+        let input_assembly_state = PipelineInputAssemblyStateCreateInfo::default()
+            .topology(self.topology)
+            .primitive_restart_enable(self.primitive_restart);
+        let tessellation_state = PipelineTessellationStateCreateInfo::default()
+            .patch_control_points(self.patch_control_points);
+        let viewport_state = PipelineViewportStateCreateInfo::default()
+            .viewports(&self.viewports)
+            .scissors(&self.scissors);
+        let rasterization_state = PipelineRasterizationStateCreateInfo::default()
+            .polygon_mode(self.polygon_mode)
+            .cull_mode(self.cull_mode)
+            .front_face(self.front_face)
+            .line_width(self.line_width);
+        let multisample_state = PipelineMultisampleStateCreateInfo::default()
+            .rasterization_samples(self.rasterization_samples);
+
+        let noop_stencil = StencilOpState::default()
+            .fail_op(StencilOp::KEEP)
+            .pass_op(StencilOp::KEEP)
+            .depth_fail_op(StencilOp::KEEP)
+            .compare_op(CompareOp::ALWAYS);
+        let (front, back) = self.stencil.unwrap_or((noop_stencil, noop_stencil));
+        let depth_stencil_state = PipelineDepthStencilStateCreateInfo::default()
+            .depth_test_enable(self.depth_test)
+            .depth_write_enable(self.depth_write)
+            .depth_compare_op(self.depth_compare_op)
+            .stencil_test_enable(self.stencil.is_some())
+            .front(front)
+            .back(back)
+            .max_depth_bounds(1.0);
+        let color_attachment_count = if self.render_pass == RenderPass::null() {
+            self.color_attachment_formats.len()
+        } else {
+            1
+        };
+        let color_blend_attachments = match &self.color_blend_attachments {
+            Some(color_blend_attachments) => {
+                // The subpass of a render pass is not known here, only dynamic rendering can be checked.
+                if self.render_pass == RenderPass::null()
+                    && color_blend_attachments.len() != color_attachment_count
+                {
+                    return Err(Error::ColorAttachmentCountMismatch {
+                        blend_attachments: color_blend_attachments.len(),
+                        color_attachments: color_attachment_count,
+                    }
+                    .with_context(name));
+                }
+
+                color_blend_attachments.clone()
+            }
+            None => vec![Self::opaque_attachment(); color_attachment_count],
+        };
+        let color_blend_state =
+            PipelineColorBlendStateCreateInfo::default().attachments(&color_blend_attachments);
+        let dynamic_state =
+            PipelineDynamicStateCreateInfo::default().dynamic_states(&self.dynamic_states);
+        let mut rendering_create_info = PipelineRenderingCreateInfo::default()
+            .color_attachment_formats(&self.color_attachment_formats)
+            .depth_attachment_format(self.depth_attachment_format)
+            .stencil_attachment_format(self.stencil_attachment_format);
+
+        let mut create_info = GraphicsPipelineCreateInfo::default()
+            .flags(self.flags)
+            .stages(&stage_create_infos)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .depth_stencil_state(&depth_stencil_state)
+            .color_blend_state(&color_blend_state)
+            .dynamic_state(&dynamic_state)
+            .layout(layout.handle)
+            .render_pass(self.render_pass)
+            .subpass(self.subpass);
+
+        if !is_mesh_pipeline {
+            create_info = create_info
+                .vertex_input_state(&vertex_input_state)
+                .input_assembly_state(&input_assembly_state);
+        }
+
+        if self.patch_control_points != 0 {
+            create_info = create_info.tessellation_state(&tessellation_state);
+        }
+
+        if self.render_pass == RenderPass::null() {
+            create_info = create_info.push_next(&mut rendering_create_info);
+        }
+
+        let mut pipelines =
+            UsamiDevice::create_graphics_pipelines(device, name, pipeline_cache, &[create_info])?;
+
+        Ok(pipelines.remove(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn specialization_value_conversion() {
+        let one = UsamiSpecializationValue::Integer(1);
+
+        assert_eq!(one.encode(UsamiScalarKind::Float, 4), 1.0f32.to_le_bytes());
+        assert_eq!(one.encode(UsamiScalarKind::Float, 8), 1.0f64.to_le_bytes());
+        assert_eq!(
+            one.encode(UsamiScalarKind::Float, 2),
+            0x3C00u16.to_le_bytes()
+        );
+        assert_eq!(one.encode(UsamiScalarKind::Bool, 4), 1u32.to_le_bytes());
+        assert_eq!(
+            UsamiSpecializationValue::Integer(-2).encode(UsamiScalarKind::Int, 2),
+            (-2i16).to_le_bytes()
+        );
+        assert_eq!(
+            UsamiSpecializationValue::Float(3.7).encode(UsamiScalarKind::Int, 4),
+            3u32.to_le_bytes()
+        );
+        assert_eq!(
+            UsamiSpecializationValue::Bool(true).encode(UsamiScalarKind::Float, 4),
+            1.0f32.to_le_bytes()
+        );
+        assert_eq!(
+            UsamiSpecializationValue::Float(0.0).encode(UsamiScalarKind::Bool, 4),
+            0u32.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn half_float_encoding() {
+        assert_eq!(f32_to_f16(0.0), 0x0000);
+        assert_eq!(f32_to_f16(-2.0), 0xC000);
+        assert_eq!(f32_to_f16(0.5), 0x3800);
+        assert_eq!(f32_to_f16(65504.0), 0x7BFF);
+        assert_eq!(f32_to_f16(65536.0), 0x7C00);
+        assert_eq!(f32_to_f16(f32::NEG_INFINITY), 0xFC00);
+        assert_eq!(f32_to_f16(f32::NAN) & 0x7E00, 0x7E00);
+        // Smallest subnormal, and a value rounded up to it.
+        assert_eq!(f32_to_f16(2.0f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_f16(2.0f32.powi(-25) * 1.5), 0x0001);
+        // 1 + 2^-11 is halfway between two halves and rounds to even.
+        assert_eq!(f32_to_f16(1.0 + 2.0f32.powi(-11)), 0x3C00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 * 2.0f32.powi(-11)), 0x3C02);
+    }
+}
@@ -1,15 +1,150 @@
 use std::sync::Arc;
 
-use ash::{
-    prelude::*,
-    vk::{Queue, SubmitInfo},
+use ash::vk::{
+    self, CommandBufferSubmitInfo, Fence, PipelineStageFlags, Queue, QueueFamilyProperties,
+    QueueFlags, Semaphore, SemaphoreSubmitInfo, SubmitInfo, SubmitInfo2,
+    TimelineSemaphoreSubmitInfo,
 };
 
-use crate::{UsamiDevice, UsamiFence};
+use crate::{Error, ResultExt, UsamiDevice, UsamiFence, UsamiResult, UsamiSubmit};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UsamiQueueKind {
+    Graphics,
+    Compute,
+    Transfer,
+}
+
+impl UsamiQueueKind {
+    pub fn queue_flags(self) -> QueueFlags {
+        match self {
+            Self::Graphics => QueueFlags::GRAPHICS,
+            Self::Compute => QueueFlags::COMPUTE,
+            Self::Transfer => QueueFlags::TRANSFER,
+        }
+    }
+
+    /// Flags that a family dedicated to this kind of work does not have.
+    fn dedicated_excluded_flags(self) -> QueueFlags {
+        match self {
+            Self::Graphics => QueueFlags::empty(),
+            Self::Compute => QueueFlags::GRAPHICS,
+            Self::Transfer => QueueFlags::GRAPHICS | QueueFlags::COMPUTE,
+        }
+    }
+
+    pub fn is_supported_by(self, queue_flags: QueueFlags) -> bool {
+        match self {
+            // Graphics and compute queues always support transfer operations.
+            Self::Transfer => queue_flags
+                .intersects(QueueFlags::TRANSFER | QueueFlags::GRAPHICS | QueueFlags::COMPUTE),
+            _ => queue_flags.contains(self.queue_flags()),
+        }
+    }
+
+    /// Return the queue family to use for this kind of work, a dedicated family is preferred.
+    pub fn find_queue_family_index(
+        self,
+        queue_family_properties: &[QueueFamilyProperties],
+    ) -> Option<u32> {
+        let excluded_flags = self.dedicated_excluded_flags();
+
+        queue_family_properties
+            .iter()
+            .position(|x| {
+                self.is_supported_by(x.queue_flags) && !x.queue_flags.intersects(excluded_flags)
+            })
+            .or_else(|| {
+                queue_family_properties
+                    .iter()
+                    .position(|x| self.is_supported_by(x.queue_flags))
+            })
+            .map(|x| x as u32)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct UsamiQueueSlot {
+    pub family_index: u32,
+    pub queue_index: u32,
+    pub handle: Queue,
+}
+
+/// Queues created alongside a [UsamiDevice].
+#[derive(Clone, Debug)]
+pub struct UsamiQueueRegistry {
+    main: UsamiQueueSlot,
+    main_queue_flags: QueueFlags,
+    slots: Vec<(UsamiQueueKind, UsamiQueueSlot)>,
+}
+
+impl UsamiQueueRegistry {
+    pub fn new(
+        main: UsamiQueueSlot,
+        main_queue_flags: QueueFlags,
+        slots: Vec<(UsamiQueueKind, UsamiQueueSlot)>,
+    ) -> Self {
+        Self {
+            main,
+            main_queue_flags,
+            slots,
+        }
+    }
+
+    /// The queue at index 0 of [UsamiDevice::vk_queue_index].
+    pub fn main(&self) -> &UsamiQueueSlot {
+        &self.main
+    }
+
+    /// Return the queue of the given kind, the main queue is used when no queue of that kind was requested.
+    pub fn get(&self, kind: UsamiQueueKind, index: usize) -> Option<&UsamiQueueSlot> {
+        if self.count(kind) == 0 {
+            if index == 0 && kind.is_supported_by(self.main_queue_flags) {
+                return Some(&self.main);
+            }
+
+            return None;
+        }
+
+        self.slots
+            .iter()
+            .filter(|(slot_kind, _)| *slot_kind == kind)
+            .map(|(_, slot)| slot)
+            .nth(index)
+    }
+
+    pub fn count(&self, kind: UsamiQueueKind) -> usize {
+        self.slots
+            .iter()
+            .filter(|(slot_kind, _)| *slot_kind == kind)
+            .count()
+    }
+
+    pub fn find_by_family(&self, family_index: u32) -> Option<&UsamiQueueSlot> {
+        std::iter::once(&self.main)
+            .chain(self.slots.iter().map(|(_, slot)| slot))
+            .find(|slot| slot.family_index == family_index)
+    }
+
+    /// All the queue family indices used by the registry, useful for concurrent sharing.
+    pub fn family_indices(&self) -> Vec<u32> {
+        let mut result = vec![self.main.family_index];
+
+        for (_, slot) in &self.slots {
+            if !result.contains(&slot.family_index) {
+                result.push(slot.family_index);
+            }
+        }
+
+        result
+    }
+}
 
 pub struct UsamiQueue {
     device: Arc<UsamiDevice>,
     pub handle: Queue,
+    pub family_index: u32,
+    pub queue_index: u32,
 }
 
 impl UsamiQueue {
@@ -17,7 +152,7 @@ impl UsamiQueue {
         device: &Arc<UsamiDevice>,
         queue_family_index: u32,
         queue_index: u32,
-    ) -> VkResult<Self> {
+    ) -> UsamiResult<Self> {
         let handle = unsafe {
             device
                 .handle
@@ -27,15 +162,157 @@ impl UsamiQueue {
         Ok(Self {
             device: device.clone(),
             handle,
+            family_index: queue_family_index,
+            queue_index,
         })
     }
 
-    pub fn submit(&self, submits: &[SubmitInfo], fence: &UsamiFence) -> VkResult<()> {
+    pub fn from_slot(device: &Arc<UsamiDevice>, slot: &UsamiQueueSlot) -> Self {
+        Self {
+            device: device.clone(),
+            handle: slot.handle,
+            family_index: slot.family_index,
+            queue_index: slot.queue_index,
+        }
+    }
+
+    pub fn submit(&self, submits: &[SubmitInfo], fence: &UsamiFence) -> UsamiResult<()> {
+        unsafe {
+            self.device
+                .handle
+                .queue_submit(self.handle, submits, fence.handle)?;
+        }
+
+        Ok(())
+    }
+
+    /// Submit batches with their semaphore waits and signals.
+    ///
+    /// `vkQueueSubmit2` is used when synchronization2 is enabled, otherwise this falls back to `vkQueueSubmit`.
+    pub fn submit2(&self, submits: &[UsamiSubmit], fence: Option<&UsamiFence>) -> UsamiResult<()> {
+        let fence = fence.map_or(Fence::null(), |x| x.handle);
+        let enabled_features = &self.device.enabled_features;
+
+        if enabled_features.vulkan13.synchronization2 == vk::TRUE {
+            let infos = submits
+                .iter()
+                .map(|submit| {
+                    (
+                        submit
+                            .wait_semaphores
+                            .iter()
+                            .map(|x| x.info())
+                            .collect::<Vec<SemaphoreSubmitInfo>>(),
+                        submit
+                            .command_buffers
+                            .iter()
+                            .map(|x| CommandBufferSubmitInfo::default().command_buffer(*x))
+                            .collect::<Vec<CommandBufferSubmitInfo>>(),
+                        submit
+                            .signal_semaphores
+                            .iter()
+                            .map(|x| x.info())
+                            .collect::<Vec<SemaphoreSubmitInfo>>(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let submit_infos = infos
+                .iter()
+                .map(|(wait_infos, command_buffer_infos, signal_infos)| {
+                    SubmitInfo2::default()
+                        .wait_semaphore_infos(wait_infos)
+                        .command_buffer_infos(command_buffer_infos)
+                        .signal_semaphore_infos(signal_infos)
+                })
+                .collect::<Vec<SubmitInfo2>>();
+
+            unsafe {
+                self.device
+                    .handle
+                    .queue_submit2(self.handle, &submit_infos, fence)?;
+            }
+
+            return Ok(());
+        }
+
+        let infos = submits
+            .iter()
+            .map(|submit| {
+                (
+                    submit
+                        .wait_semaphores
+                        .iter()
+                        .map(|x| x.semaphore)
+                        .collect::<Vec<Semaphore>>(),
+                    submit
+                        .wait_semaphores
+                        .iter()
+                        .map(|x| x.value)
+                        .collect::<Vec<u64>>(),
+                    submit
+                        .wait_semaphores
+                        .iter()
+                        .map(|x| x.legacy_stage_mask())
+                        .collect::<Vec<PipelineStageFlags>>(),
+                    submit
+                        .signal_semaphores
+                        .iter()
+                        .map(|x| x.semaphore)
+                        .collect::<Vec<Semaphore>>(),
+                    submit
+                        .signal_semaphores
+                        .iter()
+                        .map(|x| x.value)
+                        .collect::<Vec<u64>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut timeline_infos = infos
+            .iter()
+            .map(|(_, wait_values, _, _, signal_values)| {
+                TimelineSemaphoreSubmitInfo::default()
+                    .wait_semaphore_values(wait_values)
+                    .signal_semaphore_values(signal_values)
+            })
+            .collect::<Vec<TimelineSemaphoreSubmitInfo>>();
+        let use_timeline = enabled_features.vulkan12.timeline_semaphore == vk::TRUE;
+        let submit_infos = submits
+            .iter()
+            .zip(infos.iter())
+            .zip(timeline_infos.iter_mut())
+            .map(
+                |(
+                    (submit, (wait_semaphores, _, wait_stages, signal_semaphores, _)),
+                    timeline_info,
+                )| {
+                    let submit_info = SubmitInfo::default()
+                        .wait_semaphores(wait_semaphores)
+                        .wait_dst_stage_mask(wait_stages)
+                        .command_buffers(&submit.command_buffers)
+                        .signal_semaphores(signal_semaphores);
+
+                    if use_timeline {
+                        submit_info.push_next(timeline_info)
+                    } else {
+                        submit_info
+                    }
+                },
+            )
+            .collect::<Vec<SubmitInfo>>();
+
         unsafe {
             self.device
                 .handle
-                .queue_submit(self.handle, submits, fence.handle)
+                .queue_submit(self.handle, &submit_infos, fence)?;
         }
+
+        Ok(())
+    }
+
+    pub fn wait_idle(&self) -> UsamiResult<()> {
+        unsafe { self.device.handle.queue_wait_idle(self.handle)? };
+
+        Ok(())
     }
 }
 
@@ -45,11 +322,30 @@ impl UsamiDevice {
         name: String,
         queue_family_index: u32,
         queue_index: u32,
-    ) -> VkResult<UsamiQueue> {
-        let pipeline_layout = UsamiQueue::new(device, queue_family_index, queue_index)?;
+    ) -> UsamiResult<UsamiQueue> {
+        let pipeline_layout =
+            UsamiQueue::new(device, queue_family_index, queue_index).context(&name)?;
 
         device.set_debug_name(name, pipeline_layout.handle)?;
 
         Ok(pipeline_layout)
     }
+
+    pub fn get_queue(
+        device: &Arc<UsamiDevice>,
+        name: String,
+        kind: UsamiQueueKind,
+        index: usize,
+    ) -> UsamiResult<UsamiQueue> {
+        let slot = device
+            .queues
+            .get(kind, index)
+            .ok_or(Error::NoSuitableQueue { kind, index })
+            .context(&name)?;
+        let queue = UsamiQueue::from_slot(device, slot);
+
+        device.set_debug_name(name, queue.handle)?;
+
+        Ok(queue)
+    }
 }
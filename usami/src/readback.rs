@@ -0,0 +1,341 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ash::vk::{Extent3D, Format, ImageAspectFlags};
+use image::{ImageBuffer, ImageFormat, Rgba, Rgba32FImage};
+
+use crate::{
+    format::{get_format_info, UsamiComponentKind, UsamiFormatInfo, UsamiNumericFormat},
+    Error, UsamiResult,
+};
+
+/// Tightly packed content of one aspect of one mip level and array layer of an image.
+///
+/// 3D images are read back as a single layer holding every depth slice.
+pub struct UsamiImageReadback {
+    pub format: Format,
+    pub aspect_mask: ImageAspectFlags,
+    pub mip_level: u32,
+    pub array_layer: u32,
+    pub extent: Extent3D,
+    pub data: Vec<u8>,
+}
+
+impl UsamiImageReadback {
+    pub fn format_info(&self) -> UsamiResult<&'static UsamiFormatInfo> {
+        get_format_info(self.format)
+    }
+
+    /// Decode every texel to RGBA.
+    ///
+    /// Missing color components default to 0 and alpha to 1, sRGB values are left encoded and
+    /// depth or stencil values are replicated in the RGB components.
+    pub fn to_rgba32f(&self) -> UsamiResult<Vec<[f32; 4]>> {
+        let format_info = self.format_info()?;
+
+        if format_info.has_multi_texel_blocks() || !format_info.planes.is_empty() {
+            return Err(Error::UnsupportedFormat(self.format));
+        }
+
+        let expected_size = format_info.size_for_extent(self.extent, self.aspect_mask);
+
+        if self.data.len() as u64 != expected_size {
+            return Err(Error::OutOfBounds {
+                start: 0,
+                end: expected_size,
+                limit: self.data.len() as u64,
+            });
+        }
+
+        let texel_size = format_info.aspect_block_size(self.aspect_mask) as usize;
+
+        Ok(self
+            .data
+            .chunks_exact(texel_size)
+            .map(|texel| {
+                if self.aspect_mask == ImageAspectFlags::DEPTH {
+                    let depth = decode_depth(self.format, texel);
+                    [depth, depth, depth, 1.0]
+                } else if self.aspect_mask == ImageAspectFlags::STENCIL {
+                    let stencil = f32::from(texel[0]);
+                    [stencil, stencil, stencil, 1.0]
+                } else {
+                    decode_color(format_info, texel)
+                }
+            })
+            .collect())
+    }
+
+    /// Height of the saved images, depth slices are stacked vertically.
+    fn image_height(&self) -> u32 {
+        self.extent.height * self.extent.depth
+    }
+
+    /// Save as PNG, using 16 bits per channel if any component is wider than 8 bits.
+    ///
+    /// Values are clamped to [0, 1].
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> UsamiResult<()> {
+        let path = path.as_ref();
+        let format_info = self.format_info()?;
+        let pixels = self.to_rgba32f()?;
+        let wide = self.aspect_mask != ImageAspectFlags::STENCIL
+            && format_info.components.iter().any(|x| x.bits > 8);
+
+        let result = if wide {
+            let data = pixels
+                .iter()
+                .flatten()
+                .map(|x| (x.clamp(0.0, 1.0) * f32::from(u16::MAX)).round() as u16)
+                .collect();
+
+            ImageBuffer::<Rgba<u16>, Vec<u16>>::from_raw(
+                self.extent.width,
+                self.image_height(),
+                data,
+            )
+            .ok_or(Error::UnsupportedFormat(self.format))?
+            .save_with_format(path, ImageFormat::Png)
+        } else {
+            let data = pixels
+                .iter()
+                .flatten()
+                .map(|x| {
+                    if self.aspect_mask == ImageAspectFlags::STENCIL {
+                        *x as u8
+                    } else {
+                        (x.clamp(0.0, 1.0) * 255.0).round() as u8
+                    }
+                })
+                .collect();
+
+            ImageBuffer::<Rgba<u8>, Vec<u8>>::from_raw(self.extent.width, self.image_height(), data)
+                .ok_or(Error::UnsupportedFormat(self.format))?
+                .save_with_format(path, ImageFormat::Png)
+        };
+
+        result.map_err(|source| Error::Image {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Save as OpenEXR with 32-bit float channels.
+    pub fn save_exr<P: AsRef<Path>>(&self, path: P) -> UsamiResult<()> {
+        let path = path.as_ref();
+        let data = self.to_rgba32f()?.into_iter().flatten().collect();
+
+        Rgba32FImage::from_raw(self.extent.width, self.image_height(), data)
+            .ok_or(Error::UnsupportedFormat(self.format))?
+            .save_with_format(path, ImageFormat::OpenExr)
+            .map_err(|source| Error::Image {
+                path: path.to_path_buf(),
+                source,
+            })
+    }
+
+    /// Save the raw texel data along with a JSON sidecar (same path with a `json` extension)
+    /// describing its layout.
+    pub fn save_raw<P: AsRef<Path>>(&self, path: P) -> UsamiResult<()> {
+        let path = path.as_ref();
+        let sidecar_path = path.with_extension("json");
+
+        fs::write(path, &self.data).map_err(|source| Error::Io {
+            path: Some(path.to_path_buf()),
+            source,
+        })?;
+        fs::write(&sidecar_path, self.sidecar_json()).map_err(|source| Error::Io {
+            path: Some(sidecar_path),
+            source,
+        })
+    }
+
+    fn sidecar_json(&self) -> String {
+        format!(
+            concat!(
+                "{{\n",
+                "  \"format\": \"{:?}\",\n",
+                "  \"aspect\": \"{:?}\",\n",
+                "  \"mip_level\": {},\n",
+                "  \"array_layer\": {},\n",
+                "  \"width\": {},\n",
+                "  \"height\": {},\n",
+                "  \"depth\": {},\n",
+                "  \"size\": {}\n",
+                "}}\n"
+            ),
+            self.format,
+            self.aspect_mask,
+            self.mip_level,
+            self.array_layer,
+            self.extent.width,
+            self.extent.height,
+            self.extent.depth,
+            self.data.len()
+        )
+    }
+
+    /// Save using the most fitting container and return the path written.
+    ///
+    /// Float and depth data goes to OpenEXR, normalized data and stencil to PNG and everything
+    /// else (integer, scaled, block and multi-planar formats) to a raw dump.
+    pub fn save<P: AsRef<Path>>(&self, path_without_extension: P) -> UsamiResult<PathBuf> {
+        let path = path_without_extension.as_ref();
+        let format_info = self.format_info()?;
+        let numeric_format = format_info.numeric_format();
+
+        let path = if format_info.has_multi_texel_blocks() || !format_info.planes.is_empty() {
+            let path = path.with_extension("bin");
+            self.save_raw(&path)?;
+            path
+        } else if self.aspect_mask == ImageAspectFlags::DEPTH || numeric_format.is_float() {
+            let path = path.with_extension("exr");
+            self.save_exr(&path)?;
+            path
+        } else if self.aspect_mask == ImageAspectFlags::STENCIL
+            || matches!(
+                numeric_format,
+                UsamiNumericFormat::UNorm | UsamiNumericFormat::SNorm | UsamiNumericFormat::Srgb
+            )
+        {
+            let path = path.with_extension("png");
+            self.save_png(&path)?;
+            path
+        } else {
+            let path = path.with_extension("bin");
+            self.save_raw(&path)?;
+            path
+        };
+
+        Ok(path)
+    }
+}
+
+fn bit_mask(bits: u32) -> u64 {
+    if bits >= 64 {
+        u64::MAX
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+fn sign_extend(raw: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((raw << shift) as i64) >> shift
+}
+
+fn read_le(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |value, byte| (value << 8) | u64::from(*byte))
+}
+
+/// Decode a float with a 5-bit exponent (half and packed unsigned floats).
+fn decode_small_float(raw: u64, mantissa_bits: u32, signed: bool) -> f32 {
+    let mantissa = (raw & bit_mask(mantissa_bits)) as f32;
+    let exponent = ((raw >> mantissa_bits) & 0x1f) as i32;
+    let negative = signed && (raw >> (mantissa_bits + 5)) & 1 == 1;
+    let scale = (1u64 << mantissa_bits) as f32;
+
+    let value = match exponent {
+        0 => mantissa / scale * 2f32.powi(-14),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / scale) * 2f32.powi(exponent - 15),
+    };
+
+    if negative {
+        -value
+    } else {
+        value
+    }
+}
+
+fn decode_component(raw: u64, bits: u32, numeric_format: UsamiNumericFormat) -> f32 {
+    match numeric_format {
+        UsamiNumericFormat::UNorm | UsamiNumericFormat::Srgb => {
+            (raw as f64 / bit_mask(bits) as f64) as f32
+        }
+        UsamiNumericFormat::SNorm => {
+            let max = bit_mask(bits - 1) as f64;
+            (sign_extend(raw, bits) as f64 / max).max(-1.0) as f32
+        }
+        UsamiNumericFormat::UScaled | UsamiNumericFormat::UInt => raw as f32,
+        UsamiNumericFormat::SScaled | UsamiNumericFormat::SInt => sign_extend(raw, bits) as f32,
+        UsamiNumericFormat::UFloat => decode_small_float(raw, bits - 5, false),
+        UsamiNumericFormat::SFloat => match bits {
+            16 => decode_small_float(raw, 10, true),
+            32 => f32::from_bits(raw as u32),
+            _ => f64::from_bits(raw) as f32,
+        },
+    }
+}
+
+fn decode_color(format_info: &UsamiFormatInfo, texel: &[u8]) -> [f32; 4] {
+    let mut rgba = [0.0, 0.0, 0.0, 1.0];
+    let mut shared_exponent = None;
+    let mut offset = 0;
+    let mut word = 0;
+    let mut remaining_bits = 0;
+
+    for component in format_info.components {
+        // Packed components are laid out from the most significant bit of their word.
+        if remaining_bits == 0 {
+            let word_size = if format_info.pack_size == 0 {
+                (component.bits / 8) as usize
+            } else {
+                format_info.pack_size as usize
+            };
+
+            word = read_le(&texel[offset..offset + word_size]);
+            offset += word_size;
+            remaining_bits = word_size as u32 * 8;
+        }
+
+        remaining_bits -= component.bits;
+        let raw = (word >> remaining_bits) & bit_mask(component.bits);
+
+        let index = match component.kind {
+            UsamiComponentKind::R => 0,
+            UsamiComponentKind::G => 1,
+            UsamiComponentKind::B => 2,
+            UsamiComponentKind::A => 3,
+            UsamiComponentKind::E => {
+                shared_exponent = Some(raw as i32);
+                continue;
+            }
+            _ => continue,
+        };
+
+        rgba[index] = match shared_exponent {
+            Some(_) => raw as f32,
+            None => decode_component(raw, component.bits, component.numeric_format),
+        };
+    }
+
+    // E5B9G9R9 stores 9-bit mantissas without an implicit leading one.
+    if let Some(exponent) = shared_exponent {
+        let scale = 2f32.powi(exponent - 15 - 9);
+        for value in &mut rgba[..3] {
+            *value *= scale;
+        }
+    }
+
+    rgba
+}
+
+fn decode_depth(format: Format, texel: &[u8]) -> f32 {
+    match format {
+        Format::D16_UNORM | Format::D16_UNORM_S8_UINT => {
+            decode_component(read_le(&texel[..2]), 16, UsamiNumericFormat::UNorm)
+        }
+        Format::X8_D24_UNORM_PACK32 | Format::D24_UNORM_S8_UINT => decode_component(
+            read_le(&texel[..4]) & bit_mask(24),
+            24,
+            UsamiNumericFormat::UNorm,
+        ),
+        _ => f32::from_bits(read_le(&texel[..4]) as u32),
+    }
+}
@@ -0,0 +1,731 @@
+use std::collections::HashMap;
+
+use ash::vk::{DescriptorType, PushConstantRange, ShaderStageFlags};
+use spirv_reflect::{
+    types::{ReflectBlockVariable, ReflectDescriptorType},
+    ShaderModule,
+};
+
+use crate::{Error, UsamiResult};
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+const SPIRV_HEADER_SIZE: usize = 5;
+
+const OP_ENTRY_POINT: u32 = 15;
+const OP_EXECUTION_MODE: u32 = 16;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_CONSTANT: u32 = 43;
+const OP_CONSTANT_COMPOSITE: u32 = 44;
+const OP_SPEC_CONSTANT_TRUE: u32 = 48;
+const OP_SPEC_CONSTANT_FALSE: u32 = 49;
+const OP_SPEC_CONSTANT: u32 = 50;
+const OP_SPEC_CONSTANT_COMPOSITE: u32 = 51;
+const OP_DECORATE: u32 = 71;
+const OP_EXECUTION_MODE_ID: u32 = 331;
+
+const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+const EXECUTION_MODE_LOCAL_SIZE_ID: u32 = 38;
+
+const DECORATION_SPEC_ID: u32 = 1;
+const DECORATION_BUILT_IN: u32 = 11;
+
+const BUILT_IN_WORKGROUP_SIZE: u32 = 25;
+
+/// A descriptor binding used by one or more shader stages.
+#[derive(Clone, Debug)]
+pub struct UsamiDescriptorBinding {
+    pub name: String,
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: DescriptorType,
+    /// Number of descriptors in the binding, 0 for runtime arrays.
+    pub descriptor_count: u32,
+    pub stage_flags: ShaderStageFlags,
+}
+
+/// Scalar type of a specialization constant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UsamiScalarKind {
+    Bool,
+    Int,
+    Float,
+}
+
+/// A specialization constant declared by a shader module.
+#[derive(Clone, Debug)]
+pub struct UsamiSpecializationConstant {
+    pub constant_id: u32,
+    pub kind: UsamiScalarKind,
+    /// Default value as laid out in specialization data, booleans are 32-bit.
+    pub default_value: Vec<u8>,
+}
+
+impl UsamiSpecializationConstant {
+    pub fn size(&self) -> usize {
+        self.default_value.len()
+    }
+}
+
+/// Interface of a single entry point of a shader module.
+#[derive(Clone, Debug)]
+pub struct UsamiEntryPoint {
+    pub name: String,
+    pub stage: ShaderStageFlags,
+    /// Local workgroup size of compute, task and mesh entry points.
+    pub workgroup_size: Option<[u32; 3]>,
+    pub descriptor_bindings: Vec<UsamiDescriptorBinding>,
+    pub push_constant_ranges: Vec<PushConstantRange>,
+}
+
+/// Reflection of the interface of a SPIR-V module.
+#[derive(Clone, Debug)]
+pub struct UsamiShaderReflection {
+    pub entry_points: Vec<UsamiEntryPoint>,
+    pub specialization_constants: Vec<UsamiSpecializationConstant>,
+}
+
+impl UsamiShaderReflection {
+    pub fn new(code: &[u32]) -> UsamiResult<Self> {
+        if code.len() < SPIRV_HEADER_SIZE || code[0] != SPIRV_MAGIC {
+            return Err(Error::Reflection("Invalid SPIR-V header".into()));
+        }
+
+        let module = ShaderModule::load_u32_data(code).map_err(reflection_error)?;
+
+        let mut raw_entry_points = Vec::new();
+        let mut local_sizes = HashMap::new();
+        let mut local_size_ids = HashMap::new();
+        let mut spec_ids = HashMap::new();
+        let mut workgroup_size_id = None;
+        let mut composites = HashMap::new();
+        let mut scalar_types = HashMap::new();
+        let mut constants = HashMap::new();
+        let mut spec_constants = Vec::new();
+
+        let mut position = SPIRV_HEADER_SIZE;
+
+        while position < code.len() {
+            let word_count = (code[position] >> 16) as usize;
+            let opcode = code[position] & 0xFFFF;
+
+            if word_count == 0 || position + word_count > code.len() {
+                return Err(Error::Reflection(format!(
+                    "Invalid instruction at word {position}"
+                )));
+            }
+
+            let operands = &code[position + 1..position + word_count];
+
+            match opcode {
+                OP_ENTRY_POINT if operands.len() >= 3 => {
+                    raw_entry_points.push((operands[0], operands[1], parse_string(&operands[2..])));
+                }
+                OP_EXECUTION_MODE
+                    if operands.len() >= 5 && operands[1] == EXECUTION_MODE_LOCAL_SIZE =>
+                {
+                    local_sizes.insert(operands[0], [operands[2], operands[3], operands[4]]);
+                }
+                OP_EXECUTION_MODE_ID
+                    if operands.len() >= 5 && operands[1] == EXECUTION_MODE_LOCAL_SIZE_ID =>
+                {
+                    local_size_ids.insert(operands[0], [operands[2], operands[3], operands[4]]);
+                }
+                OP_TYPE_BOOL if !operands.is_empty() => {
+                    scalar_types.insert(operands[0], (UsamiScalarKind::Bool, 32));
+                }
+                OP_TYPE_INT if operands.len() >= 2 => {
+                    scalar_types.insert(operands[0], (UsamiScalarKind::Int, operands[1]));
+                }
+                OP_TYPE_FLOAT if operands.len() >= 2 => {
+                    scalar_types.insert(operands[0], (UsamiScalarKind::Float, operands[1]));
+                }
+                OP_CONSTANT if operands.len() >= 3 => {
+                    constants.insert(operands[1], operands[2]);
+                }
+                OP_SPEC_CONSTANT_TRUE | OP_SPEC_CONSTANT_FALSE if operands.len() >= 2 => {
+                    let value = u32::from(opcode == OP_SPEC_CONSTANT_TRUE);
+
+                    constants.insert(operands[1], value);
+                    spec_constants.push((
+                        operands[1],
+                        UsamiScalarKind::Bool,
+                        value.to_le_bytes().to_vec(),
+                    ));
+                }
+                OP_SPEC_CONSTANT if operands.len() >= 3 => {
+                    let (kind, width) = scalar_types
+                        .get(&operands[0])
+                        .copied()
+                        .unwrap_or((UsamiScalarKind::Int, 32));
+                    // Literals narrower than a word hold their value in the low-order bits.
+                    let value: Vec<u8> = operands[2..]
+                        .iter()
+                        .flat_map(|x| x.to_le_bytes())
+                        .take((width / 8) as usize)
+                        .collect();
+
+                    constants.insert(operands[1], operands[2]);
+                    spec_constants.push((operands[1], kind, value));
+                }
+                OP_CONSTANT_COMPOSITE | OP_SPEC_CONSTANT_COMPOSITE if operands.len() >= 5 => {
+                    composites.insert(operands[1], [operands[2], operands[3], operands[4]]);
+                }
+                OP_DECORATE if operands.len() >= 3 && operands[1] == DECORATION_SPEC_ID => {
+                    spec_ids.insert(operands[0], operands[2]);
+                }
+                OP_DECORATE
+                    if operands.len() >= 3
+                        && operands[1] == DECORATION_BUILT_IN
+                        && operands[2] == BUILT_IN_WORKGROUP_SIZE =>
+                {
+                    workgroup_size_id = Some(operands[0]);
+                }
+                _ => {}
+            }
+
+            position += word_count;
+        }
+
+        let resolve_ids = |ids: &[u32; 3]| ids.map(|id| constants.get(&id).copied().unwrap_or(1));
+
+        // A constant decorated with the WorkgroupSize built-in overrides the execution modes of every entry point.
+        let builtin_workgroup_size = workgroup_size_id
+            .and_then(|id| composites.get(&id))
+            .map(resolve_ids);
+
+        let mut entry_points = Vec::new();
+
+        for (execution_model, function_id, name) in raw_entry_points {
+            let stage = execution_model_to_stage(execution_model);
+            let has_workgroup = stage.intersects(
+                ShaderStageFlags::COMPUTE | ShaderStageFlags::TASK_EXT | ShaderStageFlags::MESH_EXT,
+            );
+            let workgroup_size = builtin_workgroup_size
+                .filter(|_| has_workgroup)
+                .or_else(|| local_sizes.get(&function_id).copied())
+                .or_else(|| local_size_ids.get(&function_id).map(resolve_ids));
+
+            let mut descriptor_bindings = Vec::new();
+
+            for set in module
+                .enumerate_descriptor_sets(Some(&name))
+                .map_err(reflection_error)?
+            {
+                for binding in set.bindings {
+                    descriptor_bindings.push(UsamiDescriptorBinding {
+                        name: binding.name,
+                        set: binding.set,
+                        binding: binding.binding,
+                        descriptor_type: reflect_descriptor_type(binding.descriptor_type)?,
+                        descriptor_count: binding.count,
+                        stage_flags: stage,
+                    });
+                }
+            }
+
+            let push_constant_ranges = module
+                .enumerate_push_constant_blocks(Some(&name))
+                .map_err(reflection_error)?
+                .iter()
+                .map(|block| push_constant_range(block, stage))
+                .collect();
+
+            entry_points.push(UsamiEntryPoint {
+                name,
+                stage,
+                workgroup_size,
+                descriptor_bindings,
+                push_constant_ranges,
+            });
+        }
+
+        let mut specialization_constants: Vec<UsamiSpecializationConstant> = spec_constants
+            .into_iter()
+            .filter_map(|(id, kind, default_value)| {
+                spec_ids
+                    .get(&id)
+                    .map(|constant_id| UsamiSpecializationConstant {
+                        constant_id: *constant_id,
+                        kind,
+                        default_value,
+                    })
+            })
+            .collect();
+        specialization_constants.sort_by_key(|x| x.constant_id);
+
+        Ok(Self {
+            entry_points,
+            specialization_constants,
+        })
+    }
+
+    pub fn entry_point(&self, name: &str) -> UsamiResult<&UsamiEntryPoint> {
+        self.entry_points
+            .iter()
+            .find(|x| x.name == name)
+            .ok_or_else(|| Error::Reflection(format!("Missing entry point \"{name}\"")))
+    }
+}
+
+/// Merge the descriptor bindings of multiple stages, bindings shared between stages get the union of their stage flags.
+pub fn merge_descriptor_bindings(
+    entry_points: &[&UsamiEntryPoint],
+) -> UsamiResult<Vec<UsamiDescriptorBinding>> {
+    let mut result: Vec<UsamiDescriptorBinding> = Vec::new();
+
+    for descriptor_binding in entry_points.iter().flat_map(|x| &x.descriptor_bindings) {
+        let existing = result
+            .iter_mut()
+            .find(|x| x.set == descriptor_binding.set && x.binding == descriptor_binding.binding);
+
+        match existing {
+            Some(existing) if existing.descriptor_type != descriptor_binding.descriptor_type => {
+                return Err(Error::Reflection(format!(
+                    "Binding {} of set {} is declared as both {:?} and {:?}",
+                    descriptor_binding.binding,
+                    descriptor_binding.set,
+                    existing.descriptor_type,
+                    descriptor_binding.descriptor_type
+                )));
+            }
+            Some(existing) => {
+                existing.stage_flags |= descriptor_binding.stage_flags;
+                // A runtime array in any stage keeps the binding a runtime array.
+                existing.descriptor_count =
+                    if existing.descriptor_count == 0 || descriptor_binding.descriptor_count == 0 {
+                        0
+                    } else {
+                        existing
+                            .descriptor_count
+                            .max(descriptor_binding.descriptor_count)
+                    };
+            }
+            None => result.push(descriptor_binding.clone()),
+        }
+    }
+
+    result.sort_by_key(|x| (x.set, x.binding));
+
+    Ok(result)
+}
+
+/// Merge the push constant ranges of multiple stages into a single range visible to all of them.
+pub fn merge_push_constant_ranges(entry_points: &[&UsamiEntryPoint]) -> Vec<PushConstantRange> {
+    let ranges: Vec<&PushConstantRange> = entry_points
+        .iter()
+        .flat_map(|x| &x.push_constant_ranges)
+        .collect();
+
+    if ranges.is_empty() {
+        return Vec::new();
+    }
+
+    let start = ranges.iter().map(|x| x.offset).min().unwrap();
+    let end = ranges.iter().map(|x| x.offset + x.size).max().unwrap();
+    let stage_flags = ranges
+        .iter()
+        .fold(ShaderStageFlags::empty(), |flags, x| flags | x.stage_flags);
+
+    vec![PushConstantRange::default()
+        .stage_flags(stage_flags)
+        .offset(start)
+        .size(end - start)]
+}
+
+fn reflection_error(error: &str) -> Error {
+    Error::Reflection(error.into())
+}
+
+fn parse_string(words: &[u32]) -> String {
+    let bytes: Vec<u8> = words
+        .iter()
+        .flat_map(|x| x.to_le_bytes())
+        .take_while(|x| *x != 0)
+        .collect();
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn execution_model_to_stage(execution_model: u32) -> ShaderStageFlags {
+    match execution_model {
+        0 => ShaderStageFlags::VERTEX,
+        1 => ShaderStageFlags::TESSELLATION_CONTROL,
+        2 => ShaderStageFlags::TESSELLATION_EVALUATION,
+        3 => ShaderStageFlags::GEOMETRY,
+        4 => ShaderStageFlags::FRAGMENT,
+        5 | 6 => ShaderStageFlags::COMPUTE,
+        5267 | 5364 => ShaderStageFlags::TASK_EXT,
+        5268 | 5365 => ShaderStageFlags::MESH_EXT,
+        5313 => ShaderStageFlags::RAYGEN_KHR,
+        5314 => ShaderStageFlags::INTERSECTION_KHR,
+        5315 => ShaderStageFlags::ANY_HIT_KHR,
+        5316 => ShaderStageFlags::CLOSEST_HIT_KHR,
+        5317 => ShaderStageFlags::MISS_KHR,
+        5318 => ShaderStageFlags::CALLABLE_KHR,
+        _ => ShaderStageFlags::empty(),
+    }
+}
+
+fn reflect_descriptor_type(descriptor_type: ReflectDescriptorType) -> UsamiResult<DescriptorType> {
+    let result = match descriptor_type {
+        ReflectDescriptorType::Undefined => {
+            return Err(Error::Reflection("Unknown descriptor type".into()))
+        }
+        ReflectDescriptorType::Sampler => DescriptorType::SAMPLER,
+        ReflectDescriptorType::CombinedImageSampler => DescriptorType::COMBINED_IMAGE_SAMPLER,
+        ReflectDescriptorType::SampledImage => DescriptorType::SAMPLED_IMAGE,
+        ReflectDescriptorType::StorageImage => DescriptorType::STORAGE_IMAGE,
+        ReflectDescriptorType::UniformTexelBuffer => DescriptorType::UNIFORM_TEXEL_BUFFER,
+        ReflectDescriptorType::StorageTexelBuffer => DescriptorType::STORAGE_TEXEL_BUFFER,
+        ReflectDescriptorType::UniformBuffer => DescriptorType::UNIFORM_BUFFER,
+        ReflectDescriptorType::StorageBuffer => DescriptorType::STORAGE_BUFFER,
+        ReflectDescriptorType::UniformBufferDynamic => DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+        ReflectDescriptorType::StorageBufferDynamic => DescriptorType::STORAGE_BUFFER_DYNAMIC,
+        ReflectDescriptorType::InputAttachment => DescriptorType::INPUT_ATTACHMENT,
+        ReflectDescriptorType::AccelerationStructureKHR => {
+            DescriptorType::ACCELERATION_STRUCTURE_KHR
+        }
+    };
+
+    Ok(result)
+}
+
+fn push_constant_range(block: &ReflectBlockVariable, stage: ShaderStageFlags) -> PushConstantRange {
+    let start = block
+        .members
+        .iter()
+        .map(|x| x.offset)
+        .min()
+        .unwrap_or(block.offset);
+    let end = block
+        .members
+        .iter()
+        .map(|x| x.offset + x.size)
+        .max()
+        .unwrap_or(block.offset + block.size);
+
+    PushConstantRange::default()
+        .stage_flags(stage)
+        .offset(start)
+        .size((end - start).next_multiple_of(4))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OP_CAPABILITY: u32 = 17;
+    const OP_MEMORY_MODEL: u32 = 14;
+    const OP_TYPE_VOID: u32 = 19;
+    const OP_TYPE_VECTOR: u32 = 23;
+    const OP_TYPE_FUNCTION: u32 = 33;
+    const OP_FUNCTION: u32 = 54;
+    const OP_FUNCTION_END: u32 = 56;
+    const OP_LABEL: u32 = 248;
+    const OP_RETURN: u32 = 253;
+
+    const CAPABILITY_SHADER: u32 = 1;
+    const CAPABILITY_FLOAT64: u32 = 10;
+    const CAPABILITY_INT16: u32 = 22;
+    const CAPABILITY_INT8: u32 = 39;
+
+    const VOID: u32 = 1;
+    const FUNCTION_TYPE: u32 = 2;
+    const MAIN: u32 = 3;
+    const LABEL: u32 = 4;
+    const UINT: u32 = 5;
+    const FIRST_FREE_ID: u32 = 6;
+
+    fn instruction(opcode: u32, operands: &[u32]) -> Vec<u32> {
+        let mut result = vec![((operands.len() as u32 + 1) << 16) | opcode];
+        result.extend_from_slice(operands);
+
+        result
+    }
+
+    fn string(value: &str) -> Vec<u32> {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.resize((bytes.len() + 1).next_multiple_of(4), 0);
+
+        bytes
+            .chunks(4)
+            .map(|x| u32::from_le_bytes(x.try_into().unwrap()))
+            .collect()
+    }
+
+    /// Assemble a compute module with a "main" entry point, `execution_modes` and `declarations`
+    /// are placed at their logical layout positions.
+    fn compute_module(
+        capabilities: &[u32],
+        execution_modes: &[Vec<u32>],
+        annotations: &[Vec<u32>],
+        declarations: &[Vec<u32>],
+    ) -> Vec<u32> {
+        let mut code = vec![SPIRV_MAGIC, 0x0001_0300, 0, 64, 0];
+
+        for capability in [CAPABILITY_SHADER].iter().chain(capabilities) {
+            code.extend(instruction(OP_CAPABILITY, &[*capability]));
+        }
+
+        code.extend(instruction(OP_MEMORY_MODEL, &[0, 1]));
+
+        let mut entry_point = vec![5, MAIN];
+        entry_point.extend(string("main"));
+        code.extend(instruction(OP_ENTRY_POINT, &entry_point));
+
+        for x in execution_modes.iter().chain(annotations) {
+            code.extend(x);
+        }
+
+        code.extend(instruction(OP_TYPE_VOID, &[VOID]));
+        code.extend(instruction(OP_TYPE_FUNCTION, &[FUNCTION_TYPE, VOID]));
+        code.extend(instruction(OP_TYPE_INT, &[UINT, 32, 0]));
+
+        for x in declarations {
+            code.extend(x);
+        }
+
+        code.extend(instruction(OP_FUNCTION, &[VOID, MAIN, 0, FUNCTION_TYPE]));
+        code.extend(instruction(OP_LABEL, &[LABEL]));
+        code.extend(instruction(OP_RETURN, &[]));
+        code.extend(instruction(OP_FUNCTION_END, &[]));
+
+        code
+    }
+
+    fn spec_id(id: u32, constant_id: u32) -> Vec<u32> {
+        instruction(OP_DECORATE, &[id, DECORATION_SPEC_ID, constant_id])
+    }
+
+    #[test]
+    fn local_size() {
+        let code = compute_module(
+            &[],
+            &[instruction(
+                OP_EXECUTION_MODE,
+                &[MAIN, EXECUTION_MODE_LOCAL_SIZE, 8, 4, 2],
+            )],
+            &[],
+            &[],
+        );
+        let reflection = UsamiShaderReflection::new(&code).unwrap();
+        let entry_point = reflection.entry_point("main").unwrap();
+
+        assert_eq!(entry_point.stage, ShaderStageFlags::COMPUTE);
+        assert_eq!(entry_point.workgroup_size, Some([8, 4, 2]));
+        assert!(reflection.specialization_constants.is_empty());
+    }
+
+    #[test]
+    fn local_size_id() {
+        let (x, y, z) = (FIRST_FREE_ID, FIRST_FREE_ID + 1, FIRST_FREE_ID + 2);
+        let code = compute_module(
+            &[],
+            &[instruction(
+                OP_EXECUTION_MODE_ID,
+                &[MAIN, EXECUTION_MODE_LOCAL_SIZE_ID, x, y, z],
+            )],
+            &[spec_id(y, 1)],
+            &[
+                instruction(OP_CONSTANT, &[UINT, x, 32]),
+                instruction(OP_SPEC_CONSTANT, &[UINT, y, 2]),
+                instruction(OP_CONSTANT, &[UINT, z, 1]),
+            ],
+        );
+        let reflection = UsamiShaderReflection::new(&code).unwrap();
+
+        assert_eq!(
+            reflection.entry_point("main").unwrap().workgroup_size,
+            Some([32, 2, 1])
+        );
+    }
+
+    #[test]
+    fn workgroup_size_built_in() {
+        let (vector, x, one, workgroup_size) = (
+            FIRST_FREE_ID,
+            FIRST_FREE_ID + 1,
+            FIRST_FREE_ID + 2,
+            FIRST_FREE_ID + 3,
+        );
+        let code = compute_module(
+            &[],
+            &[instruction(
+                OP_EXECUTION_MODE,
+                &[MAIN, EXECUTION_MODE_LOCAL_SIZE, 8, 4, 2],
+            )],
+            &[
+                spec_id(x, 0),
+                instruction(
+                    OP_DECORATE,
+                    &[workgroup_size, DECORATION_BUILT_IN, BUILT_IN_WORKGROUP_SIZE],
+                ),
+            ],
+            &[
+                instruction(OP_TYPE_VECTOR, &[vector, UINT, 3]),
+                instruction(OP_SPEC_CONSTANT, &[UINT, x, 64]),
+                instruction(OP_CONSTANT, &[UINT, one, 1]),
+                instruction(
+                    OP_SPEC_CONSTANT_COMPOSITE,
+                    &[vector, workgroup_size, x, one, one],
+                ),
+            ],
+        );
+        let reflection = UsamiShaderReflection::new(&code).unwrap();
+
+        assert_eq!(
+            reflection.entry_point("main").unwrap().workgroup_size,
+            Some([64, 1, 1])
+        );
+    }
+
+    #[test]
+    fn spec_constant_defaults() {
+        let (bool_type, ushort, uchar, double) = (
+            FIRST_FREE_ID,
+            FIRST_FREE_ID + 1,
+            FIRST_FREE_ID + 2,
+            FIRST_FREE_ID + 3,
+        );
+        let constants = [
+            FIRST_FREE_ID + 4,
+            FIRST_FREE_ID + 5,
+            FIRST_FREE_ID + 6,
+            FIRST_FREE_ID + 7,
+            FIRST_FREE_ID + 8,
+        ];
+        let code = compute_module(
+            &[CAPABILITY_FLOAT64, CAPABILITY_INT16, CAPABILITY_INT8],
+            &[instruction(
+                OP_EXECUTION_MODE,
+                &[MAIN, EXECUTION_MODE_LOCAL_SIZE, 1, 1, 1],
+            )],
+            &[
+                spec_id(constants[0], 4),
+                spec_id(constants[1], 3),
+                spec_id(constants[2], 2),
+                spec_id(constants[3], 1),
+                spec_id(constants[4], 0),
+            ],
+            &[
+                instruction(OP_TYPE_BOOL, &[bool_type]),
+                instruction(OP_TYPE_INT, &[ushort, 16, 0]),
+                instruction(OP_TYPE_INT, &[uchar, 8, 0]),
+                instruction(OP_TYPE_FLOAT, &[double, 64]),
+                instruction(OP_SPEC_CONSTANT_TRUE, &[bool_type, constants[0]]),
+                instruction(OP_SPEC_CONSTANT, &[ushort, constants[1], 0xBEEF]),
+                instruction(OP_SPEC_CONSTANT, &[uchar, constants[2], 0x7F]),
+                instruction(
+                    OP_SPEC_CONSTANT,
+                    &[double, constants[3], 0x8765_4321, 0x0FED_CBA9],
+                ),
+                instruction(OP_SPEC_CONSTANT, &[UINT, constants[4], 0x1234_5678]),
+            ],
+        );
+        let reflection = UsamiShaderReflection::new(&code).unwrap();
+        let defaults: Vec<(u32, UsamiScalarKind, usize, Vec<u8>)> = reflection
+            .specialization_constants
+            .iter()
+            .map(|x| (x.constant_id, x.kind, x.size(), x.default_value.clone()))
+            .collect();
+
+        assert_eq!(
+            defaults,
+            vec![
+                (
+                    0,
+                    UsamiScalarKind::Int,
+                    4,
+                    0x1234_5678u32.to_le_bytes().to_vec()
+                ),
+                (
+                    1,
+                    UsamiScalarKind::Float,
+                    8,
+                    0x0FED_CBA9_8765_4321u64.to_le_bytes().to_vec()
+                ),
+                (2, UsamiScalarKind::Int, 1, vec![0x7F]),
+                (3, UsamiScalarKind::Int, 2, 0xBEEFu16.to_le_bytes().to_vec()),
+                (4, UsamiScalarKind::Bool, 4, 1u32.to_le_bytes().to_vec()),
+            ]
+        );
+    }
+
+    fn entry_point(
+        stage: ShaderStageFlags,
+        bindings: &[(u32, DescriptorType, u32)],
+    ) -> UsamiEntryPoint {
+        UsamiEntryPoint {
+            name: "main".into(),
+            stage,
+            workgroup_size: None,
+            descriptor_bindings: bindings
+                .iter()
+                .map(
+                    |(binding, descriptor_type, descriptor_count)| UsamiDescriptorBinding {
+                        name: format!("binding_{binding}"),
+                        set: 0,
+                        binding: *binding,
+                        descriptor_type: *descriptor_type,
+                        descriptor_count: *descriptor_count,
+                        stage_flags: stage,
+                    },
+                )
+                .collect(),
+            push_constant_ranges: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn merge_bindings() {
+        let vertex = entry_point(
+            ShaderStageFlags::VERTEX,
+            &[
+                (2, DescriptorType::SAMPLED_IMAGE, 4),
+                (0, DescriptorType::UNIFORM_BUFFER, 1),
+            ],
+        );
+        let fragment = entry_point(
+            ShaderStageFlags::FRAGMENT,
+            &[
+                (0, DescriptorType::UNIFORM_BUFFER, 1),
+                (2, DescriptorType::SAMPLED_IMAGE, 0),
+                (1, DescriptorType::STORAGE_BUFFER, 2),
+            ],
+        );
+        let merged: Vec<(u32, ShaderStageFlags, u32)> =
+            merge_descriptor_bindings(&[&vertex, &fragment])
+                .unwrap()
+                .iter()
+                .map(|x| (x.binding, x.stage_flags, x.descriptor_count))
+                .collect();
+
+        assert_eq!(
+            merged,
+            vec![
+                (0, ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT, 1),
+                (1, ShaderStageFlags::FRAGMENT, 2),
+                (2, ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_bindings_type_mismatch() {
+        let vertex = entry_point(
+            ShaderStageFlags::VERTEX,
+            &[(0, DescriptorType::UNIFORM_BUFFER, 1)],
+        );
+        let fragment = entry_point(
+            ShaderStageFlags::FRAGMENT,
+            &[(0, DescriptorType::STORAGE_BUFFER, 1)],
+        );
+
+        assert!(matches!(
+            merge_descriptor_bindings(&[&vertex, &fragment]),
+            Err(Error::Reflection(_))
+        ));
+    }
+}
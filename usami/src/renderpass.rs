@@ -1,11 +1,8 @@
 use std::sync::Arc;
 
-use ash::{
-    prelude::*,
-    vk::{RenderPass, RenderPassCreateInfo},
-};
+use ash::vk::{RenderPass, RenderPassCreateInfo};
 
-use crate::UsamiDevice;
+use crate::{ResultExt, UsamiDevice, UsamiResult};
 
 pub struct UsamiRenderPass {
     device: Arc<UsamiDevice>,
@@ -13,7 +10,7 @@ pub struct UsamiRenderPass {
 }
 
 impl UsamiRenderPass {
-    pub fn new(device: &Arc<UsamiDevice>, create_info: RenderPassCreateInfo) -> VkResult<Self> {
+    pub fn new(device: &Arc<UsamiDevice>, create_info: RenderPassCreateInfo) -> UsamiResult<Self> {
         let handle = unsafe { device.handle.create_render_pass(&create_info, None)? };
 
         Ok(Self {
@@ -34,8 +31,8 @@ impl UsamiDevice {
         device: &Arc<UsamiDevice>,
         name: String,
         create_info: RenderPassCreateInfo,
-    ) -> VkResult<UsamiRenderPass> {
-        let shader = UsamiRenderPass::new(device, create_info)?;
+    ) -> UsamiResult<UsamiRenderPass> {
+        let shader = UsamiRenderPass::new(device, create_info).context(&name)?;
 
         device.set_debug_name(name, shader.handle)?;
 
@@ -0,0 +1,169 @@
+use std::sync::Arc;
+
+use ash::vk::{
+    self, PipelineStageFlags, PipelineStageFlags2, Semaphore, SemaphoreCreateInfo,
+    SemaphoreSignalInfo, SemaphoreSubmitInfo, SemaphoreType, SemaphoreTypeCreateInfo,
+    SemaphoreWaitInfo,
+};
+
+use crate::{ResultExt, UsamiCommandBuffer, UsamiDevice, UsamiResult};
+
+pub struct UsamiSemaphore {
+    device: Arc<UsamiDevice>,
+    pub handle: Semaphore,
+    pub semaphore_type: SemaphoreType,
+}
+
+impl UsamiSemaphore {
+    pub fn new(
+        device: &Arc<UsamiDevice>,
+        semaphore_type: SemaphoreType,
+        initial_value: u64,
+    ) -> UsamiResult<Self> {
+        let mut type_create_info = SemaphoreTypeCreateInfo::default()
+            .semaphore_type(semaphore_type)
+            .initial_value(initial_value);
+        let mut create_info = SemaphoreCreateInfo::default();
+
+        if semaphore_type == SemaphoreType::TIMELINE {
+            create_info = create_info.push_next(&mut type_create_info);
+        }
+
+        let handle = unsafe { device.handle.create_semaphore(&create_info, None)? };
+
+        Ok(Self {
+            device: device.clone(),
+            handle,
+            semaphore_type,
+        })
+    }
+
+    /// Signal a timeline semaphore from the host.
+    pub fn signal(&self, value: u64) -> UsamiResult<()> {
+        unsafe {
+            self.device.handle.signal_semaphore(
+                &SemaphoreSignalInfo::default()
+                    .semaphore(self.handle)
+                    .value(value),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Wait on the host for a timeline semaphore to reach the given value.
+    pub fn wait(&self, value: u64, timeout: u64) -> UsamiResult<()> {
+        unsafe {
+            self.device.handle.wait_semaphores(
+                &SemaphoreWaitInfo::default()
+                    .semaphores(&[self.handle])
+                    .values(&[value]),
+                timeout,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Return the current value of a timeline semaphore.
+    pub fn value(&self) -> UsamiResult<u64> {
+        Ok(unsafe {
+            self.device
+                .handle
+                .get_semaphore_counter_value(self.handle)?
+        })
+    }
+}
+
+impl Drop for UsamiSemaphore {
+    fn drop(&mut self) {
+        unsafe { self.device.handle.destroy_semaphore(self.handle, None) }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct UsamiSemaphoreSubmit {
+    pub semaphore: Semaphore,
+    /// Ignored for binary semaphores.
+    pub value: u64,
+    pub stage_mask: PipelineStageFlags2,
+}
+
+impl UsamiSemaphoreSubmit {
+    pub fn new(semaphore: &UsamiSemaphore, value: u64, stage_mask: PipelineStageFlags2) -> Self {
+        Self {
+            semaphore: semaphore.handle,
+            value,
+            stage_mask,
+        }
+    }
+
+    pub fn info(&self) -> SemaphoreSubmitInfo<'static> {
+        SemaphoreSubmitInfo::default()
+            .semaphore(self.semaphore)
+            .value(self.value)
+            .stage_mask(self.stage_mask)
+    }
+
+    /// Stage mask usable with the legacy submission path, only the first 32 bits of the stage mask are kept.
+    pub fn legacy_stage_mask(&self) -> PipelineStageFlags {
+        PipelineStageFlags::from_raw(self.stage_mask.as_raw() as vk::Flags)
+    }
+}
+
+/// A batch of work given to [crate::UsamiQueue::submit2].
+#[derive(Clone, Debug, Default)]
+pub struct UsamiSubmit {
+    pub wait_semaphores: Vec<UsamiSemaphoreSubmit>,
+    pub command_buffers: Vec<vk::CommandBuffer>,
+    pub signal_semaphores: Vec<UsamiSemaphoreSubmit>,
+}
+
+impl UsamiSubmit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn wait(
+        mut self,
+        semaphore: &UsamiSemaphore,
+        value: u64,
+        stage_mask: PipelineStageFlags2,
+    ) -> Self {
+        self.wait_semaphores
+            .push(UsamiSemaphoreSubmit::new(semaphore, value, stage_mask));
+        self
+    }
+
+    pub fn command_buffer(mut self, command_buffer: &UsamiCommandBuffer) -> Self {
+        self.command_buffers.push(command_buffer.handle);
+        self
+    }
+
+    pub fn signal(
+        mut self,
+        semaphore: &UsamiSemaphore,
+        value: u64,
+        stage_mask: PipelineStageFlags2,
+    ) -> Self {
+        self.signal_semaphores
+            .push(UsamiSemaphoreSubmit::new(semaphore, value, stage_mask));
+        self
+    }
+}
+
+impl UsamiDevice {
+    pub fn create_semaphore(
+        device: &Arc<UsamiDevice>,
+        name: String,
+        semaphore_type: SemaphoreType,
+        initial_value: u64,
+    ) -> UsamiResult<UsamiSemaphore> {
+        let semaphore =
+            UsamiSemaphore::new(device, semaphore_type, initial_value).context(&name)?;
+
+        device.set_debug_name(name, semaphore.handle)?;
+
+        Ok(semaphore)
+    }
+}
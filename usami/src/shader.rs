@@ -1,19 +1,17 @@
 use std::sync::Arc;
 
-use ash::{
-    prelude::*,
-    vk::{ShaderModule, ShaderModuleCreateFlags, ShaderModuleCreateInfo},
-};
+use ash::vk::{ShaderModule, ShaderModuleCreateFlags, ShaderModuleCreateInfo};
 
-use crate::UsamiDevice;
+use crate::{ResultExt, UsamiDevice, UsamiResult, UsamiShaderReflection};
 
 pub struct UsamiShader {
     device: Arc<UsamiDevice>,
     pub handle: ShaderModule,
+    code: Vec<u32>,
 }
 
 impl UsamiShader {
-    pub fn new(device: &Arc<UsamiDevice>, code: &[u32]) -> VkResult<Self> {
+    pub fn new(device: &Arc<UsamiDevice>, code: &[u32]) -> UsamiResult<Self> {
         let create_info = ShaderModuleCreateInfo::default()
             .code(code)
             .flags(ShaderModuleCreateFlags::empty());
@@ -23,8 +21,17 @@ impl UsamiShader {
         Ok(Self {
             device: device.clone(),
             handle,
+            code: code.to_vec(),
         })
     }
+
+    pub fn code(&self) -> &[u32] {
+        &self.code
+    }
+
+    pub fn reflect(&self) -> UsamiResult<UsamiShaderReflection> {
+        UsamiShaderReflection::new(&self.code)
+    }
 }
 
 impl Drop for UsamiShader {
@@ -38,8 +45,8 @@ impl UsamiDevice {
         device: &Arc<UsamiDevice>,
         name: String,
         code: &[u32],
-    ) -> VkResult<UsamiShader> {
-        let shader = UsamiShader::new(device, code)?;
+    ) -> UsamiResult<UsamiShader> {
+        let shader = UsamiShader::new(device, code).context(&name)?;
 
         device.set_debug_name(name, shader.handle)?;
 
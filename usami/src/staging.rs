@@ -0,0 +1,534 @@
+use std::sync::Arc;
+
+use ash::vk::{
+    self, AccessFlags2, Buffer, BufferCopy, BufferCreateFlags, BufferImageCopy, BufferUsageFlags,
+    Format, Image, ImageAspectFlags, ImageLayout, ImageMemoryBarrier2, ImageSubresourceRange,
+    MemoryBarrier2, MemoryPropertyFlags, PipelineStageFlags2, SharingMode,
+};
+use bytemuck::Pod;
+
+use crate::{
+    format::format_info, utils, UsamiAccess, UsamiBuffer, UsamiCommandBuffer, UsamiCommandPool,
+    UsamiDevice, UsamiImage, UsamiResourceState, UsamiResult,
+};
+
+/// Alignment of buffer copies, image copies are also aligned to their texel block size and `optimalBufferCopyOffsetAlignment`.
+const STAGING_ALIGNMENT: u64 = 4;
+
+/// Barrier of one subresource, computed from its tracked state when the copy is requested.
+struct ImageTransition {
+    subresource_range: ImageSubresourceRange,
+    src: UsamiResourceState,
+    dst: UsamiResourceState,
+}
+
+impl ImageTransition {
+    fn barrier(&self, image: Image) -> ImageMemoryBarrier2<'static> {
+        ImageMemoryBarrier2::default()
+            .src_stage_mask(self.src.stage_mask)
+            .src_access_mask(self.src.access_mask)
+            .dst_stage_mask(self.dst.stage_mask)
+            .dst_access_mask(self.dst.access_mask)
+            .old_layout(self.src.layout)
+            .new_layout(self.dst.layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(self.subresource_range)
+    }
+}
+
+enum StagingCommand {
+    UploadBuffer {
+        dst_buffer: Buffer,
+        region: BufferCopy,
+    },
+    UploadImage {
+        dst_image: Image,
+        regions: Vec<BufferImageCopy>,
+        transitions_before: Vec<ImageTransition>,
+        transitions_after: Vec<ImageTransition>,
+    },
+    DownloadBuffer {
+        src_buffer: Buffer,
+        region: BufferCopy,
+    },
+    DownloadImage {
+        src_image: Image,
+        regions: Vec<BufferImageCopy>,
+        transitions_before: Vec<ImageTransition>,
+        transitions_after: Vec<ImageTransition>,
+    },
+}
+
+/// Handle to the data of a download, available after [UsamiStagingBelt::flush].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UsamiStagingDownload(usize);
+
+/// Batch uploads and downloads of device local resources through a ring of host visible memory.
+///
+/// When the ring is full, the pending copies are flushed and it wraps around. Resources given to
+/// the belt must stay alive until the next flush and image layouts are taken from their tracked
+/// state when the copy is requested.
+pub struct UsamiStagingBelt<'a> {
+    device: Arc<UsamiDevice>,
+    name: String,
+    command_pool: &'a UsamiCommandPool,
+    capacity: u64,
+    /// Created on first use and recreated bigger if a single copy does not fit in it.
+    ring: Option<UsamiBuffer>,
+    head: u64,
+    commands: Vec<StagingCommand>,
+    pending_downloads: Vec<(UsamiStagingDownload, u64, u64)>,
+    downloads: Vec<Option<Vec<u8>>>,
+}
+
+impl<'a> UsamiStagingBelt<'a> {
+    pub fn new(
+        device: &Arc<UsamiDevice>,
+        name: String,
+        command_pool: &'a UsamiCommandPool,
+        capacity: u64,
+    ) -> Self {
+        Self {
+            device: device.clone(),
+            name,
+            command_pool,
+            capacity,
+            ring: None,
+            head: 0,
+            commands: Vec::new(),
+            pending_downloads: Vec::new(),
+            downloads: Vec::new(),
+        }
+    }
+
+    fn ring(&mut self) -> UsamiResult<&mut UsamiBuffer> {
+        if self.ring.is_none() {
+            self.ring = Some(UsamiDevice::create_buffer_with_size(
+                &self.device,
+                format!("{}_ring", self.name),
+                BufferCreateFlags::empty(),
+                SharingMode::EXCLUSIVE,
+                BufferUsageFlags::TRANSFER_SRC | BufferUsageFlags::TRANSFER_DST,
+                self.capacity,
+                MemoryPropertyFlags::HOST_VISIBLE,
+            )?);
+        }
+
+        Ok(self.ring.as_mut().unwrap())
+    }
+
+    /// Alignment of the staging range of an image copy, `bufferOffset` must be a multiple of the texel block size and 4.
+    fn image_alignment(&self, format: Format) -> u64 {
+        let block_size = format_info(format).map_or(1, |info| {
+            info.planes
+                .iter()
+                .fold(u64::from(info.block_size.max(1)), |result, plane| {
+                    lcm(result, u64::from(plane.block_size))
+                })
+        });
+        let optimal_alignment = self
+            .device
+            .physical_device
+            .properties
+            .limits
+            .optimal_buffer_copy_offset_alignment;
+
+        lcm(lcm(block_size, STAGING_ALIGNMENT), optimal_alignment.max(1))
+    }
+
+    /// Reserve a staging range aligned to `alignment` and return its offset in the ring.
+    fn allocate(&mut self, size: u64, alignment: u64) -> UsamiResult<u64> {
+        if size > self.capacity {
+            self.flush()?;
+            self.capacity = size;
+            self.ring = None;
+        }
+
+        let mut offset = self.head.next_multiple_of(alignment);
+
+        // The pending copies may still use the start of the ring, they must complete before wrapping around.
+        if offset + size > self.capacity {
+            self.flush()?;
+            offset = 0;
+        }
+
+        self.ring()?;
+        self.head = offset + size;
+
+        Ok(offset)
+    }
+
+    fn write(&mut self, data: &[u8], alignment: u64) -> UsamiResult<u64> {
+        let offset = self.allocate(data.len() as u64, alignment)?;
+        let mut staging = self.ring()?.mapped_slice_mut::<u8>()?;
+
+        staging[offset as usize..offset as usize + data.len()].copy_from_slice(data);
+        staging.flush()?;
+
+        Ok(offset)
+    }
+
+    fn register_download(&mut self, offset: u64, size: u64) -> UsamiStagingDownload {
+        let download = UsamiStagingDownload(self.downloads.len());
+
+        self.downloads.push(None);
+        self.pending_downloads.push((download, offset, size));
+
+        download
+    }
+
+    pub fn upload_buffer<T: Pod>(
+        &mut self,
+        dst_buffer: &UsamiBuffer,
+        dst_offset: u64,
+        data: &[T],
+    ) -> UsamiResult<()> {
+        let data: &[u8] = bytemuck::cast_slice(data);
+        let offset = self.write(data, STAGING_ALIGNMENT)?;
+
+        self.commands.push(StagingCommand::UploadBuffer {
+            dst_buffer: dst_buffer.handle,
+            region: BufferCopy::default()
+                .src_offset(offset)
+                .dst_offset(dst_offset)
+                .size(data.len() as u64),
+        });
+
+        Ok(())
+    }
+
+    /// Upload `data` to the whole image, leaving it in `layout`.
+    ///
+    /// The buffer offsets of `regions` are relative to `data`.
+    pub fn upload_image(
+        &mut self,
+        dst_image: &UsamiImage,
+        aspect_mask: ImageAspectFlags,
+        data: &[u8],
+        regions: &[BufferImageCopy],
+        layout: ImageLayout,
+    ) -> UsamiResult<()> {
+        let offset = self.write(data, self.image_alignment(dst_image.format))?;
+        let final_state = UsamiResourceState {
+            layout,
+            ..UsamiAccess::General.state()
+        };
+        let mut transitions_before = Vec::new();
+        let mut transitions_after = Vec::new();
+
+        for subresource_range in subresource_ranges(
+            aspect_mask,
+            (0..dst_image.mip_levels).flat_map(|mip_level| {
+                (0..dst_image.array_layers).map(move |array_layer| (mip_level, array_layer))
+            }),
+        ) {
+            let mut tracked = dst_image.tracked_state(
+                subresource_range.base_mip_level,
+                subresource_range.base_array_layer,
+            );
+
+            for (transitions, dst) in [
+                (&mut transitions_before, UsamiAccess::TransferWrite.state()),
+                (&mut transitions_after, final_state),
+            ] {
+                if let Some(src) = tracked.access(dst) {
+                    transitions.push(ImageTransition {
+                        subresource_range,
+                        src,
+                        dst,
+                    });
+                }
+            }
+
+            dst_image.set_tracked_state(subresource_range, tracked);
+        }
+
+        self.commands.push(StagingCommand::UploadImage {
+            dst_image: dst_image.handle,
+            regions: offset_regions(regions, offset),
+            transitions_before,
+            transitions_after,
+        });
+
+        Ok(())
+    }
+
+    pub fn download_buffer(
+        &mut self,
+        src_buffer: &UsamiBuffer,
+        src_offset: u64,
+        size: u64,
+    ) -> UsamiResult<UsamiStagingDownload> {
+        let offset = self.allocate(size, STAGING_ALIGNMENT)?;
+
+        self.commands.push(StagingCommand::DownloadBuffer {
+            src_buffer: src_buffer.handle,
+            region: BufferCopy::default()
+                .src_offset(src_offset)
+                .dst_offset(offset)
+                .size(size),
+        });
+
+        Ok(self.register_download(offset, size))
+    }
+
+    /// Download `size` bytes from the subresources of `regions`, their tracked layout is restored after the copy.
+    ///
+    /// Subresources in [ImageLayout::UNDEFINED] have no layout to go back to and are left in
+    /// [ImageLayout::TRANSFER_SRC_OPTIMAL]. The buffer offsets of `regions` are relative to the
+    /// downloaded data.
+    pub fn download_image(
+        &mut self,
+        src_image: &UsamiImage,
+        aspect_mask: ImageAspectFlags,
+        regions: &[BufferImageCopy],
+        size: u64,
+    ) -> UsamiResult<UsamiStagingDownload> {
+        let offset = self.allocate(size, self.image_alignment(src_image.format))?;
+        let transfer_state = UsamiAccess::TransferRead.state();
+        let mut transitions_before = Vec::new();
+        let mut transitions_after = Vec::new();
+
+        for subresource_range in subresource_ranges(
+            aspect_mask,
+            regions.iter().flat_map(|region| {
+                let subresource = region.image_subresource;
+
+                (subresource.base_array_layer
+                    ..subresource.base_array_layer + subresource.layer_count)
+                    .map(move |array_layer| (subresource.mip_level, array_layer))
+            }),
+        ) {
+            let mut tracked = src_image.tracked_state(
+                subresource_range.base_mip_level,
+                subresource_range.base_array_layer,
+            );
+            let layout = tracked.layout();
+
+            if let Some(src) = tracked.access(transfer_state) {
+                transitions_before.push(ImageTransition {
+                    subresource_range,
+                    src,
+                    dst: transfer_state,
+                });
+            }
+
+            if layout != ImageLayout::UNDEFINED && layout != transfer_state.layout {
+                let restored_state = UsamiResourceState {
+                    layout,
+                    ..UsamiAccess::General.state()
+                };
+
+                if let Some(src) = tracked.access(restored_state) {
+                    transitions_after.push(ImageTransition {
+                        subresource_range,
+                        src,
+                        dst: restored_state,
+                    });
+                }
+            }
+
+            src_image.set_tracked_state(subresource_range, tracked);
+        }
+
+        self.commands.push(StagingCommand::DownloadImage {
+            src_image: src_image.handle,
+            regions: offset_regions(regions, offset),
+            transitions_before,
+            transitions_after,
+        });
+
+        Ok(self.register_download(offset, size))
+    }
+
+    /// Return the data of a download once the belt was flushed.
+    pub fn take_download(&mut self, download: UsamiStagingDownload) -> Option<Vec<u8>> {
+        self.downloads.get_mut(download.0).and_then(Option::take)
+    }
+
+    fn record(&self, command_buffer: &UsamiCommandBuffer) {
+        let Some(ring) = &self.ring else {
+            return;
+        };
+        let vk_device = &self.device.handle;
+
+        for command in &self.commands {
+            match command {
+                StagingCommand::UploadBuffer { dst_buffer, region } => unsafe {
+                    vk_device.cmd_copy_buffer(
+                        command_buffer.handle,
+                        ring.handle,
+                        *dst_buffer,
+                        &[*region],
+                    );
+                },
+                StagingCommand::UploadImage {
+                    dst_image,
+                    regions,
+                    transitions_before,
+                    transitions_after,
+                } => {
+                    command_buffer.pipeline_barrier2(
+                        &[],
+                        &[],
+                        &image_barriers(*dst_image, transitions_before),
+                    );
+
+                    unsafe {
+                        vk_device.cmd_copy_buffer_to_image(
+                            command_buffer.handle,
+                            ring.handle,
+                            *dst_image,
+                            ImageLayout::TRANSFER_DST_OPTIMAL,
+                            regions,
+                        );
+                    }
+
+                    command_buffer.pipeline_barrier2(
+                        &[],
+                        &[],
+                        &image_barriers(*dst_image, transitions_after),
+                    );
+                }
+                StagingCommand::DownloadBuffer { src_buffer, region } => unsafe {
+                    vk_device.cmd_copy_buffer(
+                        command_buffer.handle,
+                        *src_buffer,
+                        ring.handle,
+                        &[*region],
+                    );
+                },
+                StagingCommand::DownloadImage {
+                    src_image,
+                    regions,
+                    transitions_before,
+                    transitions_after,
+                } => {
+                    command_buffer.pipeline_barrier2(
+                        &[],
+                        &[],
+                        &image_barriers(*src_image, transitions_before),
+                    );
+
+                    unsafe {
+                        vk_device.cmd_copy_image_to_buffer(
+                            command_buffer.handle,
+                            *src_image,
+                            ImageLayout::TRANSFER_SRC_OPTIMAL,
+                            ring.handle,
+                            regions,
+                        );
+                    }
+
+                    command_buffer.pipeline_barrier2(
+                        &[],
+                        &[],
+                        &image_barriers(*src_image, transitions_after),
+                    );
+                }
+            }
+        }
+
+        command_buffer.pipeline_barrier2(
+            &[MemoryBarrier2::default()
+                .src_stage_mask(PipelineStageFlags2::TRANSFER)
+                .src_access_mask(AccessFlags2::TRANSFER_WRITE)
+                .dst_stage_mask(PipelineStageFlags2::ALL_COMMANDS | PipelineStageFlags2::HOST)
+                .dst_access_mask(
+                    AccessFlags2::MEMORY_READ
+                        | AccessFlags2::MEMORY_WRITE
+                        | AccessFlags2::HOST_READ,
+                )],
+            &[],
+            &[],
+        );
+    }
+
+    /// Execute all the pending copies in one submission and wait for them to complete.
+    pub fn flush(&mut self) -> UsamiResult<()> {
+        if self.commands.is_empty() {
+            return Ok(());
+        }
+
+        utils::record_and_execute_command_buffer(
+            &self.device,
+            self.command_pool,
+            format!("{}_cmd_buffer", self.name),
+            |_, command_buffer| {
+                self.record(command_buffer);
+
+                Ok(())
+            },
+        )?;
+
+        for (download, offset, size) in std::mem::take(&mut self.pending_downloads) {
+            let staging = self.ring()?.mapped_slice::<u8>()?;
+
+            self.downloads[download.0] =
+                Some(staging[offset as usize..(offset + size) as usize].to_vec());
+        }
+
+        self.commands.clear();
+
+        Ok(())
+    }
+}
+
+/// One range per distinct mip level and array layer, in order of first appearance.
+fn subresource_ranges(
+    aspect_mask: ImageAspectFlags,
+    subresources: impl Iterator<Item = (u32, u32)>,
+) -> Vec<ImageSubresourceRange> {
+    let mut result: Vec<ImageSubresourceRange> = Vec::new();
+
+    for (mip_level, array_layer) in subresources {
+        if !result
+            .iter()
+            .any(|x| x.base_mip_level == mip_level && x.base_array_layer == array_layer)
+        {
+            result.push(
+                ImageSubresourceRange::default()
+                    .aspect_mask(aspect_mask)
+                    .base_mip_level(mip_level)
+                    .level_count(1)
+                    .base_array_layer(array_layer)
+                    .layer_count(1),
+            );
+        }
+    }
+
+    result
+}
+
+fn image_barriers(
+    image: Image,
+    transitions: &[ImageTransition],
+) -> Vec<ImageMemoryBarrier2<'static>> {
+    transitions
+        .iter()
+        .map(|transition| transition.barrier(image))
+        .collect()
+}
+
+fn offset_regions(regions: &[BufferImageCopy], offset: u64) -> Vec<BufferImageCopy> {
+    regions
+        .iter()
+        .map(|region| {
+            let mut region = *region;
+            region.buffer_offset += offset;
+            region
+        })
+        .collect()
+}
+
+fn lcm(a: u64, b: u64) -> u64 {
+    let (mut x, mut y) = (a, b);
+
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+
+    a / x * b
+}
@@ -0,0 +1,283 @@
+use ash::vk::{
+    self, AccessFlags, AccessFlags2, ImageLayout, PipelineStageFlags, PipelineStageFlags2,
+};
+
+/// High-level use of a resource, used to derive the stage, access and layout of barriers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UsamiAccess {
+    /// No previous use, the content of images is discarded.
+    Nothing,
+    IndirectBuffer,
+    IndexBuffer,
+    VertexBuffer,
+    /// Uniform, sampled or storage read in a vertex shader.
+    VertexShaderRead,
+    /// Uniform or sampled read in a fragment shader.
+    FragmentSampledRead,
+    /// Storage read in a fragment shader.
+    FragmentStorageRead,
+    /// Storage write in a fragment shader.
+    FragmentStorageWrite,
+    ColorAttachmentRead,
+    ColorAttachmentWrite,
+    DepthStencilAttachmentRead,
+    DepthStencilAttachmentWrite,
+    /// Uniform or sampled read in a compute shader.
+    ComputeSampledRead,
+    /// Storage read in a compute shader.
+    ComputeRead,
+    /// Storage write in a compute shader.
+    ComputeWrite,
+    TransferRead,
+    TransferWrite,
+    HostRead,
+    HostWrite,
+    /// Any read or write from any stage.
+    General,
+    Present,
+}
+
+impl UsamiAccess {
+    pub fn stage_mask(self) -> PipelineStageFlags2 {
+        match self {
+            Self::Nothing | Self::Present => PipelineStageFlags2::NONE,
+            Self::IndirectBuffer => PipelineStageFlags2::DRAW_INDIRECT,
+            Self::IndexBuffer | Self::VertexBuffer => PipelineStageFlags2::VERTEX_INPUT,
+            Self::VertexShaderRead => PipelineStageFlags2::VERTEX_SHADER,
+            Self::FragmentSampledRead | Self::FragmentStorageRead | Self::FragmentStorageWrite => {
+                PipelineStageFlags2::FRAGMENT_SHADER
+            }
+            Self::ColorAttachmentRead | Self::ColorAttachmentWrite => {
+                PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT
+            }
+            Self::DepthStencilAttachmentRead | Self::DepthStencilAttachmentWrite => {
+                PipelineStageFlags2::EARLY_FRAGMENT_TESTS | PipelineStageFlags2::LATE_FRAGMENT_TESTS
+            }
+            Self::ComputeSampledRead | Self::ComputeRead | Self::ComputeWrite => {
+                PipelineStageFlags2::COMPUTE_SHADER
+            }
+            Self::TransferRead | Self::TransferWrite => PipelineStageFlags2::TRANSFER,
+            Self::HostRead | Self::HostWrite => PipelineStageFlags2::HOST,
+            Self::General => PipelineStageFlags2::ALL_COMMANDS,
+        }
+    }
+
+    pub fn access_mask(self) -> AccessFlags2 {
+        match self {
+            Self::Nothing | Self::Present => AccessFlags2::NONE,
+            Self::IndirectBuffer => AccessFlags2::INDIRECT_COMMAND_READ,
+            Self::IndexBuffer => AccessFlags2::INDEX_READ,
+            Self::VertexBuffer => AccessFlags2::VERTEX_ATTRIBUTE_READ,
+            Self::VertexShaderRead | Self::FragmentSampledRead | Self::ComputeSampledRead => {
+                AccessFlags2::UNIFORM_READ | AccessFlags2::SHADER_READ
+            }
+            Self::FragmentStorageRead | Self::ComputeRead => AccessFlags2::SHADER_READ,
+            Self::FragmentStorageWrite | Self::ComputeWrite => AccessFlags2::SHADER_WRITE,
+            Self::ColorAttachmentRead => AccessFlags2::COLOR_ATTACHMENT_READ,
+            Self::ColorAttachmentWrite => {
+                AccessFlags2::COLOR_ATTACHMENT_READ | AccessFlags2::COLOR_ATTACHMENT_WRITE
+            }
+            Self::DepthStencilAttachmentRead => AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ,
+            Self::DepthStencilAttachmentWrite => {
+                AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ
+                    | AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE
+            }
+            Self::TransferRead => AccessFlags2::TRANSFER_READ,
+            Self::TransferWrite => AccessFlags2::TRANSFER_WRITE,
+            Self::HostRead => AccessFlags2::HOST_READ,
+            Self::HostWrite => AccessFlags2::HOST_WRITE,
+            Self::General => AccessFlags2::MEMORY_READ | AccessFlags2::MEMORY_WRITE,
+        }
+    }
+
+    /// Layout an image must be in for this use.
+    pub fn image_layout(self) -> ImageLayout {
+        match self {
+            Self::Nothing => ImageLayout::UNDEFINED,
+            Self::VertexShaderRead | Self::FragmentSampledRead | Self::ComputeSampledRead => {
+                ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            }
+            Self::ColorAttachmentRead | Self::ColorAttachmentWrite => {
+                ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+            }
+            Self::DepthStencilAttachmentRead => ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            Self::DepthStencilAttachmentWrite => ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            Self::TransferRead => ImageLayout::TRANSFER_SRC_OPTIMAL,
+            Self::TransferWrite => ImageLayout::TRANSFER_DST_OPTIMAL,
+            Self::Present => ImageLayout::PRESENT_SRC_KHR,
+            _ => ImageLayout::GENERAL,
+        }
+    }
+
+    pub fn state(self) -> UsamiResourceState {
+        UsamiResourceState {
+            stage_mask: self.stage_mask(),
+            access_mask: self.access_mask(),
+            layout: self.image_layout(),
+        }
+    }
+}
+
+/// Last stage, access and layout a resource was used with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UsamiResourceState {
+    pub stage_mask: PipelineStageFlags2,
+    pub access_mask: AccessFlags2,
+    /// Ignored for buffers.
+    pub layout: ImageLayout,
+}
+
+impl UsamiResourceState {
+    pub const fn new(
+        stage_mask: PipelineStageFlags2,
+        access_mask: AccessFlags2,
+        layout: ImageLayout,
+    ) -> Self {
+        Self {
+            stage_mask,
+            access_mask,
+            layout,
+        }
+    }
+
+    /// State of a resource that was never used.
+    pub const fn initial(layout: ImageLayout) -> Self {
+        Self::new(PipelineStageFlags2::NONE, AccessFlags2::NONE, layout)
+    }
+
+    /// State after a legacy barrier.
+    pub fn from_legacy(
+        stage_mask: PipelineStageFlags,
+        access_mask: AccessFlags,
+        layout: ImageLayout,
+    ) -> Self {
+        Self::new(
+            PipelineStageFlags2::from_raw(u64::from(stage_mask.as_raw())),
+            AccessFlags2::from_raw(u64::from(access_mask.as_raw())),
+            layout,
+        )
+    }
+
+    pub fn is_write(&self) -> bool {
+        self.access_mask.intersects(
+            AccessFlags2::SHADER_WRITE
+                | AccessFlags2::SHADER_STORAGE_WRITE
+                | AccessFlags2::COLOR_ATTACHMENT_WRITE
+                | AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE
+                | AccessFlags2::TRANSFER_WRITE
+                | AccessFlags2::HOST_WRITE
+                | AccessFlags2::MEMORY_WRITE,
+        )
+    }
+
+    /// Stage mask usable with the legacy barrier path, only the first 32 bits of the stage mask are kept.
+    pub fn legacy_stage_mask(&self) -> PipelineStageFlags {
+        PipelineStageFlags::from_raw(self.stage_mask.as_raw() as vk::Flags)
+    }
+
+    /// Access mask usable with the legacy barrier path, only the first 32 bits of the access mask are kept.
+    pub fn legacy_access_mask(&self) -> AccessFlags {
+        AccessFlags::from_raw(self.access_mask.as_raw() as vk::Flags)
+    }
+}
+
+/// Synchronization state of a resource across barriers.
+///
+/// The last write is tracked separately from the reads that followed it, so a read skips its
+/// barrier only when the barrier that followed the last write already made it visible to that read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UsamiTrackedState {
+    /// Stages and accesses of the last write along with the current layout.
+    pub write: UsamiResourceState,
+    /// Stages the last write was made visible to.
+    pub visible_stage_mask: PipelineStageFlags2,
+    /// Accesses the last write was made visible to.
+    pub visible_access_mask: AccessFlags2,
+    /// Stages that read the resource since the last write, later writes must wait for them.
+    pub reader_stage_mask: PipelineStageFlags2,
+}
+
+impl UsamiTrackedState {
+    /// State after a use of the resource, reads are assumed to see every previous write.
+    pub fn new(state: UsamiResourceState) -> Self {
+        if state.is_write() {
+            return Self {
+                write: state,
+                visible_stage_mask: PipelineStageFlags2::NONE,
+                visible_access_mask: AccessFlags2::NONE,
+                reader_stage_mask: PipelineStageFlags2::NONE,
+            };
+        }
+
+        Self {
+            write: UsamiResourceState::new(state.stage_mask, AccessFlags2::NONE, state.layout),
+            visible_stage_mask: state.stage_mask,
+            visible_access_mask: state.access_mask,
+            reader_stage_mask: state.stage_mask,
+        }
+    }
+
+    pub fn layout(&self) -> ImageLayout {
+        self.write.layout
+    }
+
+    /// Source of a barrier waiting for every previous use of the resource.
+    pub fn src(&self) -> UsamiResourceState {
+        UsamiResourceState {
+            stage_mask: self.write.stage_mask | self.reader_stage_mask,
+            ..self.write
+        }
+    }
+
+    /// Record a use of the resource and return the source of the barrier it needs, if any.
+    pub fn access(&mut self, next: UsamiResourceState) -> Option<UsamiResourceState> {
+        let is_read = !next.is_write() && next.layout == self.write.layout;
+
+        if is_read
+            && self.visible_stage_mask.contains(next.stage_mask)
+            && self.visible_access_mask.contains(next.access_mask)
+        {
+            self.reader_stage_mask |= next.stage_mask;
+
+            return None;
+        }
+
+        if is_read {
+            // Make the last write visible to this read too, other readers do not need to be waited for.
+            let src = self.write;
+
+            self.visible_stage_mask |= next.stage_mask;
+            self.visible_access_mask |= next.access_mask;
+            self.reader_stage_mask |= next.stage_mask;
+
+            return Some(src);
+        }
+
+        let src = self.src();
+
+        *self = if next.is_write() {
+            Self::new(next)
+        } else {
+            // The layout transition happens before the stages of `next`, later readers must wait
+            // for them as well as for the last write.
+            Self {
+                write: UsamiResourceState::new(
+                    self.write.stage_mask | next.stage_mask,
+                    self.write.access_mask,
+                    next.layout,
+                ),
+                visible_stage_mask: next.stage_mask,
+                visible_access_mask: next.access_mask,
+                reader_stage_mask: next.stage_mask,
+            }
+        };
+
+        Some(src)
+    }
+}
+
+impl From<UsamiAccess> for UsamiResourceState {
+    fn from(value: UsamiAccess) -> Self {
+        value.state()
+    }
+}
@@ -1,18 +1,14 @@
 use std::{fs::File, io::Read, path::Path, sync::Arc};
 
-use ash::{
-    prelude::VkResult,
-    vk::{
-        AccessFlags, BufferImageCopy, CommandBufferLevel, CommandBufferUsageFlags, Extent2D,
-        Extent3D, FenceCreateFlags, Format, ImageAspectFlags, ImageLayout, ImageSubresourceLayers,
-        PipelineStageFlags, SubmitInfo,
-    },
+use ash::vk::{
+    AccessFlags, CommandBufferLevel, CommandBufferUsageFlags, Extent2D, Extent3D, FenceCreateFlags,
+    Format, ImageLayout, PipelineStageFlags, SubmitInfo,
 };
 use image::{EncodableLayout, ImageBuffer, RgbaImage};
 
 use crate::{
     image::{RawImageArrayInfo, RawImageData, RawImageLevelInfo},
-    UsamiBuffer, UsamiCommandBuffer, UsamiCommandPool, UsamiDevice, UsamiImage,
+    Error, UsamiCommandBuffer, UsamiCommandPool, UsamiDevice, UsamiImage, UsamiQueue, UsamiResult,
 };
 
 #[macro_export]
@@ -26,13 +22,18 @@ macro_rules! offset_of {
     }};
 }
 
-pub fn read_spv_file<P: AsRef<Path>>(file_path: P) -> Vec<u32> {
+pub fn read_spv_file<P: AsRef<Path>>(file_path: P) -> UsamiResult<Vec<u32>> {
+    let file_path = file_path.as_ref();
     let mut data = Vec::new();
 
-    let mut file = File::open(file_path).unwrap();
-    file.read_to_end(&mut data).unwrap();
+    File::open(file_path)
+        .and_then(|mut file| file.read_to_end(&mut data))
+        .map_err(|source| Error::Io {
+            path: Some(file_path.into()),
+            source,
+        })?;
 
-    as_u32_vec(&data)
+    Ok(as_u32_vec(&data))
 }
 
 pub fn as_u32_vec(data: &[u8]) -> Vec<u32> {
@@ -61,16 +62,6 @@ pub unsafe fn any_as_u8_slice<T: Sized>(p: &T) -> &[u8] {
     std::slice::from_raw_parts((p as *const T) as *const u8, ::std::mem::size_of::<T>())
 }
 
-pub fn get_format_size(format: Format) -> u32 {
-    match format {
-        Format::R8_UNORM => 1,
-        Format::R8G8_UNORM => 2,
-        Format::R8G8B8_UNORM => 3,
-        Format::R8G8B8A8_UNORM => 4,
-        _ => todo!("Unknown format size for {}", format.as_raw()),
-    }
-}
-
 pub fn compute_mip_pyramid_levels(width: u32, height: u32) -> u32 {
     let max = (std::cmp::max(width, height)) as f32;
 
@@ -147,40 +138,28 @@ pub fn create_gradient_image_with_layers(width: u32, height: u32, layers: u32) -
     RawImageData::new(Format::R8G8B8A8_UNORM, data, level_infos)
 }
 
+/// Record `callback` and transition `image` from the layout it returns to a transfer source, ready to be read back.
 pub fn record_command_buffer_with_image_dep<
     F: Fn(&UsamiDevice, &UsamiCommandBuffer, &UsamiImage) -> ImageLayout,
 >(
     command_buffer: &UsamiCommandBuffer,
     image: &UsamiImage,
-    buffer: &UsamiBuffer,
     callback: F,
-) -> VkResult<()> {
+) -> UsamiResult<()> {
     command_buffer.record(
         CommandBufferUsageFlags::SIMULTANEOUS_USE,
         |device, command_buffer| {
             let old_image_layout = callback(device, command_buffer, image);
 
-            command_buffer.copy_image_to_buffer(
+            command_buffer.add_image_barrier(
                 image,
-                buffer,
-                &[BufferImageCopy::default()
-                    .image_subresource(
-                        ImageSubresourceLayers::default()
-                            .aspect_mask(ImageAspectFlags::COLOR)
-                            .mip_level(0)
-                            .layer_count(1),
-                    )
-                    .image_extent(Extent3D {
-                        width: image.extent.width,
-                        height: image.extent.height,
-                        depth: 1,
-                    })],
+                None,
+                PipelineStageFlags::ALL_COMMANDS,
+                PipelineStageFlags::TRANSFER,
                 AccessFlags::COLOR_ATTACHMENT_WRITE,
+                AccessFlags::TRANSFER_READ,
                 old_image_layout,
-                1,
-                1,
-                ImageAspectFlags::COLOR,
-                PipelineStageFlags::ALL_COMMANDS,
+                ImageLayout::TRANSFER_SRC_OPTIMAL,
             )?;
 
             Ok(())
@@ -189,13 +168,13 @@ pub fn record_command_buffer_with_image_dep<
 }
 
 pub fn record_and_execute_command_buffer<
-    F: Fn(&Arc<UsamiDevice>, &UsamiCommandBuffer) -> VkResult<()>,
+    F: Fn(&Arc<UsamiDevice>, &UsamiCommandBuffer) -> UsamiResult<()>,
 >(
     device: &Arc<UsamiDevice>,
     command_pool: &UsamiCommandPool,
     command_buffer_name: String,
     callback: F,
-) -> VkResult<()> {
+) -> UsamiResult<()> {
     let command_buffers = command_pool.allocate_command_buffers(
         command_buffer_name.clone(),
         CommandBufferLevel::PRIMARY,
@@ -212,7 +191,18 @@ pub fn record_and_execute_command_buffer<
         FenceCreateFlags::empty(),
     )?;
 
-    let queue = UsamiDevice::get_device_queue(device, "queue".into(), device.vk_queue_index, 0)?;
+    let queue = match device
+        .queues
+        .find_by_family(command_pool.queue_family_index)
+    {
+        Some(slot) => UsamiQueue::from_slot(device, slot),
+        None => UsamiDevice::get_device_queue(
+            device,
+            "queue".into(),
+            command_pool.queue_family_index,
+            0,
+        )?,
+    };
 
     queue.submit(
         &[SubmitInfo::default().command_buffers(&[command_buffer.handle])],